@@ -3,8 +3,7 @@
 use brush_process::process::process_stream;
 use brush_ui::app::App;
 
-use brush_cli::Cli;
-use clap::Parser;
+use brush_cli::{Cli, Command};
 
 #[cfg(target_family = "windows")]
 fn is_console() -> bool {
@@ -20,10 +19,10 @@ fn is_console() -> bool {
 
 #[allow(clippy::unnecessary_wraps)] // Error isn't need on wasm but that's ok.
 fn main() -> Result<(), anyhow::Error> {
-    let args = Cli::parse().validate()?;
+    let args = Cli::parse_with_config()?.validate()?;
 
     #[cfg(target_family = "windows")]
-    if args.with_viewer && !is_console() {
+    if args.command.is_none() && args.with_viewer && !is_console() {
         // Hide the console window on windows when running as a GUI.
         // SAFETY: FFI.
         unsafe {
@@ -52,6 +51,22 @@ fn main() -> Result<(), anyhow::Error> {
                 .target(env_logger::Target::Stdout)
                 .init();
 
+            if let Some(command) = args.command {
+                let device = brush_render::burn_init_setup().await;
+                match command {
+                    Command::Panorama(panorama_args) => {
+                        brush_cli::export_panorama(panorama_args, device).await?;
+                    }
+                    Command::Render(render_args) => {
+                        brush_cli::render_trajectory(render_args, device).await?;
+                    }
+                    Command::Eval(eval_args) => {
+                        brush_cli::eval_splat(eval_args, device).await?;
+                    }
+                }
+                return Ok(());
+            }
+
             let (sender, args_receiver) = tokio::sync::oneshot::channel();
             let _ = sender.send(args.process.clone());
 
@@ -91,8 +106,14 @@ fn main() -> Result<(), anyhow::Error> {
                     panic!("Validation of args failed?");
                 };
                 let device = brush_render::burn_init_setup().await;
-                let stream = process_stream(source, args_receiver, device);
-                brush_cli::process_ui(stream, args.process).await?;
+                let metrics_log = args
+                    .metrics_file
+                    .as_deref()
+                    .map(brush_cli::MetricsLog::create)
+                    .transpose()?;
+                let cancel = brush_cli::cancel_on_ctrl_c();
+                let stream = process_stream(source, args_receiver, cancel, device);
+                brush_cli::process_ui(stream, args.process, metrics_log).await?;
             }
 
             anyhow::Result::<(), anyhow::Error>::Ok(())
@@ -1,6 +1,6 @@
 use anyhow::{Context, Result};
 use brush_render::{
-    MainBackend,
+    MainBackend, RenderScale,
     camera::{Camera, focal_to_fov, fov_to_focal},
     gaussian_splats::Splats,
 };
@@ -133,6 +133,10 @@ async fn test_reference() -> Result<()> {
             splats.sh_coeffs.val().into_primitive().tensor(),
             splats.raw_opacity.val().into_primitive().tensor(),
             Vec3::ZERO,
+            false,
+            RenderScale::Native,
+            false,
+            false,
         );
 
         let (out, aux) = (
@@ -1,7 +1,7 @@
 #![recursion_limit = "256"]
 
 use brush_dataset::scene::SceneBatch;
-use brush_render::{MainBackend, camera::Camera, gaussian_splats::Splats};
+use brush_render::{MainBackend, RenderScale, camera::Camera, gaussian_splats::Splats};
 use brush_render_bwd::burn_glue::SplatForwardDiff;
 use brush_train::{config::TrainConfig, train::SplatTrainer};
 use burn::{
@@ -146,6 +146,8 @@ fn generate_training_batch(
         img_tensor,
         alpha_is_mask: false,
         camera,
+        view_index: 0,
+        depth_tensor: None,
     }
 }
 
@@ -153,7 +155,7 @@ fn generate_training_batch(
 mod forward_rendering {
     use crate::{
         AutodiffModule, Backend, Camera, ITERS_PER_SYNC, MainBackend, Quat, RESOLUTIONS,
-        SPLAT_COUNTS, Vec3, WgpuDevice, gen_splats,
+        RenderScale, SPLAT_COUNTS, Vec3, WgpuDevice, gen_splats,
     };
 
     #[divan::bench(args = SPLAT_COUNTS)]
@@ -170,7 +172,15 @@ mod forward_rendering {
 
         bencher.bench_local(move || {
             for _ in 0..ITERS_PER_SYNC {
-                let _ = splats.render(&camera, glam::uvec2(1920, 1080), Vec3::ZERO, None);
+                let _ = splats.render(
+                    &camera,
+                    glam::uvec2(1920, 1080),
+                    Vec3::ZERO,
+                    None,
+                    RenderScale::Native,
+                    false,
+                    false,
+                );
             }
             MainBackend::sync(&device);
         });
@@ -190,7 +200,15 @@ mod forward_rendering {
 
         bencher.bench_local(move || {
             for _ in 0..ITERS_PER_SYNC {
-                let _ = splats.render(&camera, glam::uvec2(width, height), Vec3::ZERO, None);
+                let _ = splats.render(
+                    &camera,
+                    glam::uvec2(width, height),
+                    Vec3::ZERO,
+                    None,
+                    RenderScale::Native,
+                    false,
+                    false,
+                );
             }
             MainBackend::sync(&device);
         });
@@ -200,7 +218,7 @@ mod forward_rendering {
 #[divan::bench_group(max_time = 2)]
 mod backward_rendering {
     use crate::{
-        Backend, Camera, DiffBackend, ITERS_PER_SYNC, MainBackend, Quat, RESOLUTIONS,
+        Backend, Camera, DiffBackend, ITERS_PER_SYNC, MainBackend, Quat, RESOLUTIONS, RenderScale,
         SplatForwardDiff, Tensor, TensorPrimitive, Vec3, WgpuDevice, gen_splats,
     };
 
@@ -227,6 +245,10 @@ mod backward_rendering {
                     splats.sh_coeffs.val().into_primitive().tensor(),
                     splats.raw_opacity.val().into_primitive().tensor(),
                     Vec3::ZERO,
+                    false,
+                    RenderScale::Native,
+                    false,
+                    false,
                 );
                 let img: Tensor<DiffBackend, 3> =
                     Tensor::from_primitive(TensorPrimitive::Float(diff_out.img));
@@ -258,6 +280,10 @@ mod backward_rendering {
                     splats.sh_coeffs.val().into_primitive().tensor(),
                     splats.raw_opacity.val().into_primitive().tensor(),
                     Vec3::ZERO,
+                    false,
+                    RenderScale::Native,
+                    false,
+                    false,
                 );
                 let img: Tensor<DiffBackend, 3> =
                     Tensor::from_primitive(TensorPrimitive::Float(diff_out.img));
@@ -311,7 +337,15 @@ mod integration_tests {
             glam::vec2(0.5, 0.5),
         );
 
-        let (img, _) = splats.render(&camera, glam::uvec2(256, 256), Vec3::ZERO, None);
+        let (img, _) = splats.render(
+            &camera,
+            glam::uvec2(256, 256),
+            Vec3::ZERO,
+            None,
+            RenderScale::Native,
+            false,
+            false,
+        );
         let dims = img.dims();
         assert_eq!(dims, [256, 256, 3]);
 
@@ -355,6 +389,10 @@ mod integration_tests {
             splats.sh_coeffs.val().into_primitive().tensor(),
             splats.raw_opacity.val().into_primitive().tensor(),
             Vec3::ZERO,
+            false,
+            RenderScale::Native,
+            false,
+            false,
         );
 
         let img: Tensor<DiffBackend, 3> =
@@ -4,7 +4,8 @@
 
 use brush_dataset::scene::SceneBatch;
 use brush_render::{
-    MainBackend, camera::Camera, gaussian_splats::Splats, validation::validate_splat_gradients,
+    MainBackend, RenderScale, camera::Camera, gaussian_splats::Splats,
+    validation::validate_splat_gradients,
 };
 use brush_render_bwd::burn_glue::SplatForwardDiff;
 use brush_train::{config::TrainConfig, train::SplatTrainer};
@@ -126,6 +127,8 @@ fn generate_test_batch(device: &WgpuDevice, resolution: (u32, u32)) -> SceneBatc
         img_tensor,
         alpha_is_mask: false,
         camera,
+        view_index: 0,
+        depth_tensor: None,
     }
 }
 
@@ -236,6 +239,10 @@ fn test_gradient_validation() {
         splats.sh_coeffs.val().into_primitive().tensor(),
         splats.raw_opacity.val().into_primitive().tensor(),
         Vec3::ZERO,
+        false,
+        RenderScale::Native,
+        false,
+        false,
     );
 
     let rendered: Tensor<DiffBackend, 3> =
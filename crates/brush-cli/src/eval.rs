@@ -0,0 +1,93 @@
+use crate::render::load_splats;
+use anyhow::Context;
+use brush_dataset::config::LoadDataseConfig;
+use brush_process::{
+    eval_only::{eval_scene, load_lpips},
+    message::EvalView,
+};
+use brush_vfs::DataSource;
+use burn_wgpu::WgpuDevice;
+use clap::{ArgAction, Args};
+use std::{path::PathBuf, sync::Arc};
+
+#[derive(Clone, Args)]
+pub struct EvalArgs {
+    /// Ply, .splat, .spz or .glb file to evaluate (path or URL).
+    #[arg(value_name = "PATH_OR_URL")]
+    pub source: DataSource,
+
+    /// Dataset to evaluate against (path or URL). Its eval views are used, see --eval-split-every.
+    #[arg(long)]
+    pub dataset: DataSource,
+
+    /// Fit the colors of each view on the left half of the image, and only score the right half.
+    /// Use this for splats trained with appearance compensation.
+    #[arg(long, default_value = "false")]
+    pub fit_appearance: bool,
+
+    /// Save the rendered eval images to this directory.
+    #[arg(long)]
+    pub save_renders: Option<PathBuf>,
+
+    /// Compute LPIPS for each view. Pass `--eval-lpips false` to only compute PSNR and SSIM.
+    #[arg(long, default_value = "true", action = ArgAction::Set)]
+    pub eval_lpips: bool,
+
+    /// Compute LPIPS with AlexNet instead of VGG, to match published benchmarks. This is the path
+    /// to AlexNet LPIPS weights converted with lpips-convert.
+    #[arg(long)]
+    pub lpips_alex: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub load_config: LoadDataseConfig,
+}
+
+/// Score a splat against the eval views of a dataset, and print the metrics of each view.
+pub async fn eval_splat(args: EvalArgs, device: WgpuDevice) -> anyhow::Result<()> {
+    let (splats, _) = load_splats(args.source, device.clone()).await?;
+
+    let vfs = Arc::new(args.dataset.into_vfs().await?);
+    let (_, dataset) = brush_dataset::load_dataset(vfs, &args.load_config, &device).await?;
+    let eval_scene = dataset
+        .eval
+        .context("Dataset has no eval views, pass --eval-split-every to create them")?;
+
+    let lpips = if args.eval_lpips {
+        Some(load_lpips(args.lpips_alex.as_deref(), &device)?)
+    } else {
+        None
+    };
+
+    log::info!("Evaluating {} views", eval_scene.views.len());
+    let metrics = eval_scene(
+        &splats,
+        &eval_scene,
+        args.fit_appearance,
+        lpips.as_ref(),
+        args.save_renders.as_deref(),
+        &device,
+    )
+    .await?;
+
+    println!("{:<40} {:>8} {:>8} {:>8}", "view", "PSNR", "SSIM", "LPIPS");
+    for m in &metrics {
+        println!(
+            "{:<40} {:>8.3} {:>8.4} {:>8.4}",
+            m.name,
+            m.psnr,
+            m.ssim,
+            m.lpips.unwrap_or(f32::NAN)
+        );
+    }
+
+    let count = metrics.len() as f32;
+    let mean = |metric: fn(&EvalView) -> f32| metrics.iter().map(metric).sum::<f32>() / count;
+    println!(
+        "{:<40} {:>8.3} {:>8.4} {:>8.4}",
+        "mean",
+        mean(|m| m.psnr),
+        mean(|m| m.ssim),
+        mean(|m| m.lpips.unwrap_or(f32::NAN))
+    );
+    Ok(())
+}
@@ -1,11 +1,26 @@
 #![recursion_limit = "256"]
 
+mod eval;
+mod metrics;
+mod panorama;
+mod render;
+
+pub use eval::{EvalArgs, eval_splat};
+pub use metrics::MetricsLog;
+pub use panorama::{PanoramaArgs, export_panorama};
+pub use render::{RenderArgs, render_trajectory};
+
+use anyhow::Context;
 use brush_process::{config::ProcessArgs, message::ProcessMessage};
 use brush_vfs::DataSource;
-use clap::{Error, Parser, builder::ArgPredicate, error::ErrorKind};
+use clap::{
+    CommandFactory, Error, FromArgMatches, Parser, Subcommand, builder::ArgPredicate,
+    error::ErrorKind, parser::ValueSource,
+};
 use indicatif::{ProgressBar, ProgressStyle};
-use std::time::Duration;
+use std::{path::PathBuf, time::Duration};
 use tokio_stream::{Stream, StreamExt};
+use tokio_util::sync::CancellationToken;
 use tracing::trace_span;
 
 #[derive(Parser)]
@@ -13,9 +28,13 @@ use tracing::trace_span;
     author,
     version,
     arg_required_else_help = false,
-    about = "Brush - universal splats"
+    about = "Brush - universal splats",
+    args_conflicts_with_subcommands = true
 )]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Source to load from (path or URL).
     #[arg(value_name = "PATH_OR_URL")]
     pub source: Option<DataSource>,
@@ -28,13 +47,58 @@ pub struct Cli {
     )]
     pub with_viewer: bool,
 
+    /// Write training metrics to this file, as one JSON object per line.
+    #[arg(long)]
+    pub metrics_file: Option<PathBuf>,
+
+    /// Load training options from a TOML or JSON config file, like the `config.toml` written next
+    /// to exports. Options passed on the command line override the file.
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
     #[clap(flatten)]
     pub process: ProcessArgs,
 }
 
+/// Tools that run on their own, instead of training or viewing.
+#[derive(Subcommand)]
+pub enum Command {
+    /// Render a 360° equirectangular panorama of a splat.
+    Panorama(PanoramaArgs),
+    /// Render a splat along a camera trajectory to a sequence of images.
+    Render(RenderArgs),
+    /// Score a splat against the eval views of a dataset, without training.
+    Eval(EvalArgs),
+}
+
 impl Cli {
+    /// Parse the command line, and apply the --config file if one is given.
+    ///
+    /// This keeps track of which options were passed explicitly, either on the command line or in
+    /// the config file.
+    pub fn parse_with_config() -> anyhow::Result<Self> {
+        let matches = Self::command().get_matches();
+        let mut cli = Self::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+        let on_command_line = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+        cli.process.explicit_options = matches
+            .ids()
+            .filter(|id| on_command_line(id.as_str()))
+            .map(|id| id.to_string())
+            .collect();
+
+        if let Some(path) = &cli.config {
+            let config = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read config {}", path.display()))?;
+            cli.process = cli
+                .process
+                .merge_config(&config, on_command_line)
+                .with_context(|| format!("Invalid config {}", path.display()))?;
+        }
+        Ok(cli)
+    }
+
     pub fn validate(self) -> Result<Self, Error> {
-        if !self.with_viewer && self.source.is_none() {
+        if self.command.is_none() && !self.with_viewer && self.source.is_none() {
             return Err(Error::raw(
                 ErrorKind::MissingRequiredArgument,
                 "When --with-viewer is false, --source must be provided",
@@ -44,9 +108,29 @@ impl Cli {
     }
 }
 
+/// Cancel the returned token on Ctrl-C, to stop training gracefully and export the current splat.
+/// A second Ctrl-C quits right away.
+#[allow(clippy::exit)] // Quitting is what a second Ctrl-C is for.
+pub fn cancel_on_ctrl_c() -> CancellationToken {
+    let cancel = CancellationToken::new();
+    let token = cancel.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        log::info!("Stopping after the current step, press Ctrl-C again to quit immediately.");
+        token.cancel();
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(130);
+        }
+    });
+    cancel
+}
+
 pub async fn process_ui(
     stream: impl Stream<Item = anyhow::Result<ProcessMessage>>,
     process_args: ProcessArgs,
+    mut metrics_log: Option<MetricsLog>,
 ) -> Result<(), anyhow::Error> {
     // TODO: Find a way to make logging and indicatif to play nicely with eachother.
     // let mut stream = std::pin::pin!(stream);
@@ -126,8 +210,6 @@ pub async fn process_ui(
 
     let mut stream = std::pin::pin!(stream);
     while let Some(msg) = stream.next().await {
-        let _span = trace_span!("CLI UI").entered();
-
         let msg = match msg {
             Ok(msg) => msg,
             Err(error) => {
@@ -137,6 +219,12 @@ pub async fn process_ui(
             }
         };
 
+        if let Some(metrics_log) = &mut metrics_log {
+            metrics_log.log(&msg).await?;
+        }
+
+        let _span = trace_span!("CLI UI").entered();
+
         match msg {
             ProcessMessage::NewSource => {
                 main_spinner.set_message("Starting process...");
@@ -144,7 +232,7 @@ pub async fn process_ui(
             ProcessMessage::StartLoading { training } => {
                 if !training {
                     // Display a big warning saying viewing splats from the CLI doesn't make sense.
-                    let _ = sp.println("❌ Only training is supported in the CLI (try passing --with-viewer to view a splat)");
+                    let _ = sp.println("❌ Only training is supported in the CLI (try passing --with-viewer to view a splat, or brush render to render it)");
                     break;
                 }
                 main_spinner.set_message("Loading data...");
@@ -193,12 +281,15 @@ pub async fn process_ui(
                 iter,
                 avg_psnr,
                 avg_ssim,
+                avg_lpips,
+                ..
             } => {
-                log::info!("Eval iter {iter}: PSNR {avg_psnr}, ssim {avg_ssim}");
-
-                eval_spinner.set_message(format!(
-                    "Eval iter {iter}: PSNR {avg_psnr}, ssim {avg_ssim}"
-                ));
+                let mut message = format!("Eval iter {iter}: PSNR {avg_psnr}, ssim {avg_ssim}");
+                if let Some(avg_lpips) = avg_lpips {
+                    message += &format!(", lpips {avg_lpips}");
+                }
+                log::info!("{message}");
+                eval_spinner.set_message(message);
             }
             ProcessMessage::Warning { error } => {
                 log::warn!("{error}");
@@ -0,0 +1,130 @@
+use brush_process::message::ProcessMessage;
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{LineWriter, Write},
+    path::Path,
+    time::Instant,
+};
+
+#[derive(Serialize)]
+struct ViewMetrics<'a> {
+    name: &'a str,
+    psnr: f32,
+    ssim: f32,
+    lpips: Option<f32>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum MetricsEvent<'a> {
+    TrainStep {
+        iter: u32,
+        wall_time: f64,
+        train_time: f64,
+        loss: f32,
+        num_visible: i32,
+        num_intersections: i32,
+        lr_mean: f64,
+        lr_rotation: f64,
+        lr_scale: f64,
+        lr_coeffs: f64,
+        lr_opac: f64,
+    },
+    RefineStep {
+        iter: u32,
+        wall_time: f64,
+        num_added: u32,
+        num_pruned: u32,
+        num_splats: u32,
+    },
+    EvalResult {
+        iter: u32,
+        wall_time: f64,
+        avg_psnr: f32,
+        avg_ssim: f32,
+        avg_lpips: Option<f32>,
+        views: Vec<ViewMetrics<'a>>,
+    },
+}
+
+/// Writes training metrics from the process messages as JSON lines.
+///
+/// Each line is one event, with the iteration and the wall time in seconds since the log was
+/// created. Lines are flushed as they're written, so the file can be followed while training.
+pub struct MetricsLog {
+    writer: LineWriter<File>,
+    start: Instant,
+}
+
+impl MetricsLog {
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        Ok(Self {
+            writer: LineWriter::new(File::create(path)?),
+            start: Instant::now(),
+        })
+    }
+
+    pub async fn log(&mut self, message: &ProcessMessage) -> anyhow::Result<()> {
+        let wall_time = self.start.elapsed().as_secs_f64();
+
+        let event = match message {
+            ProcessMessage::TrainStep {
+                stats,
+                iter,
+                total_elapsed,
+                ..
+            } => MetricsEvent::TrainStep {
+                iter: *iter,
+                wall_time,
+                train_time: total_elapsed.as_secs_f64(),
+                loss: stats.loss.clone().into_scalar_async().await,
+                num_visible: stats.num_visible.clone().into_scalar_async().await,
+                num_intersections: stats.num_intersections.clone().into_scalar_async().await,
+                lr_mean: stats.lr_mean,
+                lr_rotation: stats.lr_rotation,
+                lr_scale: stats.lr_scale,
+                lr_coeffs: stats.lr_coeffs,
+                lr_opac: stats.lr_opac,
+            },
+            ProcessMessage::RefineStep {
+                stats,
+                cur_splat_count,
+                iter,
+            } => MetricsEvent::RefineStep {
+                iter: *iter,
+                wall_time,
+                num_added: stats.num_added,
+                num_pruned: stats.num_pruned,
+                num_splats: *cur_splat_count,
+            },
+            ProcessMessage::EvalResult {
+                iter,
+                avg_psnr,
+                avg_ssim,
+                avg_lpips,
+                views,
+            } => MetricsEvent::EvalResult {
+                iter: *iter,
+                wall_time,
+                avg_psnr: *avg_psnr,
+                avg_ssim: *avg_ssim,
+                avg_lpips: *avg_lpips,
+                views: views
+                    .iter()
+                    .map(|v| ViewMetrics {
+                        name: &v.name,
+                        psnr: v.psnr,
+                        ssim: v.ssim,
+                        lpips: v.lpips,
+                    })
+                    .collect(),
+            },
+            _ => return Ok(()),
+        };
+
+        serde_json::to_writer(&mut self.writer, &event)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+use crate::render::{load_splats, save_image};
+use brush_vfs::DataSource;
+use burn_wgpu::WgpuDevice;
+use clap::Args;
+use glam::{EulerRot, Quat, Vec3};
+use std::path::PathBuf;
+
+#[derive(Clone, Args)]
+pub struct PanoramaArgs {
+    /// Ply, .splat, .spz or .glb file to render (path or URL).
+    #[arg(value_name = "PATH_OR_URL")]
+    pub source: DataSource,
+
+    /// Position to render the panorama from, as x,y,z.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        num_args = 3,
+        allow_negative_numbers = true,
+        default_value = "0,0,0"
+    )]
+    pub position: Vec<f32>,
+
+    /// Rotation around the up axis of the center of the panorama, in degrees.
+    #[arg(long, allow_negative_numbers = true, default_value = "0")]
+    pub yaw: f32,
+
+    /// Rotation up (positive) or down of the center of the panorama, in degrees.
+    #[arg(long, allow_negative_numbers = true, default_value = "0")]
+    pub pitch: f32,
+
+    /// Width of the panorama in pixels. The height is half the width.
+    #[arg(long, default_value = "4096")]
+    pub width: u32,
+
+    /// Background color, as r,g,b between 0 and 1.
+    #[arg(long, value_delimiter = ',', num_args = 3, default_value = "0,0,0")]
+    pub background: Vec<f32>,
+
+    /// Scale the size of all splats by this factor.
+    #[arg(long)]
+    pub splat_scale: Option<f32>,
+
+    /// File to save the panorama to. The format is picked from the extension, eg. png or exr.
+    #[arg(long, short, default_value = "panorama.png")]
+    pub output: PathBuf,
+}
+
+/// Render a panorama of a splat to disk.
+pub async fn export_panorama(args: PanoramaArgs, device: WgpuDevice) -> anyhow::Result<()> {
+    let (splats, up_axis) = load_splats(args.source, device).await?;
+
+    // The camera looks along +Z with -Y as up, line that up with the up axis of the splat.
+    let up = Quat::from_rotation_arc(Vec3::NEG_Y, up_axis.unwrap_or(Vec3::NEG_Y).normalize());
+    let rotation = up
+        * Quat::from_euler(
+            EulerRot::YXZ,
+            args.yaw.to_radians(),
+            args.pitch.to_radians(),
+            0.0,
+        );
+
+    log::info!("Rendering {} wide panorama", args.width);
+    let img = splats.render_equirect(
+        Vec3::from_slice(&args.position),
+        rotation,
+        args.width,
+        Vec3::from_slice(&args.background),
+        args.splat_scale,
+    );
+    let [h, w, _] = img.dims();
+    let data = img
+        .into_data_async()
+        .await
+        .into_vec::<f32>()
+        .expect("Wrong type");
+    let img = image::Rgba32FImage::from_raw(w as u32, h as u32, data)
+        .expect("Failed to create image from tensor");
+    save_image(img.into(), &args.output)?;
+    log::info!("Saved panorama to {}", args.output.display());
+    Ok(())
+}
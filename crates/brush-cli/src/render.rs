@@ -0,0 +1,215 @@
+use anyhow::Context;
+use brush_dataset::config::LoadDataseConfig;
+use brush_render::{MainBackend, RenderScale, camera::Camera, gaussian_splats::Splats};
+use brush_serde::SplatFormat;
+use brush_vfs::DataSource;
+use burn_wgpu::WgpuDevice;
+use clap::{ArgGroup, Args};
+use glam::{UVec2, Vec3};
+use image::DynamicImage;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+#[derive(Clone, Args)]
+#[command(group(ArgGroup::new("trajectory").required(true).args(["camera_path", "dataset"])))]
+pub struct RenderArgs {
+    /// Ply, .splat, .spz or .glb file to render (path or URL).
+    #[arg(value_name = "PATH_OR_URL")]
+    pub source: DataSource,
+
+    /// Render the cameras of a nerfstudio `camera_path.json`.
+    #[arg(long)]
+    pub camera_path: Option<PathBuf>,
+
+    /// The `dataparser_transforms.json` of the nerfstudio run the camera path was made in. The
+    /// nerfstudio viewer centers and scales the scene, this maps the camera path back to the space
+    /// of the dataset. By default this file is looked for next to the camera path and in the
+    /// folder above it. Without it, the camera path is used as is, and only lines up with the
+    /// splat if nerfstudio didn't change the poses.
+    #[arg(long, requires = "camera_path")]
+    pub dataparser_transforms: Option<PathBuf>,
+
+    /// Render the cameras of a dataset (path or URL).
+    #[arg(long)]
+    pub dataset: Option<DataSource>,
+
+    /// Instead of the cameras of the dataset, render this many frames orbiting around it.
+    #[arg(long, requires = "dataset")]
+    pub orbit: Option<usize>,
+
+    /// Width of the frames. Defaults to the resolution of the trajectory, or 1920 if it has none.
+    #[arg(long)]
+    pub width: Option<u32>,
+
+    /// Height of the frames. Defaults to match the aspect ratio of the cameras.
+    #[arg(long)]
+    pub height: Option<u32>,
+
+    /// Background color, as r,g,b between 0 and 1.
+    #[arg(long, value_delimiter = ',', num_args = 3, default_value = "0,0,0")]
+    pub background: Vec<f32>,
+
+    /// Scale the size of all splats by this factor.
+    #[arg(long)]
+    pub splat_scale: Option<f32>,
+
+    /// Directory to write the frames to.
+    #[arg(long, short, default_value = "renders")]
+    pub output: PathBuf,
+
+    /// Image format of the frames, eg. png or exr.
+    #[arg(long, default_value = "png")]
+    pub format: String,
+
+    #[clap(flatten)]
+    pub load_config: LoadDataseConfig,
+}
+
+pub(crate) async fn load_splats(
+    source: DataSource,
+    device: WgpuDevice,
+) -> anyhow::Result<(Splats<MainBackend>, Option<Vec3>)> {
+    let vfs = source.into_vfs().await?;
+    let (path, format) = vfs
+        .file_paths()
+        .find_map(|p| SplatFormat::from_path(&p).map(|f| (p, f)))
+        .context("No splat file found in source")?;
+    let message =
+        brush_serde::load_splat(vfs.reader_at_path(&path).await?, format, None, device).await?;
+    Ok((message.splats, message.meta.up_axis))
+}
+
+/// Save an image, picking the format from the extension of the path.
+///
+/// EXR files are written as floating point, other formats as 8 bit RGBA.
+pub(crate) fn save_image(img: DynamicImage, path: &Path) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let is_exr = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("exr"));
+    if is_exr {
+        img.into_rgba32f().save(path)?;
+    } else {
+        img.into_rgba8().save(path)?;
+    }
+    Ok(())
+}
+
+// Size to render a camera at. A missing width or height follows the aspect ratio of the camera.
+fn frame_size(
+    camera: &Camera,
+    native: Option<UVec2>,
+    width: Option<u32>,
+    height: Option<u32>,
+) -> UVec2 {
+    let aspect = ((camera.fov_x / 2.0).tan() / (camera.fov_y / 2.0).tan()) as f32;
+    let from_width = |w: u32| glam::uvec2(w, ((w as f32 / aspect).round() as u32).max(1));
+    match (width, height) {
+        (Some(w), Some(h)) => glam::uvec2(w, h),
+        (Some(w), None) => from_width(w),
+        (None, Some(h)) => glam::uvec2(((h as f32 * aspect).round() as u32).max(1), h),
+        (None, None) => native.unwrap_or_else(|| from_width(1920)),
+    }
+}
+
+/// Render a splat along a camera trajectory, and write the frames to disk.
+pub async fn render_trajectory(args: RenderArgs, device: WgpuDevice) -> anyhow::Result<()> {
+    let (splats, _) = load_splats(args.source, device.clone()).await?;
+
+    // The cameras to render, with the resolution they were captured at if known.
+    let frames: Vec<(Camera, Option<UVec2>)> = if let Some(path) = &args.camera_path {
+        let json = std::fs::read_to_string(path)?;
+        let dataparser_path = args.dataparser_transforms.clone().or_else(|| {
+            path.ancestors()
+                .skip(1)
+                .take(2)
+                .map(|dir| dir.join("dataparser_transforms.json"))
+                .find(|p| p.is_file())
+        });
+        let dataparser = if let Some(dataparser_path) = dataparser_path {
+            log::info!("Using dataparser transforms {}", dataparser_path.display());
+            Some(std::fs::read_to_string(dataparser_path)?)
+        } else {
+            log::warn!(
+                "No dataparser_transforms.json found, using the camera path without converting it"
+            );
+            None
+        };
+        let (cameras, size) = brush_dataset::camera_path_json(&json, dataparser.as_deref())?;
+        cameras.into_iter().map(|c| (c, size)).collect()
+    } else {
+        let source = args
+            .dataset
+            .context("Either a camera path or dataset is required")?;
+        let vfs = Arc::new(source.into_vfs().await?);
+        let (_, dataset) = brush_dataset::load_dataset(vfs, &args.load_config, &device).await?;
+        let views: Vec<_> = dataset
+            .train
+            .views
+            .iter()
+            .chain(dataset.eval.iter().flat_map(|e| e.views.as_slice()))
+            .collect();
+
+        if let Some(orbit_frames) = args.orbit {
+            let size = views.first().map(|v| v.image.dimensions());
+            dataset
+                .orbit_cameras(orbit_frames)
+                .into_iter()
+                .map(|c| (c, size))
+                .collect()
+        } else {
+            views
+                .iter()
+                .map(|v| (v.camera.clone(), Some(v.image.dimensions())))
+                .collect()
+        }
+    };
+
+    let background = Vec3::from_slice(&args.background);
+    // Render EXR frames as floats, so they aren't quantized to 8 bits.
+    let float_img = args.format.eq_ignore_ascii_case("exr");
+    log::info!("Rendering {} frames", frames.len());
+
+    for (i, (camera, native)) in frames.iter().enumerate() {
+        let size = frame_size(camera, *native, args.width, args.height);
+        let (img, _) = splats.render_inner(
+            camera,
+            size,
+            background,
+            args.splat_scale,
+            RenderScale::Native,
+            false,
+            false,
+            float_img,
+        );
+
+        let data = img
+            .into_data_async()
+            .await
+            .into_vec::<f32>()
+            .expect("Wrong type");
+        let img: DynamicImage = if float_img {
+            image::Rgba32FImage::from_raw(size.x, size.y, data)
+                .expect("Failed to create image from tensor")
+                .into()
+        } else {
+            // The render is packed as one RGBA8 value per pixel.
+            let bytes = data
+                .iter()
+                .flat_map(|p| p.to_bits().to_le_bytes())
+                .collect();
+            image::RgbaImage::from_raw(size.x, size.y, bytes)
+                .expect("Failed to create image from tensor")
+                .into()
+        };
+
+        let path = args.output.join(format!("frame_{i:05}.{}", args.format));
+        save_image(img, &path)?;
+        log::info!("Saved frame {} to {}", i + 1, path.display());
+    }
+    Ok(())
+}
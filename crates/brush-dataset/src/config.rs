@@ -1,13 +1,14 @@
 use clap::Args;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Args)]
+#[derive(Clone, Debug, Args, Serialize, Deserialize)]
 pub struct ModelConfig {
     /// SH degree of splats.
     #[arg(long, help_heading = "Model Options", default_value = "3")]
     pub sh_degree: u32,
 }
 
-#[derive(Clone, Debug, Args)]
+#[derive(Clone, Debug, Args, Serialize, Deserialize)]
 pub struct LoadDataseConfig {
     /// Max nr. of frames of dataset to load
     #[arg(long, help_heading = "Dataset Options")]
@@ -24,4 +25,7 @@ pub struct LoadDataseConfig {
     /// Load only every nth point from the initial sfm data
     #[arg(long, help_heading = "Dataset Options")]
     pub subsample_points: Option<u32>,
+    /// Scale to convert 16 bit depth maps to scene units. The default assumes depths in millimeters.
+    #[arg(long, help_heading = "Dataset Options", default_value = "0.001")]
+    pub depth_scale: f32,
 }
@@ -4,21 +4,28 @@ use super::FormatError;
 use crate::{
     Dataset,
     config::LoadDataseConfig,
-    formats::find_mask_path,
+    formats::{find_depth_path, find_mask_path},
     scene::{LoadImage, SceneView},
 };
 use brush_render::{
-    camera::{self, Camera},
+    camera::{self, Camera, Distortion},
     gaussian_splats::Splats,
     sh::rgb_to_sh,
 };
 use brush_serde::{ParseMetadata, SplatMessage};
 use brush_vfs::BrushVfs;
 use burn::backend::wgpu::WgpuDevice;
+use colmap_reader::CameraModel;
 use std::collections::HashMap;
 use tokio_with_wasm::alias as tokio_wasm;
 
-fn find_mask_and_img(vfs: &BrushVfs, name: &str) -> Option<(PathBuf, Option<PathBuf>)> {
+struct ImagePaths {
+    image: PathBuf,
+    mask: Option<PathBuf>,
+    depth: Option<PathBuf>,
+}
+
+fn find_mask_and_img(vfs: &BrushVfs, name: &str) -> Option<ImagePaths> {
     // Colmap only specifies an image name, not a full path. We brute force
     // search for the image in the archive.
     //
@@ -31,22 +38,66 @@ fn find_mask_and_img(vfs: &BrushVfs, name: &str) -> Option<(PathBuf, Option<Path
     let mut path_masks = HashMap::new();
     let mut masks = vec![];
 
-    // First pass: collect images & masks.
+    // First pass: collect images, masks & depth maps.
     for path in paths {
         let mask = find_mask_path(vfs, &path);
-        path_masks.insert(path.clone(), mask.clone());
-        if let Some(mask_path) = mask {
-            masks.push(mask_path);
-        }
+        let depth = find_depth_path(vfs, &path);
+        masks.extend(mask.clone());
+        masks.extend(depth.clone());
+        path_masks.insert(path.clone(), (mask, depth));
     }
 
-    // Remove masks from candidates - shouldn't count as an input image.
+    // Remove masks & depth maps from candidates - shouldn't count as an input image.
     for mask in masks {
         path_masks.remove(&mask);
     }
 
     // Sort and return the first candidate (alphabetically).
-    path_masks.into_iter().min_by_key(|kv| kv.0.clone())
+    path_masks
+        .into_iter()
+        .min_by_key(|kv| kv.0.clone())
+        .map(|(image, (mask, depth))| ImagePaths { image, mask, depth })
+}
+
+// Read the lens distortion from the parameters of a colmap camera.
+fn camera_distortion(cam: &colmap_reader::Camera) -> Option<Distortion> {
+    let p = |i: usize| cam.params.get(i).copied().unwrap_or(0.0) as f32;
+
+    match cam.model {
+        CameraModel::SimplePinhole | CameraModel::Pinhole => None,
+        CameraModel::SimpleRadial => Some(Distortion::OpenCv {
+            radial: [p(3), 0.0, 0.0, 0.0, 0.0, 0.0],
+            tangential: [0.0, 0.0],
+        }),
+        CameraModel::Radial => Some(Distortion::OpenCv {
+            radial: [p(3), p(4), 0.0, 0.0, 0.0, 0.0],
+            tangential: [0.0, 0.0],
+        }),
+        CameraModel::OpenCV => Some(Distortion::OpenCv {
+            radial: [p(4), p(5), 0.0, 0.0, 0.0, 0.0],
+            tangential: [p(6), p(7)],
+        }),
+        CameraModel::FullOpenCV => Some(Distortion::OpenCv {
+            radial: [p(4), p(5), p(8), p(9), p(10), p(11)],
+            tangential: [p(6), p(7)],
+        }),
+        CameraModel::SimpleRadialFisheye => Some(Distortion::Fisheye {
+            radial: [p(3), 0.0, 0.0, 0.0],
+        }),
+        CameraModel::RadialFisheye => Some(Distortion::Fisheye {
+            radial: [p(3), p(4), 0.0, 0.0],
+        }),
+        CameraModel::OpenCvFishEye => Some(Distortion::Fisheye {
+            radial: [p(4), p(5), p(6), p(7)],
+        }),
+        CameraModel::Fov | CameraModel::ThinPrismFisheye => {
+            log::warn!(
+                "Camera model {:?} is not supported, ignoring lens distortion",
+                cam.model
+            );
+            None
+        }
+    }
 }
 
 pub(crate) async fn load_dataset(
@@ -125,7 +176,12 @@ async fn load_dataset_inner(
         let center_uv = center / glam::vec2(cam_data.width as f32, cam_data.height as f32);
 
         // If image isn't found, just ignore it. We can still train on the remaining images.
-        let Some((path, mask_path)) = find_mask_and_img(&vfs, &img_info.name) else {
+        let Some(ImagePaths {
+            image: path,
+            mask: mask_path,
+            depth: depth_path,
+        }) = find_mask_and_img(&vfs, &img_info.name)
+        else {
             log::warn!("Image not found: {}", img_info.name);
             continue;
         };
@@ -135,17 +191,16 @@ async fn load_dataset_inner(
         let cam_to_world = world_to_cam.inverse();
         let (_, quat, translation) = cam_to_world.to_scale_rotation_translation();
 
-        let camera = Camera::new(translation, quat, fovx, fovy, center_uv);
+        let camera = Camera::new(translation, quat, fovx, fovy, center_uv)
+            .with_distortion(camera_distortion(&cam_data));
 
         log::info!("Loaded COLMAP image at path {path:?}");
 
-        let load_img =
-            LoadImage::new(vfs.clone(), &path, mask_path, load_args.max_resolution).await?;
+        let load_img = LoadImage::new(vfs.clone(), &path, mask_path, load_args.max_resolution)
+            .await?
+            .with_depth(depth_path, load_args.depth_scale);
 
-        let view = SceneView {
-            camera,
-            image: load_img,
-        };
+        let view = SceneView::new(load_img, camera);
 
         if let Some(eval_period) = load_args.eval_split_every {
             if i % eval_period == 0 {
@@ -88,17 +88,26 @@ pub async fn load_dataset(
     Ok((init_splat, dataset))
 }
 
-fn find_mask_path(vfs: &BrushVfs, path: &Path) -> Option<PathBuf> {
+// Find a file with the same name as the image, in a directory next to the image directory.
+fn find_sibling_path(vfs: &BrushVfs, path: &Path, dir_name: &str) -> Option<PathBuf> {
     let parent = path.parent()?.clean();
     let file_stem = path.file_stem()?.to_str()?;
-    let masks_dir = parent.parent()?.join("masks").clean();
+    let sibling_dir = parent.parent()?.join(dir_name).clean();
     for candidate in vfs.files_with_stem(file_stem) {
         let Some(file_parent) = candidate.parent() else {
             continue;
         };
-        if file_parent == masks_dir {
+        if file_parent == sibling_dir {
             return Some(candidate);
         }
     }
     None
 }
+
+fn find_mask_path(vfs: &BrushVfs, path: &Path) -> Option<PathBuf> {
+    find_sibling_path(vfs, path, "masks")
+}
+
+fn find_depth_path(vfs: &BrushVfs, path: &Path) -> Option<PathBuf> {
+    find_sibling_path(vfs, path, "depths")
+}
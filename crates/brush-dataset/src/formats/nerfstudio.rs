@@ -1,22 +1,21 @@
 use super::FormatError;
-use super::find_mask_path;
+use super::{find_depth_path, find_mask_path};
 use crate::{
     Dataset,
     config::LoadDataseConfig,
     scene::{LoadImage, SceneView},
 };
 use brush_render::camera::fov_to_focal;
-use brush_render::camera::{Camera, focal_to_fov};
+use brush_render::camera::{Camera, Distortion, focal_to_fov};
 use brush_serde::{SplatMessage, load_splat_from_ply};
 use brush_vfs::BrushVfs;
 use burn::backend::wgpu::WgpuDevice;
-use std::path::Path;
+use std::path::{Component, Path};
 use std::sync::Arc;
 use tokio::io::AsyncReadExt;
 use tokio_with_wasm::alias as tokio_wasm;
 
 #[derive(serde::Deserialize, Clone)]
-#[allow(unused)] // not reading camera distortions yet.
 struct JsonScene {
     // Horizontal FOV.
     camera_angle_x: Option<f64>,
@@ -28,7 +27,7 @@ struct JsonScene {
     /// Focal length y
     fl_y: Option<f64>,
 
-    // Not really used atm.
+    /// Camera model, eg. `OPENCV` or `OPENCV_FISHEYE`.
     camera_model: Option<String>,
     // Nerfstudio doesn't mention this in their format? But fine to include really.
     ply_file_path: Option<String>,
@@ -46,20 +45,25 @@ struct JsonScene {
     k1: Option<f64>,
     /// Second radial distortion parameter used by [`OPENCV`, `OPENCV_FISHEYE`]
     k2: Option<f64>,
-    /// Third radial distortion parameter used by [`OPENCV_FISHEYE`]
+    /// Third radial distortion parameter used by [`OPENCV`, `OPENCV_FISHEYE`]
     k3: Option<f64>,
-    /// Fourth radial distortion parameter used by [`OPENCV_FISHEYE`]
+    /// Fourth radial distortion parameter used by [`OPENCV_FISHEYE`, `FULL_OPENCV`]
     k4: Option<f64>,
+    /// Fifth and sixth radial distortion parameters used by [`FULL_OPENCV`]
+    k5: Option<f64>,
+    k6: Option<f64>,
     /// First tangential distortion parameter used by [`OPENCV`]
     p1: Option<f64>,
     /// Second tangential distortion parameter used by [`OPENCV`]
     p2: Option<f64>,
 
+    /// Scale to convert depth maps to scene units.
+    depth_unit_scale_factor: Option<f32>,
+
     frames: Vec<FrameData>,
 }
 
 #[derive(serde::Deserialize, Clone)]
-#[allow(unused)] // not reading camera distortions yet.
 struct FrameData {
     // Horizontal FOV.
     camera_angle_x: Option<f64>,
@@ -80,15 +84,17 @@ struct FrameData {
     /// Image height. Should be an integer but read as float, fine to truncate.
     h: Option<f64>,
 
-    // Nb: These are unused currently until we can optimize distorted cameras.
     /// First radial distortion parameter used by [`OPENCV`, `OPENCV_FISHEYE`]
     k1: Option<f64>,
     /// Second radial distortion parameter used by [`OPENCV`, `OPENCV_FISHEYE`]
     k2: Option<f64>,
-    /// Third radial distortion parameter used by [`OPENCV_FISHEYE`]
+    /// Third radial distortion parameter used by [`OPENCV`, `OPENCV_FISHEYE`]
     k3: Option<f64>,
-    /// Fourth radial distortion parameter used by [`OPENCV_FISHEYE`]
+    /// Fourth radial distortion parameter used by [`OPENCV_FISHEYE`, `FULL_OPENCV`]
     k4: Option<f64>,
+    /// Fifth and sixth radial distortion parameters used by [`FULL_OPENCV`]
+    k5: Option<f64>,
+    k6: Option<f64>,
     /// First tangential distortion parameter used by [`OPENCV`]
     p1: Option<f64>,
     /// Second tangential distortion parameter used by [`OPENCV`]
@@ -96,6 +102,39 @@ struct FrameData {
 
     transform_matrix: Vec<Vec<f32>>,
     file_path: String,
+    depth_file_path: Option<String>,
+}
+
+// Read the lens distortion of a frame, falling back to the parameters of the scene.
+fn frame_distortion(scene: &JsonScene, frame: &FrameData) -> Option<Distortion> {
+    let k = |frame_k: Option<f64>, scene_k: Option<f64>| frame_k.or(scene_k).unwrap_or(0.0) as f32;
+    let k1 = k(frame.k1, scene.k1);
+    let k2 = k(frame.k2, scene.k2);
+    let k3 = k(frame.k3, scene.k3);
+    let k4 = k(frame.k4, scene.k4);
+    let k5 = k(frame.k5, scene.k5);
+    let k6 = k(frame.k6, scene.k6);
+    let p1 = k(frame.p1, scene.p1);
+    let p2 = k(frame.p2, scene.p2);
+
+    match scene.camera_model.as_deref() {
+        None | Some("PINHOLE" | "SIMPLE_PINHOLE") => None,
+        Some("OPENCV") => Some(Distortion::OpenCv {
+            radial: [k1, k2, k3, 0.0, 0.0, 0.0],
+            tangential: [p1, p2],
+        }),
+        Some("FULL_OPENCV") => Some(Distortion::OpenCv {
+            radial: [k1, k2, k3, k4, k5, k6],
+            tangential: [p1, p2],
+        }),
+        Some("OPENCV_FISHEYE") => Some(Distortion::Fisheye {
+            radial: [k1, k2, k3, k4],
+        }),
+        Some(model) => {
+            log::warn!("Camera model {model} is not supported, ignoring lens distortion");
+            None
+        }
+    }
 }
 
 async fn read_transforms_file(
@@ -131,8 +170,18 @@ async fn read_transforms_file(
             path = path.with_extension("png");
         }
         let mask_path = find_mask_path(&vfs, &path);
-
-        let image = LoadImage::new(vfs.clone(), &path, mask_path, load_args.max_resolution).await;
+        let depth_path = frame
+            .depth_file_path
+            .as_ref()
+            .map(|p| transforms_path.parent().expect("unreachable").join(p))
+            .or_else(|| find_depth_path(&vfs, &path));
+        let depth_scale = scene
+            .depth_unit_scale_factor
+            .unwrap_or(load_args.depth_scale);
+
+        let image = LoadImage::new(vfs.clone(), &path, mask_path, load_args.max_resolution)
+            .await
+            .map(|image| image.with_depth(depth_path, depth_scale));
 
         let image = match image {
             Ok(image) => image,
@@ -178,15 +227,271 @@ async fn read_transforms_file(
 
         let cuv = glam::vec2((cx / w as f64) as f32, (cy / h as f64) as f32);
 
-        let view = SceneView {
-            image,
-            camera: Camera::new(translation, rotation, fovx, fovy, cuv),
-        };
+        let camera = Camera::new(translation, rotation, fovx, fovy, cuv)
+            .with_distortion(frame_distortion(&scene, frame));
+        let view = SceneView::new(image, camera);
         results.push(view);
     }
     Ok(results)
 }
 
+#[derive(serde::Serialize)]
+struct ExportFrame {
+    file_path: String,
+    transform_matrix: [[f32; 4]; 4],
+    fl_x: f64,
+    fl_y: f64,
+    cx: f64,
+    cy: f64,
+    w: u32,
+    h: u32,
+    #[serde(flatten)]
+    distortion: ExportDistortion,
+}
+
+#[derive(serde::Serialize, Default)]
+struct ExportDistortion {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    k1: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    k2: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    k3: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    k4: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    k5: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    k6: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    p1: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    p2: Option<f32>,
+}
+
+impl ExportDistortion {
+    fn new(distortion: Option<&Distortion>) -> Self {
+        match distortion {
+            None => Self::default(),
+            Some(Distortion::OpenCv { radial, tangential }) => Self {
+                k1: Some(radial[0]),
+                k2: Some(radial[1]),
+                k3: Some(radial[2]),
+                k4: Some(radial[3]),
+                k5: Some(radial[4]),
+                k6: Some(radial[5]),
+                p1: Some(tangential[0]),
+                p2: Some(tangential[1]),
+            },
+            Some(Distortion::Fisheye { radial }) => Self {
+                k1: Some(radial[0]),
+                k2: Some(radial[1]),
+                k3: Some(radial[2]),
+                k4: Some(radial[3]),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct ExportScene {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    camera_model: Option<&'static str>,
+    frames: Vec<ExportFrame>,
+}
+
+// The nerfstudio camera model that can describe the lens distortion of all cameras.
+fn export_camera_model(cameras: &[Camera]) -> Result<Option<&'static str>, FormatError> {
+    let fisheye = cameras
+        .iter()
+        .filter(|c| matches!(c.distortion, Some(Distortion::Fisheye { .. })))
+        .count();
+    if fisheye > 0 {
+        // A fisheye model without coefficients still distorts, so all cameras need to be fisheyes.
+        return if fisheye == cameras.len() {
+            Ok(Some("OPENCV_FISHEYE"))
+        } else {
+            Err(FormatError::InvalidCamera(
+                "Can't export a mix of fisheye and other cameras".to_owned(),
+            ))
+        };
+    }
+
+    let radial = cameras.iter().filter_map(|c| match c.distortion {
+        Some(Distortion::OpenCv { radial, .. }) => Some(radial),
+        _ => None,
+    });
+    let mut model = None;
+    for radial in radial {
+        if radial[3..].iter().any(|&k| k != 0.0) {
+            return Ok(Some("FULL_OPENCV"));
+        }
+        model = Some("OPENCV");
+    }
+    Ok(model)
+}
+
+// A path in the dataset, relative to the root of the dataset and with forward slashes.
+fn dataset_relative_path(path: &Path) -> String {
+    path.components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => Some(part.to_string_lossy()),
+            Component::ParentDir => Some("..".into()),
+            Component::Prefix(_) | Component::RootDir | Component::CurDir => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Write views as a nerfstudio `transforms.json`, with `cameras` replacing the cameras of the views.
+///
+/// This is used to export camera poses that were refined during training. Image paths are relative
+/// to the root of the dataset, so the file can be placed there to load the dataset with these poses.
+pub fn transforms_json(views: &[SceneView], cameras: &[Camera]) -> Result<String, FormatError> {
+    let camera_model = export_camera_model(cameras)?;
+    let frames = views
+        .iter()
+        .zip(cameras)
+        .map(|(view, camera)| {
+            let size = view.image.dimensions();
+            let focal = camera.focal(size);
+            let center = camera.center(size);
+
+            // Undo the basis swap done when reading transforms.
+            let mut transform =
+                glam::Mat4::from_rotation_translation(camera.rotation, camera.position);
+            transform.y_axis *= -1.0;
+            transform.z_axis *= -1.0;
+
+            ExportFrame {
+                file_path: dataset_relative_path(&view.image.path),
+                transform_matrix: transform.transpose().to_cols_array_2d(),
+                fl_x: focal.x as f64,
+                fl_y: focal.y as f64,
+                cx: center.x as f64,
+                cy: center.y as f64,
+                w: size.x,
+                h: size.y,
+                distortion: ExportDistortion::new(camera.distortion.as_ref()),
+            }
+        })
+        .collect();
+
+    Ok(serde_json::to_string_pretty(&ExportScene {
+        camera_model,
+        frames,
+    })?)
+}
+
+#[derive(serde::Deserialize)]
+struct CameraPathKeyframe {
+    /// Row major camera-to-world transform.
+    camera_to_world: Vec<f32>,
+    /// Vertical FOV in degrees.
+    fov: f64,
+    aspect: f64,
+}
+
+#[derive(serde::Deserialize)]
+struct CameraPath {
+    /// Camera type, eg. `perspective` or `equirectangular`.
+    camera_type: Option<String>,
+    render_width: Option<u32>,
+    render_height: Option<u32>,
+    camera_path: Vec<CameraPathKeyframe>,
+}
+
+#[derive(serde::Deserialize)]
+struct DataparserTransforms {
+    /// Row major 3x4 transform applied to the poses of the dataset.
+    transform: Vec<Vec<f32>>,
+    /// Scale applied to the positions after the transform.
+    scale: f32,
+}
+
+impl DataparserTransforms {
+    // Undo the normalization of a camera-to-world transform.
+    fn to_dataset_space(&self, transform: glam::Mat4) -> Result<glam::Mat4, FormatError> {
+        let rows: Vec<f32> = self.transform.iter().flatten().copied().collect();
+        if rows.len() != 12 || self.scale <= 0.0 {
+            return Err(FormatError::InvalidCamera(
+                "dataparser transform must be a 3x4 matrix with a positive scale".to_owned(),
+            ));
+        }
+        let mut normalize = [0.0; 16];
+        normalize[..12].copy_from_slice(&rows);
+        normalize[15] = 1.0;
+        let normalize = glam::Mat4::from_cols_array(&normalize).transpose();
+
+        let mut transform = transform;
+        transform.w_axis = (transform.w_axis.truncate() / self.scale).extend(1.0);
+        Ok(normalize.inverse() * transform)
+    }
+}
+
+/// Read the cameras of a nerfstudio `camera_path.json`, as written by the nerfstudio viewer.
+///
+/// The nerfstudio viewer works in the space of its dataparser, which centers and scales the
+/// dataset. Pass the contents of the `dataparser_transforms.json` of the nerfstudio run to map the
+/// poses back to the space of the frames in the `transforms.json`. Without it, the poses are used
+/// as they are, and only line up with the scene if the dataparser didn't change the poses.
+///
+/// Returns the cameras, and the resolution to render them at if the file has one.
+pub fn camera_path_json(
+    json: &str,
+    dataparser_transforms: Option<&str>,
+) -> Result<(Vec<Camera>, Option<glam::UVec2>), FormatError> {
+    let path: CameraPath = serde_json::from_str(json)?;
+    let dataparser_transforms: Option<DataparserTransforms> = dataparser_transforms
+        .map(serde_json::from_str)
+        .transpose()?;
+
+    if let Some(camera_type) = path.camera_type.as_deref()
+        && camera_type != "perspective"
+    {
+        return Err(FormatError::InvalidCamera(format!(
+            "Camera type {camera_type} is not supported, only perspective camera paths are"
+        )));
+    }
+
+    let cameras = path
+        .camera_path
+        .iter()
+        .map(|keyframe| {
+            if keyframe.camera_to_world.len() != 16 {
+                return Err(FormatError::InvalidCamera(
+                    "camera_to_world must be a 4x4 matrix".to_owned(),
+                ));
+            }
+            let mut transform = glam::Mat4::from_cols_slice(&keyframe.camera_to_world).transpose();
+            if let Some(dataparser) = &dataparser_transforms {
+                transform = dataparser.to_dataset_space(transform)?;
+            }
+            // Swap basis like the transforms of a transforms.json.
+            transform.y_axis *= -1.0;
+            transform.z_axis *= -1.0;
+            let (_, rotation, translation) = transform.to_scale_rotation_translation();
+
+            let fov_y = keyframe.fov.to_radians();
+            let fov_x = 2.0 * ((fov_y / 2.0).tan() * keyframe.aspect).atan();
+            Ok(Camera::new(
+                translation,
+                rotation,
+                fov_x,
+                fov_y,
+                glam::vec2(0.5, 0.5),
+            ))
+        })
+        .collect::<Result<_, _>>()?;
+
+    let size = path
+        .render_width
+        .zip(path.render_height)
+        .map(|(w, h)| glam::uvec2(w, h));
+    Ok((cameras, size))
+}
+
 pub async fn read_dataset(
     vfs: Arc<BrushVfs>,
     load_args: &LoadDataseConfig,
@@ -295,3 +600,124 @@ async fn read_dataset_inner(
 
     Ok((init_splat, dataset))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera(distortion: Option<Distortion>) -> Camera {
+        Camera::new(
+            glam::Vec3::ZERO,
+            glam::Quat::IDENTITY,
+            0.8,
+            0.6,
+            glam::vec2(0.5, 0.5),
+        )
+        .with_distortion(distortion)
+    }
+
+    #[test]
+    fn exports_camera_model() {
+        let opencv = Distortion::OpenCv {
+            radial: [0.1, 0.0, 0.0, 0.0, 0.0, 0.0],
+            tangential: [0.01, 0.0],
+        };
+        let full_opencv = Distortion::OpenCv {
+            radial: [0.1, 0.0, 0.0, 0.2, 0.0, 0.0],
+            tangential: [0.0, 0.0],
+        };
+        let fisheye = Distortion::Fisheye {
+            radial: [0.1, 0.0, 0.0, 0.0],
+        };
+
+        let model = |cameras: &[Camera]| export_camera_model(cameras).ok();
+        assert_eq!(model(&[camera(None)]), Some(None));
+        assert_eq!(
+            model(&[camera(None), camera(Some(opencv))]),
+            Some(Some("OPENCV"))
+        );
+        assert_eq!(
+            model(&[camera(Some(opencv)), camera(Some(full_opencv))]),
+            Some(Some("FULL_OPENCV"))
+        );
+        assert_eq!(
+            model(&[camera(Some(fisheye))]),
+            Some(Some("OPENCV_FISHEYE"))
+        );
+        assert_eq!(model(&[camera(Some(fisheye)), camera(None)]), None);
+
+        let exported = ExportDistortion::new(Some(&opencv));
+        assert_eq!(exported.k1, Some(0.1));
+        assert_eq!(exported.p1, Some(0.01));
+    }
+
+    #[test]
+    fn exports_relative_paths() {
+        assert_eq!(
+            dataset_relative_path(Path::new("/images/frame_001.png")),
+            "images/frame_001.png"
+        );
+        assert_eq!(
+            dataset_relative_path(Path::new("./scene/images/a.jpg")),
+            "scene/images/a.jpg"
+        );
+    }
+
+    #[test]
+    fn reads_camera_path() {
+        let json = r#"{
+            "camera_type": "perspective",
+            "render_width": 640,
+            "render_height": 480,
+            "camera_path": [{
+                "camera_to_world": [1, 0, 0, 1, 0, 1, 0, 2, 0, 0, 1, 3, 0, 0, 0, 1],
+                "fov": 90,
+                "aspect": 2.0
+            }]
+        }"#;
+        let (cameras, size) = camera_path_json(json, None).expect("Failed to read camera path");
+        assert_eq!(size, Some(glam::uvec2(640, 480)));
+        assert_eq!(cameras.len(), 1);
+
+        let camera = &cameras[0];
+        assert!((camera.position - glam::vec3(1.0, 2.0, 3.0)).length() < 1e-6);
+        // Nerfstudio cameras look along -z, brush cameras along +z.
+        let forward = camera.rotation * glam::Vec3::Z;
+        assert!((forward - glam::Vec3::NEG_Z).length() < 1e-6);
+        assert!((camera.fov_y - std::f64::consts::FRAC_PI_2).abs() < 1e-6);
+        assert!((camera.fov_x - 2.0 * 2.0f64.atan()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rejects_equirect_camera_path() {
+        let json = r#"{ "camera_type": "equirectangular", "camera_path": [] }"#;
+        assert!(camera_path_json(json, None).is_err());
+    }
+
+    #[test]
+    fn applies_dataparser_transforms() {
+        let json = r#"{
+            "camera_path": [{
+                "camera_to_world": [1, 0, 0, 2, 0, 1, 0, 4, 0, 0, 1, 6, 0, 0, 0, 1],
+                "fov": 90,
+                "aspect": 1.0
+            }]
+        }"#;
+        // Rotates 90 degrees around z, shifts by (1, 0, 0) and scales by 2.
+        let dataparser = r#"{
+            "transform": [[0, -1, 0, 1], [1, 0, 0, 0], [0, 0, 1, 0]],
+            "scale": 2.0
+        }"#;
+        let (cameras, _) =
+            camera_path_json(json, Some(dataparser)).expect("Failed to read camera path");
+        let camera = &cameras[0];
+
+        // Undo the scale: (1, 2, 3), the shift: (0, 2, 3), then the rotation: (2, 0, 3).
+        assert!((camera.position - glam::vec3(2.0, 0.0, 3.0)).length() < 1e-5);
+        // The camera is rotated back as well, so it looks along -z and its x axis points along -y.
+        let forward = camera.rotation * glam::Vec3::Z;
+        assert!((forward - glam::Vec3::NEG_Z).length() < 1e-5);
+        let right = camera.rotation * glam::Vec3::X;
+        assert!((right - glam::Vec3::NEG_Y).length() < 1e-5);
+    }
+}
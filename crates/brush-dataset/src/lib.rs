@@ -6,10 +6,14 @@ pub mod scene_loader;
 
 mod formats;
 
-pub use formats::load_dataset;
+pub use formats::{
+    load_dataset,
+    nerfstudio::{camera_path_json, transforms_json},
+};
 
+use brush_render::camera::Camera;
 use core::f32;
-use glam::{Mat3, Mat4, Vec3};
+use glam::{Mat3, Mat4, Quat, Vec3};
 use scene::Scene;
 use scene::SceneView;
 
@@ -181,4 +185,55 @@ impl Dataset {
 
         Vec3::new(-transform.col(0).z, -transform.col(1).z, transform.col(2).z)
     }
+
+    /// Cameras on a circle around the views of the dataset, all looking at its center.
+    ///
+    /// The circle is level with respect to [`Self::estimate_up`], and goes through the average
+    /// distance of the views to their center. The cameras use the field of view of the first
+    /// training view.
+    pub fn orbit_cameras(&self, frames: usize) -> Vec<Camera> {
+        let views: Vec<_> = self
+            .train
+            .views
+            .iter()
+            .chain(self.eval.iter().flat_map(|e| e.views.as_slice()))
+            .collect();
+        let Some(first) = views.first() else {
+            return vec![];
+        };
+
+        let up = self.estimate_up().normalize();
+        let center = views.iter().map(|v| v.camera.position).sum::<Vec3>() / views.len() as f32;
+        let radius = views
+            .iter()
+            .map(|v| {
+                (v.camera.position - center)
+                    .reject_from_normalized(up)
+                    .length()
+            })
+            .sum::<f32>()
+            / views.len() as f32;
+        let (axis_a, axis_b) = up.any_orthonormal_pair();
+
+        (0..frames)
+            .map(|i| {
+                let angle = i as f32 / frames as f32 * std::f32::consts::TAU;
+                let position = center + (axis_a * angle.cos() + axis_b * angle.sin()) * radius;
+
+                // Cameras look along +z, with +y pointing down.
+                let forward = (center - position).normalize();
+                let right = (-up).cross(forward).normalize();
+                let down = forward.cross(right);
+                let rotation = Quat::from_mat3(&Mat3::from_cols(right, down, forward));
+
+                Camera::new(
+                    position,
+                    rotation,
+                    first.camera.fov_x,
+                    first.camera.fov_y,
+                    glam::vec2(0.5, 0.5),
+                )
+            })
+            .collect()
+    }
 }
@@ -1,11 +1,16 @@
-use brush_render::{bounding_box::BoundingBox, camera::Camera};
+use brush_render::{
+    bounding_box::BoundingBox,
+    camera::{Camera, Distortion},
+};
 use brush_vfs::BrushVfs;
 use burn::{
     prelude::Backend,
     tensor::{Tensor, TensorData},
 };
 use glam::{Affine3A, Vec3, vec3};
-use image::{ColorType, DynamicImage, ImageDecoder, ImageReader};
+use image::{
+    ColorType, DynamicImage, GenericImageView, ImageBuffer, ImageDecoder, ImageReader, Pixel,
+};
 use std::{
     io::Cursor,
     path::{Path, PathBuf},
@@ -21,11 +26,20 @@ pub enum ViewType {
     Test,
 }
 
+/// A single channel depth map.
+pub type DepthImage = image::ImageBuffer<image::Luma<f32>, Vec<f32>>;
+
 #[derive(Clone, Debug)]
 pub struct LoadImage {
     pub vfs: Arc<BrushVfs>,
     pub path: PathBuf,
     pub mask_path: Option<PathBuf>,
+    /// Path of a depth map that goes with this image.
+    pub depth_path: Option<PathBuf>,
+    // Converts values of 16 bit depth maps to scene units.
+    depth_scale: f32,
+    /// Camera with lens distortion this image was taken with. The image is undistorted on load.
+    undistort_camera: Option<Camera>,
     color: image::ColorType,
     size: glam::UVec2,
     max_resolution: u32,
@@ -80,12 +94,22 @@ impl LoadImage {
             vfs,
             path: path.to_path_buf(),
             mask_path,
+            depth_path: None,
+            depth_scale: 1.0,
+            undistort_camera: None,
             max_resolution,
             size: data.0,
             color: data.1,
         })
     }
 
+    /// Attach a depth map to this image. Values of 16 bit depth maps are multiplied by `depth_scale`.
+    pub fn with_depth(mut self, depth_path: Option<PathBuf>, depth_scale: f32) -> Self {
+        self.depth_path = depth_path;
+        self.depth_scale = depth_scale;
+        self
+    }
+
     pub fn has_alpha(&self) -> bool {
         self.color.has_alpha() || self.is_masked()
     }
@@ -147,18 +171,94 @@ impl LoadImage {
             }
             img = masked_img.into();
         }
-        if img.width() <= self.max_resolution && img.height() <= self.max_resolution {
-            return Ok(img);
+        if img.width() > self.max_resolution || img.height() > self.max_resolution {
+            img = img.resize(
+                self.max_resolution,
+                self.max_resolution,
+                image::imageops::FilterType::Triangle,
+            );
+        }
+        if let Some((camera, distortion)) = self.undistortion() {
+            img = undistort(
+                &img.into_rgba8(),
+                camera,
+                distortion,
+                image::Rgba([0; 4]),
+                |img, p| image::imageops::interpolate_bilinear(img, p.x - 0.5, p.y - 0.5),
+            )
+            .into();
         }
-        Ok(img.resize(
-            self.max_resolution,
-            self.max_resolution,
-            image::imageops::FilterType::Triangle,
-        ))
+        Ok(img)
     }
 
+    /// Load the depth map of this image, resized to the dimensions of the image.
+    ///
+    /// Pixels without a known depth are 0.
+    #[instrument(name = "load_scene_depth")]
+    pub async fn load_depth(&self) -> image::ImageResult<Option<DepthImage>> {
+        let Some(depth_path) = &self.depth_path else {
+            return Ok(None);
+        };
+
+        let mut depth_bytes = vec![];
+        self.vfs
+            .reader_at_path(depth_path)
+            .await?
+            .read_to_end(&mut depth_bytes)
+            .await?;
+        let depth_img = image::load_from_memory(&depth_bytes)?;
+        let (w, h) = (depth_img.width(), depth_img.height());
+
+        let values = match depth_img {
+            // 16 bit depth maps store depth in some fixed unit, eg. millimeters.
+            DynamicImage::ImageLuma16(buf) => buf
+                .into_raw()
+                .into_iter()
+                .map(|d| d as f32 * self.depth_scale)
+                .collect(),
+            // Otherwise treat the depth as relative, eg. from a monocular depth estimator.
+            img => img.to_luma32f().into_raw(),
+        };
+        let depth = DepthImage::from_raw(w, h, values).expect("Depth map has the wrong size");
+
+        let dim = self.dimensions();
+        // Nearest neighbour sampling, as blending across depth edges gives depths that don't exist.
+        let depth = if depth.dimensions() == (dim.x, dim.y) {
+            depth
+        } else {
+            image::imageops::resize(&depth, dim.x, dim.y, image::imageops::FilterType::Nearest)
+        };
+        let Some((camera, distortion)) = self.undistortion() else {
+            return Ok(Some(depth));
+        };
+        Ok(Some(undistort(
+            &depth,
+            camera,
+            distortion,
+            image::Luma([0.0]),
+            |depth, p| {
+                let (x, y) = (p.x.floor(), p.y.floor());
+                (x >= 0.0 && y >= 0.0 && depth.in_bounds(x as u32, y as u32))
+                    .then(|| *depth.get_pixel(x as u32, y as u32))
+            },
+        )))
+    }
+
+    fn undistortion(&self) -> Option<(&Camera, &Distortion)> {
+        let camera = self.undistort_camera.as_ref()?;
+        Some((camera, camera.distortion.as_ref()?))
+    }
+
+    /// Whether the alpha channel of the image is a mask of pixels to train on.
+    ///
+    /// This is the case for images with a mask, and for undistorted images where pixels outside
+    /// of the original image are masked out.
     pub fn is_masked(&self) -> bool {
-        self.mask_path.is_some()
+        self.mask_path.is_some() || self.undistort_camera.is_some()
+    }
+
+    pub fn has_depth(&self) -> bool {
+        self.depth_path.is_some()
     }
 
     pub fn aspect_ratio(&self) -> f32 {
@@ -167,12 +267,49 @@ impl LoadImage {
     }
 }
 
+/// Undistorts an image taken with a camera with lens distortion.
+///
+/// The undistorted image is seen through the same camera without distortion. Pixels are looked
+/// up with `sample` at a position in pixels of the distorted image, and pixels that fall outside
+/// of the distorted image are set to `empty`.
+fn undistort<P: Pixel>(
+    img: &ImageBuffer<P, Vec<P::Subpixel>>,
+    camera: &Camera,
+    distortion: &Distortion,
+    empty: P,
+    sample: impl Fn(&ImageBuffer<P, Vec<P::Subpixel>>, glam::Vec2) -> Option<P>,
+) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    let size = glam::uvec2(img.width(), img.height());
+    let focal = camera.focal(size);
+    let center = camera.center(size);
+    ImageBuffer::from_fn(size.x, size.y, |x, y| {
+        let p = (glam::vec2(x as f32, y as f32) + 0.5 - center) / focal;
+        sample(img, distortion.distort(p) * focal + center).unwrap_or(empty)
+    })
+}
+
 #[derive(Clone)]
 pub struct SceneView {
     pub image: LoadImage,
     pub camera: Camera,
 }
 
+impl SceneView {
+    /// Create a view of an image taken with `camera`.
+    ///
+    /// When the camera has lens distortion, the image is undistorted on load, and the view
+    /// uses the same camera without distortion.
+    pub fn new(mut image: LoadImage, camera: Camera) -> Self {
+        if camera.distortion.is_some_and(|d| !d.is_identity()) {
+            image.undistort_camera = Some(camera.clone());
+        }
+        Self {
+            image,
+            camera: camera.with_distortion(None),
+        }
+    }
+}
+
 // Encapsulates a multi-view scene including cameras and the splats.
 // Also provides methods for checkpointing the training process.
 #[derive(Clone)]
@@ -268,11 +405,23 @@ pub fn sample_to_tensor<B: Backend>(sample: &DynamicImage, device: &B::Device) -
     Tensor::from_data(data, device)
 }
 
+pub fn depth_to_tensor<B: Backend>(depth: &DepthImage, device: &B::Device) -> Tensor<B, 2> {
+    let (w, h) = depth.dimensions();
+    Tensor::from_data(
+        TensorData::new(depth.as_raw().clone(), [h as usize, w as usize]),
+        device,
+    )
+}
+
 #[derive(Clone, Debug)]
 pub struct SceneBatch<B: Backend> {
     pub img_tensor: Tensor<B, 3>,
     pub alpha_is_mask: bool,
     pub camera: Camera,
+    /// Index of the view in the [`Scene`] this batch was loaded from.
+    pub view_index: usize,
+    /// [H, W] depth map of the view, 0 where the depth is unknown.
+    pub depth_tensor: Option<Tensor<B, 2>>,
 }
 
 impl<B: Backend> SceneBatch<B> {
@@ -8,14 +8,66 @@ use tokio::sync::{RwLock, mpsc};
 use tokio_with_wasm::alias as tokio_wasm;
 use tracing::{Instrument, trace_span};
 
-use crate::scene::{Scene, SceneBatch, sample_to_tensor, view_to_sample_image};
+use crate::scene::{
+    DepthImage, Scene, SceneBatch, depth_to_tensor, sample_to_tensor, view_to_sample_image,
+};
 
 pub struct SceneLoader<B: Backend> {
     receiver: Receiver<SceneBatch<B>>,
+    state: LoaderState,
+}
+
+/// Where a [`SceneLoader`] is in its sequence of views.
+///
+/// The order of the views only depends on the seed, so a loader created from the state of another
+/// loader continues with the exact same views.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoaderState {
+    pub seed: u64,
+    /// Nr. of batches handed out so far.
+    pub position: u64,
+}
+
+// The view at some position in the sequence of a loader. Each pass over the dataset uses its own
+// shuffled order.
+struct ViewOrder {
+    seed: u64,
+    num_views: usize,
+    epoch: Option<u64>,
+    order: Vec<usize>,
+}
+
+impl ViewOrder {
+    fn view_at(&mut self, position: u64) -> usize {
+        assert!(self.num_views > 0, "Need at least one view in dataset");
+        let epoch = position / self.num_views as u64;
+        if self.epoch != Some(epoch) {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(self.seed.wrapping_add(epoch));
+            self.order = (0..self.num_views).collect();
+            self.order.shuffle(&mut rng);
+            self.epoch = Some(epoch);
+        }
+        self.order[(position % self.num_views as u64) as usize]
+    }
+}
+
+struct LoadedView {
+    sample: DynamicImage,
+    depth: Option<DepthImage>,
+}
+
+impl LoadedView {
+    fn size_bytes(&self) -> usize {
+        self.sample.as_bytes().len()
+            + self
+                .depth
+                .as_ref()
+                .map_or(0, |d| d.as_raw().len() * size_of::<f32>())
+    }
 }
 
 struct ImageCache {
-    states: Vec<Option<Arc<DynamicImage>>>,
+    states: Vec<Option<Arc<LoadedView>>>,
     max_size: usize,
     size: usize,
 }
@@ -39,12 +91,12 @@ impl ImageCache {
         }
     }
 
-    fn try_get(&self, index: usize) -> Option<Arc<DynamicImage>> {
+    fn try_get(&self, index: usize) -> Option<Arc<LoadedView>> {
         self.states[index].clone()
     }
 
-    fn insert(&mut self, index: usize, data: Arc<DynamicImage>) {
-        let data_size_mb = data.as_bytes().len() / (1024 * 1024);
+    fn insert(&mut self, index: usize, data: Arc<LoadedView>) {
+        let data_size_mb = data.size_bytes() / (1024 * 1024);
 
         if self.size + data_size_mb < self.max_size && self.states[index].is_none() {
             self.states[index] = Some(data);
@@ -55,10 +107,12 @@ impl ImageCache {
 
 impl<B: Backend> SceneLoader<B> {
     pub fn new(scene: &Scene, seed: u64, device: &B::Device) -> Self {
-        let num_img_queue = 32;
+        Self::from_state(scene, LoaderState { seed, position: 0 }, device)
+    }
 
-        // The bounded size == number of batches to prefetch.
-        let (send_img, mut rec_imag) = mpsc::channel(num_img_queue);
+    /// Create a loader that continues from `state`.
+    pub fn from_state(scene: &Scene, state: LoaderState, device: &B::Device) -> Self {
+        let num_img_queue = 32;
 
         // On wasm, there is little point to spawning multiple of these. In theory there would be
         // IF file reading truly was async, but since the zip archive is just in memory it isn't really
@@ -71,54 +125,64 @@ impl<B: Backend> SceneLoader<B> {
                 .unwrap_or(8)
                 // Don't need more threads than the image queue can hold, most
                 // threads would just sit around idling!
-                .min(num_img_queue) as u64
+                .min(num_img_queue)
         };
         let num_views = scene.views.len();
 
         let load_cache = Arc::new(RwLock::new(ImageCache::new(MAX_CACHE_MB, num_views)));
 
+        // Each loader handles every n-th position, and sends its views over its own channel. That
+        // way the views can be put back in order, no matter which loader finishes first.
+        let mut img_receivers = vec![];
+
         for i in 0..parallelism {
-            let mut rng = rand::rngs::StdRng::seed_from_u64(seed + i);
-            let send_img = send_img.clone();
-            let views = scene.views.clone();
+            // The bounded size == number of images to prefetch.
+            let (send_img, rec_img) = mpsc::channel((num_img_queue / parallelism).max(1));
+            img_receivers.push(rec_img);
 
+            let views = scene.views.clone();
             let load_cache = load_cache.clone();
+            let mut view_order = ViewOrder {
+                seed: state.seed,
+                num_views,
+                epoch: None,
+                order: vec![],
+            };
 
             tokio_wasm::spawn(async move {
-                let mut shuf_indices = vec![];
+                let mut position = state.position + i as u64;
 
                 loop {
                     let load = async {
-                        let index = shuf_indices.pop().unwrap_or_else(|| {
-                            shuf_indices = (0..num_views).collect();
-                            shuf_indices.shuffle(&mut rng);
-                            shuf_indices
-                                .pop()
-                                .expect("Need at least one view in dataset")
-                        });
-
+                        let index = view_order.view_at(position);
                         let view = &views[index];
 
-                        let sample = if let Some(image) = load_cache.read().await.try_get(index) {
-                            image
+                        let loaded = if let Some(loaded) = load_cache.read().await.try_get(index) {
+                            loaded
                         } else {
                             let image =
                                 view.image.load().await.expect(
                                     "Scene loader encountered an error while loading an image",
                                 );
+                            let depth = view.image.load_depth().await.expect(
+                                "Scene loader encountered an error while loading a depth map",
+                            );
                             // Don't premultiply the image if it's a mask - treat as fully opaque.
-                            let sample =
-                                Arc::new(view_to_sample_image(image, view.image.is_masked()));
-                            load_cache.write().await.insert(index, sample.clone());
-                            sample
+                            let loaded = Arc::new(LoadedView {
+                                sample: view_to_sample_image(image, view.image.is_masked()),
+                                depth,
+                            });
+                            load_cache.write().await.insert(index, loaded.clone());
+                            loaded
                         };
 
                         let _ = send_img
-                            .send((sample, view.image.is_masked(), view.camera.clone()))
+                            .send((loaded, view.image.is_masked(), view.camera.clone(), index))
                             .await;
                     };
 
                     load.instrument(trace_span!("SceneLoader load image")).await;
+                    position += parallelism as u64;
 
                     if send_img.is_closed() {
                         break;
@@ -130,16 +194,22 @@ impl<B: Backend> SceneLoader<B> {
 
         let device = device.clone();
         tokio_wasm::spawn(async move {
-            while let Some(rec) = rec_imag.recv().await {
-                let (sample, alpha_is_mask, camera) = rec;
+            for i in (0..parallelism).cycle() {
+                let Some(rec) = img_receivers[i].recv().await else {
+                    break;
+                };
+                let (loaded, alpha_is_mask, camera, view_index) = rec;
 
-                let img_tensor = sample_to_tensor(&sample, &device);
+                let img_tensor = sample_to_tensor(&loaded.sample, &device);
+                let depth_tensor = loaded.depth.as_ref().map(|d| depth_to_tensor(d, &device));
 
                 if send_batch
                     .send(SceneBatch {
                         img_tensor,
                         alpha_is_mask,
                         camera,
+                        view_index,
+                        depth_tensor,
                     })
                     .await
                     .is_err()
@@ -153,13 +223,54 @@ impl<B: Backend> SceneLoader<B> {
 
         Self {
             receiver: rec_batch,
+            state,
         }
     }
 
     pub async fn next_batch(&mut self) -> SceneBatch<B> {
-        self.receiver
+        let batch = self
+            .receiver
             .recv()
             .await
-            .expect("Somehow lost data loading channel!")
+            .expect("Somehow lost data loading channel!");
+        self.state.position += 1;
+        batch
+    }
+
+    /// The state to continue loading from, after the batches handed out so far.
+    pub fn state(&self) -> LoaderState {
+        self.state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view_order(seed: u64) -> ViewOrder {
+        ViewOrder {
+            seed,
+            num_views: 7,
+            epoch: None,
+            order: vec![],
+        }
+    }
+
+    #[test]
+    fn test_view_order() {
+        let mut order = view_order(3);
+        let views: Vec<_> = (0..21).map(|p| order.view_at(p)).collect();
+
+        // Every pass over the dataset visits each view once.
+        for epoch in views.chunks(7) {
+            let mut epoch = epoch.to_vec();
+            epoch.sort_unstable();
+            assert_eq!(epoch, (0..7).collect::<Vec<_>>());
+        }
+
+        // Starting halfway through gives the same views.
+        let mut resumed = view_order(3);
+        let rest: Vec<_> = (10..21).map(|p| resumed.view_at(p)).collect();
+        assert_eq!(rest, views[10..]);
     }
 }
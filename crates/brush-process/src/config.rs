@@ -1,23 +1,46 @@
 use brush_dataset::config::{LoadDataseConfig, ModelConfig};
+use brush_render::bounding_box::BoundingBox;
+use brush_serde::{ExportOptions, UpAxis};
 use brush_train::config::TrainConfig;
 use clap::{Args, Parser};
+use glam::{EulerRot, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-#[derive(Clone, Args)]
+#[derive(Clone, Args, Serialize, Deserialize)]
 pub struct ProcessConfig {
     /// Random seed.
     #[arg(long, help_heading = "Process options", default_value = "42")]
     pub seed: u64,
 
     /// Iteration to resume from
+    // Not part of config files, so loading the config of a run doesn't skip iterations.
     #[arg(long, help_heading = "Process options", default_value = "0")]
+    #[serde(skip)]
     pub start_iter: u32,
 
+    /// Resume training from a checkpoint written by a previous run. This restores the splats,
+    /// optimizer state, data order and train config of that run, and continues from the iteration
+    /// it was saved at. Of the train options, only --total-steps can be changed, to extend the run.
+    // Not part of config files, so loading the config of a run doesn't resume a stale checkpoint.
+    #[arg(long, help_heading = "Process options")]
+    #[serde(skip)]
+    pub resume: Option<String>,
+
     /// Eval every this many steps.
     #[arg(long, help_heading = "Process options", default_value = "1000")]
     pub eval_every: u32,
     /// Save the rendered eval images to disk. Uses export-path for the file location.
     #[arg(long, help_heading = "Process options", default_value = "false")]
     pub eval_save_to_disk: bool,
+    /// Also compute LPIPS when evaluating. This makes evaluation slower, and isn't supported on the
+    /// web.
+    #[arg(long, help_heading = "Process options", default_value = "false")]
+    pub eval_lpips: bool,
+    /// Compute the eval LPIPS with AlexNet instead of VGG, to match published benchmarks. This is
+    /// the path to AlexNet LPIPS weights converted with lpips-convert. Only used with --eval-lpips.
+    #[arg(long, help_heading = "Process options")]
+    pub eval_lpips_alex: Option<String>,
 
     /// Export every this many steps.
     #[arg(long, help_heading = "Process options", default_value = "5000")]
@@ -27,16 +50,123 @@ pub struct ProcessConfig {
     /// This path can be set to be relative to the CWD.
     #[arg(long, help_heading = "Process options", default_value = ".")]
     pub export_path: String,
-    /// Filename of exported ply file
+    /// Filename of exported ply file. Names ending in .compressed.ply are written as quantized
+    /// compressed plys, which are about 4x smaller. Names ending in .splat are written in the
+    /// .splat format used by many web viewers, which only keeps the base colors. Names ending in
+    /// .spz are written as gzipped SPZ files, with up to 3 degrees of SH. Names ending in .glb are
+    /// written as binary glTF files with the Khronos gaussian splatting extension.
     #[arg(
         long,
         help_heading = "Process options",
         default_value = "export_{iter}.ply"
     )]
     pub export_name: String,
+    /// Only export SH coefficients up to this degree, to make exports smaller.
+    #[arg(long, help_heading = "Process options")]
+    pub export_sh_degree: Option<u32>,
+    /// Leave out splats with an opacity below this value (between 0 and 1) from exports.
+    #[arg(long, help_heading = "Process options")]
+    pub export_min_opacity: Option<f32>,
+    /// Rotate exports so the up direction of the scene is +Y (y) or +Z (z), for tools that
+    /// expect those conventions. By default exports keep the coordinates of the input data.
+    #[arg(long, help_heading = "Process options")]
+    pub export_up: Option<UpAxis>,
+    /// Only export splats with a center inside a box, given by its minimum and maximum corners as
+    /// x,y,z. The box is in the coordinates of the input data.
+    #[arg(
+        long,
+        help_heading = "Process options",
+        value_delimiter = ',',
+        num_args = 3,
+        allow_negative_numbers = true,
+        requires = "export_box_max"
+    )]
+    pub export_box_min: Option<Vec<f32>>,
+    /// Maximum corner of the box to export splats in, see --export-box-min.
+    #[arg(
+        long,
+        help_heading = "Process options",
+        value_delimiter = ',',
+        num_args = 3,
+        allow_negative_numbers = true,
+        requires = "export_box_min"
+    )]
+    pub export_box_max: Option<Vec<f32>>,
+    /// Scale exports uniformly around the origin by this factor.
+    #[arg(long, help_heading = "Process options")]
+    pub export_scale: Option<f32>,
+    /// Rotate exports by these angles around the x, y and z axis (applied in that order), in
+    /// degrees. This rotation is applied after --export-up.
+    #[arg(
+        long,
+        help_heading = "Process options",
+        value_delimiter = ',',
+        num_args = 3,
+        allow_negative_numbers = true
+    )]
+    pub export_rotation: Option<Vec<f32>>,
+    /// Move exports by this offset as x,y,z, after scaling and rotating them.
+    #[arg(
+        long,
+        help_heading = "Process options",
+        value_delimiter = ',',
+        num_args = 3,
+        allow_negative_numbers = true
+    )]
+    pub export_translation: Option<Vec<f32>>,
+    /// Filename of the training checkpoint written next to each export. Use --resume to continue from it.
+    #[arg(
+        long,
+        help_heading = "Process options",
+        default_value = "checkpoint_{iter}.ckpt"
+    )]
+    pub checkpoint_name: String,
+}
+
+// Read a vector of 3 values from the config.
+fn config_vec3(name: &str, values: &[f32]) -> anyhow::Result<Vec3> {
+    anyhow::ensure!(values.len() == 3, "{name} must have 3 values, as x,y,z");
+    Ok(Vec3::from_slice(values))
+}
+
+impl ProcessConfig {
+    /// The options to export splats with. `up_axis` is the up direction of the splats.
+    pub fn export_options(&self, up_axis: Vec3) -> anyhow::Result<ExportOptions> {
+        let mut options = ExportOptions {
+            max_sh_degree: self.export_sh_degree,
+            min_opacity: self.export_min_opacity,
+            ..Default::default()
+        };
+        if let (Some(min), Some(max)) = (&self.export_box_min, &self.export_box_max) {
+            options.bounds = Some(BoundingBox::from_min_max(
+                config_vec3("export_box_min", min)?,
+                config_vec3("export_box_max", max)?,
+            ));
+        }
+        if let Some(scale) = self.export_scale {
+            anyhow::ensure!(scale > 0.0, "export_scale must be positive");
+            options.scale = scale;
+        }
+        if let Some(target) = self.export_up {
+            options = options.with_up_axis(up_axis, target);
+        }
+        if let Some(rotation) = &self.export_rotation {
+            let degrees = config_vec3("export_rotation", rotation)?;
+            options = options.rotated(Quat::from_euler(
+                EulerRot::ZYX,
+                degrees.z.to_radians(),
+                degrees.y.to_radians(),
+                degrees.x.to_radians(),
+            ));
+        }
+        if let Some(translation) = &self.export_translation {
+            options.translation = config_vec3("export_translation", translation)?;
+        }
+        Ok(options)
+    }
 }
 
-#[derive(Parser, Clone)]
+#[derive(Parser, Clone, Serialize, Deserialize)]
 pub struct ProcessArgs {
     #[clap(flatten)]
     pub train_config: TrainConfig,
@@ -48,6 +178,12 @@ pub struct ProcessArgs {
     pub process_config: ProcessConfig,
     #[clap(flatten)]
     pub rerun_config: RerunConfig,
+
+    /// Ids of the options that were set explicitly, eg. on the command line, rather than left at
+    /// their defaults. Options loaded from a config file count as explicit too.
+    #[arg(skip)]
+    #[serde(skip)]
+    pub explicit_options: Vec<String>,
 }
 
 impl Default for ProcessArgs {
@@ -56,7 +192,99 @@ impl Default for ProcessArgs {
     }
 }
 
-#[derive(Clone, Args)]
+impl ProcessArgs {
+    /// Apply a TOML or JSON config file, like the one written by [`Self::to_toml`], on top of
+    /// these args.
+    ///
+    /// The file has a table per section, eg. `[train_config]`, and only needs to list the values
+    /// it changes. Values for which `keep` returns true are left as they are, which lets flags
+    /// passed on the command line take precedence over the file. The options set by the file are
+    /// added to [`Self::explicit_options`].
+    pub fn merge_config(self, config: &str, keep: impl Fn(&str) -> bool) -> anyhow::Result<Self> {
+        // TOML documents can't start with a '{', so that's enough to tell the two apart.
+        let file: Value = if config.trim_start().starts_with('{') {
+            serde_json::from_str(config)?
+        } else {
+            toml::from_str(config)?
+        };
+        let Value::Object(sections) = file else {
+            anyhow::bail!("Config must be a table of sections");
+        };
+
+        // These aren't part of config files, so always keep them.
+        let start_iter = self.process_config.start_iter;
+        let resume = self.process_config.resume.clone();
+        let mut explicit_options = self.explicit_options.clone();
+
+        let mut args = serde_json::to_value(self)?;
+        for (section, values) in sections {
+            let Some(Value::Object(target)) = args.get_mut(&section) else {
+                anyhow::bail!("Unknown config section '{section}'");
+            };
+            let Value::Object(values) = values else {
+                anyhow::bail!("Config section '{section}' must be a table");
+            };
+            for (key, value) in values {
+                anyhow::ensure!(
+                    target.contains_key(&key),
+                    "Unknown config option '{key}' in section '{section}'"
+                );
+                if !keep(&key) {
+                    if !explicit_options.contains(&key) {
+                        explicit_options.push(key.clone());
+                    }
+                    target.insert(key, value);
+                }
+            }
+        }
+        let mut merged: Self = serde_json::from_value(args)?;
+        merged.process_config.start_iter = start_iter;
+        merged.process_config.resume = resume;
+        merged.explicit_options = explicit_options;
+        Ok(merged)
+    }
+
+    /// Serialize these args to a TOML config file, which can be loaded again with
+    /// [`Self::merge_config`].
+    pub fn to_toml(&self) -> anyhow::Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+
+    /// Continue with the train config of a checkpoint.
+    ///
+    /// The checkpoint decides everything that shapes training, like the learning rates and refine
+    /// settings. Only the total steps can be changed, to extend a run. Other train options that
+    /// were set explicitly and differ from the checkpoint are an error, as they would otherwise be
+    /// silently ignored.
+    pub fn resume_train_config(&mut self, checkpoint: &TrainConfig) -> anyhow::Result<()> {
+        let Value::Object(values) = serde_json::to_value(&self.train_config)? else {
+            anyhow::bail!("Train config must be a table");
+        };
+
+        let mut resumed = serde_json::to_value(checkpoint)?;
+        let mut conflicts = vec![];
+        for (key, value) in values {
+            if !self.explicit_options.contains(&key) || value == resumed[key.as_str()] {
+                continue;
+            }
+            if key == "total_steps" {
+                resumed[key.as_str()] = value;
+            } else {
+                conflicts.push(format!("--{}", key.replace('_', "-")));
+            }
+        }
+        anyhow::ensure!(
+            conflicts.is_empty(),
+            "Can't change {} when resuming, these are restored from the checkpoint",
+            conflicts.join(", ")
+        );
+
+        self.train_config = serde_json::from_value(resumed)?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Args, Serialize, Deserialize)]
 pub struct RerunConfig {
     /// Whether to enable rerun.io logging for this run.
     #[arg(long, help_heading = "Rerun options", default_value = "false")]
@@ -71,3 +299,171 @@ pub struct RerunConfig {
     #[arg(long, help_heading = "Rerun options", default_value = "512")]
     pub rerun_max_img_size: u32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toml_round_trip() {
+        let mut args = ProcessArgs::default();
+        args.train_config.total_steps = 1234;
+        args.load_config.max_frames = Some(16);
+        args.process_config.export_name = "run_{iter}.ply".to_owned();
+
+        let toml = args.to_toml().expect("Failed to serialize");
+        let loaded = ProcessArgs::default()
+            .merge_config(&toml, |_| false)
+            .expect("Failed to load");
+        assert_eq!(loaded.train_config.total_steps, 1234);
+        assert_eq!(loaded.load_config.max_frames, Some(16));
+        assert_eq!(loaded.process_config.export_name, "run_{iter}.ply");
+    }
+
+    #[test]
+    fn test_partial_config() {
+        let config =
+            "[train_config]\ntotal_steps = 500\nssim_weight = 0\n\n[model_config]\nsh_degree = 1\n";
+        let args = ProcessArgs::parse_from(["", "--sh-degree", "2"])
+            .merge_config(config, |id| id == "sh_degree")
+            .expect("Failed to load");
+        assert_eq!(args.train_config.total_steps, 500);
+        assert_eq!(args.train_config.ssim_weight, 0.0);
+        // Kept values aren't overridden by the file.
+        assert_eq!(args.model_config.sh_degree, 2);
+        // Values missing from the file keep their defaults.
+        assert_eq!(args.process_config.seed, 42);
+        // Values from the file count as explicitly set.
+        assert!(args.explicit_options.contains(&"total_steps".to_owned()));
+        assert!(!args.explicit_options.contains(&"sh_degree".to_owned()));
+
+        let json = r#"{ "process_config": { "seed": 7 } }"#;
+        let args = args.merge_config(json, |_| false).expect("Failed to load");
+        assert_eq!(args.process_config.seed, 7);
+    }
+
+    #[test]
+    fn test_resume_not_in_config() {
+        let mut args = ProcessArgs::default();
+        args.process_config.start_iter = 500;
+        args.process_config.resume = Some("checkpoint_500.ckpt".to_owned());
+
+        let toml = args.to_toml().expect("Failed to serialize");
+        assert!(!toml.contains("start_iter"));
+        assert!(!toml.contains("resume"));
+
+        let loaded = ProcessArgs::default()
+            .merge_config(&toml, |_| false)
+            .expect("Failed to load");
+        assert_eq!(loaded.process_config.start_iter, 0);
+        assert_eq!(loaded.process_config.resume, None);
+
+        // Flags given alongside a config are kept.
+        let loaded = args
+            .clone()
+            .merge_config(&toml, |_| false)
+            .expect("Failed to load");
+        assert_eq!(loaded.process_config.start_iter, 500);
+        assert_eq!(loaded.process_config.resume, args.process_config.resume);
+        assert!(
+            ProcessArgs::default()
+                .merge_config("[process_config]\nresume = \"old.ckpt\"\n", |_| false)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_export_options() {
+        let args = ProcessArgs::parse_from([
+            "",
+            "--export-box-min",
+            "-1,-2,-3",
+            "--export-box-max",
+            "1,2,3",
+            "--export-scale",
+            "2",
+            "--export-rotation",
+            "0,0,90",
+            "--export-translation",
+            "0,1,0",
+        ]);
+        let options = args
+            .process_config
+            .export_options(Vec3::NEG_Y)
+            .expect("Invalid export options");
+        let bounds = options.bounds.expect("Missing bounds");
+        assert_eq!(bounds.min(), Vec3::new(-1.0, -2.0, -3.0));
+        assert_eq!(bounds.max(), Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(options.scale, 2.0);
+        assert!((options.rotation * Vec3::X).distance(Vec3::Y) < 1e-5);
+        assert_eq!(options.translation, Vec3::Y);
+
+        let mut config = ProcessArgs::default().process_config;
+        config.export_scale = Some(0.0);
+        assert!(config.export_options(Vec3::NEG_Y).is_err());
+        config.export_scale = None;
+        config.export_translation = Some(vec![1.0]);
+        assert!(config.export_options(Vec3::NEG_Y).is_err());
+    }
+
+    #[test]
+    fn test_unknown_option() {
+        let config = "[train_config]\ntotal_stepz = 500\n";
+        assert!(
+            ProcessArgs::default()
+                .merge_config(config, |_| false)
+                .is_err()
+        );
+        assert!(
+            ProcessArgs::default()
+                .merge_config("[trian_config]\n", |_| false)
+                .is_err()
+        );
+    }
+
+    // Parse args like the CLI does, marking all passed options as explicit.
+    fn parse_explicit(args: &[&str]) -> ProcessArgs {
+        let mut parsed = ProcessArgs::parse_from(args);
+        parsed.explicit_options = args
+            .iter()
+            .filter_map(|arg| arg.strip_prefix("--"))
+            .map(|arg| arg.replace('-', "_"))
+            .collect();
+        parsed
+    }
+
+    #[test]
+    fn test_resume_train_config() {
+        let checkpoint = TrainConfig {
+            total_steps: 5000,
+            max_splats: 5000,
+            lr_mean: 1e-4,
+            ..Default::default()
+        };
+
+        // Without changes, everything comes from the checkpoint.
+        let mut args = ProcessArgs::default();
+        args.resume_train_config(&checkpoint)
+            .expect("Failed to resume");
+        assert_eq!(args.train_config.total_steps, 5000);
+        assert_eq!(args.train_config.lr_mean, 1e-4);
+
+        // The total steps can be changed to extend the run, even to the default.
+        for total_steps in ["8000", "30000"] {
+            let mut args = parse_explicit(&["", "--total-steps", total_steps]);
+            args.resume_train_config(&checkpoint)
+                .expect("Failed to resume");
+            assert_eq!(args.train_config.total_steps.to_string(), total_steps);
+            assert_eq!(args.train_config.lr_mean, 1e-4);
+        }
+
+        // Other changes can't be applied, even when passing a default value.
+        let mut args = parse_explicit(&["", "--lr-mean", "1e-3"]);
+        assert!(args.resume_train_config(&checkpoint).is_err());
+        let mut args = parse_explicit(&["", "--max-splats", "10000000"]);
+        assert!(args.resume_train_config(&checkpoint).is_err());
+        // Passing the value of the checkpoint is fine.
+        let mut args = parse_explicit(&["", "--lr-mean", "1e-4"]);
+        assert!(args.resume_train_config(&checkpoint).is_ok());
+    }
+}
@@ -1,7 +1,12 @@
-use anyhow::Result;
-use brush_train::eval::EvalSample;
+use crate::{message::EvalView, visualize_tools::VisualizeTools};
+use anyhow::{Context, Result};
+use brush_dataset::scene::Scene;
+use brush_render::{MainBackend, gaussian_splats::Splats};
+use brush_train::eval::{EvalSample, eval_stats};
 use burn::prelude::Backend;
+use burn_wgpu::WgpuDevice;
 use std::path::Path;
+use tokio_with_wasm::alias as tokio_wasm;
 
 #[allow(unused)]
 pub async fn eval_save_to_disk<B: Backend>(sample: &EvalSample<B>, path: &Path) -> Result<()> {
@@ -30,3 +35,66 @@ pub async fn eval_save_to_disk<B: Backend>(sample: &EvalSample<B>, path: &Path)
     }
     Ok(())
 }
+
+/// Render and score each view of a scene. This is shared by `brush eval` and the evaluation
+/// while training, so both measure and save renders the same way.
+///
+/// When `visualize` is set, each sample is also logged to rerun at the given iteration.
+pub(crate) async fn eval_views(
+    splats: &Splats<MainBackend>,
+    scene: &Scene,
+    fit_appearance: bool,
+    #[cfg(not(target_family = "wasm"))] lpips: Option<&lpips::LpipsNet<MainBackend>>,
+    save_dir: Option<&Path>,
+    visualize: Option<(&VisualizeTools, u32)>,
+    device: &WgpuDevice,
+) -> Result<Vec<EvalView>> {
+    let mut metrics = vec![];
+
+    for (i, view) in scene.views.iter().enumerate() {
+        tokio_wasm::task::yield_now().await;
+
+        let eval_img = view.image.load().await?;
+        let sample = eval_stats(
+            splats,
+            &view.camera,
+            eval_img,
+            view.image.is_masked(),
+            fit_appearance,
+            #[cfg(not(target_family = "wasm"))]
+            lpips,
+            device,
+        )
+        .await
+        .context("Failed to run eval for sample.")?;
+
+        let name = view
+            .image
+            .path
+            .file_stem()
+            .expect("No file name for eval view.")
+            .to_string_lossy()
+            .into_owned();
+
+        if let Some(save_dir) = save_dir {
+            eval_save_to_disk(&sample, &save_dir.join(format!("{name}.png"))).await?;
+        }
+
+        let lpips = match &sample.lpips {
+            Some(lpips) => Some(lpips.clone().into_scalar_async().await),
+            None => None,
+        };
+        metrics.push(EvalView {
+            name,
+            psnr: sample.psnr.clone().into_scalar_async().await,
+            ssim: sample.ssim.clone().into_scalar_async().await,
+            lpips,
+        });
+
+        if let Some((visualize, iter)) = visualize {
+            visualize.log_eval_sample(iter, i as u32, sample).await?;
+        }
+    }
+
+    Ok(metrics)
+}
@@ -0,0 +1,40 @@
+use crate::{eval_export::eval_views, message::EvalView};
+use anyhow::Context;
+use brush_dataset::scene::Scene;
+use brush_render::{MainBackend, gaussian_splats::Splats};
+use burn_wgpu::WgpuDevice;
+use lpips::LpipsNet;
+use std::path::Path;
+
+/// Load the LPIPS model to evaluate with.
+///
+/// This is the bundled VGG model, or AlexNet when given the path to its converted weights.
+pub fn load_lpips(
+    alex_weights: Option<&Path>,
+    device: &WgpuDevice,
+) -> anyhow::Result<LpipsNet<MainBackend>> {
+    Ok(if let Some(path) = alex_weights {
+        LpipsNet::Alex(
+            lpips::load_alex_lpips(path, device)
+                .with_context(|| format!("Failed to load LPIPS weights {}", path.display()))?,
+        )
+    } else {
+        LpipsNet::Vgg(lpips::load_vgg_lpips(device))
+    })
+}
+
+/// Score splats against the views of a scene, without any training.
+///
+/// Each view is rendered and compared like the evaluation while training. LPIPS is only computed
+/// when an `lpips` model is given. When `save_dir` is set, the renders are saved there, named
+/// after the images of the views.
+pub async fn eval_scene(
+    splats: &Splats<MainBackend>,
+    scene: &Scene,
+    fit_appearance: bool,
+    lpips: Option<&LpipsNet<MainBackend>>,
+    save_dir: Option<&Path>,
+    device: &WgpuDevice,
+) -> anyhow::Result<Vec<EvalView>> {
+    eval_views(splats, scene, fit_appearance, lpips, save_dir, None, device).await
+}
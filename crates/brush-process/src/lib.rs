@@ -1,6 +1,8 @@
 #![recursion_limit = "256"]
 
 pub mod config;
+#[cfg(not(target_family = "wasm"))]
+pub mod eval_only;
 pub mod message;
 pub mod process;
 pub mod train_stream;
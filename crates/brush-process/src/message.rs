@@ -5,6 +5,17 @@ use brush_train::msg::{RefineStats, TrainStepStats};
 use glam::Vec3;
 use web_time::Duration;
 
+/// Eval metrics of a single view.
+#[derive(Clone)]
+pub struct EvalView {
+    /// File name of the image of the view, without extension.
+    pub name: String,
+    pub psnr: f32,
+    pub ssim: f32,
+    /// LPIPS, if it was computed.
+    pub lpips: Option<f32>,
+}
+
 pub enum ProcessMessage {
     NewSource,
     StartLoading {
@@ -49,6 +60,8 @@ pub enum ProcessMessage {
         iter: u32,
         avg_psnr: f32,
         avg_ssim: f32,
+        avg_lpips: Option<f32>,
+        views: Vec<EvalView>,
     },
     /// Some warning occurred during the process, but the process can continue.
     Warning {
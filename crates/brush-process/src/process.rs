@@ -1,11 +1,13 @@
 use std::sync::Arc;
 
 use async_fn_stream::try_fn_stream;
+use brush_serde::SplatFormat;
 use brush_vfs::DataSource;
 use burn_cubecl::cubecl::Runtime;
 use burn_wgpu::{WgpuDevice, WgpuRuntime};
 use tokio::sync::oneshot::Receiver;
 use tokio_stream::Stream;
+use tokio_util::sync::CancellationToken;
 
 #[allow(unused)]
 use brush_serde;
@@ -15,9 +17,14 @@ use crate::{
     view_stream::view_stream,
 };
 
+/// Load a source, and either view it, or train on it.
+///
+/// Cancelling `cancel` stops training gracefully: the current step is finished, and the splat is
+/// evaluated and exported as if it was the last step, before the stream ends.
 pub fn process_stream(
     source: DataSource,
     process_args: Receiver<ProcessArgs>,
+    cancel: CancellationToken,
     device: WgpuDevice,
 ) -> impl Stream<Item = Result<ProcessMessage, anyhow::Error>> + 'static {
     try_fn_stream(|emitter| async move {
@@ -31,22 +38,25 @@ pub fn process_stream(
         client.memory_cleanup();
 
         let vfs_counts = vfs.file_count();
-        let ply_count = vfs.files_with_extension("ply").count();
+        let splat_count = vfs
+            .file_paths()
+            .filter(|p| SplatFormat::from_path(p).is_some())
+            .count();
 
         log::info!(
-            "Mounted VFS with {} files. (plys: {})",
+            "Mounted VFS with {} files. (splat files: {})",
             vfs.file_count(),
-            ply_count
+            splat_count
         );
 
         log::info!("Start of view stream");
 
-        if vfs_counts == ply_count {
+        if vfs_counts == splat_count {
             drop(process_args);
             view_stream(vfs, device, emitter).await?;
         } else {
             // Receive the processing args.
-            train_stream(vfs, process_args, device, emitter).await?;
+            train_stream(vfs, process_args, cancel, device, emitter).await?;
         };
 
         Ok(())
@@ -1,19 +1,24 @@
 use crate::{
     config::{ProcessArgs, ProcessConfig, RerunConfig},
     emit_warnings::WarningEmitter,
-    eval_export::eval_save_to_disk,
+    eval_export::eval_views,
     message::ProcessMessage,
     visualize_tools::VisualizeTools,
 };
 use anyhow::Context;
 use async_fn_stream::TryStreamEmitter;
-use brush_dataset::{load_dataset, scene::Scene, scene_loader::SceneLoader};
+use brush_dataset::{
+    load_dataset,
+    scene::{Scene, SceneView},
+    scene_loader::{LoaderState, SceneLoader},
+};
 use brush_render::{
     MainBackend,
+    camera::Camera,
     gaussian_splats::{RandomSplatsConfig, Splats},
 };
 use brush_train::{
-    eval::eval_stats,
+    checkpoint::TrainCheckpoint,
     msg::{RefineStats, TrainStepStats},
     train::SplatTrainer,
 };
@@ -21,16 +26,18 @@ use brush_vfs::BrushVfs;
 use burn::{backend::Autodiff, module::AutodiffModule, prelude::Backend};
 use burn_cubecl::cubecl::Runtime;
 use burn_wgpu::{WgpuDevice, WgpuRuntime};
+use glam::Vec3;
 use rand::SeedableRng;
 use std::{path::Path, sync::Arc};
 use tokio::sync::oneshot::Receiver;
-use tokio_with_wasm::alias as tokio_wasm;
+use tokio_util::sync::CancellationToken;
 use tracing::{Instrument, trace_span};
 use web_time::{Duration, Instant};
 
 pub(crate) async fn train_stream(
     vfs: Arc<BrushVfs>,
     process_args: Receiver<ProcessArgs>,
+    cancel: CancellationToken,
     device: WgpuDevice,
     emitter: TryStreamEmitter<ProcessMessage, anyhow::Error>,
 ) -> anyhow::Result<()> {
@@ -43,7 +50,29 @@ pub(crate) async fn train_stream(
         .await;
 
     // Now wait for the process args.
-    let process_args = process_args.await?;
+    let mut process_args = process_args.await?;
+
+    // When resuming, the checkpoint determines how training continues.
+    let checkpoint = if let Some(path) = &process_args.process_config.resume {
+        let checkpoint = load_checkpoint(path, &device)
+            .await
+            .with_context(|| format!("Failed to load checkpoint {path}"))?;
+        log::info!(
+            "Resuming from checkpoint at iteration {}",
+            checkpoint.iter()
+        );
+        process_args.resume_train_config(checkpoint.config())?;
+        process_args.process_config.seed = checkpoint.seed();
+        process_args.process_config.start_iter = checkpoint.iter();
+        anyhow::ensure!(
+            checkpoint.iter() < process_args.train_config.total_steps,
+            "Checkpoint is at iteration {}, pass a larger --total-steps to continue training",
+            checkpoint.iter()
+        );
+        Some(checkpoint)
+    } else {
+        None
+    };
 
     let visualize = tracing::trace_span!("Create rerun")
         .in_scope(|| VisualizeTools::new(process_args.rerun_config.rerun_enabled));
@@ -72,15 +101,19 @@ pub(crate) async fn train_stream(
         })
         .await;
 
-    let estimated_up = dataset.estimate_up();
+    // If the metadata has an up axis prefer that, otherwise estimate the up direction.
+    let up_axis = initial_splats
+        .as_ref()
+        .and_then(|init| init.meta.up_axis)
+        .unwrap_or_else(|| dataset.estimate_up());
     log::info!("Loading initial splats if any.");
 
-    if let Some(init) = &initial_splats {
+    if let Some(init) = &initial_splats
+        && checkpoint.is_none()
+    {
         emitter
             .emit(ProcessMessage::ViewSplats {
-                // If the metadata has an up axis prefer that, otherwise estimate
-                // the up direction.
-                up_axis: init.meta.up_axis.or(Some(estimated_up)),
+                up_axis: Some(up_axis),
                 splats: Box::new(init.splats.clone()),
                 frame: 0,
                 total_frames: 0,
@@ -91,28 +124,57 @@ pub(crate) async fn train_stream(
 
     emitter.emit(ProcessMessage::DoneLoading).await;
 
-    let splats = if let Some(init_msg) = initial_splats {
-        init_msg.splats
+    let start_iter = process_config.start_iter;
+
+    // Fresh runs use a fixed loader seed, resumed runs continue where the checkpoint left off.
+    let loader_state = checkpoint.as_ref().map_or(
+        LoaderState {
+            seed: 42,
+            position: 0,
+        },
+        TrainCheckpoint::loader_state,
+    );
+
+    let (mut splats, mut trainer) = if let Some(checkpoint) = checkpoint {
+        let (trainer, splats) = SplatTrainer::from_checkpoint(checkpoint, &device);
+        (splats, trainer)
     } else {
-        log::info!("Starting with random splat config.");
-        // Create a bounding box the size of all the cameras plus a bit.
-        let mut bounds = dataset.train.bounds();
-        bounds.extent *= 1.25;
-        let config = RandomSplatsConfig::new();
-        Splats::from_random_config(&config, bounds, &mut rng, &device)
+        let splats = if let Some(init_msg) = initial_splats {
+            init_msg.splats
+        } else {
+            log::info!("Starting with random splat config.");
+            // Create a bounding box the size of all the cameras plus a bit.
+            let mut bounds = dataset.train.bounds();
+            bounds.extent *= 1.25;
+            let config = RandomSplatsConfig::new();
+            Splats::from_random_config(&config, bounds, &mut rng, &device)
+        };
+
+        let splats = splats.with_sh_degree(process_args.model_config.sh_degree);
+        let splats = splats.into_autodiff();
+        let trainer = SplatTrainer::new(&process_args.train_config, &device, splats.clone()).await;
+        // Resumed splats already have the 3D filter they were trained with.
+        let splats = trainer.update_filter_3d(splats, &dataset.train);
+        (splats, trainer)
     };
-
-    let splats = splats.with_sh_degree(process_args.model_config.sh_degree);
-    let mut splats = splats.into_autodiff();
+    trainer.reseed(iter_seed(process_config.seed, start_iter), &device);
 
     let mut eval_scene = dataset.eval;
+    // The LPIPS weights aren't available on the web, so there eval always skips LPIPS.
+    #[cfg(not(target_family = "wasm"))]
+    let eval_lpips = if eval_scene.is_some() && process_config.eval_lpips {
+        let alex_weights = process_config.eval_lpips_alex.as_deref().map(Path::new);
+        Some(crate::eval_only::load_lpips(alex_weights, &device)?)
+    } else {
+        None
+    };
 
     let mut train_duration = Duration::from_secs(0);
-    let mut dataloader = SceneLoader::new(&dataset.train, 42, &device);
-    let mut trainer = SplatTrainer::new(&process_args.train_config, &device, splats.clone()).await;
+    // When resuming, the loader continues with the same view order as the original run.
+    let mut dataloader = SceneLoader::from_state(&dataset.train, loader_state, &device);
 
     log::info!("Start training loop.");
-    for iter in process_args.process_config.start_iter..process_args.train_config.total_steps {
+    for iter in start_iter..process_args.train_config.total_steps {
         let step_time = Instant::now();
 
         let batch = dataloader
@@ -123,14 +185,21 @@ pub(crate) async fn train_stream(
         let (new_splats, stats) = trainer.step(&batch, splats);
         splats = new_splats;
         let (new_splats, refine) = trainer
-            .refine_if_needed(iter, splats)
+            .refine_if_needed(iter, splats, &dataset.train)
             .instrument(trace_span!("Refine splats"))
             .await;
         splats = new_splats;
 
         // We just finished iter 'iter', now starting iter + 1.
         let iter = iter + 1;
-        let is_last_step = iter == process_args.train_config.total_steps;
+
+        // When cancelled, treat this step as the last one, so the current splat is still
+        // evaluated and exported.
+        let cancelled = cancel.is_cancelled();
+        if cancelled {
+            log::info!("Training stopped at iteration {iter}");
+        }
+        let is_last_step = iter == process_args.train_config.total_steps || cancelled;
 
         // Add up time from this step.
         train_duration += step_time.elapsed();
@@ -148,6 +217,9 @@ pub(crate) async fn train_stream(
                 splats.valid(),
                 iter,
                 eval_scene,
+                process_args.train_config.appearance,
+                #[cfg(not(target_family = "wasm"))]
+                eval_lpips.as_ref(),
             )
             .await;
             warner
@@ -156,10 +228,25 @@ pub(crate) async fn train_stream(
         }
 
         if iter % process_config.export_every == 0 || is_last_step {
-            let res = export_checkpoint(&process_args, process_config, splats.valid(), iter).await;
+            let checkpoint =
+                trainer.checkpoint(&splats, iter, process_config.seed, dataloader.state());
+            let refined_cameras = trainer.refined_cameras(&dataset.train).await;
+            let res = export_checkpoint(
+                &process_args,
+                process_config,
+                splats.valid(),
+                up_axis,
+                checkpoint,
+                refined_cameras.map(|cams| (dataset.train.views.as_slice(), cams)),
+                iter,
+            )
+            .await;
             warner
                 .warn_if_err(res.context(format!("Export at iteration {iter} failed")))
                 .await;
+
+            // Reseed at every checkpoint, so resuming from it continues with the same random state.
+            trainer.reseed(iter_seed(process_config.seed, iter), &device);
         }
 
         let res = rerun_log(
@@ -199,6 +286,10 @@ pub(crate) async fn train_stream(
             };
             emitter.emit(message).await;
         }
+
+        if cancelled {
+            break;
+        }
     }
 
     Ok(())
@@ -212,61 +303,73 @@ async fn run_eval(
     splats: Splats<MainBackend>,
     iter: u32,
     eval_scene: &Scene,
+    fit_appearance: bool,
+    #[cfg(not(target_family = "wasm"))] lpips: Option<&lpips::LpipsNet<MainBackend>>,
 ) -> Result<(), anyhow::Error> {
-    let mut psnr = 0.0;
-    let mut ssim = 0.0;
-    let mut count = 0;
     log::info!("Running evaluation for iteration {iter}");
 
-    for (i, view) in eval_scene.views.iter().enumerate() {
-        tokio_wasm::task::yield_now().await;
-
-        let eval_img = view.image.load().await?;
-        let sample = eval_stats(
-            &splats,
-            &view.camera,
-            eval_img,
-            view.image.is_masked(),
-            device,
-        )
-        .context("Failed to run eval for sample.")?;
-
-        count += 1;
-        psnr += sample.psnr.clone().into_scalar_async().await;
-        ssim += sample.ssim.clone().into_scalar_async().await;
-
-        let export_path = Path::new(&process_config.export_path).to_owned();
-        if process_config.eval_save_to_disk {
-            let img_name = Path::new(&view.image.path)
-                .file_stem()
-                .expect("No file name for eval view.")
-                .to_string_lossy();
-            let path = Path::new(&export_path)
-                .join(format!("eval_{iter}"))
-                .join(format!("{img_name}.png"));
-            eval_save_to_disk(&sample, &path).await?;
-        }
-
-        visualize.log_eval_sample(iter, i as u32, sample).await?;
-    }
-    psnr /= count as f32;
-    ssim /= count as f32;
-    visualize.log_eval_stats(iter, psnr, ssim)?;
+    let save_dir = process_config
+        .eval_save_to_disk
+        .then(|| Path::new(&process_config.export_path).join(format!("eval_{iter}")));
+    let views = eval_views(
+        &splats,
+        eval_scene,
+        fit_appearance,
+        #[cfg(not(target_family = "wasm"))]
+        lpips,
+        save_dir.as_deref(),
+        Some((visualize, iter)),
+        device,
+    )
+    .await?;
+
+    let count = views.len() as f32;
+    let psnr = views.iter().map(|v| v.psnr).sum::<f32>() / count;
+    let ssim = views.iter().map(|v| v.ssim).sum::<f32>() / count;
+    let lpips = views
+        .iter()
+        .map(|v| v.lpips)
+        .sum::<Option<f32>>()
+        .map(|lpips| lpips / count);
+    visualize.log_eval_stats(iter, psnr, ssim, lpips)?;
     emitter
         .emit(ProcessMessage::EvalResult {
             iter,
             avg_psnr: psnr,
             avg_ssim: ssim,
+            avg_lpips: lpips,
+            views,
         })
         .await;
 
     Ok(())
 }
 
+fn iter_seed(seed: u64, iter: u32) -> u64 {
+    seed.wrapping_add(iter as u64)
+}
+
+async fn load_checkpoint(path: &str, device: &WgpuDevice) -> anyhow::Result<TrainCheckpoint> {
+    #[cfg(not(target_family = "wasm"))]
+    {
+        let data = tokio::fs::read(path).await?;
+        TrainCheckpoint::from_bytes(data, device)
+    }
+    #[cfg(target_family = "wasm")]
+    {
+        let _ = path;
+        let _ = device;
+        anyhow::bail!("Resuming from a checkpoint is not supported on the web")
+    }
+}
+
 async fn export_checkpoint(
     process_args: &ProcessArgs,
     process_config: &ProcessConfig,
     splats: Splats<MainBackend>,
+    up_axis: Vec3,
+    checkpoint: TrainCheckpoint,
+    refined_cameras: Option<(&[SceneView], Vec<Camera>)>,
     iter: u32,
 ) -> Result<(), anyhow::Error> {
     // TODO: Want to support this on WASM somehow. Maybe have user pick a file once,
@@ -276,25 +379,47 @@ async fn export_checkpoint(
         use tokio::fs;
         let total_steps = process_args.train_config.total_steps;
         let digits = ((total_steps as f64).log10().floor() as usize) + 1;
-        let export_name = process_config
-            .export_name
-            .replace("{iter}", &format!("{iter:0digits$}"));
+        let iter_str = format!("{iter:0digits$}");
+        let export_name = process_config.export_name.replace("{iter}", &iter_str);
+        let checkpoint_name = process_config.checkpoint_name.replace("{iter}", &iter_str);
         let export_path = Path::new(&process_config.export_path).to_owned();
         fs::create_dir_all(&export_path)
             .await
             .context("Creating export directory")?;
-        let splat_data = brush_serde::splat_to_ply(splats)
-            .await
-            .context("Serializing splat data")?;
+        let export_options = process_config.export_options(up_axis)?;
+        let splat_data =
+            brush_serde::splat_to_file_data(splats, &export_name, &export_options, Some(up_axis))
+                .await
+                .context("Serializing splat data")?;
         fs::write(export_path.join(&export_name), splat_data)
             .await
             .context(format!("Failed to export ply {export_path:?}"))?;
+        let checkpoint_data = checkpoint.to_bytes()?;
+        fs::write(export_path.join(&checkpoint_name), checkpoint_data)
+            .await
+            .context(format!("Failed to write checkpoint {export_path:?}"))?;
+        // Keep the config used for this run next to the export, so it can be reproduced with --config.
+        fs::write(export_path.join("config.toml"), process_args.to_toml()?)
+            .await
+            .context(format!("Failed to write config {export_path:?}"))?;
+        if let Some((views, cameras)) = refined_cameras {
+            let transforms = brush_dataset::transforms_json(views, &cameras)?;
+            fs::write(
+                export_path.join(format!("transforms_{iter_str}.json")),
+                transforms,
+            )
+            .await
+            .context(format!("Failed to write refined cameras {export_path:?}"))?;
+        }
     }
     #[cfg(target_family = "wasm")]
     {
         let _ = process_args;
         let _ = process_config;
         let _ = splats;
+        let _ = up_axis;
+        let _ = checkpoint;
+        let _ = refined_cameras;
         let _ = iter;
     }
     Ok(())
@@ -3,7 +3,7 @@ use crate::message::ProcessMessage;
 use std::{pin::pin, sync::Arc};
 
 use async_fn_stream::TryStreamEmitter;
-use brush_serde;
+use brush_serde::{self, SplatFormat};
 use brush_vfs::BrushVfs;
 use burn_cubecl::cubecl::Runtime;
 use burn_wgpu::{WgpuDevice, WgpuRuntime};
@@ -22,15 +22,17 @@ pub(crate) async fn view_stream(
     for (i, path) in paths.iter().enumerate() {
         tokio_wasm::task::yield_now().await;
 
-        log::info!("Loading single ply file");
+        log::info!("Loading single splat file");
 
         emitter
             .emit(ProcessMessage::StartLoading { training: false })
             .await;
 
         let sub_sample = None; // Subsampling a trained ply doesn't really make sense.
-        let splat_stream = brush_serde::stream_splat_from_ply(
+        let format = SplatFormat::from_path(path).unwrap_or(SplatFormat::Ply);
+        let splat_stream = brush_serde::stream_splat(
             vfs.reader_at_path(path).await?,
+            format,
             sub_sample,
             device.clone(),
             true,
@@ -143,13 +143,23 @@ mod visualize_tools_impl {
         }
 
         #[allow(unused_variables)]
-        pub fn log_eval_stats(&self, iter: u32, avg_psnr: f32, avg_ssim: f32) -> Result<()> {
+        pub fn log_eval_stats(
+            &self,
+            iter: u32,
+            avg_psnr: f32,
+            avg_ssim: f32,
+            avg_lpips: Option<f32>,
+        ) -> Result<()> {
             if self.rec.is_enabled() {
                 self.rec.set_time_sequence("iterations", iter);
                 self.rec
                     .log("psnr/eval", &rerun::Scalars::new(vec![avg_psnr as f64]))?;
                 self.rec
                     .log("ssim/eval", &rerun::Scalars::new(vec![avg_ssim as f64]))?;
+                if let Some(avg_lpips) = avg_lpips {
+                    self.rec
+                        .log("lpips/eval", &rerun::Scalars::new(vec![avg_lpips as f64]))?;
+                }
             }
             Ok(())
         }
@@ -218,6 +228,14 @@ mod visualize_tools_impl {
                         eval.ssim.clone().into_scalar_async().await.elem::<f32>() as f64,
                     ]),
                 )?;
+                if let Some(lpips) = &eval.lpips {
+                    self.rec.log(
+                        format!("lpips/eval_{index}"),
+                        &rerun::Scalars::new(vec![
+                            lpips.clone().into_scalar_async().await.elem::<f32>() as f64,
+                        ]),
+                    )?;
+                }
             }
 
             Ok(())
@@ -362,7 +380,13 @@ mod visualize_tools_impl {
 
         #[allow(unused_variables)]
         #[allow(clippy::unnecessary_wraps, clippy::unused_self)]
-        pub fn log_eval_stats(&self, _iter: u32, _avg_psnr: f32, _avg_ssim: f32) -> Result<()> {
+        pub fn log_eval_stats(
+            &self,
+            _iter: u32,
+            _avg_psnr: f32,
+            _avg_ssim: f32,
+            _avg_lpips: Option<f32>,
+        ) -> Result<()> {
             Ok(())
         }
 
@@ -5,6 +5,7 @@ fn main() -> miette::Result<()> {
         &[
             "src/shaders/rasterize_backwards.wgsl",
             "src/shaders/project_backwards.wgsl",
+            "src/shaders/upscale_backwards.wgsl",
         ],
         &["../brush-render/src/shaders/helpers.wgsl"],
         "src/shaders/mod.rs",
@@ -1,5 +1,5 @@
 use brush_render::{
-    MainBackendBase, SplatForward,
+    MainBackendBase, RenderScale, SplatForward,
     camera::Camera,
     render_aux::RenderAux,
     sh::{sh_coeffs_for_degree, sh_degree_from_coeffs},
@@ -39,7 +39,10 @@ pub trait SplatForwardDiff<B: Backend> {
     /// Render splats to a buffer.
     ///
     /// This projects the gaussians, sorts them, and rasterizes them to a buffer, in a
-    /// differentiable way.
+    /// differentiable way. When rasterizing below the native resolution, the upscale pass
+    /// is differentiated as well. Gradients for the camera view matrix are only calculated when
+    /// `camera_grad` is set. When `bwd_depth` is set, the image has an extra differentiable
+    /// alpha blended depth channel.
     #[allow(clippy::too_many_arguments)]
     fn render_splats(
         camera: &Camera,
@@ -50,6 +53,10 @@ pub trait SplatForwardDiff<B: Backend> {
         sh_coeffs: FloatTensor<B>,
         raw_opacity: FloatTensor<B>,
         background: Vec3,
+        mip_filter: bool,
+        render_scale: RenderScale,
+        camera_grad: bool,
+        bwd_depth: bool,
     ) -> SplatOutputDiff<B>;
 }
 
@@ -74,13 +81,17 @@ impl SplatBackwardOps<Self> for MainBackendBase {
             state.means,
             state.quats,
             state.log_scales,
+            state.raw_opac,
             state.out_img,
+            state.img_gradient,
             state.projected_splats,
             state.uniforms_buffer,
             state.compact_gid_from_isect,
             state.global_from_compact_gid,
             state.tile_offsets,
             state.sh_degree,
+            state.camera_grad,
+            state.bwd_depth,
         )
     }
 }
@@ -91,19 +102,23 @@ pub struct GaussianBackwardState<B: Backend> {
     quats: FloatTensor<B>,
     log_scales: FloatTensor<B>,
     raw_opac: FloatTensor<B>,
+    /// The rasterized image, before upscaling.
     out_img: FloatTensor<B>,
+    img_gradient: FloatTensor<B>,
     projected_splats: FloatTensor<B>,
     uniforms_buffer: IntTensor<B>,
     compact_gid_from_isect: IntTensor<B>,
     global_from_compact_gid: IntTensor<B>,
     tile_offsets: IntTensor<B>,
     sh_degree: u32,
+    camera_grad: bool,
+    bwd_depth: bool,
 }
 
 #[derive(Debug)]
 struct RenderBackwards;
 
-const NUM_BWD_ARGS: usize = 6;
+const NUM_BWD_ARGS: usize = 7;
 
 // Implement gradient registration when rendering backwards.
 impl<B: Backend + SplatBackwardOps<B>> Backward<B, NUM_BWD_ARGS> for RenderBackwards {
@@ -130,6 +145,7 @@ impl<B: Backend + SplatBackwardOps<B>> Backward<B, NUM_BWD_ARGS> for RenderBackw
             quats_parent,
             coeffs_parent,
             raw_opacity_parent,
+            viewmat_parent,
         ] = ops.parents;
 
         let v_tens = B::render_splats_bwd(state, v_output);
@@ -158,6 +174,11 @@ impl<B: Backend + SplatBackwardOps<B>> Backward<B, NUM_BWD_ARGS> for RenderBackw
         if let Some(node) = raw_opacity_parent {
             grads.register::<B>(node.id, v_tens.v_raw_opac);
         }
+
+        // Register the gradients for the dummy view matrix input.
+        if let Some(node) = viewmat_parent {
+            grads.register::<B>(node.id, v_tens.v_viewmat);
+        }
     }
 }
 
@@ -165,6 +186,9 @@ pub struct SplatOutputDiff<B: Backend> {
     pub img: FloatTensor<B>,
     pub aux: RenderAux<B>,
     pub refine_weight_holder: Tensor<B, 1>,
+    /// Dummy tensor, whose gradient is the gradient of the top 3x4 rows of the camera view matrix.
+    /// Only tracked when rendering with `camera_grad`.
+    pub viewmat_holder: Tensor<B, 2>,
 }
 
 // Implement
@@ -180,12 +204,22 @@ impl<B: Backend + SplatBackwardOps<B> + SplatForward<B>, C: CheckpointStrategy>
         sh_coeffs: FloatTensor<Self>,
         raw_opacity: FloatTensor<Self>,
         background: Vec3,
+        mip_filter: bool,
+        render_scale: RenderScale,
+        camera_grad: bool,
+        bwd_depth: bool,
     ) -> SplatOutputDiff<Self> {
         // Get backend tensors & dequantize if needed. Could try and support quantized inputs
         // in the future.
         let device =
             Tensor::<Self, 2>::from_primitive(TensorPrimitive::Float(means.clone())).device();
         let refine_weight_holder = Tensor::<Self, 1>::zeros([1], &device).require_grad();
+        let viewmat_holder = Tensor::<Self, 2>::zeros([3, 4], &device);
+        let viewmat_holder = if camera_grad {
+            viewmat_holder.require_grad()
+        } else {
+            viewmat_holder
+        };
 
         // Prepare backward pass, and check if we even need to do it. Store nodes that need gradients.
         let prep_nodes = RenderBackwards
@@ -196,6 +230,7 @@ impl<B: Backend + SplatBackwardOps<B> + SplatForward<B>, C: CheckpointStrategy>
                 quats.node.clone(),
                 sh_coeffs.node.clone(),
                 raw_opacity.node.clone(),
+                viewmat_holder.clone().into_primitive().tensor().node,
             ])
             .compute_bound()
             .stateful();
@@ -210,7 +245,13 @@ impl<B: Backend + SplatBackwardOps<B> + SplatForward<B>, C: CheckpointStrategy>
             sh_coeffs.clone().into_primitive(),
             raw_opacity.clone().into_primitive(),
             background,
+            mip_filter,
+            render_scale,
+            // The aux depth and normal maps are only for display, and aren't needed to train.
+            false,
+            false,
             true,
+            bwd_depth,
         );
 
         let wrapped_aux = RenderAux::<Self> {
@@ -222,7 +263,16 @@ impl<B: Backend + SplatBackwardOps<B> + SplatForward<B>, C: CheckpointStrategy>
             uniforms_buffer: aux.uniforms_buffer.clone(),
             visible: <Self as AutodiffBackend>::from_inner(aux.visible),
             img_size: aux.img_size,
+            raster_img: <Self as AutodiffBackend>::from_inner(aux.raster_img.clone()),
             img_gradient: <Self as AutodiffBackend>::from_inner(aux.img_gradient.clone()),
+            depth: <Self as AutodiffBackend>::from_inner(aux.depth),
+        };
+
+        // The backward pass works on the image before it was upscaled.
+        let raster_img = if aux.img_size == img_size {
+            out_img.clone()
+        } else {
+            aux.raster_img
         };
 
         match prep_nodes {
@@ -237,12 +287,15 @@ impl<B: Backend + SplatBackwardOps<B> + SplatForward<B>, C: CheckpointStrategy>
                         Tensor::<Self, 3>::from_primitive(TensorPrimitive::Float(sh_coeffs)).dims()
                             [1] as u32,
                     ),
-                    out_img: out_img.clone(),
+                    out_img: raster_img,
+                    img_gradient: aux.img_gradient,
                     projected_splats: aux.projected_splats,
                     uniforms_buffer: aux.uniforms_buffer,
                     tile_offsets: aux.tile_offsets,
                     compact_gid_from_isect: aux.compact_gid_from_isect,
                     global_from_compact_gid: aux.global_from_compact_gid,
+                    camera_grad,
+                    bwd_depth,
                 };
 
                 let out_img = prep.finish(state, out_img);
@@ -251,6 +304,7 @@ impl<B: Backend + SplatBackwardOps<B> + SplatForward<B>, C: CheckpointStrategy>
                     img: out_img,
                     aux: wrapped_aux,
                     refine_weight_holder,
+                    viewmat_holder,
                 }
             }
             OpsKind::UnTracked(prep) => {
@@ -260,6 +314,7 @@ impl<B: Backend + SplatBackwardOps<B> + SplatForward<B>, C: CheckpointStrategy>
                     img: prep.finish(out_img),
                     aux: wrapped_aux,
                     refine_weight_holder,
+                    viewmat_holder,
                 }
             }
         }
@@ -275,6 +330,8 @@ impl SplatBackwardOps<Self> for Fusion<MainBackendBase> {
         struct CustomOp {
             desc: CustomOpIr,
             sh_degree: u32,
+            camera_grad: bool,
+            bwd_depth: bool,
         }
 
         impl<BT: BoolElement> Operation<FusionCubeRuntime<WgpuRuntime, BT>> for CustomOp {
@@ -290,13 +347,22 @@ impl SplatBackwardOps<Self> for Fusion<MainBackendBase> {
                         log_scales,
                         raw_opac,
                         out_img,
+                        img_gradient,
                         projected_splats,
                         uniforms_buffer,
                         tile_offsets,
                         compact_gid_from_isect,
                         global_from_compact_gid,
                     ],
-                    [v_means, v_quats, v_scales, v_coeffs, v_raw_opac, v_refine],
+                    [
+                        v_means,
+                        v_quats,
+                        v_scales,
+                        v_coeffs,
+                        v_raw_opac,
+                        v_refine,
+                        v_viewmat,
+                    ],
                 ) = self.desc.as_fixed();
 
                 let inner_state = GaussianBackwardState {
@@ -305,6 +371,7 @@ impl SplatBackwardOps<Self> for Fusion<MainBackendBase> {
                     quats: h.get_float_tensor::<MainBackendBase>(quats),
                     raw_opac: h.get_float_tensor::<MainBackendBase>(raw_opac),
                     out_img: h.get_float_tensor::<MainBackendBase>(out_img),
+                    img_gradient: h.get_float_tensor::<MainBackendBase>(img_gradient),
                     projected_splats: h.get_float_tensor::<MainBackendBase>(projected_splats),
                     uniforms_buffer: h.get_int_tensor::<MainBackendBase>(uniforms_buffer),
                     tile_offsets: h.get_int_tensor::<MainBackendBase>(tile_offsets),
@@ -313,6 +380,8 @@ impl SplatBackwardOps<Self> for Fusion<MainBackendBase> {
                     global_from_compact_gid: h
                         .get_int_tensor::<MainBackendBase>(global_from_compact_gid),
                     sh_degree: self.sh_degree,
+                    camera_grad: self.camera_grad,
+                    bwd_depth: self.bwd_depth,
                 };
 
                 let grads =
@@ -328,6 +397,7 @@ impl SplatBackwardOps<Self> for Fusion<MainBackendBase> {
                 h.register_float_tensor::<MainBackendBase>(&v_coeffs.id, grads.v_coeffs);
                 h.register_float_tensor::<MainBackendBase>(&v_raw_opac.id, grads.v_raw_opac);
                 h.register_float_tensor::<MainBackendBase>(&v_refine.id, grads.v_refine_weight);
+                h.register_float_tensor::<MainBackendBase>(&v_viewmat.id, grads.v_viewmat);
             }
         }
 
@@ -342,6 +412,7 @@ impl SplatBackwardOps<Self> for Fusion<MainBackendBase> {
             v_coeffs: client.tensor_uninitialized(vec![num_points, coeffs, 3], DType::F32),
             v_raw_opac: client.tensor_uninitialized(vec![num_points], DType::F32),
             v_refine_weight: client.tensor_uninitialized(vec![num_points], DType::F32),
+            v_viewmat: client.tensor_uninitialized(vec![3, 4], DType::F32),
         };
 
         let input_tensors = [
@@ -351,6 +422,7 @@ impl SplatBackwardOps<Self> for Fusion<MainBackendBase> {
             state.log_scales,
             state.raw_opac,
             state.out_img,
+            state.img_gradient,
             state.projected_splats,
             state.uniforms_buffer,
             state.tile_offsets,
@@ -365,6 +437,7 @@ impl SplatBackwardOps<Self> for Fusion<MainBackendBase> {
             &grads.v_coeffs,
             &grads.v_raw_opac,
             &grads.v_refine_weight,
+            &grads.v_viewmat,
         ];
 
         let mut stream = OperationStreams::default();
@@ -385,6 +458,8 @@ impl SplatBackwardOps<Self> for Fusion<MainBackendBase> {
                 // state,
                 desc,
                 sh_degree: state.sh_degree,
+                camera_grad: state.camera_grad,
+                bwd_depth: state.bwd_depth,
             },
         );
         grads
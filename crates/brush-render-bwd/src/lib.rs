@@ -1,3 +1,6 @@
 pub mod burn_glue;
 mod render_bwd;
 mod shaders;
+
+#[cfg(all(test, not(target_family = "wasm")))]
+mod tests;
@@ -1,21 +1,33 @@
-use super::shaders::{project_backwards, rasterize_backwards};
-use brush_kernel::{CubeCount, CubeTensor, calc_cube_count, kernel_source_gen};
+use super::shaders::{project_backwards, rasterize_backwards, upscale_backwards};
+use brush_kernel::{CubeCount, CubeTensor, calc_cube_count, create_tensor, kernel_source_gen};
 
 use brush_render::MainBackendBase;
 use brush_render::sh::sh_coeffs_for_degree;
-use burn::tensor::FloatDType;
 use burn::tensor::ops::FloatTensorOps;
+use burn::tensor::{DType, FloatDType};
 use burn::{backend::wgpu::WgpuRuntime, prelude::Backend, tensor::ops::FloatTensor};
 use burn_cubecl::cubecl::AtomicFeature;
 use burn_cubecl::cubecl::server::Bindings;
 use burn_cubecl::kernel::into_contiguous;
 use glam::uvec2;
 
-kernel_source_gen!(ProjectBackwards {}, project_backwards);
 kernel_source_gen!(
-    RasterizeBackwards { hard_float, webgpu },
+    ProjectBackwards {
+        camera_grad,
+        bwd_depth
+    },
+    project_backwards
+);
+kernel_source_gen!(
+    RasterizeBackwards {
+        hard_float,
+        bwd_depth,
+        upscale,
+        webgpu
+    },
     rasterize_backwards
 );
+kernel_source_gen!(UpscaleBackwards { bwd_depth }, upscale_backwards);
 
 #[derive(Debug, Clone)]
 pub struct SplatGrads<B: Backend> {
@@ -25,6 +37,8 @@ pub struct SplatGrads<B: Backend> {
     pub v_coeffs: FloatTensor<B>,
     pub v_raw_opac: FloatTensor<B>,
     pub v_refine_weight: FloatTensor<B>,
+    /// Gradient of the top 3x4 rows of the view matrix, zero unless camera gradients were requested.
+    pub v_viewmat: FloatTensor<B>,
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -34,7 +48,9 @@ pub(crate) fn render_backward(
     means: CubeTensor<WgpuRuntime>,
     quats: CubeTensor<WgpuRuntime>,
     log_scales: CubeTensor<WgpuRuntime>,
+    raw_opac: CubeTensor<WgpuRuntime>,
     out_img: CubeTensor<WgpuRuntime>,
+    img_gradient: CubeTensor<WgpuRuntime>,
 
     projected_splats: CubeTensor<WgpuRuntime>,
     uniforms_buffer: CubeTensor<WgpuRuntime>,
@@ -42,6 +58,8 @@ pub(crate) fn render_backward(
     global_from_compact_gid: CubeTensor<WgpuRuntime>,
     tile_offsets: CubeTensor<WgpuRuntime>,
     sh_degree: u32,
+    camera_grad: bool,
+    bwd_depth: bool,
 ) -> SplatGrads<MainBackendBase> {
     // Comes from loss, might not be contiguous.
     let v_output = into_contiguous(v_output);
@@ -50,6 +68,7 @@ pub(crate) fn render_backward(
     let means = into_contiguous(means);
     let log_scales = into_contiguous(log_scales);
     let quats = into_contiguous(quats);
+    let raw_opac = into_contiguous(raw_opac);
 
     // We're in charge of these, SHOULD be contiguous but might as well.
     let projected_splats = into_contiguous(projected_splats);
@@ -57,6 +76,7 @@ pub(crate) fn render_backward(
     let compact_gid_from_isect = into_contiguous(compact_gid_from_isect);
     let global_from_compact_gid = into_contiguous(global_from_compact_gid);
     let tile_offsets = into_contiguous(tile_offsets);
+    let img_gradient = into_contiguous(img_gradient);
 
     let device = &out_img.device;
     let img_dimgs = out_img.shape.dims;
@@ -66,6 +86,48 @@ pub(crate) fn render_backward(
 
     let client = &means.client;
 
+    let tile_bounds = uvec2(
+        img_size
+            .x
+            .div_ceil(brush_render::shaders::helpers::TILE_WIDTH),
+        img_size
+            .y
+            .div_ceil(brush_render::shaders::helpers::TILE_WIDTH),
+    );
+
+    // The output was upscaled if it's larger than the rasterized image. In that case, first
+    // get the gradients of the rasterized image and its derivatives.
+    let upscale = v_output.shape != out_img.shape;
+
+    let (v_output, v_img_gradient) = if upscale {
+        let v_img = create_tensor(
+            [img_dimgs[0], img_dimgs[1], img_dimgs[2]],
+            device,
+            DType::F32,
+        );
+        let v_img_gradient = create_tensor([img_dimgs[0], img_dimgs[1], 4 * 3], device, DType::F32);
+
+        tracing::trace_span!("UpscaleBackwards").in_scope(|| {
+            // SAFETY: Kernel checked to have no OOB, bounded loops.
+            unsafe {
+                client.execute_unchecked(
+                    UpscaleBackwards::task(bwd_depth),
+                    CubeCount::Static(tile_bounds.x * tile_bounds.y, 1, 1),
+                    Bindings::new().with_buffers(vec![
+                        uniforms_buffer.handle.clone().binding(),
+                        v_output.handle.binding(),
+                        v_img.handle.clone().binding(),
+                        v_img_gradient.handle.clone().binding(),
+                    ]),
+                );
+            }
+        });
+
+        (v_img, Some(v_img_gradient))
+    } else {
+        (v_output, None)
+    };
+
     // Setup tensors.
     // Nb: these are packed vec3 values, special care is taken in the kernel to respect alignment.
     let v_means = MainBackendBase::float_zeros([num_points, 3].into(), device, FloatDType::F32);
@@ -81,15 +143,11 @@ pub(crate) fn render_backward(
     let v_grads = MainBackendBase::float_zeros([num_points, 8].into(), device, FloatDType::F32);
     let v_refine_weight =
         MainBackendBase::float_zeros([num_points].into(), device, FloatDType::F32);
-
-    let tile_bounds = uvec2(
-        img_size
-            .x
-            .div_ceil(brush_render::shaders::helpers::TILE_WIDTH),
-        img_size
-            .y
-            .div_ceil(brush_render::shaders::helpers::TILE_WIDTH),
-    );
+    // Only pay for the per splat camera and depth gradients when they're needed.
+    let v_viewmat = camera_grad
+        .then(|| MainBackendBase::float_zeros([num_points, 12].into(), device, FloatDType::F32));
+    let v_depths = bwd_depth
+        .then(|| MainBackendBase::float_zeros([num_points].into(), device, FloatDType::F32));
 
     let hard_floats =
         client
@@ -100,48 +158,66 @@ pub(crate) fn render_backward(
 
     let webgpu = cfg!(target_family = "wasm");
 
+    let mut rasterize_buffers = vec![
+        uniforms_buffer.handle.clone().binding(),
+        compact_gid_from_isect.handle.binding(),
+        global_from_compact_gid.handle.clone().binding(),
+        tile_offsets.handle.binding(),
+        projected_splats.handle.binding(),
+        out_img.handle.binding(),
+        v_output.handle.binding(),
+        v_grads.handle.clone().binding(),
+        v_raw_opac.handle.clone().binding(),
+        v_refine_weight.handle.clone().binding(),
+    ];
+    if let Some(v_depths) = &v_depths {
+        rasterize_buffers.push(v_depths.handle.clone().binding());
+    }
+    if let Some(v_img_gradient) = v_img_gradient {
+        rasterize_buffers.push(img_gradient.handle.binding());
+        rasterize_buffers.push(v_img_gradient.handle.binding());
+    }
+
     // Use checked execution, as the atomic loops are potentially unbounded.
     tracing::trace_span!("RasterizeBackwards").in_scope(|| {
         // SAFETY: Kernel checked to have no OOB, bounded loops.
         unsafe {
             client.execute_unchecked(
-                RasterizeBackwards::task(hard_floats, webgpu),
+                RasterizeBackwards::task(hard_floats, bwd_depth, upscale, webgpu),
                 CubeCount::Static(tile_bounds.x * tile_bounds.y, 1, 1),
-                Bindings::new().with_buffers(vec![
-                    uniforms_buffer.handle.clone().binding(),
-                    compact_gid_from_isect.handle.binding(),
-                    global_from_compact_gid.handle.clone().binding(),
-                    tile_offsets.handle.binding(),
-                    projected_splats.handle.binding(),
-                    out_img.handle.binding(),
-                    v_output.handle.binding(),
-                    v_grads.handle.clone().binding(),
-                    v_raw_opac.handle.clone().binding(),
-                    v_refine_weight.handle.clone().binding(),
-                ]),
+                Bindings::new().with_buffers(rasterize_buffers),
             );
         }
     });
 
+    let mut project_buffers = vec![
+        uniforms_buffer.handle.binding(),
+        means.handle.binding(),
+        log_scales.handle.binding(),
+        quats.handle.binding(),
+        global_from_compact_gid.handle.binding(),
+        v_grads.handle.binding(),
+        v_means.handle.clone().binding(),
+        v_scales.handle.clone().binding(),
+        v_quats.handle.clone().binding(),
+        v_coeffs.handle.clone().binding(),
+        raw_opac.handle.binding(),
+        v_raw_opac.handle.clone().binding(),
+    ];
+    if let Some(v_viewmat) = &v_viewmat {
+        project_buffers.push(v_viewmat.handle.clone().binding());
+    }
+    if let Some(v_depths) = v_depths {
+        project_buffers.push(v_depths.handle.binding());
+    }
+
     tracing::trace_span!("ProjectBackwards").in_scope(||
         // SAFETY: Kernel has to contain no OOB indexing, bounded loops.
         unsafe {
         client.execute_unchecked(
-            ProjectBackwards::task(),
+            ProjectBackwards::task(camera_grad, bwd_depth),
             calc_cube_count([num_points as u32], ProjectBackwards::WORKGROUP_SIZE),
-            Bindings::new().with_buffers(
-            vec![
-                uniforms_buffer.handle.binding(),
-                means.handle.binding(),
-                log_scales.handle.binding(),
-                quats.handle.binding(),
-                global_from_compact_gid.handle.binding(),
-                v_grads.handle.binding(),
-                v_means.handle.clone().binding(),
-                v_scales.handle.clone().binding(),
-                v_quats.handle.clone().binding(),
-                v_coeffs.handle.clone().binding()
-            ]),
+            Bindings::new().with_buffers(project_buffers),
         );
     });
 
@@ -152,6 +228,14 @@ pub(crate) fn render_backward(
     assert!(v_raw_opac.is_contiguous(), "Grads must be contiguous");
     assert!(v_refine_weight.is_contiguous(), "Grads must be contiguous");
 
+    // Sum up the contributions of all splats to the camera gradient.
+    let v_viewmat = if let Some(v_viewmat) = v_viewmat {
+        let v_viewmat = MainBackendBase::float_sum_dim(v_viewmat, 0);
+        MainBackendBase::float_reshape(v_viewmat, [3, 4].into())
+    } else {
+        MainBackendBase::float_zeros([3, 4].into(), device, FloatDType::F32)
+    };
+
     SplatGrads {
         v_means,
         v_quats,
@@ -159,5 +243,6 @@ pub(crate) fn render_backward(
         v_coeffs,
         v_raw_opac,
         v_refine_weight,
+        v_viewmat,
     }
 }
@@ -15,6 +15,23 @@
 @group(0) @binding(7) var<storage, read_write> v_scales: array<helpers::PackedVec3>;
 @group(0) @binding(8) var<storage, read_write> v_quats: array<vec4f>;
 @group(0) @binding(9) var<storage, read_write> v_coeffs: array<f32>;
+@group(0) @binding(10) var<storage, read> raw_opacities: array<f32>;
+// Holds the gradient of the projected opacity, which is replaced by the raw opacity gradient.
+@group(0) @binding(11) var<storage, read_write> v_opacs: array<f32>;
+
+// Bindings are numbered in order, the optional buffers come last.
+#ifdef CAMERA_GRAD
+    // Per splat gradient of the top 3x4 rows of the view matrix, stored row-major.
+    @group(0) @binding(12) var<storage, read_write> v_viewmat: array<f32>;
+    #ifdef BWD_DEPTH
+        // Gradient of the camera space depth of each splat.
+        @group(0) @binding(13) var<storage, read> v_depths: array<f32>;
+    #endif
+#else
+    #ifdef BWD_DEPTH
+        @group(0) @binding(12) var<storage, read> v_depths: array<f32>;
+    #endif
+#endif
 
 const SH_C0: f32 = 0.2820947917738781f;
 
@@ -209,6 +226,14 @@ fn inverse_vjp(Minv: mat2x2f, v_Minv: mat2x2f) -> mat2x2f {
     return mat2x2f(-Minv[0], -Minv[1]) * v_Minv * Minv;
 }
 
+fn cov_compensation_vjp(cov2d: mat2x2f, blur: f32, compensation: f32, v_compensation: f32) -> mat2x2f {
+    // c = sqrt(det(cov_orig) / det(cov2d)), with cov_orig = cov2d - blur * I
+    // -> dc/dcov2d = c / 2 * (cov_orig^-1 - cov2d^-1)
+    let cov_orig = cov2d - mat2x2f(vec2f(blur, 0.0), vec2f(0.0, blur));
+    let v_inv = helpers::inverse(cov_orig) - helpers::inverse(cov2d);
+    return v_inv * (0.5f * compensation * v_compensation);
+}
+
 fn outer_product(a: vec3<f32>, b: vec3<f32>) -> mat3x3<f32> {
     return mat3x3f(
         a.x * b.x, a.x * b.y, a.x * b.z,
@@ -228,6 +253,7 @@ fn persp_proj_vjp(
     // grad outputs
     v_cov2d: mat2x2f,
     v_mean2d: vec2f,
+    v_depth: f32,
 ) -> vec3f {
     let x = mean3d.x;
     let y = mean3d.y;
@@ -282,12 +308,22 @@ fn persp_proj_vjp(
                   2.f * focal.y * ty * rz3 * v_J[2][1];
 
     // add contribution from v_depths
-    // Disabled as there is no depth supervision currently.
-    // v_mean3d.z += v_depths[0];
+    v_mean3d.z += v_depth;
 
     return v_mean3d;
 }
 
+fn ortho_proj_vjp(
+    focal: vec2f,
+    // grad outputs
+    v_mean2d: vec2f,
+    v_depth: f32,
+) -> vec3f {
+    // The orthographic projection is linear: mean2d = focal * mean3d.xy + pixel_center.
+    // The projected covariance doesn't depend on the mean either, as J is constant.
+    return vec3f(focal * v_mean2d, v_depth);
+}
+
 @compute
 @workgroup_size(256, 1, 1)
 fn main(@builtin(global_invocation_id) gid: vec3u) {
@@ -316,7 +352,7 @@ fn main(@builtin(global_invocation_id) gid: vec3u) {
     let v_conics = vec3f(grad0.z, grad0.w, grad1.x);
     let v_color = vec3f(grad1.y, grad1.z, grad1.w);
 
-    let viewdir = normalize(mean - uniforms.camera_position.xyz);
+    let viewdir = helpers::calc_viewdir(mean, uniforms.camera_position.xyz, viewmat, uniforms.orthographic);
 
     let sh_degree = uniforms.sh_degree;
     let v_coeff = sh_coeffs_to_color_fast_vjp(sh_degree, viewdir, v_color);
@@ -368,19 +404,40 @@ fn main(@builtin(global_invocation_id) gid: vec3u) {
     let M = rotmat * S;
 
     let covar = M * transpose(M);
-    let cov2d = helpers::calc_cov2d(covar, mean_c, focal, img_size, pixel_center, viewmat);
+    let blur = helpers::cov_blur(uniforms.mip_filter);
+    let cov2d = helpers::calc_cov2d(covar, mean_c, focal, img_size, pixel_center, viewmat, blur, uniforms.orthographic);
     let covar2d_inv = helpers::inverse(cov2d);
 
     let v_covar2d_inv = mat2x2f(vec2f(v_conics.x, v_conics.y * 0.5f), vec2f(v_conics.y * 0.5f, v_conics.z));
 
-    let v_covar2d = inverse_vjp(covar2d_inv, v_covar2d_inv);
+    var v_covar2d = inverse_vjp(covar2d_inv, v_covar2d_inv);
+
+    // The projected opacity is sigmoid(raw_opacity), scaled by the 2D filter compensation if enabled.
+    let opac = helpers::sigmoid(raw_opacities[global_gid]);
+    let v_opac = v_opacs[global_gid];
+    var compensation = 1.0f;
+    if uniforms.mip_filter == 1u {
+        compensation = helpers::cov_compensation(cov2d, blur);
+        v_covar2d += cov_compensation_vjp(cov2d, blur, compensation, v_opac * opac);
+    }
+    v_opacs[global_gid] = v_opac * compensation * opac * (1.0f - opac);
 
     // covar_world_to_cam
     let covar_c = R * covar * transpose(R);
 
     // persp_proj_vjp
-    let J = helpers::calc_cam_J(mean_c, focal, img_size, pixel_center);
-    let v_mean_c = persp_proj_vjp(J, mean_c, covar_c, focal, pixel_center, img_size, v_covar2d, v_mean2d);
+    let J = helpers::calc_cam_J(mean_c, focal, img_size, pixel_center, uniforms.orthographic);
+#ifdef BWD_DEPTH
+    let v_depth = v_depths[global_gid];
+#else
+    let v_depth = 0.0;
+#endif
+    var v_mean_c: vec3f;
+    if uniforms.orthographic == 1u {
+        v_mean_c = ortho_proj_vjp(focal, v_mean2d, v_depth);
+    } else {
+        v_mean_c = persp_proj_vjp(J, mean_c, covar_c, focal, pixel_center, img_size, v_covar2d, v_mean2d, v_depth);
+    }
     // cov = J * V * Jt; G = df/dcov = v_cov
     // -> df/dV = Jt * G * J
     // -> df/dJ = G * J * Vt + Gt * J * V
@@ -393,14 +450,26 @@ fn main(@builtin(global_invocation_id) gid: vec3u) {
     // for D = W * X, G = df/dD
     // df/dW = G * XT, df/dX = WT * G
 
-    // TODO: Camera gradient is not done yet.
-    // var v_R = outer_product(v_mean_c, mean);
-    let v_mean = transpose(R) * v_mean_c;
+#ifdef CAMERA_GRAD
+    // mean_c = R * mean + t
+    var v_R = mat3x3f(v_mean_c * mean.x, v_mean_c * mean.y, v_mean_c * mean.z);
+    let v_t = v_mean_c;
 
     // covar_world_to_cam_vjp
-    // TODO: Camera gradient is not done yet.
-    // v_R += v_covar_c * R * transpose(covar) +
-    //        transpose(v_covar_c) * R * covar;
+    v_R += v_covar_c * R * transpose(covar) +
+           transpose(v_covar_c) * R * covar;
+
+    // Nb: The SH view direction also depends on the camera position, this is ignored.
+    let base_viewmat = global_gid * 12;
+    for (var row = 0u; row < 3u; row++) {
+        v_viewmat[base_viewmat + row * 4 + 0] = v_R[0][row];
+        v_viewmat[base_viewmat + row * 4 + 1] = v_R[1][row];
+        v_viewmat[base_viewmat + row * 4 + 2] = v_R[2][row];
+        v_viewmat[base_viewmat + row * 4 + 3] = v_t[row];
+    }
+#endif
+
+    let v_mean = transpose(R) * v_mean_c;
 
     let v_covar = transpose(R) * v_covar_c * R;
 
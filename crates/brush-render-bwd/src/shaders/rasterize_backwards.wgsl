@@ -5,13 +5,17 @@
 @group(0) @binding(2) var<storage, read> global_from_compact_gid: array<u32>;
 @group(0) @binding(3) var<storage, read> tile_offsets: array<u32>;
 @group(0) @binding(4) var<storage, read> projected: array<helpers::ProjectedSplat>;
-@group(0) @binding(5) var<storage, read> output: array<vec4f>;
-@group(0) @binding(6) var<storage, read> v_output: array<vec4f>;
+// Both store helpers::BWD_INFO_CHANNELS floats per pixel, the depth channel is only present with BWD_DEPTH.
+@group(0) @binding(5) var<storage, read> output: array<f32>;
+@group(0) @binding(6) var<storage, read> v_output: array<f32>;
 
 #ifdef HARD_FLOAT
     @group(0) @binding(7) var<storage, read_write> v_splats: array<atomic<f32>>;
     @group(0) @binding(8) var<storage, read_write> v_opacs: array<atomic<f32>>;
     @group(0) @binding(9) var<storage, read_write> v_refines: array<atomic<f32>>;
+    #ifdef BWD_DEPTH
+        @group(0) @binding(10) var<storage, read_write> v_depths: array<atomic<f32>>;
+    #endif
 
     fn write_grads_atomic(id: u32, grads: f32) {
         atomicAdd(&v_splats[id], grads);
@@ -22,10 +26,18 @@
     fn write_opac_atomic(id: u32, grads: f32) {
         atomicAdd(&v_opacs[id], grads);
     }
+    #ifdef BWD_DEPTH
+        fn write_depth_atomic(id: u32, grads: f32) {
+            atomicAdd(&v_depths[id], grads);
+        }
+    #endif
 #else
     @group(0) @binding(7) var<storage, read_write> v_splats: array<atomic<u32>>;
     @group(0) @binding(8) var<storage, read_write> v_opacs: array<atomic<u32>>;
     @group(0) @binding(9) var<storage, read_write> v_refines: array<atomic<u32>>;
+    #ifdef BWD_DEPTH
+        @group(0) @binding(10) var<storage, read_write> v_depths: array<atomic<u32>>;
+    #endif
 
     fn add_bitcast(cur: u32, add: f32) -> u32 {
         return bitcast<u32>(bitcast<f32>(cur) + add);
@@ -51,6 +63,26 @@
             if cas.exchanged { break; } else { old_value = cas.old_value; }
         }
     }
+    #ifdef BWD_DEPTH
+        fn write_depth_atomic(id: u32, grads: f32) {
+            var old_value = atomicLoad(&v_depths[id]);
+            loop {
+                let cas = atomicCompareExchangeWeak(&v_depths[id], old_value, add_bitcast(old_value, grads));
+                if cas.exchanged { break; } else { old_value = cas.old_value; }
+            }
+        }
+    #endif
+#endif
+
+#ifdef UPSCALE
+    // The dx, dy and dxy derivatives of the RGBA image used for upscaling, and their gradients.
+    #ifdef BWD_DEPTH
+        @group(0) @binding(11) var<storage, read> img_gradient: array<array<vec4f, 3>>;
+        @group(0) @binding(12) var<storage, read> v_img_gradient: array<array<vec4f, 3>>;
+    #else
+        @group(0) @binding(10) var<storage, read> img_gradient: array<array<vec4f, 3>>;
+        @group(0) @binding(11) var<storage, read> v_img_gradient: array<array<vec4f, 3>>;
+    #endif
 #endif
 
 const THREAD_COUNT: u32 = 64u;
@@ -76,6 +108,21 @@ fn main(
     var pix_outs = array<vec4f, PIXELS_PER_THREAD>();
     var dones = array<bool, PIXELS_PER_THREAD>();
     var rgb_pixel_finals = array<vec4f, PIXELS_PER_THREAD>();
+    #ifdef BWD_DEPTH
+        var depth_outs = array<f32, PIXELS_PER_THREAD>();
+        var depth_finals = array<f32, PIXELS_PER_THREAD>();
+        var v_depth_outs = array<f32, PIXELS_PER_THREAD>();
+    #endif
+
+    #ifdef UPSCALE
+        // Derivatives of the image so far, and of the final image, without background.
+        // These store the RGB derivatives and the alpha derivatives in the last channel.
+        var pix_grads = array<array<vec4f, 3>, PIXELS_PER_THREAD>();
+        var final_grads = array<array<vec4f, 3>, PIXELS_PER_THREAD>();
+        // Gradients of the final image and its derivatives, with respect to the image without background.
+        var v_finals = array<vec4f, PIXELS_PER_THREAD>();
+        var v_grads = array<array<vec4f, 3>, PIXELS_PER_THREAD>();
+    #endif
 
     for (var i = 0u; i < PIXELS_PER_THREAD; i++) {
         // Process 4 consecutive pixels in the original linear order
@@ -85,17 +132,37 @@ fn main(
         pix_ids[i] = pix_id;
 
         if pix_locs[i].x < uniforms.img_size.x && pix_locs[i].y < uniforms.img_size.y {
-            let final_color = output[pix_id];
-            let v_out = v_output[pix_id];
+            let base = pix_id * helpers::BWD_INFO_CHANNELS;
+            let final_color = vec4f(output[base + 0], output[base + 1], output[base + 2], output[base + 3]);
+            let v_out = vec4f(v_output[base + 0], v_output[base + 1], v_output[base + 2], v_output[base + 3]);
+            #ifdef BWD_DEPTH
+                depth_finals[i] = output[base + 4];
+                v_depth_outs[i] = v_output[base + 4];
+            #endif
             let T_final = 1.0f - final_color.a;
             rgb_pixel_finals[i] = vec4f(final_color.rgb - T_final * uniforms.background.rgb, final_color.a);
             v_outs[i] = vec4f(v_out.rgb, (v_out.a - dot(uniforms.background.rgb, v_out.rgb)) * T_final);
             dones[i] = false;
+
+            #ifdef UPSCALE
+                // The background is composed with a weight of 1 - alpha.
+                let background = uniforms.background.rgb;
+                let grad = img_gradient[pix_id];
+                let v_grad = v_img_gradient[pix_id];
+                for (var g = 0u; g < 3u; g++) {
+                    final_grads[i][g] = vec4f(grad[g].rgb + grad[g].a * background, grad[g].a);
+                    v_grads[i][g] = vec4f(v_grad[g].rgb, v_grad[g].a - dot(background, v_grad[g].rgb));
+                }
+                v_finals[i] = vec4f(v_out.rgb, v_out.a - dot(background, v_out.rgb));
+            #endif
         } else {
             dones[i] = true;
         }
 
         pix_outs[i] = vec4f(0.0, 0.0, 0.0, 1.0);
+        #ifdef BWD_DEPTH
+            depth_outs[i] = 0.0;
+        #endif
     }
 
     let tile_loc = vec2u(pix_locs[0].x / helpers::TILE_WIDTH, pix_locs[0].y / helpers::TILE_WIDTH);
@@ -132,6 +199,9 @@ fn main(
             var v_rgb_thread = vec3f(0.0f);
             var v_alpha_thread = 0.0f;
             var v_refine_thread = 0.0f;
+            #ifdef BWD_DEPTH
+                var v_depth_thread = 0.0f;
+            #endif
             var hasGrad = false;
 
             let proj = local_batch[t];
@@ -161,17 +231,90 @@ fn main(
                     continue;
                 }
 
-                let vis = alpha * pix_outs[i].a;
+                let T = pix_outs[i].a;
+                let vis = alpha * T;
+
+                #ifdef UPSCALE
+                    // Derivatives of the gaussian with respect to the pixel coordinate, over the gaussian.
+                    let dg_dx = conic.x * delta.x + conic.y * delta.y;
+                    let dg_dy = conic.y * delta.x + conic.z * delta.y;
+                    let dg_dxy = dg_dx * dg_dy - conic.y;
+                    let dalpha = alpha * vec3f(dg_dx, dg_dy, dg_dxy);
+
+                    // The final image is the image so far, plus T times the composite of this splat over the
+                    // splats behind it. Get the gradients of this composite, and its derivatives.
+                    let prev = pix_grads[i];
+                    let v_comp = T * v_finals[i] - prev[0].a * v_grads[i][0] - prev[1].a * v_grads[i][1] - prev[2].a * v_grads[i][2];
+                    let v_comp_dx = T * v_grads[i][0] - prev[1].a * v_grads[i][2];
+                    let v_comp_dy = T * v_grads[i][1] - prev[0].a * v_grads[i][2];
+                    let v_comp_dxy = T * v_grads[i][2];
+
+                    // Add this splat to the image derivatives, as done when rasterizing.
+                    let color_alpha = vec4f(clamped_rgb, 1.0f);
+                    pix_grads[i][0] = prev[0] + color_alpha * (T * dalpha.x - alpha * prev[0].a);
+                    pix_grads[i][1] = prev[1] + color_alpha * (T * dalpha.y - alpha * prev[1].a);
+                    pix_grads[i][2] = prev[2] + color_alpha * (T * dalpha.z - alpha * prev[2].a - dalpha.x * prev[1].a - dalpha.y * prev[0].a);
+                    let next = pix_grads[i];
+
+                    // Composite of the splats behind this one, and its derivatives, derived from the final image.
+                    let pix_next = vec4f(pix_outs[i].rgb + vis * clamped_rgb, 1.0f - next_T);
+                    let behind = (rgb_pixel_finals[i] - pix_next) / next_T;
+                    let behind_dx = (final_grads[i][0] - next[0] + next[0].a * behind) / next_T;
+                    let behind_dy = (final_grads[i][1] - next[1] + next[1].a * behind) / next_T;
+                    let behind_dxy = (final_grads[i][2] - next[2] + next[2].a * behind + next[0].a * behind_dy + next[1].a * behind_dx) / next_T;
+
+                    let color_diff = color_alpha - behind;
+                    let v_dalpha = vec3f(
+                        dot(v_comp_dx, color_diff) - dot(v_comp_dxy, behind_dy),
+                        dot(v_comp_dy, color_diff) - dot(v_comp_dxy, behind_dx),
+                        dot(v_comp_dxy, color_diff),
+                    );
+
+                    let v_color = v_comp * alpha + v_comp_dx * dalpha.x + v_comp_dy * dalpha.y + v_comp_dxy * dalpha.z;
+                    let v_rgb_local = select(vec3f(0.0f), v_color.rgb, color.rgb >= vec3f(0.0f));
+                #else
+                    let v_rgb_local = select(vec3f(0.0f), vis * v_outs[i].rgb, color.rgb >= vec3f(0.0f));
+                #endif
 
                 // update v_colors for this gaussian
-                let v_rgb_local = select(vec3f(0.0f), vis * v_outs[i].rgb, color.rgb >= vec3f(0.0f));
                 v_rgb_thread += v_rgb_local;
 
                 // add contribution of this gaussian to the pixel
                 pix_outs[i] = vec4f(pix_outs[i].rgb + vis * clamped_rgb, pix_outs[i].a);
 
                 let ra = 1.0f / (1.0f - alpha);
-                let v_alpha = dot(pix_outs[i].a * clamped_rgb + (pix_outs[i].rgb - rgb_pixel_finals[i].rgb) * ra, v_outs[i].rgb) + v_outs[i].a * ra;
+                #ifdef BWD_DEPTH
+                    // update v_depth for this gaussian
+                    v_depth_thread += vis * v_depth_outs[i];
+                    depth_outs[i] += vis * proj.depth;
+
+                    let v_alpha_depth = (pix_outs[i].a * proj.depth + (depth_outs[i] - depth_finals[i]) * ra) * v_depth_outs[i];
+                #else
+                    let v_alpha_depth = 0.0f;
+                #endif
+
+                #ifdef UPSCALE
+                    let v_alpha = dot(v_comp, color_diff) - dot(v_comp_dx, behind_dx) - dot(v_comp_dy, behind_dy) - dot(v_comp_dxy, behind_dxy) +
+                                  dot(v_dalpha, vec3f(dg_dx, dg_dy, dg_dxy)) + v_alpha_depth;
+
+                    // Gradients through the derivatives of the gaussian, these apply even when alpha is clamped.
+                    let v_dg = alpha * v_dalpha;
+                    let v_dg_dx = v_dg.x + v_dg.z * dg_dy;
+                    let v_dg_dy = v_dg.y + v_dg.z * dg_dx;
+                    v_conic_thread += vec3f(
+                        v_dg_dx * delta.x,
+                        v_dg_dx * delta.y + v_dg_dy * delta.x - v_dg.z,
+                        v_dg_dy * delta.y
+                    );
+                    v_xy_thread += vec2f(
+                        v_dg_dx * conic.x + v_dg_dy * conic.y,
+                        v_dg_dx * conic.y + v_dg_dy * conic.z
+                    );
+                #else
+                    let v_alpha = dot(pix_outs[i].a * clamped_rgb + (pix_outs[i].rgb - rgb_pixel_finals[i].rgb) * ra, v_outs[i].rgb) + v_outs[i].a * ra +
+                                  v_alpha_depth;
+                #endif
+
                 let v_sigma = -alpha * v_alpha;
                 let v_xy_local = v_sigma * vec2f(
                     conic.x * delta.x + conic.y * delta.y,
@@ -187,7 +330,8 @@ fn main(
                     );
 
                     v_xy_thread += v_xy_local;
-                    v_alpha_thread += alpha * (1.0f - color.a) * v_alpha;
+                    // Gradient of the projected opacity, project_backwards turns this into the raw opacity gradient.
+                    v_alpha_thread += gaussian * v_alpha;
                     let final_a = max(rgb_pixel_finals[i].a, 1e-5f);
                     // Divide as we don't have sum vis == 1, so reweight these gradients comparatively.
                     v_refine_thread += length(v_xy_local * vec2f(uniforms.img_size.xy)) / final_a;
@@ -212,6 +356,9 @@ fn main(
                 let sum_rgb = subgroupAdd(v_rgb_thread);
                 let sum_alpha = subgroupAdd(v_alpha_thread);
                 let sum_refine = subgroupAdd(v_refine_thread);
+                #ifdef BWD_DEPTH
+                    let sum_depth = subgroupAdd(v_depth_thread);
+                #endif
 
                 if doAdd {
                     let global_gid = load_gid[t];
@@ -226,6 +373,9 @@ fn main(
                     write_grads_atomic(global_gid * 8 + 7, sum_rgb.z);
                     write_opac_atomic(global_gid, sum_alpha);
                     write_refine_atomic(global_gid, sum_refine);
+                    #ifdef BWD_DEPTH
+                        write_depth_atomic(global_gid, sum_depth);
+                    #endif
                 }
             }
         }
@@ -0,0 +1,90 @@
+#import helpers
+
+@group(0) @binding(0) var<storage, read> uniforms: helpers::RenderUniforms;
+// Both store helpers::BWD_INFO_CHANNELS floats per pixel.
+@group(0) @binding(1) var<storage, read> v_output: array<f32>;
+@group(0) @binding(2) var<storage, read_write> v_img: array<f32>;
+// Gradients of the dx, dy and dxy derivatives of the RGBA image.
+@group(0) @binding(3) var<storage, read_write> v_img_gradient: array<array<vec4f, 3>>;
+
+// Backward pass of the upscale kernel, each thread treats a single pixel of the rasterized image.
+//
+// Rather than scattering the gradients of each upscaled pixel with atomics, each thread gathers
+// the gradients of all upscaled pixels that are interpolated from its pixel.
+@compute
+@workgroup_size(helpers::TILE_SIZE, 1, 1)
+fn main(
+    @builtin(global_invocation_id) global_id: vec3u,
+) {
+    let pix_loc = helpers::map_1d_to_2d(global_id.x, uniforms.tile_bounds.x);
+
+    if pix_loc.x >= uniforms.img_size.x || pix_loc.y >= uniforms.img_size.y {
+        return;
+    }
+
+    let pix_id = pix_loc.x + pix_loc.y * uniforms.img_size.x;
+
+    // Upscaled pixels interpolate between the two nearest rasterized pixels along each axis.
+    // Find the range of upscaled pixels that could use this pixel, with some margin for rounding.
+    let scale = vec2f(uniforms.target_size) / vec2f(uniforms.img_size);
+    let range_min = max(vec2i(floor((vec2f(pix_loc) - 0.5f) * scale - 0.5f)) - 1, vec2i(0));
+    let range_max = min(vec2i(ceil((vec2f(pix_loc) + 1.5f) * scale - 0.5f)) + 1, vec2i(uniforms.target_size) - 1);
+
+    var v_color = vec4f(0.0f);
+#ifdef BWD_DEPTH
+    var v_depth = 0.0f;
+#endif
+    var v_dx = vec4f(0.0f);
+    var v_dy = vec4f(0.0f);
+    var v_dxy = vec4f(0.0f);
+
+    for (var y = range_min.y; y <= range_max.y; y++) {
+        for (var x = range_min.x; x <= range_max.x; x++) {
+            let target_loc = vec2u(u32(x), u32(y));
+            let source_coord = helpers::upscale_source_coord(target_loc, uniforms.img_size, uniforms.target_size);
+            let source_base = floor(source_coord);
+            let hx = helpers::hermite_basis(source_coord.x - source_base.x);
+            let hy = helpers::hermite_basis(source_coord.y - source_base.y);
+
+            let base = (target_loc.x + target_loc.y * uniforms.target_size.x) * helpers::BWD_INFO_CHANNELS;
+            let v_out = vec4f(v_output[base + 0], v_output[base + 1], v_output[base + 2], v_output[base + 3]);
+#ifdef BWD_DEPTH
+            let v_out_depth = v_output[base + 4];
+#endif
+
+            // Match the interpolation of the forward pass, including clamping to the border.
+            for (var j = 0u; j < 2u; j++) {
+                for (var i = 0u; i < 2u; i++) {
+                    let source_loc = clamp(
+                        vec2i(source_base) + vec2i(i32(i), i32(j)),
+                        vec2i(0),
+                        vec2i(uniforms.img_size) - 1,
+                    );
+
+                    if all(vec2u(source_loc) == pix_loc) {
+                        v_color += hx[i] * hy[j] * v_out;
+#ifdef BWD_DEPTH
+                        v_depth += hx[i] * hy[j] * v_out_depth;
+#endif
+                        v_dx += hx[i + 2u] * hy[j] * v_out;
+                        v_dy += hx[i] * hy[j + 2u] * v_out;
+                        v_dxy += hx[i + 2u] * hy[j + 2u] * v_out;
+                    }
+                }
+            }
+        }
+    }
+
+    let base = pix_id * helpers::BWD_INFO_CHANNELS;
+    v_img[base + 0] = v_color.r;
+    v_img[base + 1] = v_color.g;
+    v_img[base + 2] = v_color.b;
+    v_img[base + 3] = v_color.a;
+#ifdef BWD_DEPTH
+    v_img[base + 4] = v_depth;
+#endif
+
+    v_img_gradient[pix_id][0] = v_dx;
+    v_img_gradient[pix_id][1] = v_dy;
+    v_img_gradient[pix_id][2] = v_dxy;
+}
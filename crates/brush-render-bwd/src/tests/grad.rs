@@ -0,0 +1,173 @@
+use brush_render::{MainBackend, RenderScale, camera::Camera, gaussian_splats::Splats};
+use burn::{
+    backend::{Autodiff, wgpu::WgpuDevice},
+    tensor::{Distribution, Tensor, TensorPrimitive, s},
+};
+use glam::{Quat, Vec3};
+use rand::{Rng, SeedableRng};
+
+use crate::burn_glue::SplatForwardDiff;
+
+type DiffBackend = Autodiff<MainBackend>;
+
+const TEST_SEED: u64 = 12345;
+
+/// Weighted sum of the rendered RGBA, so every pixel contributes differently to the gradients.
+fn weighted_render_loss(
+    splats: &Splats<DiffBackend>,
+    camera: &Camera,
+    weights: Tensor<DiffBackend, 3>,
+    render_scale: RenderScale,
+) -> Tensor<DiffBackend, 1> {
+    let [h, w, _] = weights.dims();
+
+    let result = <DiffBackend as SplatForwardDiff<DiffBackend>>::render_splats(
+        camera,
+        glam::uvec2(w as u32, h as u32),
+        splats.means.val().into_primitive().tensor(),
+        splats.log_scales.val().into_primitive().tensor(),
+        splats.rotation.val().into_primitive().tensor(),
+        splats.sh_coeffs.val().into_primitive().tensor(),
+        splats.raw_opacity.val().into_primitive().tensor(),
+        Vec3::ZERO,
+        false,
+        render_scale,
+        false,
+        false,
+    );
+
+    let rendered: Tensor<DiffBackend, 3> =
+        Tensor::from_primitive(TensorPrimitive::Float(result.img));
+    (rendered.slice(s![.., .., 0..4]) * weights).sum()
+}
+
+/// A few large, semi-transparent splats that overlap, so that both the colors and the
+/// transmittance of splats behind each other matter.
+fn overlapping_test_splats(device: &WgpuDevice) -> Splats<DiffBackend> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(TEST_SEED);
+
+    let count = 16;
+    let means = (0..count * 3)
+        .map(|_| rng.random_range(-0.6..0.6))
+        .collect();
+    let log_scales = (0..count * 3)
+        .map(|_| rng.random_range(0.15..0.35_f32).ln())
+        .collect();
+    let rotations = (0..count)
+        .flat_map(|_| {
+            [
+                1.0,
+                rng.random_range(-0.5..0.5),
+                rng.random_range(-0.5..0.5),
+                rng.random_range(-0.5..0.5),
+            ]
+        })
+        .collect();
+    let sh_coeffs = (0..count * 3)
+        .map(|_| rng.random_range(-0.5..0.5))
+        .collect();
+    let raw_opacities = (0..count).map(|_| rng.random_range(-0.5..1.5)).collect();
+    Splats::<DiffBackend>::from_raw(
+        means,
+        Some(rotations),
+        Some(log_scales),
+        Some(sh_coeffs),
+        Some(raw_opacities),
+        device,
+    )
+}
+
+/// Check the gradients of [`weighted_render_loss`] against central differences, along a random
+/// direction in parameter space.
+fn check_render_gradients(camera: &Camera, render_scale: RenderScale) {
+    let device = WgpuDevice::default();
+    let splats = overlapping_test_splats(&device);
+
+    let weights =
+        Tensor::<DiffBackend, 3>::random([48, 48, 4], Distribution::Uniform(-1.0, 1.0), &device);
+
+    let loss = weighted_render_loss(&splats, camera, weights.clone(), render_scale);
+    let grads = loss.backward();
+
+    let dir_means = Tensor::random(
+        splats.means.dims(),
+        Distribution::Uniform(-1.0, 1.0),
+        &device,
+    );
+    let dir_scales = Tensor::random(
+        splats.log_scales.dims(),
+        Distribution::Uniform(-1.0, 1.0),
+        &device,
+    );
+    let dir_coeffs = Tensor::random(
+        splats.sh_coeffs.dims(),
+        Distribution::Uniform(-1.0, 1.0),
+        &device,
+    );
+    let dir_opac = Tensor::random(
+        splats.raw_opacity.dims(),
+        Distribution::Uniform(-1.0, 1.0),
+        &device,
+    );
+
+    // Derivative of the loss along the random direction, from the gradients.
+    let analytic = (splats.means.grad(&grads).expect("Missing gradient")
+        * dir_means.clone().inner())
+    .sum()
+    .into_scalar()
+        + (splats.log_scales.grad(&grads).expect("Missing gradient") * dir_scales.clone().inner())
+            .sum()
+            .into_scalar()
+        + (splats.sh_coeffs.grad(&grads).expect("Missing gradient") * dir_coeffs.clone().inner())
+            .sum()
+            .into_scalar()
+        + (splats.raw_opacity.grad(&grads).expect("Missing gradient") * dir_opac.clone().inner())
+            .sum()
+            .into_scalar();
+
+    // And from central differences.
+    let eps = 1e-3;
+    let perturbed_loss = |sign: f32| {
+        let perturbed = Splats::from_tensor_data(
+            splats.means.val() + dir_means.clone() * (sign * eps),
+            splats.rotation.val(),
+            splats.log_scales.val() + dir_scales.clone() * (sign * eps),
+            splats.sh_coeffs.val() + dir_coeffs.clone() * (sign * eps),
+            splats.raw_opacity.val() + dir_opac.clone() * (sign * eps),
+        );
+        weighted_render_loss(&perturbed, camera, weights.clone(), render_scale).into_scalar()
+    };
+    let numeric = (perturbed_loss(1.0) - perturbed_loss(-1.0)) / (2.0 * eps);
+
+    assert!(
+        (analytic - numeric).abs() <= 0.05 * analytic.abs().max(1.0),
+        "Gradients don't match finite differences for {:?} at {render_scale:?}: {analytic} vs {numeric}",
+        camera.projection
+    );
+}
+
+#[test]
+fn test_upscale_gradients() {
+    let camera = Camera::new(
+        Vec3::new(0.0, 0.0, -5.0),
+        Quat::IDENTITY,
+        0.5,
+        0.5,
+        glam::vec2(0.5, 0.5),
+    );
+    for render_scale in [RenderScale::Native, RenderScale::Half] {
+        check_render_gradients(&camera, render_scale);
+    }
+}
+
+#[test]
+fn test_orthographic_gradients() {
+    let camera = Camera::new_orthographic(
+        Vec3::new(0.0, 0.0, -5.0),
+        Quat::IDENTITY,
+        2.5,
+        2.5,
+        glam::vec2(0.5, 0.5),
+    );
+    check_render_gradients(&camera, RenderScale::Native);
+}
@@ -8,6 +8,10 @@ fn main() -> miette::Result<()> {
             "src/shaders/map_gaussian_to_intersects.wgsl",
             "src/shaders/rasterize.wgsl",
             "src/shaders/upscale.wgsl",
+            "src/shaders/rasterize_depth.wgsl",
+            "src/shaders/depth_colormap.wgsl",
+            "src/shaders/rasterize_normals.wgsl",
+            "src/shaders/normal_colormap.wgsl",
         ],
         &["src/shaders/helpers.wgsl"],
         "src/shaders/mod.rs",
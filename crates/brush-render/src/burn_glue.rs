@@ -10,9 +10,9 @@ use burn_wgpu::WgpuRuntime;
 use glam::Vec3;
 
 use crate::{
-    MainBackendBase, SplatForward,
+    MainBackendBase, RenderScale, SplatForward,
     camera::Camera,
-    render::{calc_tile_bounds, max_intersections, render_forward},
+    render::{bwd_info_channels, calc_tile_bounds, max_intersections, render_forward},
     render_aux::RenderAux,
     shaders,
 };
@@ -28,10 +28,28 @@ impl SplatForward<Self> for MainBackendBase {
         sh_coeffs: FloatTensor<Self>,
         opacity: FloatTensor<Self>,
         background: Vec3,
+        mip_filter: bool,
+        render_scale: RenderScale,
+        render_depth: bool,
+        render_normals: bool,
         bwd_info: bool,
+        bwd_depth: bool,
     ) -> (FloatTensor<Self>, RenderAux<Self>) {
         render_forward(
-            camera, img_size, means, log_scales, quats, sh_coeffs, opacity, background, bwd_info,
+            camera,
+            img_size,
+            means,
+            log_scales,
+            quats,
+            sh_coeffs,
+            opacity,
+            background,
+            mip_filter,
+            render_scale,
+            render_depth,
+            render_normals,
+            bwd_info,
+            bwd_depth,
         )
     }
 }
@@ -46,14 +64,24 @@ impl SplatForward<Self> for Fusion<MainBackendBase> {
         sh_coeffs: FloatTensor<Self>,
         opacity: FloatTensor<Self>,
         background: Vec3,
+        mip_filter: bool,
+        render_scale: RenderScale,
+        render_depth: bool,
+        render_normals: bool,
         bwd_info: bool,
+        bwd_depth: bool,
     ) -> (FloatTensor<Self>, RenderAux<Self>) {
         #[derive(Debug)]
         struct CustomOp {
             cam: Camera,
             img_size: glam::UVec2,
             bwd_info: bool,
+            bwd_depth: bool,
             background: Vec3,
+            mip_filter: bool,
+            render_scale: RenderScale,
+            render_depth: bool,
+            render_normals: bool,
             desc: CustomOpIr,
         }
 
@@ -73,8 +101,11 @@ impl SplatForward<Self> for Fusion<MainBackendBase> {
                     compact_gid_from_isect,
                     global_from_compact_gid,
                     out_img,
-                    out_img_gradient,
                     visible,
+                    raster_img,
+                    img_gradient,
+                    depth,
+                    normals,
                 ] = outputs;
 
                 let (img, aux) = MainBackendBase::render_splats(
@@ -86,12 +117,16 @@ impl SplatForward<Self> for Fusion<MainBackendBase> {
                     h.get_float_tensor::<MainBackendBase>(sh_coeffs),
                     h.get_float_tensor::<MainBackendBase>(opacity),
                     self.background,
+                    self.mip_filter,
+                    self.render_scale,
+                    self.render_depth,
+                    self.render_normals,
                     self.bwd_info,
+                    self.bwd_depth,
                 );
 
                 // Register output.
                 h.register_float_tensor::<MainBackendBase>(&out_img.id, img);
-                h.register_float_tensor::<MainBackendBase>(&out_img_gradient.id, aux.img_gradient);
                 h.register_float_tensor::<MainBackendBase>(
                     &projected_splats.id,
                     aux.projected_splats,
@@ -112,6 +147,10 @@ impl SplatForward<Self> for Fusion<MainBackendBase> {
                 );
 
                 h.register_float_tensor::<MainBackendBase>(&visible.id, aux.visible);
+                h.register_float_tensor::<MainBackendBase>(&raster_img.id, aux.raster_img);
+                h.register_float_tensor::<MainBackendBase>(&img_gradient.id, aux.img_gradient);
+                h.register_float_tensor::<MainBackendBase>(&depth.id, aux.depth);
+                h.register_float_tensor::<MainBackendBase>(&normals.id, aux.normals);
             }
         }
 
@@ -121,23 +160,47 @@ impl SplatForward<Self> for Fusion<MainBackendBase> {
 
         let proj_size = size_of::<shaders::helpers::ProjectedSplat>() / 4;
         let uniforms_size = size_of::<shaders::helpers::RenderUniforms>() / 4;
-        let tile_bounds = calc_tile_bounds(img_size);
-        let max_intersects = max_intersections(img_size, num_points as u32);
+        // Splats are rasterized at a lower resolution when upscaling.
+        let raster_size = render_scale.raster_size(img_size);
+        let tile_bounds = calc_tile_bounds(raster_size);
+        let max_intersects = max_intersections(raster_size, num_points as u32);
 
         // If render_u32_buffer is true, we render a packed buffer of u32 values, otherwise
-        // render RGBA f32 values.
-        let channels = if bwd_info { 4 } else { 1 };
+        // render RGBA + depth f32 values.
+        let channels = if bwd_info {
+            bwd_info_channels(bwd_depth)
+        } else {
+            1
+        };
 
+        let img_dtype = if bwd_info { DType::F32 } else { DType::U32 };
         let out_img = client.tensor_uninitialized(
             vec![img_size.y as usize, img_size.x as usize, channels],
-            if bwd_info { DType::F32 } else { DType::U32 },
+            img_dtype,
         );
-        let out_img_gradient = client.tensor_uninitialized(
-            vec![img_size.y as usize, img_size.x as usize, 4*3],
-            DType::F32,
-        ); 
 
         let visible_shape = if bwd_info { vec![num_points] } else { vec![1] };
+        let (raster_img, img_gradient_shape) = if raster_size != img_size {
+            (
+                client.tensor_uninitialized(
+                    vec![raster_size.y as usize, raster_size.x as usize, channels],
+                    img_dtype,
+                ),
+                vec![raster_size.y as usize, raster_size.x as usize, 4 * 3],
+            )
+        } else {
+            (client.tensor_uninitialized(vec![1], DType::F32), vec![1])
+        };
+        let depth_shape = if render_depth {
+            vec![img_size.y as usize, img_size.x as usize, 2]
+        } else {
+            vec![1]
+        };
+        let normals_shape = if render_normals {
+            vec![img_size.y as usize, img_size.x as usize, 3]
+        } else {
+            vec![1]
+        };
 
         let aux = RenderAux::<Self> {
             projected_splats: client.tensor_uninitialized(vec![num_points, proj_size], DType::F32),
@@ -151,8 +214,11 @@ impl SplatForward<Self> for Fusion<MainBackendBase> {
                 .tensor_uninitialized(vec![max_intersects as usize], DType::U32),
             global_from_compact_gid: client.tensor_uninitialized(vec![num_points], DType::U32),
             visible: client.tensor_uninitialized(visible_shape, DType::F32),
-            img_size,
-            img_gradient: out_img_gradient,
+            img_size: raster_size,
+            raster_img,
+            img_gradient: client.tensor_uninitialized(img_gradient_shape, DType::F32),
+            depth: client.tensor_uninitialized(depth_shape, DType::F32),
+            normals: client.tensor_uninitialized(normals_shape, DType::F32),
         };
 
         let mut stream = OperationStreams::default();
@@ -166,7 +232,10 @@ impl SplatForward<Self> for Fusion<MainBackendBase> {
             &aux.global_from_compact_gid,
             &out_img,
             &aux.visible,
+            &aux.raster_img,
             &aux.img_gradient,
+            &aux.depth,
+            &aux.normals,
         ];
         for inp in &input_tensors {
             stream.tensor(inp);
@@ -180,7 +249,12 @@ impl SplatForward<Self> for Fusion<MainBackendBase> {
             cam: cam.clone(),
             img_size,
             bwd_info,
+            bwd_depth,
             background,
+            mip_filter,
+            render_scale,
+            render_depth,
+            render_normals,
             desc: desc.clone(),
         };
         client.register(stream, OperationIr::Custom(desc), op);
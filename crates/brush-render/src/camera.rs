@@ -1,5 +1,79 @@
 use glam::Affine3A;
 
+/// Lens distortion of a camera, following the camera models of `OpenCV`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Distortion {
+    /// Radial coefficients `k1..k6` of the rational model, and tangential coefficients `p1, p2`.
+    ///
+    /// This covers the `OPENCV` and `FULL_OPENCV` models, where `OPENCV` leaves
+    /// the denominator coefficients `k4..k6` at zero.
+    OpenCv {
+        radial: [f32; 6],
+        tangential: [f32; 2],
+    },
+    /// Coefficients `k1..k4` of the equidistant fisheye model (`OPENCV_FISHEYE`).
+    Fisheye { radial: [f32; 4] },
+}
+
+impl Distortion {
+    /// Whether this doesn't distort anything, ie. all coefficients of the `OpenCV` model are
+    /// zero. Nb: A fisheye lens without coefficients still distorts.
+    pub fn is_identity(&self) -> bool {
+        match self {
+            Self::OpenCv { radial, tangential } => {
+                radial.iter().chain(tangential).all(|&k| k == 0.0)
+            }
+            Self::Fisheye { .. } => false,
+        }
+    }
+
+    /// Distorts a point in normalized image coordinates, ie. `(x / z, y / z)` in camera space.
+    pub fn distort(&self, p: glam::Vec2) -> glam::Vec2 {
+        match *self {
+            Self::OpenCv {
+                radial: k,
+                tangential: [p1, p2],
+            } => {
+                let r2 = p.length_squared();
+                let r4 = r2 * r2;
+                let r6 = r4 * r2;
+                let radial = (1.0 + k[0] * r2 + k[1] * r4 + k[2] * r6)
+                    / (1.0 + k[3] * r2 + k[4] * r4 + k[5] * r6);
+                let xy = p.x * p.y;
+                let tangential = glam::vec2(
+                    2.0 * p1 * xy + p2 * (r2 + 2.0 * p.x * p.x),
+                    p1 * (r2 + 2.0 * p.y * p.y) + 2.0 * p2 * xy,
+                );
+                p * radial + tangential
+            }
+            Self::Fisheye { radial: k } => {
+                let r = p.length();
+                if r < 1e-8 {
+                    return p;
+                }
+                let theta = r.atan();
+                let theta2 = theta * theta;
+                let theta_d = theta
+                    * (1.0 + theta2 * (k[0] + theta2 * (k[1] + theta2 * (k[2] + theta2 * k[3]))));
+                p * (theta_d / r)
+            }
+        }
+    }
+}
+
+/// How a camera projects the scene onto the image.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// A pinhole camera with the field of view of the camera.
+    #[default]
+    Perspective,
+    /// An orthographic camera, which sees a view volume of `width` by `height` world units.
+    ///
+    /// The field of view of the camera is ignored. Splats behind the camera are still culled,
+    /// so the camera should be placed in front of the scene.
+    Orthographic { width: f64, height: f64 },
+}
+
 #[derive(Debug, Default, Clone, PartialEq)]
 pub struct Camera {
     pub fov_x: f64,
@@ -7,6 +81,10 @@ pub struct Camera {
     pub center_uv: glam::Vec2,
     pub position: glam::Vec3,
     pub rotation: glam::Quat,
+    /// Lens distortion of the camera, if any. Rendering always uses an undistorted pinhole
+    /// camera, so images taken with a distorted camera have to be undistorted first.
+    pub distortion: Option<Distortion>,
+    pub projection: Projection,
 }
 
 impl Camera {
@@ -23,14 +101,48 @@ impl Camera {
             center_uv,
             position,
             rotation,
+            distortion: None,
+            projection: Projection::Perspective,
+        }
+    }
+
+    /// Create an orthographic camera, which sees a view volume of `width` by `height` world units.
+    pub fn new_orthographic(
+        position: glam::Vec3,
+        rotation: glam::Quat,
+        width: f64,
+        height: f64,
+        center_uv: glam::Vec2,
+    ) -> Self {
+        Self {
+            projection: Projection::Orthographic { width, height },
+            ..Self::new(position, rotation, 0.0, 0.0, center_uv)
         }
     }
 
+    /// Set the lens distortion of this camera.
+    pub fn with_distortion(mut self, distortion: Option<Distortion>) -> Self {
+        self.distortion = distortion;
+        self
+    }
+
+    /// The focal length in pixels. For an orthographic camera this is the number of pixels per
+    /// world unit instead.
     pub fn focal(&self, img_size: glam::UVec2) -> glam::Vec2 {
-        glam::vec2(
-            fov_to_focal(self.fov_x, img_size.x) as f32,
-            fov_to_focal(self.fov_y, img_size.y) as f32,
-        )
+        match self.projection {
+            Projection::Perspective => glam::vec2(
+                fov_to_focal(self.fov_x, img_size.x) as f32,
+                fov_to_focal(self.fov_y, img_size.y) as f32,
+            ),
+            Projection::Orthographic { width, height } => glam::vec2(
+                (img_size.x as f64 / width) as f32,
+                (img_size.y as f64 / height) as f32,
+            ),
+        }
+    }
+
+    pub fn is_orthographic(&self) -> bool {
+        matches!(self.projection, Projection::Orthographic { .. })
     }
 
     pub fn center(&self, img_size: glam::UVec2) -> glam::Vec2 {
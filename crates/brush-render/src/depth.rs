@@ -0,0 +1,46 @@
+use brush_kernel::{calc_cube_count, create_tensor};
+use burn::tensor::{DType, Tensor, TensorPrimitive};
+use burn_cubecl::{cubecl::server::Bindings, kernel::into_contiguous};
+
+use crate::{MainBackendBase, kernels::DepthColormap};
+
+/// Colors a [H, W] depth map, eg. from [`crate::render_aux::RenderAux::expected_depth`], to
+/// display it.
+///
+/// The inverse depth is mapped with the turbo colormap between the furthest and the nearest
+/// pixel. Returns a packed RGBA [H, W, 1] image like the one rendered for display, in which
+/// pixels with a depth of 0 are transparent.
+pub fn depth_colormap(depth: Tensor<MainBackendBase, 2>) -> Tensor<MainBackendBase, 3> {
+    let [h, w] = depth.dims();
+    let device = depth.device();
+
+    let empty = depth.clone().lower_equal_elem(0.0);
+    let inv_depth = depth.clone().clamp_min(1e-6).recip();
+    let inv_depth_range = Tensor::cat(
+        vec![
+            inv_depth.clone().mask_fill(empty.clone(), f32::MAX).min(),
+            inv_depth.mask_fill(empty, 0.0).max(),
+        ],
+        0,
+    );
+
+    let depth = into_contiguous(depth.into_primitive().tensor());
+    let inv_depth_range = into_contiguous(inv_depth_range.into_primitive().tensor());
+    let out_img = create_tensor([h, w, 1], &device, DType::F32);
+
+    let client = depth.client.clone();
+    // SAFETY: Kernel checked to have no OOB, bounded loops.
+    unsafe {
+        client.execute_unchecked(
+            DepthColormap::task(),
+            calc_cube_count([(h * w) as u32], DepthColormap::WORKGROUP_SIZE),
+            Bindings::new().with_buffers(vec![
+                depth.handle.binding(),
+                inv_depth_range.handle.binding(),
+                out_img.handle.clone().binding(),
+            ]),
+        );
+    }
+
+    Tensor::from_primitive(TensorPrimitive::Float(out_img))
+}
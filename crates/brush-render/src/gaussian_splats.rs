@@ -1,9 +1,9 @@
 use crate::{
-    SplatForward,
+    RenderScale, SplatForward,
     bounding_box::BoundingBox,
     camera::Camera,
     render_aux::RenderAux,
-    sh::{sh_coeffs_for_degree, sh_degree_from_coeffs},
+    sh::{sh_coeffs_for_degree, sh_degree_from_coeffs, sh_rotation_matrix},
     validation::validate_tensor_val,
 };
 use ball_tree::BallTree;
@@ -12,11 +12,12 @@ use burn::{
     module::{Module, Param, ParamId},
     prelude::Backend,
     tensor::{
-        Tensor, TensorData, TensorPrimitive, activation::sigmoid, backend::AutodiffBackend, s,
+        Int, Tensor, TensorData, TensorPrimitive, activation::sigmoid, backend::AutodiffBackend, s,
     },
 };
-use glam::Vec3;
+use glam::{Mat3, Quat, Vec2, Vec3};
 use rand::Rng;
+use std::f32::consts::{FRAC_PI_2, PI};
 use tracing::trace_span;
 
 #[derive(Config, Debug)]
@@ -32,6 +33,11 @@ pub struct Splats<B: Backend> {
     pub log_scales: Param<Tensor<B, 2>>,
     pub sh_coeffs: Param<Tensor<B, 3>>,
     pub raw_opacity: Param<Tensor<B, 1>>,
+    /// Size of the Mip-Splatting 3D smoothing filter of each splat, if any.
+    ///
+    /// This isn't optimized but derived from the training views. Splats with a 3D filter
+    /// are also rendered with the Mip-Splatting 2D filter.
+    pub filter_3d: Option<Tensor<B, 1>>,
 }
 
 fn norm_vec<B: Backend>(vec: Tensor<B, 2>) -> Tensor<B, 2> {
@@ -228,7 +234,142 @@ impl<B: Backend> Splats<B> {
             rotation: Param::initialized(ParamId::new(), rotation.detach().require_grad()),
             raw_opacity: Param::initialized(ParamId::new(), raw_opacity.detach().require_grad()),
             log_scales: Param::initialized(ParamId::new(), log_scales.detach().require_grad()),
+            filter_3d: None,
+        }
+    }
+
+    /// Set the size of the 3D smoothing filter of each splat.
+    pub fn with_filter_3d(mut self, filter_3d: Option<Tensor<B, 1>>) -> Self {
+        if let Some(filter_3d) = &filter_3d {
+            assert_eq!(
+                filter_3d.dims()[0],
+                self.num_splats() as usize,
+                "Filter must have one value per splat"
+            );
+        }
+        self.filter_3d = filter_3d;
+        self
+    }
+
+    /// The log scales, widened by the 3D smoothing filter if there is one.
+    pub fn filtered_log_scales(&self) -> Tensor<B, 2> {
+        let log_scales = self.log_scales.val();
+        let Some(filter_3d) = &self.filter_3d else {
+            return log_scales;
+        };
+        let variance = (log_scales * 2.0).exp() + filter_3d.clone().powi_scalar(2).unsqueeze_dim(1);
+        variance.log() * 0.5
+    }
+
+    /// The raw opacities, lowered by the 3D smoothing filter if there is one.
+    ///
+    /// The filter widens the splats, so the opacity is scaled down to keep the total density the same.
+    pub fn filtered_raw_opacity(&self) -> Tensor<B, 1> {
+        let raw_opacity = self.raw_opacity.val();
+        let Some(filter_3d) = &self.filter_3d else {
+            return raw_opacity;
+        };
+        let variance = (self.log_scales.val() * 2.0).exp();
+        let filtered = variance.clone() + filter_3d.clone().powi_scalar(2).unsqueeze_dim(1);
+        let compensation = (variance.prod_dim(1) / filtered.prod_dim(1))
+            .sqrt()
+            .squeeze(1);
+        let opac = (sigmoid(raw_opacity) * compensation).clamp(1e-6, 1.0 - 1e-6);
+        (opac.clone() / (1.0 - opac)).log()
+    }
+
+    /// Fold the 3D smoothing filter into the scales and opacities, for formats that can't store it.
+    pub fn with_baked_filter_3d(self) -> Self {
+        if self.filter_3d.is_none() {
+            return self;
+        }
+        Self::from_tensor_data(
+            self.means.val(),
+            self.rotation.val(),
+            self.filtered_log_scales(),
+            self.sh_coeffs.val(),
+            self.filtered_raw_opacity(),
+        )
+    }
+
+    /// Only keep the splats at these indices.
+    pub fn keep(self, indices: Tensor<B, 1, Int>) -> Self {
+        let filter_3d = self.filter_3d.map(|f| f.select(0, indices.clone()));
+        Self::from_tensor_data(
+            self.means.val().select(0, indices.clone()),
+            self.rotation.val().select(0, indices.clone()),
+            self.log_scales.val().select(0, indices.clone()),
+            self.sh_coeffs.val().select(0, indices.clone()),
+            self.raw_opacity.val().select(0, indices),
+        )
+        .with_filter_3d(filter_3d)
+    }
+
+    /// Apply a similarity transform, scaling the splats uniformly around the origin, then rotating
+    /// and translating them.
+    ///
+    /// The orientations and SH coefficients of the splats are rotated as well, so the splats look
+    /// the same when the camera is transformed along with them.
+    pub fn transformed(self, scale: f32, rotation: Quat, translation: Vec3) -> Self {
+        assert!(scale > 0.0, "Scale must be positive");
+        let device = self.device();
+        let rotation = rotation.normalize();
+        let [n, _, _] = self.sh_coeffs.dims();
+
+        // Transposed, as the means are row vectors.
+        let rot_mat =
+            Tensor::<B, 1>::from_floats(Mat3::from_quat(rotation).to_cols_array(), &device)
+                .reshape([3, 3]);
+        let translation =
+            Tensor::<B, 1>::from_floats(translation.to_array(), &device).reshape([1, 3]);
+        let means = self.means.val().matmul(rot_mat) * scale + translation;
+
+        // Left multiply the quaternions (scalar first) with the rotation, again transposed.
+        let [w, x, y, z] = [rotation.w, rotation.x, rotation.y, rotation.z];
+        let quat_mat = Tensor::<B, 1>::from_floats(
+            [
+                w, x, y, z, //
+                -x, w, z, -y, //
+                -y, -z, w, x, //
+                -z, y, -x, w,
+            ],
+            &device,
+        )
+        .reshape([4, 4]);
+        let rotations = self.rotation.val().matmul(quat_mat);
+
+        let sh_coeffs = self.sh_coeffs.val();
+        let mut bands = vec![sh_coeffs.clone().slice(s![.., 0..1])];
+        for degree in 1..=self.sh_degree() {
+            let start = (degree * degree) as usize;
+            let size = 2 * degree as usize + 1;
+            let sh_rot = Tensor::<B, 2>::from_data(
+                TensorData::new(sh_rotation_matrix(degree, rotation), [size, size]),
+                &device,
+            )
+            .transpose();
+            // Rotate the coefficients of each color channel.
+            let band = sh_coeffs
+                .clone()
+                .slice(s![.., start..start + size])
+                .swap_dims(1, 2)
+                .reshape([n * 3, size])
+                .matmul(sh_rot)
+                .reshape([n, 3, size])
+                .swap_dims(1, 2);
+            bands.push(band);
         }
+        let sh_coeffs = Tensor::cat(bands, 1);
+
+        let filter_3d = self.filter_3d.map(|f| f * scale);
+        Self::from_tensor_data(
+            means,
+            rotations,
+            self.log_scales.val() + scale.ln(),
+            sh_coeffs,
+            self.raw_opacity.val(),
+        )
+        .with_filter_3d(filter_3d)
     }
 
     pub fn opacities(&self) -> Tensor<B, 1> {
@@ -336,6 +477,14 @@ impl<B: Backend> Splats<B> {
             n_sh, num_splats as usize,
             "Inconsistent number of splats in SH coeffs"
         );
+        if let Some(filter_3d) = &self.filter_3d {
+            validate_tensor_val(filter_3d, "filter_3d", Some(0.0), None);
+            assert_eq!(
+                filter_3d.dims()[0],
+                num_splats as usize,
+                "Inconsistent number of splats in 3D filter"
+            );
+        }
     }
 
     // TODO: This should probably exist in Burn. Maybe make a PR.
@@ -361,6 +510,7 @@ impl<B: Backend> Splats<B> {
                 raw_opacity_id,
                 Tensor::from_inner(raw_opacity).require_grad(),
             ),
+            filter_3d: self.filter_3d.map(Tensor::from_inner),
         }
     }
 
@@ -404,9 +554,82 @@ fn bounds_from_pos(percentile: f32, means: &[f32]) -> BoundingBox {
     )
 }
 
+/// Rotations of the cube faces rendered for a panorama, relative to the panorama camera.
+fn cube_face_rotations() -> [Quat; 6] {
+    [
+        Quat::IDENTITY,
+        Quat::from_rotation_y(PI),
+        Quat::from_rotation_y(FRAC_PI_2),
+        Quat::from_rotation_y(-FRAC_PI_2),
+        Quat::from_rotation_x(FRAC_PI_2),
+        Quat::from_rotation_x(-FRAC_PI_2),
+    ]
+}
+
+/// Camera space direction of the center of a pixel of an equirectangular panorama.
+///
+/// The center of the panorama looks along the forward axis of the camera, and the top row
+/// looks straight up (-y).
+fn equirect_direction(pixel: glam::UVec2, size: glam::UVec2) -> Vec3 {
+    let uv = (pixel.as_vec2() + 0.5) / size.as_vec2();
+    let lon = (uv.x - 0.5) * 2.0 * PI;
+    let lat = (0.5 - uv.y) * PI;
+    Vec3::new(lat.cos() * lon.sin(), -lat.sin(), lat.cos() * lon.cos())
+}
+
+/// For each pixel of a panorama, the four texels of the cube faces to blend bilinearly, and
+/// their weights. Both are laid out as [4, H * W].
+fn equirect_taps(size: glam::UVec2, face_size: u32, faces: &[Quat; 6]) -> (Vec<i32>, Vec<f32>) {
+    let n = (size.x * size.y) as usize;
+    let mut indices = vec![0; 4 * n];
+    let mut weights = vec![0.0; 4 * n];
+    let max_texel = Vec2::splat(face_size as f32 - 1.0);
+
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let dir = equirect_direction(glam::uvec2(x, y), size);
+
+            // Sample the face the direction is most aligned with.
+            let (face, local) = faces
+                .iter()
+                .map(|rot| rot.inverse() * dir)
+                .enumerate()
+                .max_by(|(_, a), (_, b)| a.z.total_cmp(&b.z))
+                .expect("unreachable");
+
+            // Faces have a 90 degree field of view, so the focal length is half the face size.
+            let p = (local.truncate() / local.z * 0.5 + 0.5) * face_size as f32 - 0.5;
+            let p0 = p.floor().clamp(Vec2::ZERO, max_texel);
+            let p1 = (p0 + 1.0).min(max_texel);
+            let t = (p - p0).clamp(Vec2::ZERO, Vec2::ONE);
+
+            let base = face as u32 * face_size * face_size;
+            let texel = |q: Vec2| (base + q.y as u32 * face_size + q.x as u32) as i32;
+
+            let pix = (y * size.x + x) as usize;
+            let taps = [
+                (p0, (1.0 - t.x) * (1.0 - t.y)),
+                (glam::vec2(p1.x, p0.y), t.x * (1.0 - t.y)),
+                (glam::vec2(p0.x, p1.y), (1.0 - t.x) * t.y),
+                (p1, t.x * t.y),
+            ];
+            for (tap, (q, w)) in taps.into_iter().enumerate() {
+                indices[tap * n + pix] = texel(q);
+                weights[tap * n + pix] = w;
+            }
+        }
+    }
+
+    (indices, weights)
+}
+
 impl<B: Backend + SplatForward<B>> Splats<B> {
     /// Render the splats.
     ///
+    /// The splats are rasterized at the resolution given by `render_scale`, and upscaled to `img_size`.
+    /// When `render_depth` or `render_normals` are set, the depth or normals of the splats are
+    /// rendered to the [`RenderAux`] too.
+    ///
     /// NB: This doesn't work on a differentiable backend.
     pub fn render(
         &self,
@@ -414,8 +637,40 @@ impl<B: Backend + SplatForward<B>> Splats<B> {
         img_size: glam::UVec2,
         background: Vec3,
         splat_scale: Option<f32>,
+        render_scale: RenderScale,
+        render_depth: bool,
+        render_normals: bool,
+    ) -> (Tensor<B, 3>, RenderAux<B>) {
+        self.render_inner(
+            camera,
+            img_size,
+            background,
+            splat_scale,
+            render_scale,
+            render_depth,
+            render_normals,
+            false,
+        )
+    }
+
+    /// Render the splats, like [`Self::render`].
+    ///
+    /// When `float_img` is set, this returns a [H, W, 4] image of floating point RGBA instead of
+    /// packed RGBA8 values. Float images are only supported at [`RenderScale::Native`].
+    ///
+    /// NB: This doesn't work on a differentiable backend.
+    pub fn render_inner(
+        &self,
+        camera: &Camera,
+        img_size: glam::UVec2,
+        background: Vec3,
+        splat_scale: Option<f32>,
+        render_scale: RenderScale,
+        render_depth: bool,
+        render_normals: bool,
+        float_img: bool,
     ) -> (Tensor<B, 3>, RenderAux<B>) {
-        let mut scales = self.log_scales.val();
+        let mut scales = self.filtered_log_scales();
 
         #[cfg(any(feature = "debug-validation", test))]
         self.validate_values();
@@ -432,8 +687,13 @@ impl<B: Backend + SplatForward<B>> Splats<B> {
             scales.into_primitive().tensor(),
             self.rotation.val().into_primitive().tensor(),
             self.sh_coeffs.val().into_primitive().tensor(),
-            self.raw_opacity.val().into_primitive().tensor(),
+            self.filtered_raw_opacity().into_primitive().tensor(),
             background,
+            self.filter_3d.is_some(),
+            render_scale,
+            render_depth,
+            render_normals,
+            float_img,
             false,
         );
         let img = Tensor::from_primitive(TensorPrimitive::Float(img));
@@ -441,4 +701,65 @@ impl<B: Backend + SplatForward<B>> Splats<B> {
         aux.validate_values();
         (img, aux)
     }
+
+    /// Render a 360 degree equirectangular panorama, seen from `position`.
+    ///
+    /// The panorama is `width` pixels wide and half as high. Its center looks along the forward
+    /// axis of `rotation`. It's resampled from six renders of the faces of a cube. Unlike
+    /// [`Self::render`], this returns a [H, W, 4] image of floating point RGBA.
+    ///
+    /// NB: This doesn't work on a differentiable backend.
+    pub fn render_equirect(
+        &self,
+        position: Vec3,
+        rotation: Quat,
+        width: u32,
+        background: Vec3,
+        splat_scale: Option<f32>,
+    ) -> Tensor<B, 3> {
+        let size = glam::uvec2(width, (width / 2).max(1));
+        // Match the resolution of the panorama at the center of the faces.
+        let face_size = ((width as f32 / PI).ceil() as u32).max(1);
+        let faces = cube_face_rotations();
+        let device = self.device();
+
+        let face_imgs = faces
+            .iter()
+            .map(|&face| {
+                let camera = Camera::new(
+                    position,
+                    rotation * face,
+                    std::f64::consts::FRAC_PI_2,
+                    std::f64::consts::FRAC_PI_2,
+                    glam::vec2(0.5, 0.5),
+                );
+                let (img, _) = self.render_inner(
+                    &camera,
+                    glam::uvec2(face_size, face_size),
+                    background,
+                    splat_scale,
+                    RenderScale::Native,
+                    false,
+                    false,
+                    true,
+                );
+                let texels = (face_size * face_size) as usize;
+                img.slice(s![.., .., 0..4]).reshape([texels, 4])
+            })
+            .collect();
+        let face_imgs = Tensor::cat(face_imgs, 0);
+
+        let n = (size.x * size.y) as usize;
+        let (indices, weights) = equirect_taps(size, face_size, &faces);
+        let indices = Tensor::<B, 2, Int>::from_data(TensorData::new(indices, [4, n]), &device);
+        let weights = Tensor::<B, 2>::from_data(TensorData::new(weights, [4, n]), &device);
+
+        let mut panorama = Tensor::zeros([n, 4], &device);
+        for tap in 0..4 {
+            let tap_indices = indices.clone().slice(s![tap, ..]).reshape([n]);
+            let tap_weights = weights.clone().slice(s![tap, ..]).reshape([n, 1]);
+            panorama = panorama + face_imgs.clone().select(0, tap_indices) * tap_weights;
+        }
+        panorama.reshape([size.y as usize, size.x as usize, 4])
+    }
 }
@@ -1,5 +1,6 @@
 use super::shaders::{
-    map_gaussian_to_intersects, project_forward, project_visible, rasterize, upscale,
+    depth_colormap, map_gaussian_to_intersects, normal_colormap, project_forward, project_visible,
+    rasterize, rasterize_depth, rasterize_normals, upscale,
 };
 use brush_kernel::kernel_source_gen;
 
@@ -9,5 +10,23 @@ kernel_source_gen!(
     MapGaussiansToIntersect { prepass },
     map_gaussian_to_intersects
 );
-kernel_source_gen!(Rasterize { bwd_info, webgpu }, rasterize);
-kernel_source_gen!(Upscale {}, upscale);
+kernel_source_gen!(
+    Rasterize {
+        bwd_info,
+        bwd_depth,
+        upscale,
+        webgpu
+    },
+    rasterize
+);
+kernel_source_gen!(
+    Upscale {
+        bwd_info,
+        bwd_depth
+    },
+    upscale
+);
+kernel_source_gen!(RasterizeDepth {}, rasterize_depth);
+kernel_source_gen!(DepthColormap {}, depth_colormap);
+kernel_source_gen!(RasterizeNormals {}, rasterize_normals);
+kernel_source_gen!(NormalColormap {}, normal_colormap);
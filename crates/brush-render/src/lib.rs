@@ -7,13 +7,17 @@ use burn_fusion::Fusion;
 use burn_wgpu::graphics::{AutoGraphicsApi, GraphicsApi};
 use burn_wgpu::{RuntimeOptions, WgpuDevice, WgpuRuntime};
 use camera::Camera;
+use clap::ValueEnum;
 use glam::Vec3;
 use render_aux::RenderAux;
+use serde::{Deserialize, Serialize};
 use wgpu::{Adapter, Device, Queue};
 
 mod burn_glue;
+pub mod depth;
 mod dim_check;
 mod kernels;
+pub mod normals;
 pub mod render_aux;
 pub mod shaders;
 
@@ -44,6 +48,59 @@ const INTERSECTS_UPPER_BOUND: u32 = 512 * 65535;
 // The maximum number of gaussians that can be rendered.
 const GAUSSIANS_UPPER_BOUND: u32 = 256 * 65535;
 
+// The largest number of pixels rasterized by [`RenderScale::Adaptive`] before downscaling.
+const ADAPTIVE_MAX_PIXELS: u32 = 1280 * 720;
+
+/// Resolution at which splats are rasterized, relative to the requested image size.
+///
+/// When rasterizing at a lower resolution, the image is upscaled to the requested size
+/// with a bicubic filter, guided by the analytic image gradients of the rasterizer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RenderScale {
+    /// Rasterize every pixel, without any upscaling.
+    #[default]
+    Native,
+    /// Rasterize at half the resolution.
+    Half,
+    /// Rasterize at a quarter of the resolution.
+    Quarter,
+    /// Pick the highest resolution that keeps the number of rasterized pixels reasonable.
+    Adaptive,
+}
+
+impl RenderScale {
+    pub const ALL: [Self; 4] = [Self::Native, Self::Half, Self::Quarter, Self::Adaptive];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Native => "Native",
+            Self::Half => "Half",
+            Self::Quarter => "Quarter",
+            Self::Adaptive => "Adaptive",
+        }
+    }
+
+    /// The factor by which an image of the given size is downscaled before rasterizing.
+    pub fn factor(&self, img_size: glam::UVec2) -> u32 {
+        match self {
+            Self::Native => 1,
+            Self::Half => 2,
+            Self::Quarter => 4,
+            Self::Adaptive => [1, 2]
+                .into_iter()
+                .find(|&f| (img_size / f).element_product() <= ADAPTIVE_MAX_PIXELS)
+                .unwrap_or(4),
+        }
+    }
+
+    /// The size at which an image of the given size is rasterized.
+    pub fn raster_size(&self, img_size: glam::UVec2) -> glam::UVec2 {
+        let factor = self.factor(img_size);
+        glam::uvec2(img_size.x.div_ceil(factor), img_size.y.div_ceil(factor))
+    }
+}
+
 pub trait SplatForward<B: Backend> {
     /// Render splats to a buffer.
     ///
@@ -53,6 +110,13 @@ pub trait SplatForward<B: Backend> {
     /// The [`xy_grad_dummy`] variable is only used to carry screenspace xy gradients.
     /// This function can optionally render a "u32" buffer, which is a packed RGBA (8 bits per channel)
     /// buffer. This is useful when the results need to be displayed immediately.
+    /// When `mip_filter` is set, splats are rasterized with the Mip-Splatting 2D filter
+    /// instead of a fixed dilation.
+    /// `render_scale` sets the resolution splats are rasterized at, the output is always `img_size`.
+    /// When `render_depth` is set, the expected and median depth are rendered to [`RenderAux::depth`].
+    /// When `render_normals` is set, the normals of the splats are rendered to [`RenderAux::normals`].
+    /// When `bwd_depth` is set, the float buffer rendered with `bwd_info` has an extra alpha
+    /// blended depth channel, see [`render::bwd_info_channels`].
     fn render_splats(
         camera: &Camera,
         img_size: glam::UVec2,
@@ -62,7 +126,12 @@ pub trait SplatForward<B: Backend> {
         sh_coeffs: FloatTensor<B>,
         raw_opacities: FloatTensor<B>,
         background: Vec3,
+        mip_filter: bool,
+        render_scale: RenderScale,
+        render_depth: bool,
+        render_normals: bool,
         bwd_info: bool,
+        bwd_depth: bool,
     ) -> (FloatTensor<B>, RenderAux<B>);
 }
 
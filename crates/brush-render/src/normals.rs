@@ -0,0 +1,34 @@
+use brush_kernel::{calc_cube_count, create_tensor};
+use burn::tensor::{DType, Tensor, TensorPrimitive};
+use burn_cubecl::{cubecl::server::Bindings, kernel::into_contiguous};
+
+use crate::{MainBackendBase, kernels::NormalColormap};
+
+/// Colors a [H, W, 3] normal map, eg. from [`crate::render_aux::RenderAux::normals`], to
+/// display it.
+///
+/// Each axis of the normal is mapped from [-1, 1] to a color channel. Returns a packed RGBA
+/// [H, W, 1] image like the one rendered for display, in which pixels without a normal are
+/// transparent.
+pub fn normal_colormap(normals: Tensor<MainBackendBase, 3>) -> Tensor<MainBackendBase, 3> {
+    let [h, w, _] = normals.dims();
+    let device = normals.device();
+
+    let normals = into_contiguous(normals.into_primitive().tensor());
+    let out_img = create_tensor([h, w, 1], &device, DType::F32);
+
+    let client = normals.client.clone();
+    // SAFETY: Kernel checked to have no OOB, bounded loops.
+    unsafe {
+        client.execute_unchecked(
+            NormalColormap::task(),
+            calc_cube_count([(h * w) as u32], NormalColormap::WORKGROUP_SIZE),
+            Bindings::new().with_buffers(vec![
+                normals.handle.binding(),
+                out_img.handle.clone().binding(),
+            ]),
+        );
+    }
+
+    Tensor::from_primitive(TensorPrimitive::Float(out_img))
+}
@@ -1,9 +1,12 @@
 use super::shaders;
 use crate::{
-    INTERSECTS_UPPER_BOUND, MainBackendBase,
+    INTERSECTS_UPPER_BOUND, MainBackendBase, RenderScale,
     camera::Camera,
     dim_check::DimCheck,
-    kernels::{MapGaussiansToIntersect, ProjectSplats, ProjectVisible, Rasterize,Upscale},
+    kernels::{
+        MapGaussiansToIntersect, ProjectSplats, ProjectVisible, Rasterize, RasterizeDepth,
+        RasterizeNormals, Upscale,
+    },
     render_aux::RenderAux,
     sh::sh_degree_from_coeffs,
 };
@@ -19,7 +22,7 @@ use brush_kernel::create_uniform_buffer;
 use brush_kernel::{CubeCount, calc_cube_count};
 use brush_prefix_sum::prefix_sum;
 use brush_sort::radix_argsort;
-use burn::tensor::{DType, IntDType};
+use burn::tensor::{DType, Int, IntDType, TensorPrimitive};
 use burn::tensor::{
     FloatDType,
     ops::{FloatTensorOps, IntTensorOps},
@@ -55,6 +58,33 @@ pub fn max_intersections(img_size: glam::UVec2, num_splats: u32) -> u32 {
     max_possible.min(INTERSECTS_UPPER_BOUND)
 }
 
+/// Nr. of float channels of an image rendered with `bwd_info`. These are RGBA, followed by the
+/// alpha blended depth when rendering with `bwd_depth`.
+pub fn bwd_info_channels(bwd_depth: bool) -> usize {
+    if bwd_depth { 5 } else { 4 }
+}
+
+/// Upscale a [H, W, C] image by repeating each pixel `factor` times along both axes, cropped to
+/// `target_size`. Unlike the color upscale this doesn't blend neighbouring pixels, which would
+/// mix up outputs like the median depth.
+fn upscale_nearest(
+    img: CubeTensor<WgpuRuntime>,
+    factor: u32,
+    target_size: glam::UVec2,
+) -> CubeTensor<WgpuRuntime> {
+    let img =
+        burn::tensor::Tensor::<MainBackendBase, 3>::from_primitive(TensorPrimitive::Float(img));
+    let device = img.device();
+    let indices = |size: u32| {
+        burn::tensor::Tensor::<MainBackendBase, 1, Int>::arange(0..size as i64, &device)
+            .div_scalar(factor as i32)
+    };
+    img.select(0, indices(target_size.y))
+        .select(1, indices(target_size.x))
+        .into_primitive()
+        .tensor()
+}
+
 pub(crate) fn render_forward(
     camera: &Camera,
     img_size: glam::UVec2,
@@ -64,15 +94,22 @@ pub(crate) fn render_forward(
     sh_coeffs: CubeTensor<WgpuRuntime>,
     raw_opacities: CubeTensor<WgpuRuntime>,
     background: Vec3,
+    mip_filter: bool,
+    render_scale: RenderScale,
+    render_depth: bool,
+    render_normals: bool,
     bwd_info: bool,
+    bwd_depth: bool,
 ) -> (CubeTensor<WgpuRuntime>, RenderAux<MainBackendBase>) {
     assert!(
         img_size[0] > 0 && img_size[1] > 0,
         "Can't render images with 0 size."
     );
-    
-    let target_size = glam::UVec2::new(img_size[0], img_size[1]);
-    let img_size = glam::UVec2::new(img_size[0]/4, img_size[1]/4);
+
+    // Splats are rasterized at img_size, and upscaled to the requested target size if needed.
+    let target_size = img_size;
+    let img_size = render_scale.raster_size(target_size);
+    let upscale = img_size != target_size;
 
     // Tensor params might not be contiguous, convert them to contiguous tensors.
     let means = into_contiguous(means);
@@ -128,7 +165,8 @@ pub(crate) fn render_forward(
         // Nb: Bit of a hack as these aren't _really_ uniforms but are written to by the shaders.
         num_visible: 0,
         target_size: target_size.into(),
-        pad: [0, 0],
+        mip_filter: mip_filter as u32,
+        orthographic: camera.is_orthographic() as u32,
     };
 
     // Nb: This contains both static metadata and some dynamic data so can't pass this as metadata to execute. In the future
@@ -195,9 +233,9 @@ pub(crate) fn render_forward(
                 CubeCount::Dynamic(num_vis_wg.handle.binding()),
                 Bindings::new().with_buffers(vec![
                     uniforms_buffer.clone().handle.binding(),
-                    means.handle.binding(),
-                    log_scales.handle.binding(),
-                    quats.handle.binding(),
+                    means.handle.clone().binding(),
+                    log_scales.handle.clone().binding(),
+                    quats.handle.clone().binding(),
                     sh_coeffs.handle.binding(),
                     raw_opacities.handle.binding(),
                     global_from_compact_gid.handle.clone().binding(),
@@ -330,7 +368,7 @@ pub(crate) fn render_forward(
     let _span = tracing::trace_span!("Rasterize").entered();
 
     let out_dim = if bwd_info {
-        4
+        bwd_info_channels(bwd_depth)
     } else {
         // Channels are packed into 4 bytes, aka one float.
         1
@@ -342,21 +380,12 @@ pub(crate) fn render_forward(
         DType::F32,
     );
 
-    let out_img_gradient = create_tensor(
-        [img_size.y as usize, img_size.x as usize, 4*3],
-        device,
-        DType::F32,
-    );
-
-
-
     let mut bindings = Bindings::new().with_buffers(vec![
         uniforms_buffer.handle.clone().binding(),
         compact_gid_from_isect.handle.clone().binding(),
         tile_offsets.handle.clone().binding(),
         projected_splats.handle.clone().binding(),
         out_img.handle.clone().binding(),
-        out_img_gradient.handle.clone().binding(),
     ]);
 
     let visible = if bwd_info {
@@ -373,9 +402,22 @@ pub(crate) fn render_forward(
         create_tensor([1], device, DType::F32)
     };
 
+    // The gradients of the image for the upscale pass, dx, dy and dxy for each RGBA channel.
+    let img_gradient = if upscale {
+        let img_gradient = create_tensor(
+            [img_size.y as usize, img_size.x as usize, 4 * 3],
+            device,
+            DType::F32,
+        );
+        bindings = bindings.with_buffers(vec![img_gradient.handle.clone().binding()]);
+        img_gradient
+    } else {
+        create_tensor([1], device, DType::F32)
+    };
+
     // Compile the kernel, including/excluding info for backwards pass.
     // see the BWD_INFO define in the rasterize shader.
-    let raster_task = Rasterize::task(bwd_info, cfg!(target_family = "wasm"));
+    let raster_task = Rasterize::task(bwd_info, bwd_depth, upscale, cfg!(target_family = "wasm"));
 
     // SAFETY: Kernel checked to have no OOB, bounded loops.
     unsafe {
@@ -386,37 +428,114 @@ pub(crate) fn render_forward(
         );
     }
 
-    let out_upscaled_img = create_tensor(
-        [target_size.y as usize, target_size.x as usize, out_dim],
-        device,
-        DType::F32,
-    );
+    // The expected and median depth are rasterized in a separate pass, as they're only
+    // needed occasionally.
+    let depth = if render_depth {
+        let _span = tracing::trace_span!("RasterizeDepth").entered();
+
+        let depth = create_tensor(
+            [img_size.y as usize, img_size.x as usize, 2],
+            device,
+            DType::F32,
+        );
 
+        // SAFETY: Kernel checked to have no OOB, bounded loops.
+        unsafe {
+            client.execute_unchecked(
+                RasterizeDepth::task(),
+                CubeCount::Static(tile_bounds.x * tile_bounds.y, 1, 1),
+                Bindings::new().with_buffers(vec![
+                    uniforms_buffer.handle.clone().binding(),
+                    compact_gid_from_isect.handle.clone().binding(),
+                    tile_offsets.handle.clone().binding(),
+                    projected_splats.handle.clone().binding(),
+                    depth.handle.clone().binding(),
+                ]),
+            );
+        }
+
+        if upscale {
+            upscale_nearest(depth, render_scale.factor(target_size), target_size)
+        } else {
+            depth
+        }
+    } else {
+        create_tensor([1], device, DType::F32)
+    };
 
-    let tile_bounds_upscale = calc_tile_bounds(target_size);
+    // Normals are rasterized in a separate pass as well.
+    let normals = if render_normals {
+        let _span = tracing::trace_span!("RasterizeNormals").entered();
 
+        let normals = create_tensor(
+            [img_size.y as usize, img_size.x as usize, 3],
+            device,
+            DType::F32,
+        );
 
-    let mut uniforms_upscale = uniforms.clone();
-    uniforms_upscale.tile_bounds = tile_bounds_upscale.into();
+        // SAFETY: Kernel checked to have no OOB, bounded loops.
+        unsafe {
+            client.execute_unchecked(
+                RasterizeNormals::task(),
+                CubeCount::Static(tile_bounds.x * tile_bounds.y, 1, 1),
+                Bindings::new().with_buffers(vec![
+                    uniforms_buffer.handle.clone().binding(),
+                    compact_gid_from_isect.handle.clone().binding(),
+                    tile_offsets.handle.clone().binding(),
+                    projected_splats.handle.clone().binding(),
+                    global_from_compact_gid.handle.clone().binding(),
+                    means.handle.clone().binding(),
+                    log_scales.handle.clone().binding(),
+                    quats.handle.clone().binding(),
+                    normals.handle.clone().binding(),
+                ]),
+            );
+        }
 
-    // Nb: This contains both static metadata and some dynamic data so can't pass this as metadata to execute. In the future
-    // should separate the two.
-    let uniforms_buffer_upscale: CubeTensor<WgpuRuntime> = create_uniform_buffer(uniforms_upscale, device, &client);
+        if upscale {
+            upscale_nearest(normals, render_scale.factor(target_size), target_size)
+        } else {
+            normals
+        }
+    } else {
+        create_tensor([1], device, DType::F32)
+    };
 
-    let upscale_task = Upscale::task();
+    let (out_img, raster_img) = if upscale {
+        let _span = tracing::trace_span!("Upscale").entered();
 
-    unsafe {
-        client.execute_unchecked(
-            upscale_task,
-            CubeCount::Static(tile_bounds_upscale.x * tile_bounds_upscale.y, 1, 1),
-            Bindings::new().with_buffers(vec![
-                uniforms_buffer_upscale.handle.clone().binding(),
-                out_img.handle.clone().binding(),
-                out_img_gradient.handle.clone().binding(),
-                out_upscaled_img.handle.clone().binding(),
-            ]),
+        let upscaled_img = create_tensor(
+            [target_size.y as usize, target_size.x as usize, out_dim],
+            device,
+            DType::F32,
         );
-    }
+
+        // The upscale pass runs over the tiles of the target image.
+        let target_tile_bounds = calc_tile_bounds(target_size);
+        let upscale_uniforms = shaders::helpers::RenderUniforms {
+            tile_bounds: target_tile_bounds.into(),
+            ..uniforms
+        };
+        let upscale_uniforms_buffer = create_uniform_buffer(upscale_uniforms, device, client);
+
+        // SAFETY: Kernel checked to have no OOB, bounded loops.
+        unsafe {
+            client.execute_unchecked(
+                Upscale::task(bwd_info, bwd_depth),
+                CubeCount::Static(target_tile_bounds.x * target_tile_bounds.y, 1, 1),
+                Bindings::new().with_buffers(vec![
+                    upscale_uniforms_buffer.handle.binding(),
+                    out_img.handle.clone().binding(),
+                    img_gradient.handle.clone().binding(),
+                    upscaled_img.handle.clone().binding(),
+                ]),
+            );
+        }
+
+        (upscaled_img, out_img)
+    } else {
+        (out_img, create_tensor([1], device, DType::F32))
+    };
 
     // Sanity check the buffers.
     assert!(
@@ -442,7 +561,7 @@ pub(crate) fn render_forward(
     );
 
     (
-        out_upscaled_img,
+        out_img,
         RenderAux {
             uniforms_buffer,
             tile_offsets,
@@ -452,7 +571,10 @@ pub(crate) fn render_forward(
             global_from_compact_gid,
             visible,
             img_size,
-            img_gradient: out_img_gradient,
+            raster_img,
+            img_gradient,
+            depth,
+            normals,
         },
     )
 }
@@ -26,8 +26,21 @@ pub struct RenderAux<B: Backend> {
     pub compact_gid_from_isect: IntTensor<B>,
     pub global_from_compact_gid: IntTensor<B>,
     pub visible: FloatTensor<B>,
+    /// The size splats were rasterized at, which is smaller than the output when upscaling.
     pub img_size: glam::UVec2,
+    /// The image before upscaling, or a dummy tensor when rendering at native resolution.
+    pub raster_img: FloatTensor<B>,
+    /// The [H, W, 12] dx, dy and dxy image derivatives used for upscaling, or a dummy tensor
+    /// when rendering at native resolution.
     pub img_gradient: FloatTensor<B>,
+    /// The [H, W, 2] expected and median depth, at the size of the output image. When
+    /// upscaling, each rasterized pixel is repeated rather than interpolated. This is a dummy
+    /// tensor unless depth was requested when rendering.
+    pub depth: FloatTensor<B>,
+    /// The [H, W, 3] alpha blended world space normals, at the size of the output image, repeated
+    /// like the depth when upscaling. Like colors, these are pre-multiplied by the alpha. This is
+    /// a dummy tensor unless normals were requested when rendering.
+    pub normals: FloatTensor<B>,
 }
 
 impl<B: Backend> RenderAux<B> {
@@ -40,6 +53,25 @@ impl<B: Backend> RenderAux<B> {
         (max - min).reshape([ty as usize, tx as usize])
     }
 
+    /// The [H, W] alpha-weighted depth of the splats, normalized by the alpha of each pixel.
+    ///
+    /// Only available when depth was requested when rendering.
+    pub fn expected_depth(&self) -> Tensor<B, 2> {
+        let depth: Tensor<B, 3> =
+            Tensor::from_primitive(TensorPrimitive::Float(self.depth.clone()));
+        depth.slice(s![.., .., 0]).squeeze(2)
+    }
+
+    /// The [H, W] depth at which the transmittance of each pixel drops below 0.5, or 0 for
+    /// pixels where it doesn't.
+    ///
+    /// Only available when depth was requested when rendering.
+    pub fn median_depth(&self) -> Tensor<B, 2> {
+        let depth: Tensor<B, 3> =
+            Tensor::from_primitive(TensorPrimitive::Float(self.depth.clone()));
+        depth.slice(s![.., .., 1]).squeeze(2)
+    }
+
     pub fn num_intersections(&self) -> Tensor<B, 1, Int> {
         Tensor::from_primitive(self.num_intersections.clone())
     }
@@ -1,6 +1,6 @@
 use crate::shaders;
 
-use glam::Vec3;
+use glam::{DVec3, Quat, Vec3};
 const SH_C0: f32 = shaders::project_visible::SH_C0;
 
 pub const fn sh_coeffs_for_degree(degree: u32) -> u32 {
@@ -29,3 +29,134 @@ pub fn rgb_to_sh(rgb: Vec3) -> Vec3 {
         channel_to_sh(rgb.z),
     )
 }
+
+/// The color of the base SH coefficients, the inverse of [`rgb_to_sh`].
+pub fn sh_to_rgb(sh: Vec3) -> Vec3 {
+    sh * SH_C0 + 0.5
+}
+
+/// Evaluate all SH bases up to degree 4 in a direction, in the order and with the signs used by
+/// the renderer.
+pub(crate) fn sh_bases(dir: DVec3) -> [f64; 25] {
+    let (x, y, z) = (dir.x, dir.y, dir.z);
+    let z2 = z * z;
+
+    let mut sh = [0.0; 25];
+    sh[0] = SH_C0 as f64;
+
+    sh[1] = -0.48860251190292 * y;
+    sh[2] = 0.48860251190292 * z;
+    sh[3] = -0.48860251190292 * x;
+
+    let tmp_0b = -1.092548430592079 * z;
+    let tmp_1a = 0.5462742152960395;
+    let c1 = x * x - y * y;
+    let s1 = 2.0 * x * y;
+    sh[4] = tmp_1a * s1;
+    sh[5] = tmp_0b * y;
+    sh[6] = 0.9461746957575601 * z2 - 0.3153915652525201;
+    sh[7] = tmp_0b * x;
+    sh[8] = tmp_1a * c1;
+
+    let tmp_0c = -2.285228997322329 * z2 + 0.4570457994644658;
+    let tmp_1b = 1.445305721320277 * z;
+    let tmp_2a = -0.5900435899266435;
+    let c2 = x * c1 - y * s1;
+    let s2 = x * s1 + y * c1;
+    sh[9] = tmp_2a * s2;
+    sh[10] = tmp_1b * s1;
+    sh[11] = tmp_0c * y;
+    sh[12] = z * (1.865881662950577 * z2 - 1.119528997770346);
+    sh[13] = tmp_0c * x;
+    sh[14] = tmp_1b * c1;
+    sh[15] = tmp_2a * c2;
+
+    let tmp_0d = z * (-4.683325804901025 * z2 + 2.007139630671868);
+    let tmp_1c = 3.31161143515146 * z2 - 0.47308734787878;
+    let tmp_2b = -1.770130769779931 * z;
+    let tmp_3a = 0.6258357354491763;
+    let c3 = x * c2 - y * s2;
+    let s3 = x * s2 + y * c2;
+    sh[16] = tmp_3a * s3;
+    sh[17] = tmp_2b * s2;
+    sh[18] = tmp_1c * s1;
+    sh[19] = tmp_0d * y;
+    sh[20] = 1.984313483298443 * z * sh[12] - 1.006230589874905 * sh[6];
+    sh[21] = tmp_0d * x;
+    sh[22] = tmp_1c * c1;
+    sh[23] = tmp_2b * c2;
+    sh[24] = tmp_3a * c3;
+    sh
+}
+
+/// Solve `a * x = b` for a square matrix `a`, with Gaussian elimination.
+fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+    let n = a.len();
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+            .expect("Unreachable");
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let (pivot_a, pivot_b) = (a[col].clone(), b[col].clone());
+        for (row, (a_row, b_row)) in a.iter_mut().zip(b.iter_mut()).enumerate() {
+            if row != col {
+                let factor = a_row[col] / pivot_a[col];
+                for (val, pivot) in a_row.iter_mut().zip(&pivot_a) {
+                    *val -= factor * pivot;
+                }
+                for (val, pivot) in b_row.iter_mut().zip(&pivot_b) {
+                    *val -= factor * pivot;
+                }
+            }
+        }
+    }
+    for (row, b_row) in b.iter_mut().enumerate() {
+        for val in b_row.iter_mut() {
+            *val /= a[row][row];
+        }
+    }
+    b
+}
+
+/// The matrix rotating the SH coefficients of one degree, as a row major `[2 * degree + 1]^2`
+/// matrix.
+///
+/// Multiplying the coefficients of a splat with this matrix gives the coefficients of the splat
+/// after rotating it with `rotation`, such that the color in a rotated direction is the same as
+/// the original color in the original direction.
+pub fn sh_rotation_matrix(degree: u32, rotation: Quat) -> Vec<f32> {
+    assert!(degree <= 4, "Only SH up to degree 4 are supported");
+    let start = (degree * degree) as usize;
+    let size = 2 * degree as usize + 1;
+
+    // Fit the matrix to the bases in a spread out set of directions. As rotated bases are exactly
+    // a linear combination of the bases, the least squares fit is exact.
+    let inv_rotation = rotation.as_dquat().normalize().inverse();
+    let samples = 64;
+    let (bases, rotated): (Vec<_>, Vec<_>) = (0..samples)
+        .map(|i| {
+            let z = 1.0 - (2 * i + 1) as f64 / samples as f64;
+            let phi = i as f64 * std::f64::consts::PI * (3.0 - 5.0f64.sqrt());
+            let r = (1.0 - z * z).sqrt();
+            let dir = DVec3::new(r * phi.cos(), r * phi.sin(), z);
+            (
+                sh_bases(dir)[start..start + size].to_vec(),
+                sh_bases(inv_rotation * dir)[start..start + size].to_vec(),
+            )
+        })
+        .unzip();
+
+    // Solve the normal equations (AᵀA) D = AᵀB.
+    let gram = |lhs: &[Vec<f64>], rhs: &[Vec<f64>]| -> Vec<Vec<f64>> {
+        (0..size)
+            .map(|i| {
+                (0..size)
+                    .map(|j| lhs.iter().zip(rhs).map(|(l, r)| l[i] * r[j]).sum())
+                    .collect()
+            })
+            .collect()
+    };
+    let matrix = solve(gram(&bases, &bases), gram(&bases, &rotated));
+    matrix.into_iter().flatten().map(|v| v as f32).collect()
+}
@@ -0,0 +1,48 @@
+@group(0) @binding(0) var<storage, read> depth: array<f32>;
+// The inverse depth of the furthest and the nearest pixel.
+@group(0) @binding(1) var<storage, read> inv_depth_range: array<f32>;
+@group(0) @binding(2) var<storage, read_write> out_img: array<u32>;
+
+// Polynomial approximation of the turbo colormap, see
+// https://gist.github.com/mikhailov-work/0d177465a8151eb6ede1768d51d476c7
+fn turbo(x: f32) -> vec3f {
+    let red_vec4 = vec4f(0.13572138, 4.61539260, -42.66032258, 132.13108234);
+    let green_vec4 = vec4f(0.09140261, 2.19418839, 4.84296658, -14.18503333);
+    let blue_vec4 = vec4f(0.10667330, 12.64194608, -60.58204836, 110.36276771);
+    let red_vec2 = vec2f(-152.94239396, 59.28637943);
+    let green_vec2 = vec2f(4.27729857, 2.82956604);
+    let blue_vec2 = vec2f(-89.90310912, 27.34824973);
+
+    let t = saturate(x);
+    let v4 = vec4f(1.0, t, t * t, t * t * t);
+    let v2 = v4.zw * v4.z;
+
+    return vec3f(
+        dot(v4, red_vec4) + dot(v2, red_vec2),
+        dot(v4, green_vec4) + dot(v2, green_vec2),
+        dot(v4, blue_vec4) + dot(v2, blue_vec2),
+    );
+}
+
+// Colors each pixel of a depth map, nearby pixels are red and far away pixels are blue.
+@compute
+@workgroup_size(256, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3u) {
+    let pix_id = global_id.x;
+
+    if pix_id >= arrayLength(&out_img) {
+        return;
+    }
+
+    let d = depth[pix_id];
+
+    // Pixels without any splats are left transparent.
+    if d <= 0.0f {
+        out_img[pix_id] = 0u;
+        return;
+    }
+
+    let range = vec2f(inv_depth_range[0], inv_depth_range[1]);
+    let t = (1.0f / d - range.x) / max(range.y - range.x, 1e-6f);
+    out_img[pix_id] = pack4x8unorm(vec4f(turbo(t), 1.0f));
+}
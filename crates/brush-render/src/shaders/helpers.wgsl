@@ -1,5 +1,13 @@
 const TILE_WIDTH: u32 = 16u;
 const TILE_SIZE: u32 = TILE_WIDTH * TILE_WIDTH;
+// Nr. of channels of the float image rendered for the backward pass.
+// These are RGB, alpha, and with BWD_DEPTH the alpha blended depth.
+// Nb: Rust code should use render::bwd_info_channels, as this depends on the defines.
+#ifdef BWD_DEPTH
+    const BWD_INFO_CHANNELS: u32 = 5u;
+#else
+    const BWD_INFO_CHANNELS: u32 = 4u;
+#endif
 
 // Helper function to compact bits for 2D z-order decoding
 fn compact_bits_16(v: u32) -> u32 {
@@ -63,10 +71,13 @@ struct RenderUniforms {
     // Nb: Alpha is ignored atm.
     background: vec4f,
 
-    // Img resolution (w, h)
+    // Resolution the image is upscaled to (w, h), the same as img_size when not upscaling.
     target_size: vec2u,
 
-    pad: vec2u,
+    // Whether to use the Mip-Splatting 2D filter instead of a fixed dilation.
+    mip_filter: u32,
+    // Whether to use an orthographic projection, in which case focal is in pixels per world unit.
+    orthographic: u32,
 }
 
 struct ProjectedSplat {
@@ -79,10 +90,12 @@ struct ProjectedSplat {
     color_g: f32,
     color_b: f32,
     color_a: f32,
+    // Camera space depth of the splat center.
+    depth: f32,
 }
 
-fn create_projected_splat(xy: vec2f, conic: vec3f, color: vec4f) -> ProjectedSplat {
-    return ProjectedSplat(xy.x, xy.y, conic.x, conic.y, conic.z, color.r, color.g, color.b, color.a);
+fn create_projected_splat(xy: vec2f, conic: vec3f, color: vec4f, depth: f32) -> ProjectedSplat {
+    return ProjectedSplat(xy.x, xy.y, conic.x, conic.y, conic.z, color.r, color.g, color.b, color.a, depth);
 }
 
 struct PackedVec3 {
@@ -163,7 +176,16 @@ fn calc_cov3d(scale: vec3f, quat: vec4f) -> mat3x3f {
     return M * transpose(M);
 }
 
-fn calc_cam_J(mean_c: vec3f, focal: vec2f, img_size: vec2u, pixel_center: vec2f) -> mat3x2f {
+fn calc_cam_J(mean_c: vec3f, focal: vec2f, img_size: vec2u, pixel_center: vec2f, orthographic: u32) -> mat3x2f {
+    // An orthographic projection is linear, and doesn't depend on depth.
+    if orthographic == 1u {
+        return mat3x2f(
+            vec2f(focal.x, 0.0),
+            vec2f(0.0, focal.y),
+            vec2f(0.0, 0.0)
+        );
+    }
+
     let lims_pos = (1.15f * vec2f(img_size.xy) - pixel_center) / focal;
     let lims_neg = (-0.15f * vec2f(img_size.xy) - pixel_center) / focal;
     let rz = 1.0 / mean_c.z;
@@ -181,20 +203,38 @@ fn calc_cam_J(mean_c: vec3f, focal: vec2f, img_size: vec2u, pixel_center: vec2f)
     return J;
 }
 
-fn calc_cov2d(cov3d: mat3x3f, mean_c: vec3f, focal: vec2f, img_size: vec2u, pixel_center: vec2f, viewmat: mat4x4f) -> mat2x2f {
+fn calc_cov2d(cov3d: mat3x3f, mean_c: vec3f, focal: vec2f, img_size: vec2u, pixel_center: vec2f, viewmat: mat4x4f, blur: f32, orthographic: u32) -> mat2x2f {
     let R = mat3x3f(viewmat[0].xyz, viewmat[1].xyz, viewmat[2].xyz);
     let covar_cam = R * cov3d * transpose(R);
 
-    let J = calc_cam_J(mean_c, focal, img_size, pixel_center);
+    let J = calc_cam_J(mean_c, focal, img_size, pixel_center, orthographic);
 
     var cov2d = J * covar_cam * transpose(J);
 
     // add a little blur along axes.
-    cov2d[0][0] += COV_BLUR;
-    cov2d[1][1] += COV_BLUR;
+    cov2d[0][0] += blur;
+    cov2d[1][1] += blur;
     return cov2d;
 }
 
+// Project a camera space position to pixel coordinates.
+fn project_mean(mean_c: vec3f, focal: vec2f, pixel_center: vec2f, orthographic: u32) -> vec2f {
+    if orthographic == 1u {
+        return focal * mean_c.xy + pixel_center;
+    }
+    return focal * mean_c.xy * (1.0 / mean_c.z) + pixel_center;
+}
+
+// The direction a splat is viewed from. With an orthographic projection,
+// this is the view direction of the camera for all splats.
+fn calc_viewdir(mean: vec3f, camera_position: vec3f, viewmat: mat4x4f, orthographic: u32) -> vec3f {
+    if orthographic == 1u {
+        // The camera forward axis, the third row of the world to camera rotation.
+        return normalize(vec3f(viewmat[0].z, viewmat[1].z, viewmat[2].z));
+    }
+    return normalize(mean - camera_position);
+}
+
 fn inverse(m: mat2x2f) -> mat2x2f {
     let det = determinant(m);
     if (det <= 0.0f) {
@@ -204,12 +244,19 @@ fn inverse(m: mat2x2f) -> mat2x2f {
     return mat2x2f(vec2f(m[1][1] * inv_det, -m[0][1] * inv_det), vec2f(-m[0][1] * inv_det, m[0][0] * inv_det));
 }
 
+// Fixed dilation of the 2D covariance, so splats cover at least about a pixel.
 const COV_BLUR: f32 = 0.3;
+// Variance of the Mip-Splatting 2D filter, which approximates a box filter the size of a pixel.
+const MIP_BLUR: f32 = 0.1;
 
-fn cov_compensation(cov2d: vec3f) -> f32 {
-    let cov_orig = cov2d - vec3f(COV_BLUR, 0.0, COV_BLUR);
-    let det_orig = cov_orig.x * cov_orig.z - cov_orig.y * cov_orig.y;
-    let det = cov2d.x * cov2d.z - cov2d.y * cov2d.y;
+fn cov_blur(mip_filter: u32) -> f32 {
+    return select(COV_BLUR, MIP_BLUR, mip_filter == 1u);
+}
+
+// Opacity scale of the Mip-Splatting 2D filter, such that blurring a splat preserves its total energy.
+fn cov_compensation(cov2d: mat2x2f, blur: f32) -> f32 {
+    let det_orig = (cov2d[0][0] - blur) * (cov2d[1][1] - blur) - cov2d[0][1] * cov2d[1][0];
+    let det = determinant(cov2d);
     return sqrt(max(0.0, det_orig / det));
 }
 
@@ -284,3 +331,23 @@ fn as_vec(packed: PackedVec3) -> vec3f {
 fn as_packed(vec: vec3f) -> PackedVec3 {
     return PackedVec3(vec.x, vec.y, vec.z);
 }
+
+// Position of a pixel of the upscaled image in the rasterized image,
+// relative to the centers of the rasterized pixels.
+fn upscale_source_coord(pix_loc: vec2u, img_size: vec2u, target_size: vec2u) -> vec2f {
+    let pixel_coord = vec2f(pix_loc) + 0.5f;
+    return pixel_coord * vec2f(img_size) / vec2f(target_size) - 0.5f;
+}
+
+// Cubic hermite basis functions at t, as the weights of (p0, p1, m0, m1), the
+// values and derivatives at 0 and 1.
+fn hermite_basis(t: f32) -> vec4f {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    return vec4f(
+        1.0f - 3.0f * t2 + 2.0f * t3,
+        3.0f * t2 - 2.0f * t3,
+        t - 2.0f * t2 + t3,
+        t3 - t2,
+    );
+}
@@ -0,0 +1,23 @@
+@group(0) @binding(0) var<storage, read> normals: array<f32>;
+@group(0) @binding(1) var<storage, read_write> out_img: array<u32>;
+
+// Colors each pixel of a normal map, mapping each axis from [-1, 1] to a color channel.
+@compute
+@workgroup_size(256, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3u) {
+    let pix_id = global_id.x;
+
+    if pix_id >= arrayLength(&out_img) {
+        return;
+    }
+
+    let n = vec3f(normals[pix_id * 3 + 0], normals[pix_id * 3 + 1], normals[pix_id * 3 + 2]);
+
+    // Pixels without any splats are left transparent.
+    if length(n) < 1e-4f {
+        out_img[pix_id] = 0u;
+        return;
+    }
+
+    out_img[pix_id] = pack4x8unorm(vec4f(normalize(n) * 0.5f + 0.5f, 1.0f));
+}
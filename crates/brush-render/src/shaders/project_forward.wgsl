@@ -50,16 +50,20 @@ fn main(@builtin(global_invocation_id) global_id: vec3u) {
     quat *= inverseSqrt(quat_norm_sqr);
 
     let cov3d = helpers::calc_cov3d(scale, quat);
-    let cov2d = helpers::calc_cov2d(cov3d, mean_c, uniforms.focal, uniforms.img_size, uniforms.pixel_center, viewmat);
+    let blur = helpers::cov_blur(uniforms.mip_filter);
+    let cov2d = helpers::calc_cov2d(cov3d, mean_c, uniforms.focal, uniforms.img_size, uniforms.pixel_center, viewmat, blur, uniforms.orthographic);
 
     if abs(determinant(cov2d)) < 1e-24 {
         return;
     }
 
     // compute the projected mean
-    let mean2d = uniforms.focal * mean_c.xy * (1.0 / mean_c.z) + uniforms.pixel_center;
+    let mean2d = helpers::project_mean(mean_c, uniforms.focal, uniforms.pixel_center, uniforms.orthographic);
 
-    let opac = helpers::sigmoid(raw_opacities[global_gid]);
+    var opac = helpers::sigmoid(raw_opacities[global_gid]);
+    if uniforms.mip_filter == 1u {
+        opac *= helpers::cov_compensation(cov2d, blur);
+    }
 
     if opac < 1.0 / 255.0 {
         return;
@@ -181,19 +181,23 @@ fn main(@builtin(global_invocation_id) gid: vec3u) {
 
     // Safe to normalize, splats with length(quat) == 0 are invisible.
     let quat = normalize(quats[global_gid]);
-    let opac = helpers::sigmoid(raw_opacities[global_gid]);
+    var opac = helpers::sigmoid(raw_opacities[global_gid]);
 
     let viewmat = uniforms.viewmat;
     let R = mat3x3f(viewmat[0].xyz, viewmat[1].xyz, viewmat[2].xyz);
     let mean_c = R * mean + viewmat[3].xyz;
 
     let covar = helpers::calc_cov3d(scale, quat);
-    let cov2d = helpers::calc_cov2d(covar, mean_c, uniforms.focal, uniforms.img_size, uniforms.pixel_center, viewmat);
+    let blur = helpers::cov_blur(uniforms.mip_filter);
+    let cov2d = helpers::calc_cov2d(covar, mean_c, uniforms.focal, uniforms.img_size, uniforms.pixel_center, viewmat, blur, uniforms.orthographic);
     let conic = helpers::inverse(cov2d);
 
+    if uniforms.mip_filter == 1u {
+        opac *= helpers::cov_compensation(cov2d, blur);
+    }
+
     // compute the projected mean
-    let rz = 1.0 / mean_c.z;
-    let mean2d = uniforms.focal * mean_c.xy * rz + uniforms.pixel_center;
+    let mean2d = helpers::project_mean(mean_c, uniforms.focal, uniforms.pixel_center, uniforms.orthographic);
 
     let sh_degree = uniforms.sh_degree;
     let num_coeffs = num_sh_coeffs(sh_degree);
@@ -239,12 +243,13 @@ fn main(@builtin(global_invocation_id) gid: vec3u) {
     }
 
     // Write projected splat information.
-    let viewdir = normalize(mean - uniforms.camera_position.xyz);
+    let viewdir = helpers::calc_viewdir(mean, uniforms.camera_position.xyz, viewmat, uniforms.orthographic);
     var color = sh_coeffs_to_color(sh_degree, viewdir, sh) + vec3f(0.5);
 
     projected[compact_gid] = helpers::create_projected_splat(
         mean2d,
         vec3f(conic[0][0], conic[0][1], conic[1][1]),
-        vec4f(color, opac)
+        vec4f(color, opac),
+        mean_c.z,
     );
 }
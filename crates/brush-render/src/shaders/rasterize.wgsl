@@ -6,12 +6,21 @@
 @group(0) @binding(3) var<storage, read> projected: array<helpers::ProjectedSplat>;
 
 #ifdef BWD_INFO
-    @group(0) @binding(4) var<storage, read_write> out_img: array<vec4f>;
+    // Stores helpers::BWD_INFO_CHANNELS floats per pixel.
+    @group(0) @binding(4) var<storage, read_write> out_img: array<f32>;
     @group(0) @binding(5) var<storage, read> global_from_compact_gid: array<u32>;
     @group(0) @binding(6) var<storage, read_write> visible: array<f32>;
+
+    #ifdef UPSCALE
+        // The dx, dy and dxy derivatives of the RGBA image, used by the upscale pass.
+        @group(0) @binding(7) var<storage, read_write> out_img_gradient: array<array<vec4f, 3>>;
+    #endif
 #else
     @group(0) @binding(4) var<storage, read_write> out_img: array<u32>;
-    @group(0) @binding(5) var<storage, read_write> out_img_gradient: array<array<vec4<f32>,3>>;
+
+    #ifdef UPSCALE
+        @group(0) @binding(5) var<storage, read_write> out_img_gradient: array<array<vec4f, 3>>;
+    #endif
 #endif
 
 var<workgroup> range_uniform: vec2u;
@@ -52,10 +61,15 @@ fn main(
     // current visibility left to render
     var T = 1.0;
     var pix_out = vec3f(0.0);
-    var pix_grad_out = array<vec3f, 4>(
-        vec3f(0.0), vec3f(0.0), vec3f(0.0), vec3f(0.0)
-    );
-    var alpha_acc: vec3f= vec3f(0.0);
+    #ifdef BWD_DEPTH
+        var pix_depth = 0.0;
+    #endif
+    #ifdef UPSCALE
+        // Derivatives of the color (dxy, dx, dy) and of the alpha (dx, dy, dxy)
+        // with respect to the pixel coordinate.
+        var pix_grad_out = array<vec3f, 3>(vec3f(0.0), vec3f(0.0), vec3f(0.0));
+        var alpha_acc = vec3f(0.0);
+    #endif
     var done = !inside;
 
     // each thread loads one gaussian at a time before rasterizing its
@@ -95,15 +109,6 @@ fn main(
                     break;
                 }
 
-                let dg_dx = (- conic.x * delta.x - conic.y * delta.y);
-                let dg_dy = (- conic.y * delta.x - conic.z * delta.y);
-                let dg_dxy = -conic.y;
-
-                let dalpha_dx = dg_dx * alpha;
-                let dalpha_dy = dg_dy * alpha;
-                let dalpha_dxy = (dg_dx * dg_dy  + dg_dxy) * alpha;
-
-
                 #ifdef BWD_INFO
                     // Count visible if contribution is at least somewhat significant.
                     visible[load_gid[t]] = 1.0;
@@ -112,15 +117,28 @@ fn main(
                 let vis = alpha * T;
                 let color_rgb = max(color.rgb, vec3f(0.0));
                 pix_out += color_rgb * vis;
+                #ifdef BWD_DEPTH
+                    pix_depth += proj.depth * vis;
+                #endif
+
+                #ifdef UPSCALE
+                    // Derivatives of the gaussian with respect to the pixel coordinate, over the gaussian.
+                    let dg_dx = conic.x * delta.x + conic.y * delta.y;
+                    let dg_dy = conic.y * delta.x + conic.z * delta.y;
+                    let dg_dxy = -conic.y;
 
-                pix_grad_out[0] += color_rgb * (T*dalpha_dxy - alpha_acc[2]*alpha - alpha_acc[1]*dalpha_dx - alpha_acc[0]*dalpha_dy);
-				pix_grad_out[1] += color_rgb * (T*dalpha_dx  - alpha_acc[0]*alpha);
-				pix_grad_out[2] += color_rgb * (T*dalpha_dy  - alpha_acc[1]*alpha);
+                    let dalpha_dx = dg_dx * alpha;
+                    let dalpha_dy = dg_dy * alpha;
+                    let dalpha_dxy = (dg_dx * dg_dy + dg_dxy) * alpha;
 
+                    pix_grad_out[0] += color_rgb * (T * dalpha_dxy - alpha_acc.z * alpha - alpha_acc.y * dalpha_dx - alpha_acc.x * dalpha_dy);
+                    pix_grad_out[1] += color_rgb * (T * dalpha_dx - alpha_acc.x * alpha);
+                    pix_grad_out[2] += color_rgb * (T * dalpha_dy - alpha_acc.y * alpha);
 
-                alpha_acc.z = alpha_acc.z*(1.0 - alpha) + T * dalpha_dxy - alpha_acc.x * dalpha_dy - alpha_acc.y * dalpha_dx;
-                alpha_acc.x = alpha_acc.x*(1.0 - alpha) + T * dalpha_dx;
-                alpha_acc.y = alpha_acc.y*(1.0 - alpha) + T * dalpha_dy;
+                    alpha_acc.z = alpha_acc.z * (1.0 - alpha) + T * dalpha_dxy - alpha_acc.x * dalpha_dy - alpha_acc.y * dalpha_dx;
+                    alpha_acc.x = alpha_acc.x * (1.0 - alpha) + T * dalpha_dx;
+                    alpha_acc.y = alpha_acc.y * (1.0 - alpha) + T * dalpha_dy;
+                #endif
 
                 T = next_T;
             }
@@ -131,19 +149,29 @@ fn main(
         // Compose with background. Nb that color is already pre-multiplied
         // by definition.
         let final_color = vec4f(pix_out + T * uniforms.background.rgb, 1.0 - T);
-        
-        // TODO consider background color in gradient
 
         #ifdef BWD_INFO
-            out_img[pix_id] = final_color;
+            let base = pix_id * helpers::BWD_INFO_CHANNELS;
+            out_img[base + 0] = final_color.r;
+            out_img[base + 1] = final_color.g;
+            out_img[base + 2] = final_color.b;
+            out_img[base + 3] = final_color.a;
+            #ifdef BWD_DEPTH
+                // Nb: Depth isn't normalized by alpha, and isn't composed with the background.
+                out_img[base + 4] = pix_depth;
+            #endif
         #else
             let colors_u = vec4u(clamp(final_color * 255.0, vec4f(0.0), vec4f(255.0)));
-            // let colors_u = vec4u(vec4f(clamp((pix_grad_out[0]+1.)*0.5 * 255.0, vec3f(0.0), vec3f(255.0)),255.0));
             let packed: u32 = colors_u.x | (colors_u.y << 8u) | (colors_u.z << 16u) | (colors_u.w << 24u);
             out_img[pix_id] = packed;
-            out_img_gradient[pix_id][0] = vec4f(pix_grad_out[0],alpha_acc.x);
-            out_img_gradient[pix_id][1] = vec4f(pix_grad_out[1],alpha_acc.y);
-            out_img_gradient[pix_id][2] = vec4f(pix_grad_out[2],alpha_acc.z);
+        #endif
+
+        #ifdef UPSCALE
+            // Alpha is 1 - T, so the background is composed with a weight of 1 - alpha.
+            let background = uniforms.background.rgb;
+            out_img_gradient[pix_id][0] = vec4f(pix_grad_out[1] - alpha_acc.x * background, alpha_acc.x);
+            out_img_gradient[pix_id][1] = vec4f(pix_grad_out[2] - alpha_acc.y * background, alpha_acc.y);
+            out_img_gradient[pix_id][2] = vec4f(pix_grad_out[0] - alpha_acc.z * background, alpha_acc.z);
         #endif
     }
 }
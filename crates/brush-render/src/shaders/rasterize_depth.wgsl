@@ -0,0 +1,90 @@
+#import helpers
+
+@group(0) @binding(0) var<storage, read> uniforms: helpers::RenderUniforms;
+@group(0) @binding(1) var<storage, read> compact_gid_from_isect: array<u32>;
+@group(0) @binding(2) var<storage, read> tile_offsets: array<u32>;
+@group(0) @binding(3) var<storage, read> projected: array<helpers::ProjectedSplat>;
+// The expected and the median depth of each pixel.
+@group(0) @binding(4) var<storage, read_write> out_depth: array<vec2f>;
+
+var<workgroup> range_uniform: vec2u;
+
+var<workgroup> local_batch: array<helpers::ProjectedSplat, helpers::TILE_SIZE>;
+
+// Rasterizes the depth of the splats, each thread treats a single pixel.
+//
+// This blends splats exactly like the rasterize kernel, but only keeps track of depth.
+@compute
+@workgroup_size(helpers::TILE_SIZE, 1, 1)
+fn main(
+    @builtin(global_invocation_id) global_id: vec3u,
+    @builtin(local_invocation_index) local_idx: u32,
+) {
+    let pix_loc = helpers::map_1d_to_2d(global_id.x, uniforms.tile_bounds.x);
+    let pix_id = pix_loc.x + pix_loc.y * uniforms.img_size.x;
+    let pixel_coord = vec2f(pix_loc) + 0.5f;
+    let tile_loc = vec2u(pix_loc.x / helpers::TILE_WIDTH, pix_loc.y / helpers::TILE_WIDTH);
+
+    let tile_id = tile_loc.x + tile_loc.y * uniforms.tile_bounds.x;
+    let inside = pix_loc.x < uniforms.img_size.x && pix_loc.y < uniforms.img_size.y;
+
+    range_uniform = vec2u(
+        tile_offsets[tile_id * 2],
+        tile_offsets[tile_id * 2 + 1],
+    );
+    let range = workgroupUniformLoad(&range_uniform);
+
+    var T = 1.0;
+    var pix_depth = 0.0;
+    // Depth at which the transmittance drops below 0.5, stays 0 if it never does.
+    var median_depth = 0.0;
+    var done = !inside;
+
+    for (var batch_start = range.x; batch_start < range.y; batch_start += helpers::TILE_SIZE) {
+        let remaining = min(helpers::TILE_SIZE, range.y - batch_start);
+
+        let load_isect_id = batch_start + local_idx;
+        let compact_gid = compact_gid_from_isect[load_isect_id];
+
+        workgroupBarrier();
+        if local_idx < remaining {
+            local_batch[local_idx] = projected[compact_gid];
+        }
+        workgroupBarrier();
+
+        for (var t = 0u; !done && t < remaining; t++) {
+            let proj = local_batch[t];
+
+            let xy = vec2f(proj.xy_x, proj.xy_y);
+            let conic = vec3f(proj.conic_x, proj.conic_y, proj.conic_z);
+
+            let delta = xy - pixel_coord;
+            let sigma = 0.5f * (conic.x * delta.x * delta.x + conic.z * delta.y * delta.y) + conic.y * delta.x * delta.y;
+            let alpha = min(0.999f, proj.color_a * exp(-sigma));
+
+            if sigma >= 0.0f && alpha >= 1.0f / 255.0f {
+                let next_T = T * (1.0 - alpha);
+
+                if next_T <= 1e-4f {
+                    done = true;
+                    break;
+                }
+
+                pix_depth += proj.depth * alpha * T;
+
+                if T > 0.5f && next_T <= 0.5f {
+                    median_depth = proj.depth;
+                }
+
+                T = next_T;
+            }
+        }
+    }
+
+    if inside {
+        // Normalize the depth by the alpha, so it's the expected depth of the visible splats.
+        let alpha = 1.0 - T;
+        let expected_depth = select(0.0f, pix_depth / alpha, alpha > 0.0f);
+        out_depth[pix_id] = vec2f(expected_depth, median_depth);
+    }
+}
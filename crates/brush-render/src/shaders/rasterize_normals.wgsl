@@ -0,0 +1,108 @@
+#import helpers
+
+@group(0) @binding(0) var<storage, read> uniforms: helpers::RenderUniforms;
+@group(0) @binding(1) var<storage, read> compact_gid_from_isect: array<u32>;
+@group(0) @binding(2) var<storage, read> tile_offsets: array<u32>;
+@group(0) @binding(3) var<storage, read> projected: array<helpers::ProjectedSplat>;
+@group(0) @binding(4) var<storage, read> global_from_compact_gid: array<u32>;
+@group(0) @binding(5) var<storage, read> means: array<helpers::PackedVec3>;
+@group(0) @binding(6) var<storage, read> log_scales: array<helpers::PackedVec3>;
+@group(0) @binding(7) var<storage, read> quats: array<vec4f>;
+// The alpha blended world space normal of each pixel.
+@group(0) @binding(8) var<storage, read_write> out_normals: array<helpers::PackedVec3>;
+
+var<workgroup> range_uniform: vec2u;
+
+var<workgroup> local_batch: array<helpers::ProjectedSplat, helpers::TILE_SIZE>;
+var<workgroup> local_normals: array<vec3f, helpers::TILE_SIZE>;
+
+// The normal of a splat is the direction of its shortest axis, facing the camera.
+fn splat_normal(global_gid: u32) -> vec3f {
+    let mean = helpers::as_vec(means[global_gid]);
+    let scale = helpers::as_vec(log_scales[global_gid]);
+    // Safe to normalize, splats with length(quat) == 0 are invisible.
+    let R = helpers::quat_to_mat(normalize(quats[global_gid]));
+
+    var normal = R[0];
+    if scale.y < scale.x && scale.y <= scale.z {
+        normal = R[1];
+    } else if scale.z < scale.x && scale.z < scale.y {
+        normal = R[2];
+    }
+
+    let viewdir = helpers::calc_viewdir(mean, uniforms.camera_position.xyz, uniforms.viewmat, uniforms.orthographic);
+    if dot(normal, viewdir) > 0.0f {
+        normal = -normal;
+    }
+    return normal;
+}
+
+// Rasterizes the normals of the splats, each thread treats a single pixel.
+//
+// This blends splats exactly like the rasterize kernel, but only keeps track of normals.
+@compute
+@workgroup_size(helpers::TILE_SIZE, 1, 1)
+fn main(
+    @builtin(global_invocation_id) global_id: vec3u,
+    @builtin(local_invocation_index) local_idx: u32,
+) {
+    let pix_loc = helpers::map_1d_to_2d(global_id.x, uniforms.tile_bounds.x);
+    let pix_id = pix_loc.x + pix_loc.y * uniforms.img_size.x;
+    let pixel_coord = vec2f(pix_loc) + 0.5f;
+    let tile_loc = vec2u(pix_loc.x / helpers::TILE_WIDTH, pix_loc.y / helpers::TILE_WIDTH);
+
+    let tile_id = tile_loc.x + tile_loc.y * uniforms.tile_bounds.x;
+    let inside = pix_loc.x < uniforms.img_size.x && pix_loc.y < uniforms.img_size.y;
+
+    range_uniform = vec2u(
+        tile_offsets[tile_id * 2],
+        tile_offsets[tile_id * 2 + 1],
+    );
+    let range = workgroupUniformLoad(&range_uniform);
+
+    var T = 1.0;
+    var pix_normal = vec3f(0.0);
+    var done = !inside;
+
+    for (var batch_start = range.x; batch_start < range.y; batch_start += helpers::TILE_SIZE) {
+        let remaining = min(helpers::TILE_SIZE, range.y - batch_start);
+
+        let load_isect_id = batch_start + local_idx;
+        let compact_gid = compact_gid_from_isect[load_isect_id];
+
+        workgroupBarrier();
+        if local_idx < remaining {
+            local_batch[local_idx] = projected[compact_gid];
+            local_normals[local_idx] = splat_normal(global_from_compact_gid[compact_gid]);
+        }
+        workgroupBarrier();
+
+        for (var t = 0u; !done && t < remaining; t++) {
+            let proj = local_batch[t];
+
+            let xy = vec2f(proj.xy_x, proj.xy_y);
+            let conic = vec3f(proj.conic_x, proj.conic_y, proj.conic_z);
+
+            let delta = xy - pixel_coord;
+            let sigma = 0.5f * (conic.x * delta.x * delta.x + conic.z * delta.y * delta.y) + conic.y * delta.x * delta.y;
+            let alpha = min(0.999f, proj.color_a * exp(-sigma));
+
+            if sigma >= 0.0f && alpha >= 1.0f / 255.0f {
+                let next_T = T * (1.0 - alpha);
+
+                if next_T <= 1e-4f {
+                    done = true;
+                    break;
+                }
+
+                pix_normal += local_normals[t] * alpha * T;
+                T = next_T;
+            }
+        }
+    }
+
+    if inside {
+        // Nb: Like colors, normals are pre-multiplied by the alpha.
+        out_normals[pix_id] = helpers::as_packed(pix_normal);
+    }
+}
@@ -0,0 +1,49 @@
+use crate::camera::Distortion;
+use assert_approx_eq::assert_approx_eq;
+
+#[test]
+fn zero_distortion_is_identity() {
+    let distortion = Distortion::OpenCv {
+        radial: [0.0; 6],
+        tangential: [0.0; 2],
+    };
+    assert!(distortion.is_identity());
+    let p = glam::vec2(0.3, -0.2);
+    assert_eq!(distortion.distort(p), p);
+}
+
+#[test]
+fn opencv_distortion() {
+    let distortion = Distortion::OpenCv {
+        radial: [0.1, 0.01, 0.0, 0.0, 0.0, 0.0],
+        tangential: [0.001, 0.002],
+    };
+    assert!(!distortion.is_identity());
+
+    let p = glam::vec2(0.5, 0.25);
+    let r2 = 0.3125;
+    let radial = 1.0 + 0.1 * r2 + 0.01 * r2 * r2;
+    let expected = glam::vec2(
+        0.5 * radial + 2.0 * 0.001 * 0.125 + 0.002 * (r2 + 2.0 * 0.25),
+        0.25 * radial + 0.001 * (r2 + 2.0 * 0.0625) + 2.0 * 0.002 * 0.125,
+    );
+    let distorted = distortion.distort(p);
+    assert_approx_eq!(distorted.x, expected.x, 1e-6);
+    assert_approx_eq!(distorted.y, expected.y, 1e-6);
+
+    // The center of the image is never distorted.
+    assert_eq!(distortion.distort(glam::Vec2::ZERO), glam::Vec2::ZERO);
+}
+
+#[test]
+fn fisheye_distortion() {
+    // Without coefficients, a fisheye lens maps the angle to the optical axis linearly.
+    let distortion = Distortion::Fisheye { radial: [0.0; 4] };
+    assert!(!distortion.is_identity());
+
+    let p = glam::vec2(1.0, 0.0);
+    let distorted = distortion.distort(p);
+    assert_approx_eq!(distorted.x, std::f32::consts::FRAC_PI_4, 1e-6);
+    assert_approx_eq!(distorted.y, 0.0, 1e-6);
+    assert_eq!(distortion.distort(glam::Vec2::ZERO), glam::Vec2::ZERO);
+}
@@ -1,2 +1,4 @@
+mod camera;
 mod knn_init;
 mod render;
+mod transform;
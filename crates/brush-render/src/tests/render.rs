@@ -1,4 +1,4 @@
-use crate::{MainBackend, SplatForward, camera::Camera};
+use crate::{MainBackend, RenderScale, SplatForward, camera::Camera, gaussian_splats::Splats};
 use assert_approx_eq::assert_approx_eq;
 use burn::tensor::{Tensor, TensorPrimitive};
 use burn_wgpu::WgpuDevice;
@@ -36,7 +36,12 @@ fn renders_at_all() {
         sh_coeffs.into_primitive().tensor(),
         raw_opacity.into_primitive().tensor(),
         Vec3::ZERO,
+        false,
+        RenderScale::Native,
+        false,
+        false,
         true,
+        false,
     );
     aux.validate_values();
 
@@ -52,3 +57,366 @@ fn renders_at_all() {
     assert_approx_eq!(rgb_mean, 0.0, 1e-5);
     assert_approx_eq!(alpha_mean, 0.0);
 }
+
+#[test]
+fn renders_upscaled() {
+    let cam = Camera::new(
+        glam::vec3(0.0, 0.0, -2.0),
+        glam::Quat::IDENTITY,
+        0.5,
+        0.5,
+        glam::vec2(0.5, 0.5),
+    );
+    let img_size = glam::uvec2(64, 48);
+    let device = WgpuDevice::DefaultDevice;
+    let num_points = 4;
+    let means = Tensor::<MainBackend, 2>::random(
+        [num_points, 3],
+        burn::tensor::Distribution::Uniform(-0.5, 0.5),
+        &device,
+    );
+    let log_scales = Tensor::<MainBackend, 2>::ones([num_points, 3], &device) * -2.0;
+    let quats: Tensor<MainBackend, 2> =
+        Tensor::<MainBackend, 1>::from_floats(glam::Quat::IDENTITY.to_array(), &device)
+            .unsqueeze_dim(0)
+            .repeat_dim(0, num_points);
+    let sh_coeffs = Tensor::<MainBackend, 3>::ones([num_points, 1, 3], &device);
+    let raw_opacity = Tensor::<MainBackend, 1>::zeros([num_points], &device);
+
+    for render_scale in [RenderScale::Native, RenderScale::Half, RenderScale::Quarter] {
+        let (output, aux) = <MainBackend as SplatForward<MainBackend>>::render_splats(
+            &cam,
+            img_size,
+            means.clone().into_primitive().tensor(),
+            log_scales.clone().into_primitive().tensor(),
+            quats.clone().into_primitive().tensor(),
+            sh_coeffs.clone().into_primitive().tensor(),
+            raw_opacity.clone().into_primitive().tensor(),
+            Vec3::ZERO,
+            false,
+            render_scale,
+            false,
+            false,
+            false,
+            false,
+        );
+        aux.validate_values();
+
+        // The output is always at the requested size, but rasterized at a lower resolution.
+        let output: Tensor<MainBackend, 3> = Tensor::from_primitive(TensorPrimitive::Float(output));
+        assert_eq!(output.dims(), [48, 64, 1]);
+        assert_eq!(aux.img_size, img_size / render_scale.factor(img_size));
+    }
+}
+
+#[test]
+fn adaptive_render_scale() {
+    assert_eq!(RenderScale::Adaptive.factor(glam::uvec2(640, 480)), 1);
+    assert_eq!(RenderScale::Adaptive.factor(glam::uvec2(1920, 1080)), 2);
+    assert_eq!(RenderScale::Adaptive.factor(glam::uvec2(7680, 4320)), 4);
+    assert_eq!(
+        RenderScale::Half.raster_size(glam::uvec2(33, 16)),
+        glam::uvec2(17, 8)
+    );
+}
+
+#[test]
+fn renders_depth() {
+    let cam = Camera::new(
+        glam::vec3(0.0, 0.0, -2.0),
+        glam::Quat::IDENTITY,
+        0.5,
+        0.5,
+        glam::vec2(0.5, 0.5),
+    );
+    let img_size = glam::uvec2(32, 32);
+    let device = WgpuDevice::DefaultDevice;
+
+    // A single, nearly opaque splat in front of the camera.
+    let means = Tensor::<MainBackend, 2>::zeros([1, 3], &device);
+    let log_scales = Tensor::<MainBackend, 2>::ones([1, 3], &device) * -3.0;
+    let quats = Tensor::<MainBackend, 1>::from_floats(glam::Quat::IDENTITY.to_array(), &device)
+        .unsqueeze_dim(0);
+    let sh_coeffs = Tensor::<MainBackend, 3>::ones([1, 1, 3], &device);
+    let raw_opacity = Tensor::<MainBackend, 1>::ones([1], &device) * 5.0;
+
+    let (_, aux) = <MainBackend as SplatForward<MainBackend>>::render_splats(
+        &cam,
+        img_size,
+        means.into_primitive().tensor(),
+        log_scales.into_primitive().tensor(),
+        quats.into_primitive().tensor(),
+        sh_coeffs.into_primitive().tensor(),
+        raw_opacity.into_primitive().tensor(),
+        Vec3::ZERO,
+        false,
+        RenderScale::Native,
+        true,
+        false,
+        false,
+        false,
+    );
+    aux.validate_values();
+
+    let center = |depth: Tensor<MainBackend, 2>| {
+        assert_eq!(depth.dims(), [32, 32]);
+        depth
+            .slice([16..17, 16..17])
+            .into_data()
+            .to_vec::<f32>()
+            .expect("Wrong type")[0]
+    };
+    assert_approx_eq!(center(aux.expected_depth()), 2.0, 1e-4);
+    assert_approx_eq!(center(aux.median_depth()), 2.0, 1e-4);
+
+    // Pixels without any splats have no depth.
+    let corner = aux.expected_depth().slice([0..1, 0..1]).into_data();
+    assert_approx_eq!(corner.to_vec::<f32>().expect("Wrong type")[0], 0.0);
+}
+
+#[test]
+fn upscales_depth() {
+    let cam = Camera::new(
+        glam::vec3(0.0, 0.0, -2.0),
+        glam::Quat::IDENTITY,
+        0.5,
+        0.5,
+        glam::vec2(0.5, 0.5),
+    );
+    // An odd size, which doesn't divide evenly by the upscale factor.
+    let img_size = glam::uvec2(33, 31);
+    let device = WgpuDevice::DefaultDevice;
+
+    // A larger splat, so it covers a few pixels at the lower resolution.
+    let means = Tensor::<MainBackend, 2>::zeros([1, 3], &device);
+    let log_scales = Tensor::<MainBackend, 2>::ones([1, 3], &device) * -1.5;
+    let quats = Tensor::<MainBackend, 1>::from_floats(glam::Quat::IDENTITY.to_array(), &device)
+        .unsqueeze_dim(0);
+    let sh_coeffs = Tensor::<MainBackend, 3>::ones([1, 1, 3], &device);
+    let raw_opacity = Tensor::<MainBackend, 1>::ones([1], &device) * 5.0;
+
+    let render = |render_scale| {
+        let (_, aux) = <MainBackend as SplatForward<MainBackend>>::render_splats(
+            &cam,
+            img_size,
+            means.clone().into_primitive().tensor(),
+            log_scales.clone().into_primitive().tensor(),
+            quats.clone().into_primitive().tensor(),
+            sh_coeffs.clone().into_primitive().tensor(),
+            raw_opacity.clone().into_primitive().tensor(),
+            Vec3::ZERO,
+            false,
+            render_scale,
+            true,
+            false,
+            false,
+            false,
+        );
+        aux.validate_values();
+        aux.median_depth()
+    };
+
+    let native = render(RenderScale::Native);
+    let upscaled = render(RenderScale::Half);
+    // The depth matches the size of the output image, not the rasterized image.
+    assert_eq!(upscaled.dims(), [31, 33]);
+    assert_eq!(upscaled.dims(), native.dims());
+
+    // The depth isn't blended when upscaling, so it stays at the depth of the splat.
+    let center = upscaled
+        .slice([15..16, 16..17])
+        .into_data()
+        .to_vec::<f32>()
+        .expect("Wrong type")[0];
+    assert_approx_eq!(center, 2.0, 1e-4);
+}
+
+#[test]
+fn renders_normals() {
+    let cam = Camera::new(
+        glam::vec3(0.0, 0.0, -2.0),
+        glam::Quat::IDENTITY,
+        0.5,
+        0.5,
+        glam::vec2(0.5, 0.5),
+    );
+    let img_size = glam::uvec2(32, 32);
+    let device = WgpuDevice::DefaultDevice;
+
+    // A single flat splat, which is thinnest along the z axis.
+    let means = Tensor::<MainBackend, 2>::zeros([1, 3], &device);
+    let log_scales = Tensor::<MainBackend, 2>::from_floats([[-3.0, -3.0, -6.0]], &device);
+    let quats = Tensor::<MainBackend, 1>::from_floats(glam::Quat::IDENTITY.to_array(), &device)
+        .unsqueeze_dim(0);
+    let sh_coeffs = Tensor::<MainBackend, 3>::ones([1, 1, 3], &device);
+    let raw_opacity = Tensor::<MainBackend, 1>::ones([1], &device) * 5.0;
+
+    let (_, aux) = <MainBackend as SplatForward<MainBackend>>::render_splats(
+        &cam,
+        img_size,
+        means.into_primitive().tensor(),
+        log_scales.into_primitive().tensor(),
+        quats.into_primitive().tensor(),
+        sh_coeffs.into_primitive().tensor(),
+        raw_opacity.into_primitive().tensor(),
+        Vec3::ZERO,
+        false,
+        RenderScale::Native,
+        false,
+        true,
+        false,
+        false,
+    );
+    aux.validate_values();
+
+    let normals: Tensor<MainBackend, 3> =
+        Tensor::from_primitive(TensorPrimitive::Float(aux.normals));
+    assert_eq!(normals.dims(), [32, 32, 3]);
+    let center = normals
+        .slice([16..17, 16..17, 0..3])
+        .into_data()
+        .to_vec::<f32>()
+        .expect("Wrong type");
+    let center = glam::Vec3::from_slice(&center);
+
+    // The normal faces the camera, and is weighted by the alpha of the splat.
+    assert!(center.length() > 0.5);
+    let dir = center.normalize();
+    assert_approx_eq!(dir.x, 0.0, 1e-4);
+    assert_approx_eq!(dir.y, 0.0, 1e-4);
+    assert_approx_eq!(dir.z, -1.0, 1e-4);
+}
+
+#[test]
+fn upscales_normals() {
+    let cam = Camera::new(
+        glam::vec3(0.0, 0.0, -2.0),
+        glam::Quat::IDENTITY,
+        0.5,
+        0.5,
+        glam::vec2(0.5, 0.5),
+    );
+    let img_size = glam::uvec2(33, 31);
+    let device = WgpuDevice::DefaultDevice;
+
+    // A flat splat, large enough to cover a few pixels at the lower resolution.
+    let means = Tensor::<MainBackend, 2>::zeros([1, 3], &device);
+    let log_scales = Tensor::<MainBackend, 2>::from_floats([[-1.5, -1.5, -6.0]], &device);
+    let quats = Tensor::<MainBackend, 1>::from_floats(glam::Quat::IDENTITY.to_array(), &device)
+        .unsqueeze_dim(0);
+    let sh_coeffs = Tensor::<MainBackend, 3>::ones([1, 1, 3], &device);
+    let raw_opacity = Tensor::<MainBackend, 1>::ones([1], &device) * 5.0;
+
+    let (_, aux) = <MainBackend as SplatForward<MainBackend>>::render_splats(
+        &cam,
+        img_size,
+        means.into_primitive().tensor(),
+        log_scales.into_primitive().tensor(),
+        quats.into_primitive().tensor(),
+        sh_coeffs.into_primitive().tensor(),
+        raw_opacity.into_primitive().tensor(),
+        Vec3::ZERO,
+        false,
+        RenderScale::Half,
+        false,
+        true,
+        false,
+        false,
+    );
+    aux.validate_values();
+
+    // The normals match the size of the output image, not the rasterized image.
+    let normals: Tensor<MainBackend, 3> =
+        Tensor::from_primitive(TensorPrimitive::Float(aux.normals));
+    assert_eq!(normals.dims(), [31, 33, 3]);
+    let center = normals
+        .slice([15..16, 16..17, 0..3])
+        .into_data()
+        .to_vec::<f32>()
+        .expect("Wrong type");
+    let dir = glam::Vec3::from_slice(&center).normalize();
+    assert_approx_eq!(dir.z, -1.0, 1e-4);
+}
+
+#[test]
+fn renders_orthographic() {
+    let img_size = glam::uvec2(32, 32);
+    let device = WgpuDevice::DefaultDevice;
+
+    let means = Tensor::<MainBackend, 2>::from_floats([[0.5, 0.0, 0.0]], &device);
+    let log_scales = Tensor::<MainBackend, 2>::ones([1, 3], &device) * -3.0;
+    let quats = Tensor::<MainBackend, 1>::from_floats(glam::Quat::IDENTITY.to_array(), &device)
+        .unsqueeze_dim(0);
+    let sh_coeffs = Tensor::<MainBackend, 3>::ones([1, 1, 3], &device);
+    let raw_opacity = Tensor::<MainBackend, 1>::ones([1], &device) * 5.0;
+
+    // An orthographic view looks the same from any distance.
+    let render_from = |distance: f32| {
+        let cam = Camera::new_orthographic(
+            glam::vec3(0.0, 0.0, -distance),
+            glam::Quat::IDENTITY,
+            2.0,
+            2.0,
+            glam::vec2(0.5, 0.5),
+        );
+        let (img, aux) = <MainBackend as SplatForward<MainBackend>>::render_splats(
+            &cam,
+            img_size,
+            means.clone().into_primitive().tensor(),
+            log_scales.clone().into_primitive().tensor(),
+            quats.clone().into_primitive().tensor(),
+            sh_coeffs.clone().into_primitive().tensor(),
+            raw_opacity.clone().into_primitive().tensor(),
+            Vec3::ZERO,
+            false,
+            RenderScale::Native,
+            false,
+            false,
+            true,
+            false,
+        );
+        aux.validate_values();
+        let img: Tensor<MainBackend, 3> = Tensor::from_primitive(TensorPrimitive::Float(img));
+        img.into_data().to_vec::<f32>().expect("Wrong type")
+    };
+
+    let near = render_from(2.0);
+    let far = render_from(20.0);
+    for (&a, &b) in near.iter().zip(&far) {
+        assert_approx_eq!(a, b, 1e-4);
+    }
+
+    // The splat is at x = 0.5 in a view that is 2 units wide, so at 3/4 of the image.
+    let alpha = |x: usize, y: usize| near[(y * 32 + x) * 4 + 3];
+    assert!(alpha(24, 16) > 0.5);
+    assert!(alpha(8, 16) < 1e-4);
+}
+
+#[test]
+fn renders_equirect() {
+    let device = WgpuDevice::DefaultDevice;
+
+    // One splat in front of the camera, and one to its right.
+    let means = Tensor::<MainBackend, 2>::from_floats([[0.0, 0.0, 2.0], [2.0, 0.0, 0.0]], &device);
+    let log_scales = Tensor::<MainBackend, 2>::ones([2, 3], &device) * -1.0;
+    let quats = Tensor::<MainBackend, 1>::from_floats(glam::Quat::IDENTITY.to_array(), &device)
+        .unsqueeze_dim(0)
+        .repeat_dim(0, 2);
+    let sh_coeffs = Tensor::<MainBackend, 3>::ones([2, 1, 3], &device);
+    let raw_opacity = Tensor::<MainBackend, 1>::ones([2], &device) * 5.0;
+    let splats = Splats::from_tensor_data(means, quats, log_scales, sh_coeffs, raw_opacity);
+
+    let width = 64;
+    let img = splats.render_equirect(Vec3::ZERO, glam::Quat::IDENTITY, width, Vec3::ZERO, None);
+    assert_eq!(img.dims(), [32, 64, 4]);
+
+    let img = img.into_data().to_vec::<f32>().expect("Wrong type");
+    let alpha = |x: usize, y: usize| img[(y * 64 + x) * 4 + 3];
+    // Forward is at the center of the panorama, +X a quarter turn further.
+    assert!(alpha(32, 16) > 0.5);
+    assert!(alpha(48, 16) > 0.5);
+    // Nothing is behind the camera, or straight up.
+    assert!(alpha(0, 16) < 1e-4);
+    assert!(alpha(16, 16) < 1e-4);
+    assert!(alpha(32, 0) < 1e-4);
+}
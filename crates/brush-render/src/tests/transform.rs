@@ -0,0 +1,105 @@
+use crate::{
+    MainBackend,
+    gaussian_splats::Splats,
+    sh::{sh_bases, sh_rotation_matrix},
+};
+use assert_approx_eq::assert_approx_eq;
+use burn::tensor::Tensor;
+use burn_wgpu::WgpuDevice;
+use glam::{DVec3, EulerRot, Quat, Vec3};
+
+fn to_vec<const D: usize>(tensor: Tensor<MainBackend, D>) -> Vec<f32> {
+    tensor.to_data().to_vec().expect("Wrong type")
+}
+
+#[test]
+fn sh_rotation_identity() {
+    for degree in 1..=4 {
+        let size = 2 * degree as usize + 1;
+        let matrix = sh_rotation_matrix(degree, Quat::IDENTITY);
+        for (i, val) in matrix.iter().enumerate() {
+            let expected = if i / size == i % size { 1.0 } else { 0.0 };
+            assert_approx_eq!(*val, expected, 1e-5);
+        }
+    }
+}
+
+#[test]
+fn sh_rotation_keeps_colors() {
+    let rotation = Quat::from_euler(EulerRot::YXZ, 0.3, -1.2, 2.0);
+    let dirs = [
+        DVec3::new(0.3, -0.5, 0.8),
+        DVec3::new(-0.9, 0.1, 0.2),
+        DVec3::new(0.0, 1.0, 0.0),
+    ];
+    for degree in 1..=4 {
+        let start = (degree * degree) as usize;
+        let size = 2 * degree as usize + 1;
+        let coeffs: Vec<f32> = (0..size).map(|i| (i as f32 * 0.7).sin()).collect();
+        let matrix = sh_rotation_matrix(degree, rotation);
+        let rotated: Vec<f32> = (0..size)
+            .map(|i| (0..size).map(|j| matrix[i * size + j] * coeffs[j]).sum())
+            .collect();
+
+        // The rotated coefficients in a rotated direction should give the original color.
+        let eval = |coeffs: &[f32], dir: DVec3| -> f64 {
+            let bases = sh_bases(dir.normalize());
+            coeffs
+                .iter()
+                .zip(&bases[start..start + size])
+                .map(|(c, b)| *c as f64 * b)
+                .sum()
+        };
+        for dir in dirs {
+            let rotated_dir = rotation.as_dquat() * dir;
+            assert_approx_eq!(eval(&coeffs, dir), eval(&rotated, rotated_dir), 1e-4);
+        }
+    }
+}
+
+#[test]
+fn transform_splats() {
+    let device = WgpuDevice::DefaultDevice;
+    let rotation = Quat::from_rotation_z(std::f32::consts::FRAC_PI_2);
+    let splat_rot = Quat::from_rotation_x(0.5);
+    let splats = Splats::<MainBackend>::from_raw(
+        vec![1.0, 0.0, 0.0],
+        Some(vec![splat_rot.w, splat_rot.x, splat_rot.y, splat_rot.z]),
+        Some(vec![0.0, -1.0, 0.5]),
+        Some(vec![
+            0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9, 1.0, 1.1, 1.2,
+        ]),
+        Some(vec![0.3]),
+        &device,
+    );
+    let transformed = splats.transformed(2.0, rotation, Vec3::new(0.0, 0.0, 1.0));
+
+    let means = to_vec(transformed.means.val());
+    for (val, expected) in means.iter().zip([0.0, 2.0, 1.0]) {
+        assert_approx_eq!(*val, expected, 1e-5);
+    }
+
+    let expected_rot = rotation * splat_rot;
+    let rot = to_vec(transformed.rotation.val());
+    for (val, expected) in rot.iter().zip([
+        expected_rot.w,
+        expected_rot.x,
+        expected_rot.y,
+        expected_rot.z,
+    ]) {
+        assert_approx_eq!(*val, expected, 1e-5);
+    }
+
+    let log_scales = to_vec(transformed.log_scales.val());
+    for (val, expected) in log_scales.iter().zip([0.0, -1.0, 0.5]) {
+        assert_approx_eq!(*val, expected + 2.0f32.ln(), 1e-5);
+    }
+
+    // The base color doesn't depend on the direction.
+    let sh = to_vec(transformed.sh_coeffs.val());
+    for (val, expected) in sh.iter().zip([0.1, 0.2, 0.3]) {
+        assert_approx_eq!(*val, expected, 1e-6);
+    }
+    assert_eq!(sh.len(), 12);
+    assert_approx_eq!(to_vec(transformed.raw_opacity.val())[0], 0.3, 1e-6);
+}
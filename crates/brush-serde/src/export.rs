@@ -2,30 +2,85 @@ use std::collections::BTreeMap;
 use std::vec;
 
 use brush_render::gaussian_splats::Splats;
-use brush_render::sh::sh_coeffs_for_degree;
+use brush_render::sh::{sh_coeffs_for_degree, sh_to_rgb};
 use burn::prelude::Backend;
+use glam::{Quat, Vec3};
+use serde::ser::SerializeMap;
 use serde::{Serialize, Serializer};
 use serde_ply::{SerializeError, SerializeOptions};
 
+use crate::quant::{encode_quat, encode_sh, encode_vec_8_8_8_8, encode_vec_11_10_11};
+
+const SH_REST_NAMES: [&str; 46] = [
+    "f_rest_0",
+    "f_rest_1",
+    "f_rest_2",
+    "f_rest_3",
+    "f_rest_4",
+    "f_rest_5",
+    "f_rest_6",
+    "f_rest_7",
+    "f_rest_8",
+    "f_rest_9",
+    "f_rest_10",
+    "f_rest_11",
+    "f_rest_12",
+    "f_rest_13",
+    "f_rest_14",
+    "f_rest_15",
+    "f_rest_16",
+    "f_rest_17",
+    "f_rest_18",
+    "f_rest_19",
+    "f_rest_20",
+    "f_rest_21",
+    "f_rest_22",
+    "f_rest_23",
+    "f_rest_24",
+    "f_rest_25",
+    "f_rest_26",
+    "f_rest_27",
+    "f_rest_28",
+    "f_rest_29",
+    "f_rest_30",
+    "f_rest_31",
+    "f_rest_32",
+    "f_rest_33",
+    "f_rest_34",
+    "f_rest_35",
+    "f_rest_36",
+    "f_rest_37",
+    "f_rest_38",
+    "f_rest_39",
+    "f_rest_40",
+    "f_rest_41",
+    "f_rest_42",
+    "f_rest_43",
+    "f_rest_44",
+    "f_rest_45",
+];
+
 // Dynamic PLY structure that only includes needed SH coefficients
 #[derive(Debug)]
-struct DynamicPlyGaussian {
+pub(crate) struct DynamicPlyGaussian {
     // Core properties
-    x: f32,
-    y: f32,
-    z: f32,
-    scale_0: f32,
-    scale_1: f32,
-    scale_2: f32,
-    opacity: f32,
-    rot_0: f32,
-    rot_1: f32,
-    rot_2: f32,
-    rot_3: f32,
-    f_dc_0: f32,
-    f_dc_1: f32,
-    f_dc_2: f32,
-    rest_coeffs: Vec<f32>,
+    pub(crate) x: f32,
+    pub(crate) y: f32,
+    pub(crate) z: f32,
+    pub(crate) scale_0: f32,
+    pub(crate) scale_1: f32,
+    pub(crate) scale_2: f32,
+    pub(crate) opacity: f32,
+    pub(crate) rot_0: f32,
+    pub(crate) rot_1: f32,
+    pub(crate) rot_2: f32,
+    pub(crate) rot_3: f32,
+    pub(crate) f_dc_0: f32,
+    pub(crate) f_dc_1: f32,
+    pub(crate) f_dc_2: f32,
+    pub(crate) rest_coeffs: Vec<f32>,
+    // Mip-Splatting 3D filter, if the splats have one.
+    pub(crate) filter_3d: Option<f32>,
 }
 
 impl Serialize for DynamicPlyGaussian {
@@ -54,69 +109,26 @@ impl Serialize for DynamicPlyGaussian {
         map.insert("f_dc_1", self.f_dc_1);
         map.insert("f_dc_2", self.f_dc_2);
 
-        let sh_names = [
-            "f_rest_0",
-            "f_rest_1",
-            "f_rest_2",
-            "f_rest_3",
-            "f_rest_4",
-            "f_rest_5",
-            "f_rest_6",
-            "f_rest_7",
-            "f_rest_8",
-            "f_rest_9",
-            "f_rest_10",
-            "f_rest_11",
-            "f_rest_12",
-            "f_rest_13",
-            "f_rest_14",
-            "f_rest_15",
-            "f_rest_16",
-            "f_rest_17",
-            "f_rest_18",
-            "f_rest_19",
-            "f_rest_20",
-            "f_rest_21",
-            "f_rest_22",
-            "f_rest_23",
-            "f_rest_24",
-            "f_rest_25",
-            "f_rest_26",
-            "f_rest_27",
-            "f_rest_28",
-            "f_rest_29",
-            "f_rest_30",
-            "f_rest_31",
-            "f_rest_32",
-            "f_rest_33",
-            "f_rest_34",
-            "f_rest_35",
-            "f_rest_36",
-            "f_rest_37",
-            "f_rest_38",
-            "f_rest_39",
-            "f_rest_40",
-            "f_rest_41",
-            "f_rest_42",
-            "f_rest_43",
-            "f_rest_44",
-            "f_rest_45",
-        ];
-        for (name, val) in sh_names.iter().zip(&self.rest_coeffs) {
+        for (name, val) in SH_REST_NAMES.iter().zip(&self.rest_coeffs) {
             map.insert(name, *val);
         }
+
+        // Same name as the reference Mip-Splatting implementation.
+        if let Some(filter_3d) = self.filter_3d {
+            map.insert("filter_3D", filter_3d);
+        }
         // Serialize as a map
         map.serialize(serializer)
     }
 }
 
 #[derive(Serialize)]
-struct DynamicPly {
-    vertex: Vec<DynamicPlyGaussian>,
+pub(crate) struct DynamicPly {
+    pub(crate) vertex: Vec<DynamicPlyGaussian>,
 }
 pub use burn_cubecl::{CubeRuntime, cubecl::Compiler, tensor::CubeTensor};
 
-async fn read_splat_data<B: Backend>(splats: Splats<B>) -> DynamicPly {
+pub(crate) async fn read_splat_data<B: Backend>(splats: Splats<B>) -> DynamicPly {
     let means = splats
         .means
         .val()
@@ -154,6 +166,18 @@ async fn read_splat_data<B: Backend>(splats: Splats<B>) -> DynamicPly {
         .into_vec()
         .expect("Unreachable");
 
+    let filter_3d: Option<Vec<f32>> = if let Some(filter_3d) = splats.filter_3d.clone() {
+        Some(
+            filter_3d
+                .into_data_async()
+                .await
+                .into_vec()
+                .expect("Unreachable"),
+        )
+    } else {
+        None
+    };
+
     let sh_coeffs_num = splats.sh_coeffs.dims()[1];
     let sh_degree = splats.sh_degree();
 
@@ -219,6 +243,7 @@ async fn read_splat_data<B: Backend>(splats: Splats<B>) -> DynamicPly {
                 f_dc_1: sh_green[0],
                 f_dc_2: sh_blue[0],
                 rest_coeffs,
+                filter_3d: filter_3d.as_ref().map(|f| f[i]),
             }
         })
         .collect();
@@ -238,13 +263,193 @@ pub async fn splat_to_ply<B: Backend>(splats: Splats<B>) -> Result<Vec<u8>, Seri
     serde_ply::to_bytes(&ply, SerializeOptions::binary_le().with_comments(comments))
 }
 
+/// Number of splats that share a quantization range in a compressed ply.
+const COMPRESSED_CHUNK_SIZE: usize = 256;
+
+/// Quantization ranges of a chunk of splats in a compressed ply.
+#[derive(Serialize)]
+struct ChunkMeta {
+    min_x: f32,
+    min_y: f32,
+    min_z: f32,
+    max_x: f32,
+    max_y: f32,
+    max_z: f32,
+    min_scale_x: f32,
+    min_scale_y: f32,
+    min_scale_z: f32,
+    max_scale_x: f32,
+    max_scale_y: f32,
+    max_scale_z: f32,
+    min_r: f32,
+    min_g: f32,
+    min_b: f32,
+    max_r: f32,
+    max_g: f32,
+    max_b: f32,
+}
+
+#[derive(Serialize)]
+struct PackedVertex {
+    packed_position: u32,
+    packed_rotation: u32,
+    packed_scale: u32,
+    packed_color: u32,
+}
+
+/// Quantized higher order SH coefficients of a splat.
+struct QuantShRow(Vec<u8>);
+
+impl Serialize for QuantShRow {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (name, val) in SH_REST_NAMES.iter().zip(&self.0) {
+            map.serialize_entry(name, val)?;
+        }
+        map.end()
+    }
+}
+
+#[derive(Serialize)]
+struct CompressedPly {
+    chunk: Vec<ChunkMeta>,
+    vertex: Vec<PackedVertex>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    sh: Vec<QuantShRow>,
+}
+
+fn bounds(values: impl Iterator<Item = Vec3>) -> (Vec3, Vec3) {
+    values.fold((Vec3::MAX, Vec3::MIN), |(min, max), v| {
+        (min.min(v), max.max(v))
+    })
+}
+
+/// Normalize a value to [0, 1] within a range. Values in an empty range map to 0.
+fn normalize_in(value: Vec3, min: Vec3, max: Vec3) -> Vec3 {
+    let range = max - min;
+    Vec3::select(range.cmpgt(Vec3::ZERO), (value - min) / range, Vec3::ZERO)
+}
+
+/// Spread the lower 10 bits of a value out to every third bit.
+fn spread_bits(value: u32) -> u32 {
+    let mut x = value & 0x3FF;
+    x = (x | (x << 16)) & 0x030000FF;
+    x = (x | (x << 8)) & 0x0300F00F;
+    x = (x | (x << 4)) & 0x030C30C3;
+    (x | (x << 2)) & 0x09249249
+}
+
+/// Order the splats along a Morton curve, so that splats in the same chunk are close together and
+/// the chunk ranges are small.
+fn morton_order(positions: &[Vec3]) -> Vec<usize> {
+    let (min, max) = bounds(positions.iter().copied());
+    let keys: Vec<u32> = positions
+        .iter()
+        .map(|&pos| {
+            let cell = (normalize_in(pos, min, max) * 1023.0).as_uvec3();
+            spread_bits(cell.x) | (spread_bits(cell.y) << 1) | (spread_bits(cell.z) << 2)
+        })
+        .collect();
+    let mut order: Vec<usize> = (0..positions.len()).collect();
+    order.sort_by_key(|&i| keys[i]);
+    order
+}
+
+/// Serialize splats to the chunked & quantized compressed ply format of `SuperSplat`.
+///
+/// Splats are stored in chunks of 256, which share a quantization range for their positions,
+/// scales and colors. Rotations use the smallest three encoding, opacities and higher order SH
+/// use 8 bits. This is roughly 4x smaller than a full ply. The Mip-Splatting 3D filter can't be
+/// stored, and is baked into the scales and opacities.
+pub async fn splat_to_compressed_ply<B: Backend>(
+    splats: Splats<B>,
+) -> Result<Vec<u8>, SerializeError> {
+    let splats = splats.with_normed_rotations().with_baked_filter_3d();
+    let sh_degree = splats.sh_degree();
+    let rows = read_splat_data(splats).await.vertex;
+
+    let positions: Vec<Vec3> = rows.iter().map(|r| Vec3::new(r.x, r.y, r.z)).collect();
+    let order = morton_order(&positions);
+
+    let mut chunk = Vec::with_capacity(rows.len().div_ceil(COMPRESSED_CHUNK_SIZE));
+    let mut vertex = Vec::with_capacity(rows.len());
+    let mut sh = Vec::with_capacity(if sh_degree > 0 { rows.len() } else { 0 });
+
+    for indices in order.chunks(COMPRESSED_CHUNK_SIZE) {
+        let chunk_rows: Vec<&DynamicPlyGaussian> = indices.iter().map(|&i| &rows[i]).collect();
+        // Like SuperSplat, limit the scales to a sane range so outliers don't ruin the precision.
+        let scale = |r: &DynamicPlyGaussian| {
+            Vec3::new(r.scale_0, r.scale_1, r.scale_2).clamp(Vec3::splat(-20.0), Vec3::splat(20.0))
+        };
+        let color = |r: &DynamicPlyGaussian| sh_to_rgb(Vec3::new(r.f_dc_0, r.f_dc_1, r.f_dc_2));
+
+        let (min_pos, max_pos) = bounds(indices.iter().map(|&i| positions[i]));
+        let (min_scale, max_scale) = bounds(chunk_rows.iter().map(|r| scale(r)));
+        let (min_color, max_color) = bounds(chunk_rows.iter().map(|r| color(r)));
+
+        chunk.push(ChunkMeta {
+            min_x: min_pos.x,
+            min_y: min_pos.y,
+            min_z: min_pos.z,
+            max_x: max_pos.x,
+            max_y: max_pos.y,
+            max_z: max_pos.z,
+            min_scale_x: min_scale.x,
+            min_scale_y: min_scale.y,
+            min_scale_z: min_scale.z,
+            max_scale_x: max_scale.x,
+            max_scale_y: max_scale.y,
+            max_scale_z: max_scale.z,
+            min_r: min_color.x,
+            min_g: min_color.y,
+            min_b: min_color.z,
+            max_r: max_color.x,
+            max_g: max_color.y,
+            max_b: max_color.z,
+        });
+
+        for (&i, row) in indices.iter().zip(chunk_rows) {
+            let rotation = Quat::from_xyzw(row.rot_1, row.rot_2, row.rot_3, row.rot_0);
+            // Keep the opacity away from 0 and 1, where it can't be converted back to a raw
+            // opacity.
+            let opacity = (1.0 / (1.0 + (-row.opacity).exp())).clamp(1.0 / 255.0, 254.0 / 255.0);
+            let rgb = normalize_in(color(row), min_color, max_color);
+
+            vertex.push(PackedVertex {
+                packed_position: encode_vec_11_10_11(normalize_in(positions[i], min_pos, max_pos)),
+                packed_rotation: encode_quat(rotation),
+                packed_scale: encode_vec_11_10_11(normalize_in(scale(row), min_scale, max_scale)),
+                packed_color: encode_vec_8_8_8_8(rgb.extend(opacity)),
+            });
+
+            if sh_degree > 0 {
+                sh.push(QuantShRow(
+                    row.rest_coeffs.iter().map(|&c| encode_sh(c)).collect(),
+                ));
+            }
+        }
+    }
+
+    let comments = vec![
+        "Exported from Brush".to_owned(),
+        "Vertical axis: y".to_owned(),
+        format!("SH degree: {sh_degree}"),
+    ];
+    let ply = CompressedPly { chunk, vertex, sh };
+    serde_ply::to_bytes(&ply, SerializeOptions::binary_le().with_comments(comments))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::import::load_splat_from_ply;
-    use crate::test_utils::create_test_splats;
+    use crate::test_utils::{create_test_splats, create_test_splats_with_count};
     use brush_render::MainBackend;
     use burn::backend::wgpu::WgpuDevice;
+    use burn::tensor::Tensor;
     use std::io::Cursor;
 
     async fn assert_coeffs_match(orig: &Splats<MainBackend>, imported: &Splats<MainBackend>) {
@@ -338,4 +543,73 @@ mod tests {
             assert_coeffs_match(&original_splats, &imported_splats).await;
         }
     }
+
+    #[tokio::test]
+    async fn test_roundtrip_filter_3d() {
+        let device = WgpuDevice::default();
+
+        let splats = create_test_splats_with_count(0, 2);
+        let ply_bytes = splat_to_ply(splats.clone()).await.unwrap();
+        assert!(!String::from_utf8_lossy(&ply_bytes).contains("filter_3D"));
+
+        let filter = Tensor::<MainBackend, 1>::from_floats([0.01, 0.02], &device);
+        let ply_bytes = splat_to_ply(splats.with_filter_3d(Some(filter)))
+            .await
+            .unwrap();
+        let imported = load_splat_from_ply(Cursor::new(ply_bytes), None, device)
+            .await
+            .expect("Failed to deserialize splats")
+            .splats;
+        let filter: Vec<f32> = imported
+            .filter_3d
+            .expect("Filter should be imported")
+            .into_data_async()
+            .await
+            .into_vec()
+            .unwrap();
+        assert_eq!(filter, vec![0.01, 0.02]);
+    }
+
+    async fn max_diff<const D: usize>(a: Tensor<MainBackend, D>, b: Tensor<MainBackend, D>) -> f32 {
+        (a - b)
+            .abs()
+            .max()
+            .into_data_async()
+            .await
+            .into_vec::<f32>()
+            .unwrap()[0]
+    }
+
+    #[tokio::test]
+    async fn test_roundtrip_compressed() {
+        let device = WgpuDevice::default();
+
+        for degree in [0, 1] {
+            // Enough splats for more than one chunk.
+            let splats = create_test_splats_with_count(degree, 300);
+            let compressed = splat_to_compressed_ply(splats.clone()).await.unwrap();
+            let full = splat_to_ply(splats.clone()).await.unwrap();
+            assert!(compressed.len() * 2 < full.len());
+
+            let imported = load_splat_from_ply(Cursor::new(compressed), None, device.clone())
+                .await
+                .expect("Failed to deserialize compressed splats")
+                .splats;
+            assert_eq!(imported.num_splats(), 300);
+            assert_eq!(imported.sh_degree(), degree);
+
+            // The splats are along a line, so the Morton order keeps them in the same order.
+            // Tolerances are half a quantization step of the range of a chunk.
+            let diff = max_diff(splats.means.val(), imported.means.val()).await;
+            assert!(diff < 0.13, "Means differ by {diff}");
+            let diff = max_diff(splats.log_scales.val(), imported.log_scales.val()).await;
+            assert!(diff < 0.01, "Scales differ by {diff}");
+            let diff = max_diff(splats.rotations_normed(), imported.rotations_normed()).await;
+            assert!(diff < 2e-3, "Rotations differ by {diff}");
+            let diff = max_diff(splats.opacities(), imported.opacities()).await;
+            assert!(diff < 1.0 / 255.0 + 1e-3, "Opacities differ by {diff}");
+            let diff = max_diff(splats.sh_coeffs.val(), imported.sh_coeffs.val()).await;
+            assert!(diff < 0.06, "SH coefficients differ by {diff}");
+        }
+    }
 }
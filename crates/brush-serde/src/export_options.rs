@@ -0,0 +1,324 @@
+use std::f32::consts::PI;
+use std::str::FromStr;
+
+use brush_render::bounding_box::BoundingBox;
+use brush_render::gaussian_splats::Splats;
+use burn::prelude::Backend;
+use burn::tensor::Tensor;
+use glam::{Quat, Vec3};
+use serde::ser::Error;
+use serde::{Deserialize, Serialize};
+use serde_ply::SerializeError;
+
+use crate::{
+    splat_to_compressed_ply, splat_to_glb, splat_to_ply, splat_to_splat_file, splat_to_spz,
+};
+
+/// Up axis conventions of other tools, to export splats to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpAxis {
+    Y,
+    Z,
+}
+
+impl UpAxis {
+    pub const ALL: [Self; 2] = [Self::Y, Self::Z];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Y => "Y-up",
+            Self::Z => "Z-up",
+        }
+    }
+
+    pub fn direction(self) -> Vec3 {
+        match self {
+            Self::Y => Vec3::Y,
+            Self::Z => Vec3::Z,
+        }
+    }
+}
+
+impl FromStr for UpAxis {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "y" => Ok(Self::Y),
+            "z" => Ok(Self::Z),
+            _ => Err(format!("Unknown up axis '{s}', expected y or z")),
+        }
+    }
+}
+
+/// Changes to make to splats when exporting them.
+///
+/// The default options export the splats as they are.
+#[derive(Clone, Copy)]
+pub struct ExportOptions {
+    /// Only keep SH up to this degree.
+    pub max_sh_degree: Option<u32>,
+    /// Drop splats that are less opaque than this.
+    pub min_opacity: Option<f32>,
+    /// Drop splats with a center outside this box. The box is in the coordinates of the splats,
+    /// before they are transformed.
+    pub bounds: Option<BoundingBox>,
+    /// Uniform scale applied to the splats, before rotating and translating them.
+    pub scale: f32,
+    pub rotation: Quat,
+    pub translation: Vec3,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            max_sh_degree: None,
+            min_opacity: None,
+            bounds: None,
+            scale: 1.0,
+            rotation: Quat::IDENTITY,
+            translation: Vec3::ZERO,
+        }
+    }
+}
+
+impl ExportOptions {
+    /// Rotate the splats so `up_axis`, their current up direction, points along `target`.
+    ///
+    /// Between opposite directions this rotates around the X axis, which turns the Y-down
+    /// convention of most plys into Y-up.
+    pub fn with_up_axis(mut self, up_axis: Vec3, target: UpAxis) -> Self {
+        let up_axis = up_axis.normalize();
+        let target = target.direction();
+        self.rotation = if up_axis.dot(target) < -0.9999 {
+            Quat::from_rotation_x(PI)
+        } else {
+            Quat::from_rotation_arc(up_axis, target)
+        };
+        self
+    }
+
+    /// Rotate the splats by `rotation`, after any rotation these options already apply.
+    pub fn rotated(mut self, rotation: Quat) -> Self {
+        self.rotation = rotation * self.rotation;
+        self
+    }
+
+    fn is_transformed(&self) -> bool {
+        self.scale != 1.0 || self.rotation != Quat::IDENTITY || self.translation != Vec3::ZERO
+    }
+
+    /// Apply these options to splats: truncate the SH, drop the filtered splats, and transform
+    /// the rest. Returns `None` when no splats are left.
+    pub async fn apply<B: Backend>(&self, splats: Splats<B>) -> Option<Splats<B>> {
+        let mut splats = splats;
+        if let Some(max_degree) = self.max_sh_degree
+            && max_degree < splats.sh_degree()
+        {
+            splats = splats.with_sh_degree(max_degree);
+        }
+
+        let mut removed = None;
+        if let Some(min_opacity) = self.min_opacity {
+            removed = Some(splats.opacities().lower_elem(min_opacity));
+        }
+        if let Some(bounds) = self.bounds {
+            let device = splats.device();
+            let center = Tensor::<B, 1>::from_floats(bounds.center.to_array(), &device);
+            let extent = Tensor::<B, 1>::from_floats(bounds.extent.to_array(), &device);
+            let outside = ((splats.means.val() - center.reshape([1, 3])).abs()
+                - extent.reshape([1, 3]))
+            .greater_elem(0.0)
+            .any_dim(1)
+            .squeeze(1);
+            removed = Some(match removed {
+                Some(removed) => removed.bool_or(outside),
+                None => outside,
+            });
+        }
+        if let Some(removed) = removed {
+            let keep = removed.bool_not().argwhere_async().await;
+            let kept = keep.dims()[0];
+            if kept == 0 {
+                return None;
+            }
+            if kept < splats.num_splats() as usize {
+                splats = splats.keep(keep.squeeze(1));
+            }
+        }
+        if splats.num_splats() == 0 {
+            return None;
+        }
+
+        if self.is_transformed() {
+            splats = splats.transformed(self.scale, self.rotation, self.translation);
+        }
+        Some(splats)
+    }
+}
+
+/// Serialize splats to the format picked from a file name, after applying `options`.
+///
+/// Names ending in .compressed.ply, .splat, .spz and .glb are written in those formats, other
+/// names as plys. `up_axis` is the up direction of the splats before the options are applied,
+/// glb files keep track of it. Fails when there are no splats to export.
+pub async fn splat_to_file_data<B: Backend>(
+    splats: Splats<B>,
+    file_name: &str,
+    options: &ExportOptions,
+    up_axis: Option<Vec3>,
+) -> Result<Vec<u8>, SerializeError> {
+    let splats = options.apply(splats).await.ok_or_else(|| {
+        SerializeError::custom("No splats to export, all splats were filtered out")
+    })?;
+    let up_axis = up_axis.map(|up| options.rotation * up);
+    let name = file_name.to_lowercase();
+
+    if name.ends_with(".compressed.ply") {
+        splat_to_compressed_ply(splats).await
+    } else if name.ends_with(".splat") {
+        Ok(splat_to_splat_file(splats).await)
+    } else if name.ends_with(".spz") {
+        Ok(splat_to_spz(splats).await)
+    } else if name.ends_with(".glb") {
+        Ok(splat_to_glb(splats, up_axis).await)
+    } else {
+        splat_to_ply(splats).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_test_splats_with_count;
+    use brush_render::MainBackend;
+
+    async fn means(splats: &Splats<MainBackend>) -> Vec<Vec3> {
+        let data: Vec<f32> = splats
+            .means
+            .val()
+            .into_data_async()
+            .await
+            .into_vec()
+            .expect("Unreachable");
+        data.chunks(3).map(Vec3::from_slice).collect()
+    }
+
+    #[tokio::test]
+    async fn test_default_keeps_splats() {
+        let original = create_test_splats_with_count(2, 10);
+        let exported = ExportOptions::default()
+            .apply(original.clone())
+            .await
+            .expect("Splats were removed");
+        assert_eq!(exported.num_splats(), 10);
+        assert_eq!(exported.sh_degree(), 2);
+        assert_eq!(means(&exported).await, means(&original).await);
+    }
+
+    #[tokio::test]
+    async fn test_truncate_and_filter() {
+        let splats = create_test_splats_with_count(3, 10);
+        let options = ExportOptions {
+            max_sh_degree: Some(1),
+            min_opacity: Some(0.6),
+            ..Default::default()
+        };
+        let exported = options
+            .apply(splats.clone())
+            .await
+            .expect("All splats were removed");
+        assert_eq!(exported.sh_degree(), 1);
+        // The test splats get less opaque with each splat.
+        assert_eq!(exported.num_splats(), 4);
+
+        let options = ExportOptions {
+            min_opacity: Some(0.6),
+            bounds: Some(BoundingBox::from_min_max(
+                Vec3::new(1.0, 2.0, 3.0),
+                Vec3::new(3.0, 4.0, 5.0),
+            )),
+            ..Default::default()
+        };
+        let exported = options
+            .apply(splats.clone())
+            .await
+            .expect("All splats were removed");
+        assert_eq!(
+            means(&exported).await,
+            vec![
+                Vec3::new(1.0, 2.0, 3.0),
+                Vec3::new(2.0, 3.0, 4.0),
+                Vec3::new(3.0, 4.0, 5.0)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_up_axis() {
+        let original = create_test_splats_with_count(1, 4);
+        let original_means = means(&original).await;
+
+        for target in UpAxis::ALL {
+            let options = ExportOptions::default().with_up_axis(Vec3::NEG_Y, target);
+            assert!((options.rotation * Vec3::NEG_Y).distance(target.direction()) < 1e-5);
+
+            let exported = options
+                .apply(original.clone())
+                .await
+                .expect("Splats were removed");
+            for (exported, original) in means(&exported).await.iter().zip(&original_means) {
+                assert!(exported.distance(options.rotation * *original) < 1e-5);
+            }
+        }
+
+        // Flipping Y-down to Y-up keeps the X axis.
+        let options = ExportOptions::default().with_up_axis(Vec3::NEG_Y, UpAxis::Y);
+        assert!((options.rotation * Vec3::X).distance(Vec3::X) < 1e-5);
+    }
+
+    #[tokio::test]
+    async fn test_similarity_transform() {
+        let original = create_test_splats_with_count(1, 4);
+        let rotation = Quat::from_rotation_z(0.3);
+        let options = ExportOptions {
+            scale: 2.0,
+            translation: Vec3::new(1.0, -2.0, 0.5),
+            ..Default::default()
+        }
+        .with_up_axis(Vec3::NEG_Y, UpAxis::Z)
+        .rotated(rotation);
+        let exported = options
+            .apply(original.clone())
+            .await
+            .expect("Splats were removed");
+
+        let full_rotation = rotation
+            * ExportOptions::default()
+                .with_up_axis(Vec3::NEG_Y, UpAxis::Z)
+                .rotation;
+        for (exported, original) in means(&exported).await.iter().zip(&means(&original).await) {
+            let expected = full_rotation * (*original * 2.0) + options.translation;
+            assert!(exported.distance(expected) < 1e-4);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_nothing_left_to_export() {
+        let splats = create_test_splats_with_count(0, 4);
+        let options = ExportOptions {
+            bounds: Some(BoundingBox::from_min_max(
+                Vec3::splat(-2.0),
+                Vec3::splat(-1.0),
+            )),
+            ..Default::default()
+        };
+        assert!(options.apply(splats.clone()).await.is_none());
+        assert!(
+            splat_to_file_data(splats, "export.glb", &options, None)
+                .await
+                .is_err()
+        );
+    }
+}
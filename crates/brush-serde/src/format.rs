@@ -0,0 +1,96 @@
+use std::path::Path;
+use std::pin::pin;
+
+use async_fn_stream::try_fn_stream;
+use brush_vfs::SendNotWasm;
+use burn::backend::wgpu::WgpuDevice;
+use serde::de::Error;
+use serde_ply::DeserializeError;
+use tokio::io::AsyncRead;
+use tokio_stream::{Stream, StreamExt};
+
+use crate::gltf::stream_splat_from_glb;
+use crate::import::{SplatMessage, StreamEmitter, stream_splat_from_ply};
+use crate::splat_file::stream_splat_from_splat_file;
+use crate::spz::stream_splat_from_spz;
+
+/// File formats splats can be loaded from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SplatFormat {
+    /// A ply file, including compressed ply files.
+    Ply,
+    /// A headerless `.splat` file.
+    Splat,
+    /// A gzipped SPZ file.
+    Spz,
+    /// A binary glTF file with the `KHR_gaussian_splatting` extension.
+    Glb,
+}
+
+impl SplatFormat {
+    /// Pick the format from the extension of a path, if it's a splat file at all.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "ply" => Some(Self::Ply),
+            "splat" => Some(Self::Splat),
+            "spz" => Some(Self::Spz),
+            "glb" => Some(Self::Glb),
+            _ => None,
+        }
+    }
+}
+
+async fn forward(
+    emitter: &StreamEmitter,
+    stream: impl Stream<Item = Result<SplatMessage, DeserializeError>>,
+) -> Result<(), DeserializeError> {
+    let mut stream = pin!(stream);
+    while let Some(message) = stream.next().await {
+        emitter.emit(message?).await;
+    }
+    Ok(())
+}
+
+pub fn stream_splat<T: AsyncRead + SendNotWasm + Unpin>(
+    reader: T,
+    format: SplatFormat,
+    subsample_points: Option<u32>,
+    device: WgpuDevice,
+    streaming: bool,
+) -> impl Stream<Item = Result<SplatMessage, DeserializeError>> {
+    try_fn_stream(move |emitter| async move {
+        match format {
+            SplatFormat::Ply => {
+                let stream = stream_splat_from_ply(reader, subsample_points, device, streaming);
+                forward(&emitter, stream).await
+            }
+            SplatFormat::Splat => {
+                let stream =
+                    stream_splat_from_splat_file(reader, subsample_points, device, streaming);
+                forward(&emitter, stream).await
+            }
+            SplatFormat::Spz => {
+                let stream = stream_splat_from_spz(reader, subsample_points, device);
+                forward(&emitter, stream).await
+            }
+            SplatFormat::Glb => {
+                let stream = stream_splat_from_glb(reader, subsample_points, device);
+                forward(&emitter, stream).await
+            }
+        }
+    })
+}
+
+pub async fn load_splat<T: AsyncRead + SendNotWasm + Unpin>(
+    reader: T,
+    format: SplatFormat,
+    subsample_points: Option<u32>,
+    device: WgpuDevice,
+) -> Result<SplatMessage, DeserializeError> {
+    let stream = stream_splat(reader, format, subsample_points, device, false);
+    let Some(splat) = pin!(stream).next().await else {
+        return Err(DeserializeError::custom("Couldn't load single splat"));
+    };
+    splat
+}
@@ -0,0 +1,509 @@
+//! Binary glTF (`.glb`) files with the `KHR_gaussian_splatting` extension.
+//!
+//! The splats are stored as a single point primitive, with the position, rotation, scale, opacity
+//! and SH coefficients as float attributes. Rotations are xyzw quaternions, scales are linear and
+//! opacities are between 0 and 1. A `COLOR_0` attribute is also written, so viewers without
+//! support for the extension can at least show colored points.
+//!
+//! glTF assets are Y-up. Instead of rotating the splats themselves, the node holding them is
+//! rotated so the up axis of the scene lines up with Y.
+
+use std::collections::HashMap;
+use std::pin::pin;
+
+use async_fn_stream::try_fn_stream;
+use brush_render::MainBackend;
+use brush_render::gaussian_splats::{Splats, inverse_sigmoid};
+use brush_render::sh::{sh_coeffs_for_degree, sh_to_rgb};
+use brush_vfs::SendNotWasm;
+use burn::backend::wgpu::WgpuDevice;
+use burn::prelude::Backend;
+use burn::tensor::Tensor;
+use glam::{Quat, Vec3};
+use serde::Deserialize;
+use serde::de::Error;
+use serde_json::json;
+use serde_ply::DeserializeError;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_stream::{Stream, StreamExt};
+
+use crate::import::{ParseMetadata, SplatMessage};
+
+const GLB_MAGIC: u32 = 0x4654_6c67; // glTF
+const GLB_VERSION: u32 = 2;
+const CHUNK_JSON: u32 = 0x4e4f_534a;
+const CHUNK_BIN: u32 = 0x004e_4942;
+const COMPONENT_FLOAT: u32 = 5126;
+const MODE_POINTS: u32 = 0;
+const EXTENSION: &str = "KHR_gaussian_splatting";
+// Brush supports up to 4 degrees of SH, glTF doesn't limit it.
+const MAX_SH_DEGREE: u32 = 4;
+
+fn attribute(name: &str) -> String {
+    format!("{EXTENSION}:{name}")
+}
+
+fn sh_attribute(degree: u32, coeff: u32) -> String {
+    attribute(&format!("SH_DEGREE_{degree}_COEF_{coeff}"))
+}
+
+fn components(kind: &str) -> usize {
+    match kind {
+        "SCALAR" => 1,
+        "VEC2" => 2,
+        "VEC3" => 3,
+        "VEC4" => 4,
+        _ => 0,
+    }
+}
+
+async fn tensor_to_vec<B: Backend, const D: usize>(tensor: Tensor<B, D>) -> Vec<f32> {
+    tensor
+        .into_data_async()
+        .await
+        .into_vec()
+        .expect("Unreachable")
+}
+
+/// Serialize splats to a binary glTF file.
+///
+/// `up_axis` is the up direction of the splats, like in [`ParseMetadata::up_axis`]. Without one,
+/// the splats are assumed to be Y-down like most plys. The Mip-Splatting 3D filter is baked into
+/// the scales and opacities.
+pub async fn splat_to_glb<B: Backend>(splats: Splats<B>, up_axis: Option<Vec3>) -> Vec<u8> {
+    let splats = splats.with_normed_rotations().with_baked_filter_3d();
+    let num_splats = splats.num_splats() as usize;
+    let sh_degree = splats.sh_degree();
+    let num_coeffs = sh_coeffs_for_degree(sh_degree) as usize;
+
+    let means = tensor_to_vec(splats.means.val()).await;
+    let rotations = tensor_to_vec(splats.rotation.val()).await;
+    let scales = tensor_to_vec(splats.scales()).await;
+    let opacities = tensor_to_vec(splats.opacities()).await;
+    let sh_coeffs = tensor_to_vec(splats.sh_coeffs.val()).await;
+
+    // Nb: Brush stores quaternions scalar first, glTF scalar last.
+    let rotations = rotations
+        .chunks_exact(4)
+        .flat_map(|q| [q[1], q[2], q[3], q[0]])
+        .collect();
+    let colors = (0..num_splats)
+        .flat_map(|i| {
+            let dc = Vec3::from_slice(&sh_coeffs[i * num_coeffs * 3..]);
+            let rgb = sh_to_rgb(dc).clamp(Vec3::ZERO, Vec3::ONE);
+            [rgb.x, rgb.y, rgb.z, opacities[i]]
+        })
+        .collect();
+    let mut attributes = vec![
+        ("POSITION".to_owned(), "VEC3", means),
+        (attribute("ROTATION"), "VEC4", rotations),
+        (attribute("SCALE"), "VEC3", scales),
+        (attribute("OPACITY"), "SCALAR", opacities),
+    ];
+    for degree in 0..=sh_degree {
+        for coeff in 0..2 * degree + 1 {
+            let index = (degree * degree + coeff) as usize;
+            let values = (0..num_splats)
+                .flat_map(|i| {
+                    let start = (i * num_coeffs + index) * 3;
+                    [sh_coeffs[start], sh_coeffs[start + 1], sh_coeffs[start + 2]]
+                })
+                .collect();
+            attributes.push((sh_attribute(degree, coeff), "VEC3", values));
+        }
+    }
+    attributes.push(("COLOR_0".to_owned(), "VEC4", colors));
+
+    let mut bin = vec![];
+    let mut buffer_views = vec![];
+    let mut accessors = vec![];
+    let mut primitive_attributes = serde_json::Map::new();
+    for (index, (name, kind, values)) in attributes.iter().enumerate() {
+        buffer_views.push(json!({
+            "buffer": 0,
+            "byteOffset": bin.len(),
+            "byteLength": values.len() * 4,
+        }));
+        let mut accessor = json!({
+            "bufferView": index,
+            "componentType": COMPONENT_FLOAT,
+            "count": num_splats,
+            "type": kind,
+        });
+        // glTF requires the bounds of positions.
+        if name == "POSITION" {
+            let bound = |init: f32, f: fn(f32, f32) -> f32| {
+                (0..3)
+                    .map(|c| values.iter().skip(c).step_by(3).copied().fold(init, f))
+                    .collect::<Vec<_>>()
+            };
+            accessor["min"] = json!(bound(f32::MAX, f32::min));
+            accessor["max"] = json!(bound(f32::MIN, f32::max));
+        }
+        accessors.push(accessor);
+        primitive_attributes.insert(name.clone(), json!(index));
+        bin.extend(values.iter().flat_map(|v| v.to_le_bytes()));
+    }
+
+    let up_axis = up_axis.unwrap_or(Vec3::NEG_Y).normalize();
+    let rotation = Quat::from_rotation_arc(up_axis, Vec3::Y);
+
+    let document = json!({
+        "asset": { "version": "2.0", "generator": "Brush" },
+        "extensionsUsed": [EXTENSION],
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "mesh": 0, "rotation": rotation.to_array() }],
+        "meshes": [{
+            "primitives": [{
+                "mode": MODE_POINTS,
+                "attributes": primitive_attributes,
+                "extensions": { EXTENSION: {} },
+            }],
+        }],
+        "buffers": [{ "byteLength": bin.len() }],
+        "bufferViews": buffer_views,
+        "accessors": accessors,
+    });
+    let mut json = serde_json::to_vec(&document).expect("Unreachable");
+
+    // Chunks need to be 4 byte aligned.
+    json.resize(json.len().next_multiple_of(4), b' ');
+    bin.resize(bin.len().next_multiple_of(4), 0);
+
+    let total_len = 12 + 8 + json.len() + 8 + bin.len();
+    let mut data = Vec::with_capacity(total_len);
+    data.extend(GLB_MAGIC.to_le_bytes());
+    data.extend(GLB_VERSION.to_le_bytes());
+    data.extend((total_len as u32).to_le_bytes());
+    for (chunk_type, chunk) in [(CHUNK_JSON, json), (CHUNK_BIN, bin)] {
+        data.extend((chunk.len() as u32).to_le_bytes());
+        data.extend(chunk_type.to_le_bytes());
+        data.extend(chunk);
+    }
+    data
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GltfDocument {
+    #[serde(default)]
+    nodes: Vec<GltfNode>,
+    #[serde(default)]
+    meshes: Vec<GltfMesh>,
+    #[serde(default)]
+    accessors: Vec<GltfAccessor>,
+    #[serde(default)]
+    buffer_views: Vec<GltfBufferView>,
+}
+
+#[derive(Deserialize)]
+struct GltfNode {
+    mesh: Option<usize>,
+    rotation: Option<[f32; 4]>,
+}
+
+#[derive(Deserialize)]
+struct GltfMesh {
+    primitives: Vec<GltfPrimitive>,
+}
+
+#[derive(Deserialize)]
+struct GltfPrimitive {
+    attributes: HashMap<String, usize>,
+    #[serde(default)]
+    extensions: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GltfAccessor {
+    buffer_view: Option<usize>,
+    #[serde(default)]
+    byte_offset: usize,
+    component_type: u32,
+    count: usize,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GltfBufferView {
+    buffer: usize,
+    #[serde(default)]
+    byte_offset: usize,
+    byte_length: usize,
+    byte_stride: Option<usize>,
+}
+
+/// Read the floats of an accessor in the binary chunk of a glb file.
+fn read_accessor(
+    document: &GltfDocument,
+    bin: &[u8],
+    index: usize,
+    expected_kind: &str,
+) -> Result<Vec<f32>, DeserializeError> {
+    let accessor = document
+        .accessors
+        .get(index)
+        .ok_or_else(|| DeserializeError::custom("Invalid glTF accessor index"))?;
+    if accessor.component_type != COMPONENT_FLOAT || accessor.kind != expected_kind {
+        return Err(DeserializeError::custom(format!(
+            "Only float {expected_kind} splat attributes are supported"
+        )));
+    }
+    let view = accessor
+        .buffer_view
+        .and_then(|view| document.buffer_views.get(view))
+        .filter(|view| view.buffer == 0)
+        .ok_or_else(|| DeserializeError::custom("Splat attributes must be in the glb buffer"))?;
+
+    let components = components(&accessor.kind);
+    let stride = view.byte_stride.unwrap_or(components * 4);
+    let view_data = bin
+        .get(view.byte_offset..view.byte_offset + view.byte_length)
+        .ok_or_else(|| DeserializeError::custom("glTF buffer view is out of bounds"))?;
+
+    let mut values = Vec::with_capacity(accessor.count * components);
+    for i in 0..accessor.count {
+        let start = accessor.byte_offset + i * stride;
+        let element = view_data
+            .get(start..start + components * 4)
+            .ok_or_else(|| DeserializeError::custom("glTF accessor is out of bounds"))?;
+        values.extend(
+            element
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes(b.try_into().expect("Unreachable"))),
+        );
+    }
+    Ok(values)
+}
+
+fn parse_glb(
+    data: &[u8],
+    subsample_points: Option<u32>,
+    device: &WgpuDevice,
+) -> Result<SplatMessage, DeserializeError> {
+    let read_u32 = |i: usize| {
+        data.get(i..i + 4)
+            .map(|b| u32::from_le_bytes(b.try_into().expect("Unreachable")))
+            .ok_or_else(|| DeserializeError::custom("glb file is truncated"))
+    };
+    if read_u32(0)? != GLB_MAGIC || read_u32(4)? != GLB_VERSION {
+        return Err(DeserializeError::custom("Not a glTF 2.0 binary file"));
+    }
+
+    let mut json = None;
+    let mut bin: &[u8] = &[];
+    let mut offset = 12;
+    while offset < data.len() {
+        let len = read_u32(offset)? as usize;
+        let chunk_type = read_u32(offset + 4)?;
+        let chunk = data
+            .get(offset + 8..offset + 8 + len)
+            .ok_or_else(|| DeserializeError::custom("glb file is truncated"))?;
+        match chunk_type {
+            CHUNK_JSON => json = Some(chunk),
+            CHUNK_BIN => bin = chunk,
+            _ => {}
+        }
+        offset += 8 + len;
+    }
+    let json = json.ok_or_else(|| DeserializeError::custom("glb file has no JSON chunk"))?;
+    let document: GltfDocument = serde_json::from_slice(json).map_err(DeserializeError::custom)?;
+
+    let (mesh_index, primitive) = document
+        .meshes
+        .iter()
+        .enumerate()
+        .flat_map(|(i, mesh)| mesh.primitives.iter().map(move |p| (i, p)))
+        .find(|(_, p)| p.extensions.contains_key(EXTENSION))
+        .ok_or_else(|| DeserializeError::custom("glTF file has no gaussian splats"))?;
+
+    let read = |name: &str, kind: &str| -> Result<Vec<f32>, DeserializeError> {
+        let index = primitive.attributes.get(name).ok_or_else(|| {
+            DeserializeError::custom(format!("Splats are missing the {name} attribute"))
+        })?;
+        read_accessor(&document, bin, *index, kind)
+    };
+
+    let means = read("POSITION", "VEC3")?;
+    let rotations = read(&attribute("ROTATION"), "VEC4")?;
+    let scales = read(&attribute("SCALE"), "VEC3")?;
+    let opacities = read(&attribute("OPACITY"), "SCALAR")?;
+
+    // Use the highest degree of SH that has all coefficients.
+    let sh_degree = (1..=MAX_SH_DEGREE)
+        .take_while(|&degree| {
+            (0..2 * degree + 1).all(|coeff| {
+                primitive
+                    .attributes
+                    .contains_key(&sh_attribute(degree, coeff))
+            })
+        })
+        .last()
+        .unwrap_or(0);
+    let sh_bands = (0..=sh_degree)
+        .flat_map(|degree| (0..2 * degree + 1).map(move |coeff| (degree, coeff)))
+        .map(|(degree, coeff)| read(&sh_attribute(degree, coeff), "VEC3"))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let num_splats = means.len() / 3;
+    let valid = rotations.len() == num_splats * 4
+        && scales.len() == num_splats * 3
+        && opacities.len() == num_splats
+        && sh_bands.iter().all(|band| band.len() == num_splats * 3);
+    if !valid {
+        return Err(DeserializeError::custom(
+            "Splat attributes have different counts",
+        ));
+    }
+    if num_splats == 0 {
+        return Err(DeserializeError::custom("glTF file has no splats"));
+    }
+
+    let subsample = subsample_points.unwrap_or(1) as usize;
+    let rows: Vec<usize> = (0..num_splats).step_by(subsample).collect();
+    let splats = Splats::from_raw(
+        rows.iter()
+            .flat_map(|&i| &means[i * 3..i * 3 + 3])
+            .copied()
+            .collect(),
+        // Nb: Scalar first, like Brush.
+        Some(
+            rotations
+                .chunks_exact(4)
+                .step_by(subsample)
+                .flat_map(|q| [q[3], q[0], q[1], q[2]])
+                .collect(),
+        ),
+        Some(
+            rows.iter()
+                .flat_map(|&i| &scales[i * 3..i * 3 + 3])
+                .map(|s| s.max(1e-12).ln())
+                .collect(),
+        ),
+        Some(
+            rows.iter()
+                .flat_map(|&i| {
+                    sh_bands
+                        .iter()
+                        .flat_map(move |band| &band[i * 3..i * 3 + 3])
+                })
+                .copied()
+                .collect(),
+        ),
+        Some(
+            rows.iter()
+                .map(|&i| inverse_sigmoid(opacities[i].clamp(1e-4, 1.0 - 1e-4)))
+                .collect(),
+        ),
+        device,
+    );
+
+    let rotation = document
+        .nodes
+        .iter()
+        .find(|node| node.mesh == Some(mesh_index))
+        .and_then(|node| node.rotation)
+        .map_or(Quat::IDENTITY, |r| Quat::from_array(r).normalize());
+
+    Ok(SplatMessage {
+        meta: ParseMetadata {
+            up_axis: Some(rotation.inverse() * Vec3::Y),
+            total_splats: splats.num_splats(),
+            frame_count: 0,
+            current_frame: 0,
+            progress: 1.0,
+        },
+        splats,
+    })
+}
+
+/// Stream splats from a glb file.
+///
+/// The attributes of the splats are stored one after the other, so the splats are only emitted
+/// once the whole file has been read. The up axis is taken from the rotation of the node holding
+/// the splats.
+pub fn stream_splat_from_glb<T: AsyncRead + SendNotWasm + Unpin>(
+    mut reader: T,
+    subsample_points: Option<u32>,
+    device: WgpuDevice,
+) -> impl Stream<Item = Result<SplatMessage, DeserializeError>> {
+    try_fn_stream(|emitter| async move {
+        let mut data = vec![];
+        reader.read_to_end(&mut data).await?;
+        let message = parse_glb(&data, subsample_points, &device)?;
+        emitter.emit(message).await;
+        Ok(())
+    })
+}
+
+pub async fn load_splat_from_glb<T: AsyncRead + SendNotWasm + Unpin>(
+    reader: T,
+    subsample_points: Option<u32>,
+    device: WgpuDevice,
+) -> Result<SplatMessage, DeserializeError> {
+    let stream = stream_splat_from_glb(reader, subsample_points, device);
+    let Some(splat) = pin!(stream).next().await else {
+        return Err(DeserializeError::custom("Couldn't load glb file"));
+    };
+    splat
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_test_splats_with_count;
+    use std::io::Cursor;
+
+    async fn max_diff<const D: usize>(a: Tensor<MainBackend, D>, b: Tensor<MainBackend, D>) -> f32 {
+        tensor_to_vec((a - b).abs().max()).await[0]
+    }
+
+    #[tokio::test]
+    async fn test_roundtrip_glb() {
+        for (sh_degree, up_axis) in [(0, None), (1, Some(Vec3::Z)), (3, Some(Vec3::NEG_X))] {
+            let original = create_test_splats_with_count(sh_degree, 10).with_normed_rotations();
+            let data = splat_to_glb(original.clone(), up_axis).await;
+            assert_eq!(&data[..4], b"glTF");
+            assert_eq!(data.len() % 4, 0);
+
+            let message = load_splat_from_glb(Cursor::new(data), None, WgpuDevice::default())
+                .await
+                .expect("Failed to load glb");
+            let loaded = message.splats;
+            assert_eq!(loaded.num_splats(), 10);
+            assert_eq!(loaded.sh_degree(), sh_degree);
+
+            let up = message.meta.up_axis.expect("Missing up axis");
+            assert!(up.distance(up_axis.unwrap_or(Vec3::NEG_Y)) < 1e-5, "{up}");
+
+            assert!(max_diff(original.means.val(), loaded.means.val()).await < 1e-6);
+            assert!(max_diff(original.rotation.val(), loaded.rotation.val()).await < 1e-6);
+            assert!(max_diff(original.log_scales.val(), loaded.log_scales.val()).await < 1e-5);
+            assert!(max_diff(original.raw_opacity.val(), loaded.raw_opacity.val()).await < 1e-4);
+            assert!(max_diff(original.sh_coeffs.val(), loaded.sh_coeffs.val()).await < 1e-6);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_glb() {
+        let result =
+            load_splat_from_glb(Cursor::new(b"glTF".to_vec()), None, WgpuDevice::default()).await;
+        assert!(result.is_err());
+
+        // A valid glb without splats.
+        let json = br#"{"asset":{"version":"2.0"}}    "#;
+        let mut data = vec![];
+        data.extend(GLB_MAGIC.to_le_bytes());
+        data.extend(GLB_VERSION.to_le_bytes());
+        data.extend((20 + json.len() as u32).to_le_bytes());
+        data.extend((json.len() as u32).to_le_bytes());
+        data.extend(CHUNK_JSON.to_le_bytes());
+        data.extend(json);
+        let result = load_splat_from_glb(Cursor::new(data), None, WgpuDevice::default()).await;
+        assert!(result.is_err());
+    }
+}
@@ -18,7 +18,7 @@ use tokio_with_wasm::alias as tokio_wasm;
 
 use crate::ply_gaussian::{PlyGaussian, QuantSh, QuantSplat};
 
-type StreamEmitter = TryStreamEmitter<SplatMessage, DeserializeError>;
+pub(crate) type StreamEmitter = TryStreamEmitter<SplatMessage, DeserializeError>;
 
 pub struct ParseMetadata {
     pub up_axis: Option<Vec3>,
@@ -39,20 +39,20 @@ enum PlyFormat {
     SuperSplatCompressed,
 }
 
-struct TimedUpdate {
+pub(crate) struct TimedUpdate {
     last_update: web_time::Instant,
     update_every: Option<web_time::Duration>,
 }
 
 impl TimedUpdate {
-    fn new(update_every: Option<web_time::Duration>) -> Self {
+    pub(crate) fn new(update_every: Option<web_time::Duration>) -> Self {
         Self {
             last_update: web_time::Instant::now(),
             update_every,
         }
     }
 
-    fn should_update(&mut self) -> bool {
+    pub(crate) fn should_update(&mut self) -> bool {
         if let Some(duration) = self.update_every
             && self.last_update.elapsed() >= duration
         {
@@ -220,6 +220,9 @@ async fn parse_ply<T: AsyncRead + Unpin>(
     let mut opacity = vertex
         .has_property("opacity")
         .then(|| Vec::with_capacity(max_splats));
+    let mut filter_3d = vertex
+        .has_property("filter_3D")
+        .then(|| Vec::with_capacity(max_splats));
     let sh_count = vertex
         .properties
         .iter()
@@ -270,10 +273,14 @@ async fn parse_ply<T: AsyncRead + Unpin>(
             if let Some(opacity) = &mut opacity {
                 opacity.push(gauss.opacity);
             }
+            if let Some(filter_3d) = &mut filter_3d {
+                filter_3d.push(gauss.filter_3d);
+            }
         })
         .deserialize(&mut *file)?;
 
         if update.should_update() || row_index == total_splats {
+            let n_splats = means.len() / 3;
             let splats = Splats::from_raw(
                 means.clone(),
                 rotations.clone(),
@@ -281,7 +288,10 @@ async fn parse_ply<T: AsyncRead + Unpin>(
                 coeffs.clone(),
                 opacity.clone(),
                 &device,
-            );
+            )
+            .with_filter_3d(filter_3d.clone().map(|filter_3d| {
+                Tensor::from_data(TensorData::new(filter_3d, [n_splats]), &device)
+            }));
 
             emitter
                 .emit(SplatMessage {
@@ -462,7 +472,8 @@ async fn parse_delta_ply<T: AsyncRead + Unpin>(
                         log_scales,
                         splats.sh_coeffs.val(),
                         splats.raw_opacity.val(),
-                    ),
+                    )
+                    .with_filter_3d(splats.filter_3d.clone()),
                 })
                 .await;
             frame += 1;
@@ -588,8 +599,9 @@ async fn parse_compressed_ply<T: AsyncRead + Unpin>(
         })
         .deserialize(&mut file)?;
 
-        // Occasionally send some updated splats.
-        if update.should_update() || row_count == total_splats {
+        // Occasionally send some updated splats. When there are SH's, the final splat is sent
+        // once those are loaded.
+        if update.should_update() || (row_count == total_splats && sh_vals.is_none()) {
             // Leave 20% of progress for loading the SH's, just an estimate.
             let max_time = if sh_vals.is_some() { 0.8 } else { 1.0 };
             let progress = progress(row_count, total_splats) * max_time;
@@ -646,7 +658,7 @@ async fn parse_compressed_ply<T: AsyncRead + Unpin>(
         emitter
             .emit(SplatMessage {
                 meta: ParseMetadata {
-                    total_splats: means.len() as u32,
+                    total_splats: max_splats as u32,
                     up_axis,
                     frame_count: 0,
                     current_frame: 0,
@@ -1,14 +1,26 @@
 #![recursion_limit = "256"]
 
 pub mod export;
+pub mod export_options;
+pub mod format;
+pub mod gltf;
 pub mod import;
 pub mod ply_gaussian;
 pub mod quant;
+pub mod splat_file;
+pub mod spz;
 
 // Re-export main functionality
-pub use export::splat_to_ply;
+pub use export::{splat_to_compressed_ply, splat_to_ply};
+pub use export_options::{ExportOptions, UpAxis, splat_to_file_data};
+pub use format::{SplatFormat, load_splat, stream_splat};
+pub use gltf::{load_splat_from_glb, splat_to_glb, stream_splat_from_glb};
 pub use import::{ParseMetadata, SplatMessage, load_splat_from_ply, stream_splat_from_ply};
 pub use ply_gaussian::PlyGaussian;
+pub use splat_file::{
+    load_splat_from_splat_file, splat_to_splat_file, stream_splat_from_splat_file,
+};
+pub use spz::{load_splat_from_spz, splat_to_spz, stream_splat_from_spz};
 
 // Re-export serde-ply types for compatibility
 pub use serde_ply::{DeserializeError, SerializeError};
@@ -175,6 +175,10 @@ pub struct PlyGaussian {
     #[serde(default)]
     pub(crate) f_rest_44: f32,
 
+    // Mip-Splatting 3D filter.
+    #[serde(default, rename = "filter_3D")]
+    pub(crate) filter_3d: f32,
+
     // Color overrides. Potentially quantized.
     #[serde(default, alias = "r", skip_serializing, deserialize_with = "de_quant")]
     pub(crate) red: Option<f32>,
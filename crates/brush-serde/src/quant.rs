@@ -6,6 +6,12 @@ fn unpack_unorm(packed: u32, bits: u32) -> f32 {
     packed as f32 / max_value as f32
 }
 
+/// Packs a float in [0, 1] into an n-bit normalized integer, the inverse of `unpack_unorm`.
+fn pack_unorm(value: f32, bits: u32) -> u32 {
+    let max_value = (1 << bits) - 1;
+    (value.clamp(0.0, 1.0) * max_value as f32).round() as u32
+}
+
 pub(crate) fn decode_vec_11_10_11(value: u32) -> glam::Vec3 {
     let first = (value >> 21) & 0x7FF; // First 11 bits
     let second = (value >> 11) & 0x3FF; // Next 10 bits
@@ -17,6 +23,10 @@ pub(crate) fn decode_vec_11_10_11(value: u32) -> glam::Vec3 {
     )
 }
 
+pub(crate) fn encode_vec_11_10_11(value: glam::Vec3) -> u32 {
+    (pack_unorm(value.x, 11) << 21) | (pack_unorm(value.y, 10) << 11) | pack_unorm(value.z, 11)
+}
+
 pub(crate) fn decode_vec_8_8_8_8(value: u32) -> glam::Vec4 {
     // Create Vec4 from a u32, each component gets 8 bits
     // Extract each byte
@@ -34,6 +44,13 @@ pub(crate) fn decode_vec_8_8_8_8(value: u32) -> glam::Vec4 {
     )
 }
 
+pub(crate) fn encode_vec_8_8_8_8(value: glam::Vec4) -> u32 {
+    (pack_unorm(value.x, 8) << 24)
+        | (pack_unorm(value.y, 8) << 16)
+        | (pack_unorm(value.z, 8) << 8)
+        | pack_unorm(value.w, 8)
+}
+
 pub(crate) fn decode_quat(value: u32) -> glam::Quat {
     let largest = ((value >> 30) & 0x3) as usize; // First 2 bits
 
@@ -67,6 +84,38 @@ pub(crate) fn decode_quat(value: u32) -> glam::Quat {
     glam::Quat::from_xyzw(x, y, z, w)
 }
 
+/// Packs a rotation as its three smallest components, and the index of the largest one, which
+/// is recovered from the others as the quaternion is normalized.
+pub(crate) fn encode_quat(quat: glam::Quat) -> u32 {
+    let quat = quat.normalize();
+    let mut vals = [quat.w, quat.x, quat.y, quat.z];
+
+    let largest = (0..4)
+        .max_by(|&a, &b| vals[a].abs().total_cmp(&vals[b].abs()))
+        .unwrap_or(0);
+    // q and -q are the same rotation, flip it so the dropped component is positive.
+    if vals[largest] < 0.0 {
+        vals = vals.map(|v| -v);
+    }
+
+    let norm = 0.5 * f32::consts::SQRT_2;
+    let mut packed = largest as u32;
+    for (i, val) in vals.iter().enumerate() {
+        if i != largest {
+            packed = (packed << 10) | pack_unorm(val * norm + 0.5, 10);
+        }
+    }
+    packed
+}
+
+/// Quantizes a higher order SH coefficient to a byte, the inverse of the decoding of the SH of
+/// compressed plys.
+pub(crate) fn encode_sh(coeff: f32) -> u8 {
+    ((coeff / 8.0 + 0.5) * (u8::MAX - 1) as f32)
+        .round()
+        .clamp(0.0, u8::MAX as f32) as u8
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -94,6 +143,37 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_vector_round_trip() {
+        for i in 0..100 {
+            let t = i as f32 / 99.0;
+            let vec3 = glam::vec3(t, 1.0 - t, (t * 7.0).fract());
+            let decoded = decode_vec_11_10_11(encode_vec_11_10_11(vec3));
+            assert!((decoded - vec3).abs().max_element() < 0.5 / 1023.0);
+
+            let vec4 = glam::vec4(t, 1.0 - t, (t * 7.0).fract(), (t * 3.0).fract());
+            let decoded = decode_vec_8_8_8_8(encode_vec_8_8_8_8(vec4));
+            assert!((decoded - vec4).abs().max_element() < 0.5 / 255.0 + 1e-6);
+        }
+        // Out of range values are clamped.
+        assert_eq!(encode_vec_11_10_11(glam::Vec3::splat(2.0)), u32::MAX);
+        assert_eq!(encode_vec_8_8_8_8(glam::Vec4::splat(-1.0)), 0);
+    }
+
+    #[test]
+    fn test_quat_round_trip() {
+        for i in 0..100 {
+            let t = i as f32 * 0.37;
+            let quat = glam::Quat::from_euler(glam::EulerRot::YXZ, t, t * 1.3 - 2.0, t * 0.7 + 1.0);
+            let decoded = decode_quat(encode_quat(quat));
+            // Compare up to the sign, q and -q are the same rotation.
+            assert!(
+                decoded.dot(quat).abs() > 0.9999,
+                "Rotation mismatch: {quat} vs {decoded}"
+            );
+        }
+    }
+
     #[test]
     fn test_decode_quat() {
         let test_val = (512 << 20) | (512 << 10) | 512;
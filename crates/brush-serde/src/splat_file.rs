@@ -0,0 +1,231 @@
+//! The `.splat` format used by many web viewers.
+//!
+//! This has no header, just 32 bytes per splat: the position and scale as floats, the color and
+//! opacity as bytes, and the rotation quantized to a byte per component.
+
+use std::pin::pin;
+use std::time::Duration;
+
+use async_fn_stream::try_fn_stream;
+use brush_render::gaussian_splats::{Splats, inverse_sigmoid};
+use brush_render::sh::{rgb_to_sh, sh_to_rgb};
+use brush_vfs::SendNotWasm;
+use burn::backend::wgpu::WgpuDevice;
+use burn::prelude::Backend;
+use glam::Vec3;
+use serde::de::Error;
+use serde_ply::DeserializeError;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_stream::{Stream, StreamExt};
+use tokio_with_wasm::alias as tokio_wasm;
+
+use crate::export::read_splat_data;
+use crate::import::{ParseMetadata, SplatMessage, TimedUpdate};
+
+const SPLAT_SIZE: usize = 32;
+
+pub fn stream_splat_from_splat_file<T: AsyncRead + SendNotWasm + Unpin>(
+    mut reader: T,
+    subsample_points: Option<u32>,
+    device: WgpuDevice,
+    streaming: bool,
+) -> impl Stream<Item = Result<SplatMessage, DeserializeError>> {
+    try_fn_stream(|emitter| async move {
+        let subsample = subsample_points.unwrap_or(1) as usize;
+        let mut update = TimedUpdate::new(streaming.then(|| Duration::from_millis(1500)));
+
+        let mut means = vec![];
+        let mut log_scales = vec![];
+        let mut rotations = vec![];
+        let mut sh_coeffs = vec![];
+        let mut opacity = vec![];
+
+        let mut buf = vec![];
+        let mut row_index: usize = 0;
+
+        loop {
+            buf.reserve(8 * 1024 * 1024);
+            let done = reader.read_buf(&mut buf).await? == 0;
+
+            let whole_splats = buf.len() - buf.len() % SPLAT_SIZE;
+            for splat in buf[..whole_splats].chunks_exact(SPLAT_SIZE) {
+                row_index += 1;
+                if !row_index.is_multiple_of(subsample) {
+                    continue;
+                }
+                let float = |i: usize| {
+                    f32::from_le_bytes(splat[i * 4..i * 4 + 4].try_into().expect("Unreachable"))
+                };
+                means.extend([float(0), float(1), float(2)]);
+                log_scales.extend([float(3).ln(), float(4).ln(), float(5).ln()]);
+
+                let [r, g, b, a] = [splat[24], splat[25], splat[26], splat[27]].map(unorm);
+                sh_coeffs.extend(rgb_to_sh(Vec3::new(r, g, b)).to_array());
+                // Keep fully opaque or transparent splats finite.
+                opacity.push(inverse_sigmoid(a.clamp(1e-4, 1.0 - 1e-4)));
+                // Nb: Scalar first, like Brush.
+                rotations.extend(splat[28..32].iter().map(|&q| (q as f32 - 128.0) / 128.0));
+            }
+            buf.drain(..whole_splats);
+
+            if done && !buf.is_empty() {
+                return Err(DeserializeError::custom(
+                    "Splat file size isn't a multiple of 32 bytes",
+                ));
+            }
+            if done && means.is_empty() {
+                return Err(DeserializeError::custom("Splat file has no splats"));
+            }
+
+            if done || (update.should_update() && !means.is_empty()) {
+                emitter
+                    .emit(SplatMessage {
+                        meta: ParseMetadata {
+                            up_axis: None,
+                            total_splats: (means.len() / 3) as u32,
+                            frame_count: 0,
+                            current_frame: 0,
+                            // The total size isn't known up front.
+                            progress: if done { 1.0 } else { 0.0 },
+                        },
+                        splats: Splats::from_raw(
+                            means.clone(),
+                            Some(rotations.clone()),
+                            Some(log_scales.clone()),
+                            Some(sh_coeffs.clone()),
+                            Some(opacity.clone()),
+                            &device,
+                        ),
+                    })
+                    .await;
+            }
+            if done {
+                return Ok(());
+            }
+            tokio_wasm::task::yield_now().await;
+        }
+    })
+}
+
+fn unorm(value: u8) -> f32 {
+    value as f32 / 255.0
+}
+
+fn to_unorm(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Serialize splats to the `.splat` format.
+///
+/// This format only has base colors, so higher order SH are dropped, and the Mip-Splatting 3D
+/// filter is baked into the scales and opacities. The largest and most opaque splats are written
+/// first, so viewers that stream the file show the most important splats first.
+pub async fn splat_to_splat_file<B: Backend>(splats: Splats<B>) -> Vec<u8> {
+    let splats = splats.with_normed_rotations().with_baked_filter_3d();
+    let rows = read_splat_data(splats).await.vertex;
+
+    let importance: Vec<f32> = rows
+        .iter()
+        .map(|r| (r.scale_0 + r.scale_1 + r.scale_2).exp() / (1.0 + (-r.opacity).exp()))
+        .collect();
+    let mut order: Vec<usize> = (0..rows.len()).collect();
+    order.sort_by(|&a, &b| importance[b].total_cmp(&importance[a]));
+
+    let mut data = Vec::with_capacity(rows.len() * SPLAT_SIZE);
+    for row in order.into_iter().map(|i| &rows[i]) {
+        for val in [
+            row.x,
+            row.y,
+            row.z,
+            row.scale_0.exp(),
+            row.scale_1.exp(),
+            row.scale_2.exp(),
+        ] {
+            data.extend(val.to_le_bytes());
+        }
+        let rgb = sh_to_rgb(Vec3::new(row.f_dc_0, row.f_dc_1, row.f_dc_2));
+        let alpha = 1.0 / (1.0 + (-row.opacity).exp());
+        data.extend([rgb.x, rgb.y, rgb.z, alpha].map(to_unorm));
+        data.extend(
+            [row.rot_0, row.rot_1, row.rot_2, row.rot_3]
+                .map(|q| (q * 128.0 + 128.0).round().clamp(0.0, 255.0) as u8),
+        );
+    }
+    data
+}
+
+pub async fn load_splat_from_splat_file<T: AsyncRead + SendNotWasm + Unpin>(
+    reader: T,
+    subsample_points: Option<u32>,
+    device: WgpuDevice,
+) -> Result<SplatMessage, DeserializeError> {
+    let stream = stream_splat_from_splat_file(reader, subsample_points, device, false);
+    let Some(splat) = pin!(stream).next().await else {
+        return Err(DeserializeError::custom("Couldn't load splat file"));
+    };
+    splat
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_test_splats_with_count;
+    use brush_render::MainBackend;
+    use std::io::Cursor;
+
+    #[tokio::test]
+    async fn test_roundtrip_splat_file() {
+        let original = create_test_splats_with_count(1, 5);
+        let data = splat_to_splat_file(original.clone()).await;
+        assert_eq!(data.len(), 5 * SPLAT_SIZE);
+
+        let loaded: Splats<MainBackend> =
+            load_splat_from_splat_file(Cursor::new(data), None, WgpuDevice::default())
+                .await
+                .expect("Failed to load splat file")
+                .splats;
+        assert_eq!(loaded.num_splats(), 5);
+        assert_eq!(loaded.sh_degree(), 0);
+
+        // Splats are reordered by size, so compare sorted by position.
+        let sorted = |splats: Splats<MainBackend>| async move {
+            let rows = read_splat_data(splats.with_normed_rotations()).await.vertex;
+            let mut rows: Vec<_> = rows
+                .into_iter()
+                .map(|r| {
+                    [
+                        r.x, r.y, r.z, r.scale_0, r.scale_1, r.scale_2, r.rot_0, r.rot_1, r.rot_2,
+                        r.rot_3, r.f_dc_0, r.f_dc_1, r.f_dc_2, r.opacity,
+                    ]
+                })
+                .collect();
+            rows.sort_by(|a, b| a[0].total_cmp(&b[0]));
+            rows
+        };
+        let original = sorted(original.with_baked_filter_3d()).await;
+        let loaded = sorted(loaded).await;
+
+        for (a, b) in original.iter().zip(&loaded) {
+            // Position and scale are stored exactly.
+            for i in 0..6 {
+                assert!((a[i] - b[i]).abs() < 1e-5, "{a:?} != {b:?}");
+            }
+            // Rotation, color and opacity are quantized to a byte.
+            for i in 6..14 {
+                assert!((a[i] - b[i]).abs() < 0.05, "{a:?} != {b:?}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_splat_file() {
+        let result =
+            load_splat_from_splat_file(Cursor::new(vec![0u8; 40]), None, WgpuDevice::default())
+                .await;
+        assert!(result.is_err());
+
+        let result =
+            load_splat_from_splat_file(Cursor::new(vec![]), None, WgpuDevice::default()).await;
+        assert!(result.is_err());
+    }
+}
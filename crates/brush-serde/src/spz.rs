@@ -0,0 +1,407 @@
+//! The SPZ format from Niantic.
+//!
+//! A gzipped file with a small header, followed by all attributes of the splats stored column by
+//! column: fixed point positions, then opacities, colors, scales, rotations and SH coefficients,
+//! each quantized to bytes.
+
+use std::io::{Read, Write};
+use std::pin::pin;
+
+use async_fn_stream::try_fn_stream;
+use brush_render::MainBackend;
+use brush_render::gaussian_splats::{Splats, inverse_sigmoid};
+use brush_render::sh::sh_coeffs_for_degree;
+use brush_vfs::SendNotWasm;
+use burn::backend::wgpu::WgpuDevice;
+use burn::prelude::Backend;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::de::Error;
+use serde_ply::DeserializeError;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_stream::{Stream, StreamExt};
+
+use crate::export::read_splat_data;
+use crate::import::{ParseMetadata, SplatMessage};
+
+const MAGIC: u32 = 0x5053_474e; // NGSP
+const HEADER_SIZE: usize = 16;
+// Version 3 stores rotations as the smallest three components, version 2 as xyz with w >= 0.
+const VERSION: u32 = 3;
+const MAX_SH_DEGREE: u32 = 3;
+const MAX_FRACTIONAL_BITS: u8 = 12;
+const COLOR_SCALE: f32 = 0.15;
+
+// SPZ files are stored with x right, y up and z back, while Brush (like most plys) uses x right,
+// y down and z forward. Converting between the two flips y and z, which flips the sign of some SH
+// bases.
+const FLIP_POS: [f32; 3] = [1.0, -1.0, -1.0];
+const FLIP_SH: [f32; 15] = [
+    -1.0, -1.0, 1.0, // Degree 1
+    -1.0, 1.0, 1.0, -1.0, 1.0, // Degree 2
+    -1.0, 1.0, -1.0, -1.0, 1.0, -1.0, 1.0, // Degree 3
+];
+
+fn unorm(value: u8) -> f32 {
+    value as f32 / 255.0
+}
+
+fn to_u8(value: f32) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+fn decode_sh(value: u8) -> f32 {
+    (value as f32 - 128.0) / 128.0
+}
+
+// The first degree of SH is kept with 5 bits of precision, higher degrees with 4 bits.
+fn encode_sh(value: f32, bits: u32) -> u8 {
+    let bucket = 1 << (8 - bits);
+    let quantized = (value * 128.0 + 128.0).round() as i32;
+    let quantized = (quantized + bucket / 2) / bucket * bucket;
+    quantized.clamp(0, 255) as u8
+}
+
+/// Decode a rotation stored as the three smallest components of a quaternion in xyzw order.
+fn decode_quat_smallest_three(bytes: [u8; 4]) -> [f32; 4] {
+    const MASK: u32 = (1 << 9) - 1;
+    let mut comp = u32::from_le_bytes(bytes);
+    let largest = (comp >> 30) as usize;
+    let mut quat = [0.0; 4];
+    let mut sum_squares = 0.0;
+    for i in (0..4).rev().filter(|&i| i != largest) {
+        let mag = (comp & MASK) as f32 / MASK as f32 * std::f32::consts::FRAC_1_SQRT_2;
+        quat[i] = if (comp >> 9) & 1 == 1 { -mag } else { mag };
+        sum_squares += quat[i] * quat[i];
+        comp >>= 10;
+    }
+    quat[largest] = (1.0 - sum_squares).max(0.0).sqrt();
+    quat
+}
+
+fn encode_quat_smallest_three(quat: [f32; 4]) -> [u8; 4] {
+    const MASK: u32 = (1 << 9) - 1;
+    let largest = (0..4)
+        .max_by(|&a, &b| quat[a].abs().total_cmp(&quat[b].abs()))
+        .expect("Unreachable");
+    // q and -q are the same rotation, pick the one where the largest component is positive.
+    let negate = quat[largest] < 0.0;
+    let mut comp = largest as u32;
+    for i in (0..4).filter(|&i| i != largest) {
+        let negbit = u32::from((quat[i] < 0.0) != negate);
+        let mag = (quat[i].abs() / std::f32::consts::FRAC_1_SQRT_2 * MASK as f32 + 0.5) as u32;
+        comp = (comp << 10) | (negbit << 9) | mag.min(MASK);
+    }
+    comp.to_le_bytes()
+}
+
+fn take_column<'a>(data: &mut &'a [u8], size: usize) -> Result<&'a [u8], DeserializeError> {
+    let Some((column, rest)) = data.split_at_checked(size) else {
+        return Err(DeserializeError::custom("SPZ file is truncated"));
+    };
+    *data = rest;
+    Ok(column)
+}
+
+fn parse_spz(
+    data: &[u8],
+    subsample_points: Option<u32>,
+    device: &WgpuDevice,
+) -> Result<Splats<MainBackend>, DeserializeError> {
+    let header = data
+        .get(..HEADER_SIZE)
+        .ok_or_else(|| DeserializeError::custom("SPZ file is too small"))?;
+    let read_u32 = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().expect("Unreachable"));
+    if read_u32(0) != MAGIC {
+        return Err(DeserializeError::custom("Not an SPZ file"));
+    }
+    let version = read_u32(4);
+    if !matches!(version, 2 | 3) {
+        return Err(DeserializeError::custom(format!(
+            "Unsupported SPZ version {version}"
+        )));
+    }
+    let num_points = read_u32(8) as usize;
+    let sh_degree = header[12] as u32;
+    if sh_degree > MAX_SH_DEGREE {
+        return Err(DeserializeError::custom(format!(
+            "Unsupported SH degree {sh_degree} in SPZ file"
+        )));
+    }
+    let fractional_bits = header[13];
+    if fractional_bits > 24 {
+        return Err(DeserializeError::custom(format!(
+            "Invalid number of fractional bits {fractional_bits} in SPZ file"
+        )));
+    }
+    let fractional_scale = 1.0 / (1 << fractional_bits) as f32;
+
+    let rest_coeffs = sh_coeffs_for_degree(sh_degree) as usize - 1;
+    let rot_size = if version == 2 { 3 } else { 4 };
+
+    let mut rest = &data[HEADER_SIZE..];
+    let positions = take_column(&mut rest, num_points * 9)?;
+    let alphas = take_column(&mut rest, num_points)?;
+    let colors = take_column(&mut rest, num_points * 3)?;
+    let scales = take_column(&mut rest, num_points * 3)?;
+    let rotations = take_column(&mut rest, num_points * rot_size)?;
+    let sh = take_column(&mut rest, num_points * rest_coeffs * 3)?;
+
+    let subsample = subsample_points.unwrap_or(1) as usize;
+    let capacity = num_points / subsample;
+    let mut means = Vec::with_capacity(capacity * 3);
+    let mut log_scales = Vec::with_capacity(capacity * 3);
+    let mut quats = Vec::with_capacity(capacity * 4);
+    let mut sh_coeffs = Vec::with_capacity(capacity * (rest_coeffs + 1) * 3);
+    let mut opacity = Vec::with_capacity(capacity);
+
+    for i in (0..num_points).step_by(subsample) {
+        for (c, flip) in FLIP_POS.iter().enumerate() {
+            let b = &positions[i * 9 + c * 3..i * 9 + c * 3 + 3];
+            // Sign extend the 24 bit fixed point value.
+            let fixed = i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8;
+            means.push(fixed as f32 * fractional_scale * flip);
+        }
+        // Keep fully opaque or transparent splats finite.
+        opacity.push(inverse_sigmoid(unorm(alphas[i]).clamp(1e-4, 1.0 - 1e-4)));
+        sh_coeffs.extend(
+            colors[i * 3..i * 3 + 3]
+                .iter()
+                .map(|&c| (unorm(c) - 0.5) / COLOR_SCALE),
+        );
+        log_scales.extend(
+            scales[i * 3..i * 3 + 3]
+                .iter()
+                .map(|&s| s as f32 / 16.0 - 10.0),
+        );
+
+        let [x, y, z, w] = if version == 2 {
+            let [x, y, z] = [0, 1, 2].map(|c| rotations[i * 3 + c] as f32 / 127.5 - 1.0);
+            [x, y, z, (1.0 - x * x - y * y - z * z).max(0.0).sqrt()]
+        } else {
+            let bytes = rotations[i * 4..i * 4 + 4].try_into().expect("Unreachable");
+            decode_quat_smallest_three(bytes)
+        };
+        // Nb: Scalar first, like Brush. Flipping y and z flips the y and z rotation axes too.
+        quats.extend([w, x, -y, -z]);
+
+        for (j, flip) in FLIP_SH[..rest_coeffs].iter().enumerate() {
+            let coeffs = &sh[(i * rest_coeffs + j) * 3..(i * rest_coeffs + j) * 3 + 3];
+            sh_coeffs.extend(coeffs.iter().map(|&c| decode_sh(c) * flip));
+        }
+    }
+
+    Ok(Splats::from_raw(
+        means,
+        Some(quats),
+        Some(log_scales),
+        Some(sh_coeffs),
+        Some(opacity),
+        device,
+    ))
+}
+
+/// Stream splats from an SPZ file.
+///
+/// The attributes of the splats are stored column by column, so the splats are only emitted once
+/// the whole file has been read.
+pub fn stream_splat_from_spz<T: AsyncRead + SendNotWasm + Unpin>(
+    mut reader: T,
+    subsample_points: Option<u32>,
+    device: WgpuDevice,
+) -> impl Stream<Item = Result<SplatMessage, DeserializeError>> {
+    try_fn_stream(|emitter| async move {
+        let mut compressed = vec![];
+        reader.read_to_end(&mut compressed).await?;
+        let mut data = vec![];
+        GzDecoder::new(compressed.as_slice()).read_to_end(&mut data)?;
+
+        let splats = parse_spz(&data, subsample_points, &device)?;
+        emitter
+            .emit(SplatMessage {
+                meta: ParseMetadata {
+                    up_axis: None,
+                    total_splats: splats.num_splats(),
+                    frame_count: 0,
+                    current_frame: 0,
+                    progress: 1.0,
+                },
+                splats,
+            })
+            .await;
+        Ok(())
+    })
+}
+
+pub async fn load_splat_from_spz<T: AsyncRead + SendNotWasm + Unpin>(
+    reader: T,
+    subsample_points: Option<u32>,
+    device: WgpuDevice,
+) -> Result<SplatMessage, DeserializeError> {
+    let stream = stream_splat_from_spz(reader, subsample_points, device);
+    let Some(splat) = pin!(stream).next().await else {
+        return Err(DeserializeError::custom("Couldn't load spz file"));
+    };
+    splat
+}
+
+/// Serialize splats to an SPZ file.
+///
+/// SPZ supports up to 3 degrees of SH, higher degrees are dropped. The Mip-Splatting 3D filter is
+/// baked into the scales and opacities.
+pub async fn splat_to_spz<B: Backend>(splats: Splats<B>) -> Vec<u8> {
+    let splats = splats.with_normed_rotations().with_baked_filter_3d();
+    let sh_degree = splats.sh_degree().min(MAX_SH_DEGREE);
+    let rows = read_splat_data(splats).await.vertex;
+
+    let rest_coeffs = sh_coeffs_for_degree(sh_degree) as usize - 1;
+
+    // Use as much precision as possible for the positions, while still fitting in 24 bits.
+    let max_extent = rows
+        .iter()
+        .flat_map(|r| [r.x.abs(), r.y.abs(), r.z.abs()])
+        .fold(0.0, f32::max);
+    let fractional_bits = (0..=MAX_FRACTIONAL_BITS)
+        .rev()
+        .find(|&bits| max_extent * ((1 << bits) as f32) < (1 << 23) as f32)
+        .unwrap_or(0);
+    let fractional_scale = (1 << fractional_bits) as f32;
+
+    let mut data = Vec::with_capacity(HEADER_SIZE + rows.len() * (20 + rest_coeffs * 3));
+    data.extend(MAGIC.to_le_bytes());
+    data.extend(VERSION.to_le_bytes());
+    data.extend((rows.len() as u32).to_le_bytes());
+    data.extend([sh_degree as u8, fractional_bits, 0, 0]);
+
+    for r in &rows {
+        for (val, flip) in [r.x, r.y, r.z].into_iter().zip(FLIP_POS) {
+            let fixed = (val * flip * fractional_scale).round() as i32;
+            data.extend(&fixed.clamp(-(1 << 23), (1 << 23) - 1).to_le_bytes()[..3]);
+        }
+    }
+    for r in &rows {
+        data.push(to_u8(255.0 / (1.0 + (-r.opacity).exp())));
+    }
+    for r in &rows {
+        data.extend([r.f_dc_0, r.f_dc_1, r.f_dc_2].map(|c| to_u8((c * COLOR_SCALE + 0.5) * 255.0)));
+    }
+    for r in &rows {
+        data.extend([r.scale_0, r.scale_1, r.scale_2].map(|s| to_u8((s + 10.0) * 16.0)));
+    }
+    for r in &rows {
+        data.extend(encode_quat_smallest_three([
+            r.rot_1, -r.rot_2, -r.rot_3, r.rot_0,
+        ]));
+    }
+    for r in &rows {
+        // Ply rows store the SH per channel, SPZ per coefficient.
+        let per_channel = r.rest_coeffs.len() / 3;
+        for (j, flip) in FLIP_SH[..rest_coeffs].iter().enumerate() {
+            let bits = if j < 3 { 5 } else { 4 };
+            for c in 0..3 {
+                data.push(encode_sh(r.rest_coeffs[c * per_channel + j] * flip, bits));
+            }
+        }
+    }
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(&data)
+        .expect("Writing to memory can't fail");
+    encoder.finish().expect("Writing to memory can't fail")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_test_splats_with_count;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_quat_round_trip() {
+        for quat in [
+            [0.0, 0.0, 0.0, 1.0],
+            [0.5, -0.5, 0.5, -0.5],
+            [0.1, -0.7, 0.2, 0.67],
+            [-0.9, 0.1, 0.3, -0.2],
+        ] {
+            let norm = quat.iter().map(|q| q * q).sum::<f32>().sqrt();
+            let quat = quat.map(|q| q / norm);
+            let decoded = decode_quat_smallest_three(encode_quat_smallest_three(quat));
+            // The decoded quaternion can be the negated quaternion, which is the same rotation.
+            let dot: f32 = quat.iter().zip(decoded).map(|(a, b)| a * b).sum();
+            assert!(dot.abs() > 0.9999, "{quat:?} != {decoded:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_roundtrip_spz() {
+        for sh_degree in 0..=3 {
+            let original = create_test_splats_with_count(sh_degree, 20);
+            let data = splat_to_spz(original.clone()).await;
+            let loaded = load_splat_from_spz(Cursor::new(data), None, WgpuDevice::default())
+                .await
+                .expect("Failed to load spz file")
+                .splats;
+            assert_eq!(loaded.num_splats(), 20);
+            assert_eq!(loaded.sh_degree(), sh_degree);
+
+            let original = read_splat_data(original.with_normed_rotations())
+                .await
+                .vertex;
+            let loaded = read_splat_data(loaded).await.vertex;
+
+            for (a, b) in original.iter().zip(&loaded) {
+                let close = |x: f32, y: f32, tol: f32| assert!((x - y).abs() < tol, "{x} != {y}");
+                for (x, y) in [(a.x, b.x), (a.y, b.y), (a.z, b.z)] {
+                    close(x, y, 1e-3);
+                }
+                for (x, y) in [
+                    (a.scale_0, b.scale_0),
+                    (a.scale_1, b.scale_1),
+                    (a.scale_2, b.scale_2),
+                ] {
+                    close(x, y, 0.04);
+                }
+                for (x, y) in [
+                    (a.f_dc_0, b.f_dc_0),
+                    (a.f_dc_1, b.f_dc_1),
+                    (a.f_dc_2, b.f_dc_2),
+                ] {
+                    close(x, y, 0.02);
+                }
+                for (x, y) in [
+                    (a.rot_0, b.rot_0),
+                    (a.rot_1, b.rot_1),
+                    (a.rot_2, b.rot_2),
+                    (a.rot_3, b.rot_3),
+                ] {
+                    close(x, y, 0.01);
+                }
+                close(a.opacity, b.opacity, 0.05);
+                // SH are stored between -1 and 1.
+                for (x, y) in a.rest_coeffs.iter().zip(&b.rest_coeffs) {
+                    close(x.clamp(-1.0, 127.0 / 128.0), *y, 0.07);
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_spz() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[0; 32]).expect("Failed to compress");
+        let data = encoder.finish().expect("Failed to compress");
+        let result = load_splat_from_spz(Cursor::new(data), None, WgpuDevice::default()).await;
+        assert!(result.is_err());
+
+        let result = load_splat_from_spz(
+            Cursor::new(b"not gzip".to_vec()),
+            None,
+            WgpuDevice::default(),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,204 @@
+use crate::adam_scaled::{AdamScaled, AdamScaledConfig, AdamState};
+use brush_render::MainBackend;
+use burn::{
+    backend::Autodiff,
+    module::{Module, Param, ParamId},
+    optim::{GradientsParams, Optimizer, adaptor::OptimizerAdaptor, record::AdaptorRecord},
+    prelude::Backend,
+    tensor::{Tensor, TensorData, backend::AutodiffBackend},
+};
+use glam::{Mat4, Vec4};
+use hashbrown::HashMap;
+
+type DiffBackend = Autodiff<MainBackend>;
+
+/// Per-view affine colour transforms, stored as 3x4 matrices `[M | b]` such that `rgb' = M * rgb + b`.
+#[derive(Module, Debug)]
+pub(crate) struct AppearanceModel<B: Backend> {
+    pub transforms: Vec<Param<Tensor<B, 2>>>,
+}
+
+fn identity_transform<B: Backend>(device: &B::Device) -> Tensor<B, 2> {
+    Tensor::from_floats(
+        [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+        ],
+        device,
+    )
+}
+
+/// Learnable appearance compensation for the training views.
+///
+/// Phone captures often change exposure and white balance from frame to frame. Without
+/// compensation the splats end up baking these changes in as floaters and colour blotches.
+pub(crate) struct Appearance {
+    model: AppearanceModel<DiffBackend>,
+    optim: OptimizerAdaptor<AdamScaled, AppearanceModel<DiffBackend>, DiffBackend>,
+}
+
+impl Appearance {
+    pub(crate) fn new() -> Self {
+        Self {
+            model: AppearanceModel { transforms: vec![] },
+            optim: AdamScaledConfig::new().with_epsilon(1e-15).init(),
+        }
+    }
+
+    /// The colour transform of a view. Views which haven't been seen yet start out as the identity.
+    pub(crate) fn transform(
+        &mut self,
+        view_index: usize,
+        device: &<DiffBackend as Backend>::Device,
+    ) -> Tensor<DiffBackend, 2> {
+        while self.model.transforms.len() <= view_index {
+            self.model.transforms.push(Param::initialized(
+                ParamId::new(),
+                identity_transform(device).require_grad(),
+            ));
+        }
+        self.model.transforms[view_index].val()
+    }
+
+    pub(crate) fn step(
+        &mut self,
+        lr: f64,
+        view_index: usize,
+        grads: &mut <DiffBackend as AutodiffBackend>::Gradients,
+    ) {
+        let model = std::mem::replace(&mut self.model, AppearanceModel { transforms: vec![] });
+        let grads = GradientsParams::from_params(grads, &model, &[model.transforms[view_index].id]);
+        self.model = self.optim.step(lr, model, grads);
+    }
+
+    pub(crate) fn to_record(
+        &self,
+    ) -> (
+        Vec<Tensor<MainBackend, 2>>,
+        Vec<Option<AdamState<MainBackend, 2>>>,
+    ) {
+        let mut optim_record = self.optim.to_record();
+        self.model
+            .transforms
+            .iter()
+            .map(|t| {
+                let state = optim_record.remove(&t.id).map(AdaptorRecord::into_state);
+                (t.val().inner(), state)
+            })
+            .unzip()
+    }
+
+    pub(crate) fn from_record(
+        transforms: Vec<Tensor<MainBackend, 2>>,
+        states: Vec<Option<AdamState<MainBackend, 2>>>,
+    ) -> Self {
+        let transforms: Vec<_> = transforms
+            .into_iter()
+            .map(|t| Param::initialized(ParamId::new(), Tensor::from_inner(t).require_grad()))
+            .collect();
+
+        let optim_record: HashMap<_, _> = transforms
+            .iter()
+            .zip(states)
+            .filter_map(|(t, state)| Some((t.id, AdaptorRecord::from_state(state?))))
+            .collect();
+
+        Self {
+            model: AppearanceModel { transforms },
+            optim: AdamScaledConfig::new()
+                .with_epsilon(1e-15)
+                .init()
+                .load_record(optim_record),
+        }
+    }
+}
+
+/// Apply a 3x4 affine colour transform to an [H, W, 3] image.
+pub(crate) fn apply_color_transform<B: Backend>(
+    rgb: Tensor<B, 3>,
+    transform: Tensor<B, 2>,
+) -> Tensor<B, 3> {
+    let [h, w, _] = rgb.dims();
+    let mat = transform.clone().slice([0..3, 0..3]);
+    let bias = transform.slice([0..3, 3..4]).reshape([1, 3]);
+    (rgb.reshape([h * w, 3]).matmul(mat.transpose()) + bias).reshape([h, w, 3])
+}
+
+/// Least squares fit of the affine colour transform that maps `render` closest to `gt`.
+///
+/// Used to evaluate views for which no appearance was learned during training.
+pub(crate) async fn fit_color_transform<B: Backend>(
+    render: Tensor<B, 3>,
+    gt: Tensor<B, 3>,
+) -> Tensor<B, 2> {
+    let [h, w, _] = render.dims();
+    let n = h * w;
+    let device = render.device();
+
+    let a = Tensor::cat(
+        vec![render.reshape([n, 3]), Tensor::ones([n, 1], &device)],
+        1,
+    );
+    let b = gt.reshape([n, 3]);
+    let ata = a.clone().transpose().matmul(a.clone());
+    let atb = a.transpose().matmul(b);
+
+    let ata = ata
+        .into_data_async()
+        .await
+        .into_vec::<f32>()
+        .expect("Wrong type");
+    let atb = atb
+        .into_data_async()
+        .await
+        .into_vec::<f32>()
+        .expect("Wrong type");
+
+    // Regularize slightly towards the identity, so flat images still give a sensible fit.
+    let reg = 1e-4 * n as f32;
+    let ata = Mat4::from_cols_slice(&ata) + Mat4::from_diagonal(Vec4::splat(reg));
+    let inv = ata.inverse();
+
+    let mut transform = [0.0; 12];
+    for c in 0..3 {
+        let mut rhs = Vec4::new(atb[c], atb[3 + c], atb[6 + c], atb[9 + c]);
+        rhs[c] += reg;
+        let col = inv * rhs;
+        transform[c * 4..c * 4 + 4].copy_from_slice(&col.to_array());
+    }
+
+    Tensor::from_data(TensorData::new(transform.to_vec(), [3, 4]), &device)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use burn::backend::wgpu::WgpuDevice;
+
+    #[tokio::test]
+    async fn test_fit_color_transform() {
+        let device = WgpuDevice::DefaultDevice;
+        let render = Tensor::<MainBackend, 3>::random(
+            [8, 8, 3],
+            burn::tensor::Distribution::Uniform(0.0, 1.0),
+            &device,
+        );
+        let transform = Tensor::<MainBackend, 2>::from_floats(
+            [
+                [1.2, 0.1, 0.0, 0.05],
+                [0.0, 0.9, 0.0, -0.1],
+                [0.0, 0.0, 0.7, 0.2],
+            ],
+            &device,
+        );
+        let gt = apply_color_transform(render.clone(), transform.clone());
+
+        let fit = fit_color_transform(render, gt).await;
+        let fit: Vec<f32> = fit.into_data().into_vec().expect("Wrong type");
+        let expected: Vec<f32> = transform.into_data().into_vec().expect("Wrong type");
+        for (a, b) in fit.iter().zip(expected) {
+            assert!((a - b).abs() < 1e-2, "{a} != {b}");
+        }
+    }
+}
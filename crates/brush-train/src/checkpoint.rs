@@ -0,0 +1,190 @@
+use crate::{adam_scaled::AdamState, config::TrainConfig};
+use anyhow::Context;
+use brush_dataset::scene_loader::LoaderState;
+use brush_render::MainBackend;
+use burn::{
+    backend::wgpu::WgpuDevice,
+    prelude::Backend,
+    record::{BinBytesRecorder, FullPrecisionSettings, Record, Recorder},
+    tensor::Tensor,
+};
+
+// Bump this whenever the layout of the checkpoint record changes.
+pub(crate) const CHECKPOINT_VERSION: u32 = 5;
+
+#[derive(Record)]
+pub(crate) struct CheckpointRecord<B: Backend> {
+    pub version: u32,
+    pub iter: u32,
+    pub seed: u64,
+    /// State of the data loader, so the views continue in the same order.
+    pub loader_seed: u64,
+    pub loader_position: u64,
+    /// The train config, stored as JSON.
+    pub config: String,
+
+    pub bounds_center: [f32; 3],
+    pub bounds_extent: [f32; 3],
+
+    /// Nr. of steps the trainer has taken, which determines the learning rates.
+    pub step_count: u32,
+
+    pub means: Tensor<B, 2>,
+    pub rotation: Tensor<B, 2>,
+    pub log_scales: Tensor<B, 2>,
+    pub sh_coeffs: Tensor<B, 3>,
+    pub raw_opacity: Tensor<B, 1>,
+    /// The Mip-Splatting 3D filter, if enabled.
+    pub filter_3d: Option<Tensor<B, 1>>,
+
+    pub means_state: Option<AdamState<B, 2>>,
+    pub rotation_state: Option<AdamState<B, 2>>,
+    pub log_scales_state: Option<AdamState<B, 2>>,
+    pub sh_coeffs_state: Option<AdamState<B, 3>>,
+    pub raw_opacity_state: Option<AdamState<B, 1>>,
+
+    pub refine_weight_norm: Option<Tensor<B, 1>>,
+    pub vis_weight: Option<Tensor<B, 1>>,
+
+    /// Per view colour transforms, empty if appearance compensation is disabled.
+    pub appearance: Vec<Tensor<B, 2>>,
+    pub appearance_state: Vec<Option<AdamState<B, 2>>>,
+
+    /// Per view pose corrections, empty if pose refinement is disabled.
+    pub poses: Vec<Tensor<B, 1>>,
+    pub poses_state: Vec<Option<AdamState<B, 1>>>,
+    pub poses_cached: Vec<[f32; 6]>,
+}
+
+/// The full state of a training run at some iteration.
+///
+/// Besides the splats this holds the optimizer moments, refine statistics, learning rate
+/// schedule position and seed, such that training can continue exactly where it left off.
+pub struct TrainCheckpoint {
+    pub(crate) record: CheckpointRecord<MainBackend>,
+    pub(crate) config: TrainConfig,
+}
+
+type CheckpointRecorder = BinBytesRecorder<FullPrecisionSettings>;
+
+impl TrainCheckpoint {
+    /// The iteration training should continue from.
+    pub fn iter(&self) -> u32 {
+        self.record.iter
+    }
+
+    /// The seed the run was started with.
+    pub fn seed(&self) -> u64 {
+        self.record.seed
+    }
+
+    /// Where the data loader of the run was.
+    pub fn loader_state(&self) -> LoaderState {
+        LoaderState {
+            seed: self.record.loader_seed,
+            position: self.record.loader_position,
+        }
+    }
+
+    /// The config the run was trained with.
+    pub fn config(&self) -> &TrainConfig {
+        &self.config
+    }
+
+    pub fn to_bytes(self) -> anyhow::Result<Vec<u8>> {
+        CheckpointRecorder::default()
+            .record(self.record, ())
+            .context("Failed to serialize checkpoint")
+    }
+
+    pub fn from_bytes(data: Vec<u8>, device: &WgpuDevice) -> anyhow::Result<Self> {
+        let record: CheckpointRecord<MainBackend> =
+            Recorder::<MainBackend>::load(&CheckpointRecorder::default(), data, device)
+                .context("Failed to read checkpoint")?;
+
+        anyhow::ensure!(
+            record.version == CHECKPOINT_VERSION,
+            "Checkpoint version {} is not supported (expected {CHECKPOINT_VERSION})",
+            record.version
+        );
+
+        let config =
+            serde_json::from_str(&record.config).context("Failed to read checkpoint config")?;
+
+        Ok(Self { record, config })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::train::SplatTrainer;
+    use brush_render::gaussian_splats::Splats;
+    use burn::backend::Autodiff;
+
+    #[tokio::test]
+    async fn test_checkpoint_roundtrip() {
+        let device = WgpuDevice::DefaultDevice;
+        let means = vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0, -1.0, 0.5, 2.5];
+        let log_scales = vec![-2.0; 9];
+        let filter_3d = Tensor::from_floats([0.1, 0.2, 0.3], &device);
+        let splats =
+            Splats::<MainBackend>::from_raw(means, None, Some(log_scales), None, None, &device)
+                .with_filter_3d(Some(filter_3d))
+                .into_autodiff::<Autodiff<MainBackend>>();
+
+        let config = TrainConfig {
+            total_steps: 1234,
+            ..Default::default()
+        };
+        let trainer = SplatTrainer::new(&config, &device, splats.clone()).await;
+        let data = trainer
+            .checkpoint(
+                &splats,
+                17,
+                5,
+                LoaderState {
+                    seed: 5,
+                    position: 17,
+                },
+            )
+            .to_bytes()
+            .expect("Failed to serialize checkpoint");
+
+        let checkpoint =
+            TrainCheckpoint::from_bytes(data, &device).expect("Failed to deserialize checkpoint");
+        assert_eq!(checkpoint.iter(), 17);
+        assert_eq!(checkpoint.seed(), 5);
+        assert_eq!(
+            checkpoint.loader_state(),
+            LoaderState {
+                seed: 5,
+                position: 17
+            }
+        );
+        assert_eq!(checkpoint.config().total_steps, 1234);
+
+        let (_, loaded) = SplatTrainer::from_checkpoint(checkpoint, &device);
+        let orig: Vec<f32> = splats
+            .means
+            .val()
+            .into_data()
+            .into_vec()
+            .expect("Wrong type");
+        let loaded_means: Vec<f32> = loaded
+            .means
+            .val()
+            .into_data()
+            .into_vec()
+            .expect("Wrong type");
+        assert_eq!(orig, loaded_means);
+
+        let filter_3d: Vec<f32> = loaded
+            .filter_3d
+            .expect("3D filter should be restored")
+            .into_data()
+            .into_vec()
+            .expect("Wrong type");
+        assert_eq!(filter_3d, vec![0.1, 0.2, 0.3]);
+    }
+}
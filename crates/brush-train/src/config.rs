@@ -1,7 +1,11 @@
+use crate::{depth_loss::DepthLossKind, lr_schedule::LrScheduleKind, refine::RefineStrategyKind};
+use brush_render::RenderScale;
 use clap::Parser;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Parser, Deserialize)]
+// Options missing from a config file take their default values, so older configs still load.
+#[derive(Clone, Parser, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TrainConfig {
     /// Total number of steps to train for.
     #[arg(long, help_heading = "Training options", default_value = "30000")]
@@ -15,10 +19,19 @@ pub struct TrainConfig {
     #[arg(long, help_heading = "Training options", default_value = "2e-5")]
     pub lr_mean: f64,
 
-    /// Start learning rate for the mean parameters.
+    /// End learning rate for the mean parameters.
     #[arg(long, help_heading = "Training options", default_value = "2e-7")]
     pub lr_mean_end: f64,
 
+    /// Learning rate schedule for the mean parameters.
+    #[arg(
+        long,
+        help_heading = "Training options",
+        value_enum,
+        default_value = "exponential"
+    )]
+    pub lr_mean_schedule: LrScheduleKind,
+
     /// How much noise to add to the mean parameters of low opacity gaussians.
     #[arg(long, help_heading = "Training options", default_value = "50.0")]
     pub mean_noise_weight: f32,
@@ -27,6 +40,19 @@ pub struct TrainConfig {
     #[arg(long, help_heading = "Training options", default_value = "2e-3")]
     pub lr_coeffs_dc: f64,
 
+    /// End learning rate for the base SH (RGB) coefficients.
+    #[arg(long, help_heading = "Training options", default_value = "2e-3")]
+    pub lr_coeffs_dc_end: f64,
+
+    /// Learning rate schedule for the SH coefficients.
+    #[arg(
+        long,
+        help_heading = "Training options",
+        value_enum,
+        default_value = "constant"
+    )]
+    pub lr_coeffs_schedule: LrScheduleKind,
+
     /// How much to divide the learning rate by for higher SH orders.
     #[arg(long, help_heading = "Training options", default_value = "20.0")]
     pub lr_coeffs_sh_scale: f32,
@@ -35,18 +61,92 @@ pub struct TrainConfig {
     #[arg(long, help_heading = "Training options", default_value = "0.012")]
     pub lr_opac: f64,
 
+    /// End learning rate for the opacity parameter.
+    #[arg(long, help_heading = "Training options", default_value = "0.012")]
+    pub lr_opac_end: f64,
+
+    /// Learning rate schedule for the opacity parameter.
+    #[arg(
+        long,
+        help_heading = "Training options",
+        value_enum,
+        default_value = "constant"
+    )]
+    pub lr_opac_schedule: LrScheduleKind,
+
     /// Learning rate for the scale parameters.
     #[arg(long, help_heading = "Training options", default_value = "7e-3")]
     pub lr_scale: f64,
 
-    /// Learning rate for the scale parameters.
+    /// End learning rate for the scale parameters.
     #[arg(long, help_heading = "Training options", default_value = "5e-3")]
     pub lr_scale_end: f64,
 
+    /// Learning rate schedule for the scale parameters.
+    #[arg(
+        long,
+        help_heading = "Training options",
+        value_enum,
+        default_value = "exponential"
+    )]
+    pub lr_scale_schedule: LrScheduleKind,
+
     /// Learning rate for the rotation parameters.
     #[arg(long, help_heading = "Training options", default_value = "2e-3")]
     pub lr_rotation: f64,
 
+    /// End learning rate for the rotation parameters.
+    #[arg(long, help_heading = "Training options", default_value = "2e-3")]
+    pub lr_rotation_end: f64,
+
+    /// Learning rate schedule for the rotation parameters.
+    #[arg(
+        long,
+        help_heading = "Training options",
+        value_enum,
+        default_value = "constant"
+    )]
+    pub lr_rotation_schedule: LrScheduleKind,
+
+    /// Number of steps to linearly ramp up the learning rate for with the warmup-cosine schedule.
+    #[arg(long, help_heading = "Training options", default_value = "500")]
+    pub lr_warmup_steps: u32,
+
+    /// Number of times the learning rate drops with the step schedule.
+    #[arg(long, help_heading = "Training options", default_value = "3")]
+    pub lr_step_count: u32,
+
+    /// Learn a colour transform per training view, to compensate for exposure and white balance changes.
+    #[arg(long, help_heading = "Training options", default_value = "false")]
+    pub appearance: bool,
+
+    /// Learning rate for the per view colour transforms.
+    #[arg(long, help_heading = "Training options", default_value = "1e-3")]
+    pub lr_appearance: f64,
+
+    /// Learn corrections to the poses of the training cameras.
+    #[arg(long, help_heading = "Training options", default_value = "false")]
+    pub pose_refine: bool,
+
+    /// Learning rate for the camera pose corrections.
+    #[arg(long, help_heading = "Training options", default_value = "1e-5")]
+    pub lr_pose: f64,
+
+    /// Anti-alias with the filters of Mip-Splatting: a 3D smoothing filter based on the sampling
+    /// rate of the training views, and a 2D mip filter instead of a fixed dilation.
+    #[arg(long, help_heading = "Training options", default_value = "false")]
+    pub mip_filter: bool,
+
+    /// Resolution splats are rasterized at while training. Lower resolutions train faster, the
+    /// rendered image is upscaled to the size of the training view before computing the loss.
+    #[arg(
+        long,
+        help_heading = "Training options",
+        value_enum,
+        default_value = "native"
+    )]
+    pub render_scale: RenderScale,
+
     /// Frequency of 'refinement' where gaussians are replaced and densified. This should
     /// roughly be the number of images it takes to properly "cover" your scene.
     #[arg(long, help_heading = "Refine options", default_value = "200")]
@@ -65,6 +165,27 @@ pub struct TrainConfig {
     #[arg(long, help_heading = "Refine options", default_value = "15000")]
     pub growth_stop_iter: u32,
 
+    /// Strategy used to grow and prune splats.
+    #[arg(
+        long,
+        help_heading = "Refine options",
+        value_enum,
+        default_value = "default"
+    )]
+    pub refine_strategy: RefineStrategyKind,
+
+    /// MCMC strategy: fraction of the current number of splats to add at each refinement, until max-splats is reached.
+    #[arg(long, help_heading = "Refine options", default_value = "0.05")]
+    pub mcmc_grow_rate: f32,
+
+    /// MCMC strategy: weight of the loss on the mean opacity.
+    #[arg(long, help_heading = "Refine options", default_value = "0.01")]
+    pub mcmc_opac_reg: f32,
+
+    /// MCMC strategy: weight of the loss on the mean scale.
+    #[arg(long, help_heading = "Refine options", default_value = "0.01")]
+    pub mcmc_scale_reg: f32,
+
     /// Weight of SSIM loss (compared to l1 loss)
     #[clap(long, help_heading = "Training options", default_value = "0.2")]
     pub ssim_weight: f32,
@@ -87,6 +208,19 @@ pub struct TrainConfig {
 
     #[arg(long, help_heading = "Refine options", default_value = "0.0")]
     pub lpips_loss_weight: f32,
+
+    /// Weight of the loss on depth, for views that have a depth map. 0 disables depth supervision.
+    #[arg(long, help_heading = "Training options", default_value = "0.0")]
+    pub depth_loss_weight: f32,
+
+    /// How rendered depth is compared to the depth maps.
+    #[arg(
+        long,
+        help_heading = "Training options",
+        value_enum,
+        default_value = "metric"
+    )]
+    pub depth_loss: DepthLossKind,
 }
 
 impl Default for TrainConfig {
@@ -94,3 +228,22 @@ impl Default for TrainConfig {
         Self::parse_from([""])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_options_use_defaults() {
+        let config: TrainConfig =
+            serde_json::from_str(r#"{ "total_steps": 500, "lr_mean": 1e-4 }"#)
+                .expect("Failed to load config");
+        assert_eq!(config.total_steps, 500);
+        assert_eq!(config.lr_mean, 1e-4);
+
+        let defaults = TrainConfig::default();
+        assert_eq!(config.refine_every, defaults.refine_every);
+        assert_eq!(config.lr_pose, defaults.lr_pose);
+        assert_eq!(config.render_scale, defaults.render_scale);
+    }
+}
@@ -0,0 +1,105 @@
+use burn::{prelude::Backend, tensor::Tensor};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// How rendered depth is compared to the depth maps of the dataset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DepthLossKind {
+    /// L1 loss on the depth, for depth maps in scene units, eg. from LiDAR.
+    Metric,
+    /// L1 loss after aligning the depth map to the render with a least squares scale and shift.
+    /// Meant for relative depth maps, eg. from monocular depth estimators.
+    ScaleShiftInvariant,
+}
+
+impl DepthLossKind {
+    pub const ALL: [Self; 2] = [Self::Metric, Self::ScaleShiftInvariant];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Metric => "Metric",
+            Self::ScaleShiftInvariant => "Scale & shift invariant",
+        }
+    }
+
+    /// Loss between a rendered [H, W] depth map and a ground truth depth map.
+    ///
+    /// The rendered depth is alpha blended, and is normalized by the rendered [H, W] alpha.
+    /// Ground truth pixels with a depth of 0 are ignored.
+    pub(crate) fn loss<B: Backend>(
+        self,
+        pred_depth: Tensor<B, 2>,
+        pred_alpha: Tensor<B, 2>,
+        gt_depth: Tensor<B, 2>,
+    ) -> Tensor<B, 1> {
+        let valid = gt_depth.clone().greater_elem(0.0).float();
+        let num_valid = valid.clone().sum().clamp_min(1.0);
+        let pred_depth = pred_depth / pred_alpha.clamp_min(1e-3);
+
+        let target = match self {
+            Self::Metric => gt_depth,
+            Self::ScaleShiftInvariant => {
+                let (scale, shift) =
+                    fit_scale_shift(gt_depth.clone(), pred_depth.clone().detach(), valid.clone());
+                gt_depth * scale.unsqueeze() + shift.unsqueeze()
+            }
+        };
+
+        ((pred_depth - target).abs() * valid).sum() / num_valid
+    }
+}
+
+// Least squares fit of scale & shift such that `x * scale + shift` is closest to `y`, over the valid pixels.
+fn fit_scale_shift<B: Backend>(
+    x: Tensor<B, 2>,
+    y: Tensor<B, 2>,
+    valid: Tensor<B, 2>,
+) -> (Tensor<B, 1>, Tensor<B, 1>) {
+    let x = x * valid.clone();
+    let y = y * valid.clone();
+
+    let n = valid.sum();
+    let sx = x.clone().sum();
+    let sy = y.clone().sum();
+    let sxx = (x.clone() * x.clone()).sum();
+    let sxy = (x * y).sum();
+
+    let det = (n.clone() * sxx.clone() - sx.clone() * sx.clone()).clamp_min(1e-8);
+    let scale = (n * sxy.clone() - sx.clone() * sy.clone()) / det.clone();
+    let shift = (sxx * sy - sx * sxy) / det;
+    (scale, shift)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use brush_render::MainBackend;
+    use burn::backend::wgpu::WgpuDevice;
+
+    fn scalar(t: Tensor<MainBackend, 1>) -> f32 {
+        t.into_data().into_vec::<f32>().expect("Wrong type")[0]
+    }
+
+    #[tokio::test]
+    async fn test_depth_loss() {
+        let device = WgpuDevice::DefaultDevice;
+        let pred = Tensor::<MainBackend, 2>::from_floats([[1.0, 2.0], [3.0, 4.0]], &device);
+        let alpha = Tensor::<MainBackend, 2>::ones([2, 2], &device);
+
+        // The last pixel has no depth, and shouldn't count.
+        let gt = Tensor::<MainBackend, 2>::from_floats([[1.5, 2.0], [2.0, 0.0]], &device);
+        let metric = DepthLossKind::Metric.loss(pred.clone(), alpha.clone(), gt);
+        assert!((scalar(metric) - 0.5).abs() < 1e-5);
+
+        // A depth map that's off by a scale and shift gives no loss when invariant to these.
+        let gt = pred.clone() * 0.5 + 3.0;
+        let invariant = DepthLossKind::ScaleShiftInvariant.loss(pred.clone(), alpha, gt);
+        assert!(scalar(invariant) < 1e-3);
+
+        // Rendered depth is normalized by alpha.
+        let half_alpha = Tensor::<MainBackend, 2>::ones([2, 2], &device) * 0.5;
+        let metric = DepthLossKind::Metric.loss(pred.clone() * 0.5, half_alpha, pred);
+        assert!(scalar(metric) < 1e-5);
+    }
+}
@@ -1,29 +1,43 @@
 use anyhow::Result;
 use brush_dataset::scene::{sample_to_tensor, view_to_sample_image};
-use brush_render::SplatForward;
 use brush_render::camera::Camera;
 use brush_render::gaussian_splats::Splats;
 use brush_render::render_aux::RenderAux;
+use brush_render::{RenderScale, SplatForward};
 use burn::prelude::Backend;
 use burn::tensor::{Tensor, TensorPrimitive, s};
 use glam::Vec3;
 use image::DynamicImage;
 
-use crate::ssim::Ssim;
+use crate::{
+    appearance::{apply_color_transform, fit_color_transform},
+    ssim::Ssim,
+};
 
 pub struct EvalSample<B: Backend> {
     pub gt_img: DynamicImage,
     pub rendered: Tensor<B, 3>,
     pub psnr: Tensor<B, 1>,
     pub ssim: Tensor<B, 1>,
+    /// LPIPS, if a model to compute it was given.
+    pub lpips: Option<Tensor<B, 1>>,
     pub aux: RenderAux<B>,
 }
 
-pub fn eval_stats<B: Backend + SplatForward<B>>(
+/// Render a view and compare it to the ground truth image.
+///
+/// When `fit_appearance` is set, the colour transform of the view is fit on the left half of the
+/// image, and the metrics are only computed on the right half. This is needed for models trained
+/// with appearance compensation, as there is no learned appearance for the eval views.
+///
+/// LPIPS is only computed when an `lpips` model is given, which isn't available on the web.
+pub async fn eval_stats<B: Backend + SplatForward<B>>(
     splats: &Splats<B>,
     gt_cam: &Camera,
     gt_img: DynamicImage,
     alpha_is_mask: bool,
+    fit_appearance: bool,
+    #[cfg(not(target_family = "wasm"))] lpips: Option<&lpips::LpipsNet<B>>,
     device: &B::Device,
 ) -> Result<EvalSample<B>> {
     // Compare MSE in RGB only.
@@ -39,30 +53,67 @@ pub fn eval_stats<B: Backend + SplatForward<B>>(
             gt_cam,
             res,
             splats.means.val().into_primitive().tensor(),
-            splats.log_scales.val().into_primitive().tensor(),
+            splats.filtered_log_scales().into_primitive().tensor(),
             splats.rotation.val().into_primitive().tensor(),
             splats.sh_coeffs.val().into_primitive().tensor(),
-            splats.raw_opacity.val().into_primitive().tensor(),
+            splats.filtered_raw_opacity().into_primitive().tensor(),
             Vec3::ZERO,
+            splats.filter_3d.is_some(),
+            // Always evaluate at native resolution, to be comparable with other tools.
+            RenderScale::Native,
+            false,
+            false,
             true,
+            false,
         );
         (Tensor::from_primitive(TensorPrimitive::Float(img)), aux)
     };
     let render_rgb = img.slice(s![.., .., 0..3]);
 
+    let half_w = res.x as usize / 2;
+    let render_rgb = if fit_appearance {
+        let transform = fit_color_transform(
+            render_rgb.clone().slice(s![.., 0..half_w, ..]),
+            gt_rgb.clone().slice(s![.., 0..half_w, ..]),
+        )
+        .await;
+        apply_color_transform(render_rgb, transform).clamp(0.0, 1.0)
+    } else {
+        render_rgb
+    };
+
     // Simulate an 8-bit roundtrip for fair comparison.
     let render_rgb = (render_rgb * 255.0).round() / 255.0;
 
-    let mse = (render_rgb.clone() - gt_rgb.clone()).powi_scalar(2).mean();
+    let (metric_render, metric_gt) = if fit_appearance {
+        (
+            render_rgb.clone().slice(s![.., half_w.., ..]),
+            gt_rgb.slice(s![.., half_w.., ..]),
+        )
+    } else {
+        (render_rgb.clone(), gt_rgb)
+    };
+
+    let mse = (metric_render.clone() - metric_gt.clone())
+        .powi_scalar(2)
+        .mean();
 
     let psnr = mse.recip().log() * 10.0 / std::f32::consts::LN_10;
     let ssim_measure = Ssim::new(11, 3, device);
-    let ssim = ssim_measure.ssim(render_rgb.clone(), gt_rgb).mean();
+    let ssim = ssim_measure
+        .ssim(metric_render.clone(), metric_gt.clone())
+        .mean();
+    #[cfg(not(target_family = "wasm"))]
+    let lpips =
+        lpips.map(|lpips| lpips.lpips(metric_render.unsqueeze_dim(0), metric_gt.unsqueeze_dim(0)));
+    #[cfg(target_family = "wasm")]
+    let lpips = None;
 
     Ok(EvalSample {
         gt_img,
         psnr,
         ssim,
+        lpips,
         rendered: render_rgb,
         aux,
     })
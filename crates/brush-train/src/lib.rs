@@ -1,12 +1,19 @@
 #![recursion_limit = "256"]
 
+pub mod checkpoint;
 pub mod config;
+pub mod depth_loss;
 pub mod eval;
+pub mod lr_schedule;
 pub mod msg;
+pub mod refine;
 pub mod train;
 
 mod adam_scaled;
+mod appearance;
+mod mip_filter;
 mod multinomial;
+mod pose;
 mod quat_vec;
 mod ssim;
 mod stats;
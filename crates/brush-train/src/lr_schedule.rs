@@ -0,0 +1,164 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+/// How a learning rate moves from its start to its end value over the course of training.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LrScheduleKind {
+    /// Keep the start learning rate for the whole run.
+    Constant,
+    /// Decay exponentially from the start to the end learning rate.
+    Exponential,
+    /// Follow half a cosine from the start to the end learning rate.
+    Cosine,
+    /// Ramp up linearly from zero over the warmup steps, then follow a cosine decay.
+    WarmupCosine,
+    /// Decay in a few discrete drops, reaching the end learning rate after the last drop.
+    Step,
+}
+
+impl LrScheduleKind {
+    pub const ALL: [Self; 5] = [
+        Self::Constant,
+        Self::Exponential,
+        Self::Cosine,
+        Self::WarmupCosine,
+        Self::Step,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Constant => "Constant",
+            Self::Exponential => "Exponential",
+            Self::Cosine => "Cosine",
+            Self::WarmupCosine => "Warmup + cosine",
+            Self::Step => "Step",
+        }
+    }
+}
+
+/// A learning rate schedule for one parameter group.
+///
+/// Schedules are a pure function of the iteration, so there's no state to keep track of.
+#[derive(Clone, Debug)]
+pub struct LrSchedule {
+    pub kind: LrScheduleKind,
+    pub start: f64,
+    pub end: f64,
+    pub total_steps: u32,
+    pub warmup_steps: u32,
+    pub step_count: u32,
+}
+
+impl LrSchedule {
+    pub fn lr(&self, iter: u32) -> f64 {
+        let t = (iter as f64 / self.total_steps.max(1) as f64).clamp(0.0, 1.0);
+
+        match self.kind {
+            LrScheduleKind::Constant => self.start,
+            LrScheduleKind::Exponential => exp_lerp(self.start, self.end, t),
+            LrScheduleKind::Cosine => cosine(self.start, self.end, t),
+            LrScheduleKind::WarmupCosine => {
+                if iter < self.warmup_steps {
+                    self.start * (iter + 1) as f64 / self.warmup_steps as f64
+                } else {
+                    let decay_steps = self.total_steps.saturating_sub(self.warmup_steps).max(1);
+                    let t = ((iter - self.warmup_steps) as f64 / decay_steps as f64).min(1.0);
+                    cosine(self.start, self.end, t)
+                }
+            }
+            LrScheduleKind::Step => {
+                let drops = self.step_count.max(1) as f64;
+                let cur_drop = (t * (drops + 1.0)).floor().min(drops);
+                exp_lerp(self.start, self.end, cur_drop / drops)
+            }
+        }
+    }
+}
+
+fn exp_lerp(start: f64, end: f64, t: f64) -> f64 {
+    start * (end / start).powf(t)
+}
+
+fn cosine(start: f64, end: f64, t: f64) -> f64 {
+    end + 0.5 * (start - end) * (1.0 + (PI * t).cos())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(kind: LrScheduleKind) -> LrSchedule {
+        LrSchedule {
+            kind,
+            start: 1e-2,
+            end: 1e-4,
+            total_steps: 1000,
+            warmup_steps: 100,
+            step_count: 3,
+        }
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{a} != {b}");
+    }
+
+    #[test]
+    fn test_constant() {
+        let sched = schedule(LrScheduleKind::Constant);
+        assert_close(sched.lr(0), 1e-2);
+        assert_close(sched.lr(1000), 1e-2);
+    }
+
+    #[test]
+    fn test_start_end_values() {
+        for kind in [
+            LrScheduleKind::Exponential,
+            LrScheduleKind::Cosine,
+            LrScheduleKind::Step,
+        ] {
+            let sched = schedule(kind);
+            assert_close(sched.lr(0), 1e-2);
+            assert_close(sched.lr(1000), 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_exponential_midpoint() {
+        let sched = schedule(LrScheduleKind::Exponential);
+        assert_close(sched.lr(500), 1e-3);
+    }
+
+    #[test]
+    fn test_warmup_cosine() {
+        let sched = schedule(LrScheduleKind::WarmupCosine);
+        assert!(sched.lr(0) < sched.lr(50));
+        assert_close(sched.lr(99), 1e-2);
+        assert_close(sched.lr(100), 1e-2);
+        assert!(sched.lr(500) < 1e-2);
+        assert_close(sched.lr(1000), 1e-4);
+    }
+
+    #[test]
+    fn test_step_is_piecewise_constant() {
+        let sched = schedule(LrScheduleKind::Step);
+        assert_close(sched.lr(10), sched.lr(200));
+        assert!(sched.lr(300) < sched.lr(200));
+        assert_close(sched.lr(300), sched.lr(400));
+    }
+
+    #[test]
+    fn test_monotonic_decay() {
+        for kind in [
+            LrScheduleKind::Exponential,
+            LrScheduleKind::Cosine,
+            LrScheduleKind::Step,
+        ] {
+            let sched = schedule(kind);
+            for i in 0..1000 {
+                assert!(sched.lr(i + 1) <= sched.lr(i), "{kind:?} increased at {i}");
+            }
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use brush_render::camera::Camera;
+use burn::{
+    prelude::Backend,
+    tensor::{Tensor, s},
+};
+
+/// Variance of the 3D filter in screen space, at the highest sampling rate of a splat.
+const FILTER_VARIANCE: f32 = 0.2;
+
+/// Fraction of the image outside of the frustum in which splats still count as visible.
+const FRUSTUM_MARGIN: f32 = 0.15;
+
+// Splats closer than this to a camera don't count as visible.
+const NEAR_PLANE: f32 = 0.2;
+
+/// Size of the Mip-Splatting 3D smoothing filter for splats at the given [N, 3] positions.
+///
+/// The filter limits how small splats can be, based on the highest sampling rate (focal length
+/// over depth) of any training camera that sees them. Splats that aren't seen by any camera use
+/// the lowest sampling rate of all splats.
+pub(crate) fn filter_3d<B: Backend>(
+    means: Tensor<B, 2>,
+    cameras: impl IntoIterator<Item = (Camera, glam::UVec2)>,
+) -> Tensor<B, 1> {
+    let [n, _] = means.dims();
+    let device = means.device();
+
+    let mut max_rate = Tensor::<B, 1>::zeros([n], &device);
+
+    for (camera, img_size) in cameras {
+        let world_to_local = camera.world_to_local();
+        // Transpose of the rotation, as the means are row vectors.
+        let rotation = Tensor::<B, 1>::from_floats(
+            glam::Mat3::from(world_to_local.matrix3)
+                .to_cols_array()
+                .as_slice(),
+            &device,
+        )
+        .reshape([3, 3]);
+        let translation = Tensor::<B, 1>::from_floats(
+            glam::Vec3::from(world_to_local.translation)
+                .to_array()
+                .as_slice(),
+            &device,
+        )
+        .reshape([1, 3]);
+        let means_c = means.clone().matmul(rotation) + translation;
+
+        let z = means_c.clone().slice(s![.., 2..3]);
+        let uv = means_c.slice(s![.., 0..2]) / z.clone().clamp_min(NEAR_PLANE);
+        let z = z.squeeze(1);
+
+        let focal = camera.focal(img_size);
+        let center = camera.center(img_size);
+        let size = img_size.as_vec2();
+        let lims_neg = (-FRUSTUM_MARGIN * size - center) / focal;
+        let lims_pos = ((1.0 + FRUSTUM_MARGIN) * size - center) / focal;
+
+        let u = uv.clone().slice(s![.., 0..1]).squeeze(1);
+        let v = uv.slice(s![.., 1..2]).squeeze(1);
+
+        let visible = z
+            .clone()
+            .greater_elem(NEAR_PLANE)
+            .bool_and(u.clone().greater_equal_elem(lims_neg.x))
+            .bool_and(u.lower_equal_elem(lims_pos.x))
+            .bool_and(v.clone().greater_equal_elem(lims_neg.y))
+            .bool_and(v.lower_equal_elem(lims_pos.y));
+
+        let rate = (z.recip() * focal.max_element()) * visible.float();
+        max_rate = max_rate.max_pair(rate);
+    }
+
+    let unseen = max_rate.clone().equal_elem(0.0);
+    let min_rate = max_rate.clone().mask_fill(unseen.clone(), f32::MAX).min();
+    let max_rate = max_rate.mask_where(unseen, min_rate.expand([n]));
+
+    max_rate.recip() * FILTER_VARIANCE.sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use brush_render::MainBackend;
+    use burn::backend::wgpu::WgpuDevice;
+
+    #[tokio::test]
+    async fn test_filter_3d() {
+        let device = WgpuDevice::DefaultDevice;
+        let camera = Camera::new(
+            glam::Vec3::ZERO,
+            glam::Quat::IDENTITY,
+            0.5,
+            0.5,
+            glam::vec2(0.5, 0.5),
+        );
+        let img_size = glam::uvec2(64, 64);
+        let focal = camera.focal(img_size).x;
+
+        // Two splats in front of the camera, and one behind it.
+        let means = Tensor::<MainBackend, 2>::from_floats(
+            [[0.0, 0.0, 1.0], [0.0, 0.0, 4.0], [0.0, 0.0, -1.0]],
+            &device,
+        );
+        let filter = filter_3d(means, [(camera, img_size)])
+            .into_data()
+            .into_vec::<f32>()
+            .expect("Wrong type");
+
+        let expected = FILTER_VARIANCE.sqrt() / focal;
+        assert!((filter[0] - expected).abs() < 1e-6);
+        // Further away splats are sampled less densely, and are filtered more.
+        assert!((filter[1] - expected * 4.0).abs() < 1e-6);
+        // Unseen splats use the lowest sampling rate.
+        assert!((filter[2] - filter[1]).abs() < 1e-6);
+    }
+}
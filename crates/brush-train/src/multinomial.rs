@@ -1,7 +1,8 @@
-pub(crate) fn multinomial_sample(weights: &[f32], n: u32) -> Vec<i32> {
-    let mut rng = rand::rng();
+use rand::Rng;
+
+pub(crate) fn multinomial_sample(weights: &[f32], n: u32, rng: &mut impl Rng) -> Vec<i32> {
     rand::seq::index::sample_weighted(
-        &mut rng,
+        rng,
         weights.len(),
         |i| if weights[i].is_nan() { 0.0 } else { weights[i] },
         n as usize,
@@ -27,7 +28,7 @@ mod tests {
     fn test_multinomial_sampling() {
         // Test the complete multinomial sampling workflow (samples indices without replacement)
         let weights = vec![0.1, 0.3, 0.4, 0.2];
-        let samples = multinomial_sample(&weights, 3);
+        let samples = multinomial_sample(&weights, 3, &mut rand::rng());
 
         assert_eq!(samples.len(), 3);
         for &sample in &samples {
@@ -41,7 +42,7 @@ mod tests {
 
         // Test edge case: sampling all indices
         let single_weight = vec![1.0];
-        let single_samples = multinomial_sample(&single_weight, 1);
+        let single_samples = multinomial_sample(&single_weight, 1, &mut rand::rng());
         assert_eq!(single_samples.len(), 1);
         assert_eq!(single_samples[0], 0);
     }
@@ -50,7 +51,7 @@ mod tests {
     fn test_nan_weight_handling() {
         // Test that NaN weights are handled (converted to 0.0)
         let weights_with_nan = vec![0.5, f32::NAN, 0.3, 0.2];
-        let samples = multinomial_sample(&weights_with_nan, 2);
+        let samples = multinomial_sample(&weights_with_nan, 2, &mut rand::rng());
 
         assert_eq!(samples.len(), 2);
         // Should never sample index 1 (NaN weight becomes 0.0)
@@ -65,7 +66,7 @@ mod tests {
     fn test_all_zero_weights() {
         // Discovered behavior: returns empty vec when all weights are zero
         let zero_weights = vec![0.0, 0.0, 0.0];
-        let result = multinomial_sample(&zero_weights, 1);
+        let result = multinomial_sample(&zero_weights, 1, &mut rand::rng());
 
         // Function returns empty vector when it cannot sample any valid indices
         assert_eq!(result.len(), 0);
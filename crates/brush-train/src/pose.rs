@@ -0,0 +1,219 @@
+use crate::adam_scaled::{AdamScaled, AdamScaledConfig, AdamState};
+use brush_render::{MainBackend, camera::Camera};
+use burn::{
+    backend::Autodiff,
+    module::{Module, Param, ParamId},
+    optim::{GradientsParams, Optimizer, adaptor::OptimizerAdaptor, record::AdaptorRecord},
+    prelude::Backend,
+    tensor::{Int, Tensor, TensorData},
+};
+use glam::{Affine3A, Quat, Vec3};
+use hashbrown::HashMap;
+
+type DiffBackend = Autodiff<MainBackend>;
+
+/// Per-view pose corrections, stored as `[translation, rotation]` where the rotation is a scaled axis.
+#[derive(Module, Debug)]
+pub(crate) struct PoseModel<B: Backend> {
+    pub deltas: Vec<Param<Tensor<B, 1>>>,
+}
+
+/// Learnable corrections to the poses of the training cameras.
+///
+/// Each view has a small rigid transform which is applied to its view matrix, in camera space.
+/// Rendering needs the corrections on the CPU, and reading them back every step would stall training.
+/// Instead the corrections are read back periodically with [`Self::sync`], and views render with the
+/// latest values that were read back.
+pub(crate) struct PoseRefiner {
+    model: PoseModel<DiffBackend>,
+    optim: OptimizerAdaptor<AdamScaled, PoseModel<DiffBackend>, DiffBackend>,
+    cached: Vec<[f32; 6]>,
+}
+
+impl PoseRefiner {
+    pub(crate) fn new() -> Self {
+        Self {
+            model: PoseModel { deltas: vec![] },
+            optim: AdamScaledConfig::new().with_epsilon(1e-15).init(),
+            cached: vec![],
+        }
+    }
+
+    /// The camera of a view, with the latest known correction applied.
+    pub(crate) fn refined_camera(&self, view_index: usize, camera: &Camera) -> Camera {
+        self.cached
+            .get(view_index)
+            .map_or_else(|| camera.clone(), |delta| apply_pose_delta(camera, *delta))
+    }
+
+    /// Update the correction of a view, from the gradient of the view matrix it was rendered with.
+    pub(crate) fn step(
+        &mut self,
+        lr: f64,
+        view_index: usize,
+        rendered_camera: &Camera,
+        v_viewmat: Tensor<MainBackend, 2>,
+    ) {
+        let device = v_viewmat.device();
+
+        while self.model.deltas.len() <= view_index {
+            self.model.deltas.push(Param::initialized(
+                ParamId::new(),
+                Tensor::zeros([6], &device).require_grad(),
+            ));
+        }
+
+        // The correction is applied as viewmat' = [exp(rotation) | translation] * viewmat. For a
+        // small change, the gradient of the translation is the translation column of the gradient,
+        // and the gradient of the rotation is the skew symmetric part of v_viewmat * viewmat^T.
+        let world_to_local = rendered_camera.world_to_local();
+        let mat = world_to_local.matrix3;
+        let trans = world_to_local.translation;
+        let viewmat = Tensor::<MainBackend, 2>::from_data(
+            TensorData::new(
+                vec![
+                    mat.x_axis.x,
+                    mat.y_axis.x,
+                    mat.z_axis.x,
+                    trans.x,
+                    mat.x_axis.y,
+                    mat.y_axis.y,
+                    mat.z_axis.y,
+                    trans.y,
+                    mat.x_axis.z,
+                    mat.y_axis.z,
+                    mat.z_axis.z,
+                    trans.z,
+                ],
+                [3, 4],
+            ),
+            &device,
+        );
+        let a = v_viewmat.clone().matmul(viewmat.transpose());
+        let skew = (a.clone() - a.transpose()).reshape([9]);
+        let v_rotation = skew.select(0, Tensor::<_, 1, Int>::from_ints([7, 2, 3], &device));
+        let v_translation = v_viewmat.slice([0..3, 3..4]).reshape([3]);
+        let v_delta = Tensor::cat(vec![v_translation, v_rotation], 0);
+
+        let mut grads = GradientsParams::new();
+        grads.register(self.model.deltas[view_index].id, v_delta);
+
+        let model = std::mem::replace(&mut self.model, PoseModel { deltas: vec![] });
+        self.model = self.optim.step(lr, model, grads);
+    }
+
+    /// Read back the latest corrections to render with.
+    pub(crate) async fn sync(&mut self) {
+        if self.model.deltas.is_empty() {
+            return;
+        }
+
+        let deltas = Tensor::cat(
+            self.model.deltas.iter().map(|d| d.val().inner()).collect(),
+            0,
+        );
+        let deltas = deltas
+            .into_data_async()
+            .await
+            .into_vec::<f32>()
+            .expect("Wrong type");
+
+        self.cached = deltas
+            .chunks_exact(6)
+            .map(|d| d.try_into().expect("Chunks are 6 elements"))
+            .collect();
+    }
+
+    pub(crate) fn to_record(
+        &self,
+    ) -> (
+        Vec<Tensor<MainBackend, 1>>,
+        Vec<Option<AdamState<MainBackend, 1>>>,
+        Vec<[f32; 6]>,
+    ) {
+        let mut optim_record = self.optim.to_record();
+        let (deltas, states) = self
+            .model
+            .deltas
+            .iter()
+            .map(|d| {
+                let state = optim_record.remove(&d.id).map(AdaptorRecord::into_state);
+                (d.val().inner(), state)
+            })
+            .unzip();
+        (deltas, states, self.cached.clone())
+    }
+
+    pub(crate) fn from_record(
+        deltas: Vec<Tensor<MainBackend, 1>>,
+        states: Vec<Option<AdamState<MainBackend, 1>>>,
+        cached: Vec<[f32; 6]>,
+    ) -> Self {
+        let deltas: Vec<_> = deltas
+            .into_iter()
+            .map(|d| Param::initialized(ParamId::new(), Tensor::from_inner(d).require_grad()))
+            .collect();
+
+        let optim_record: HashMap<_, _> = deltas
+            .iter()
+            .zip(states)
+            .filter_map(|(d, state)| Some((d.id, AdaptorRecord::from_state(state?))))
+            .collect();
+
+        Self {
+            model: PoseModel { deltas },
+            optim: AdamScaledConfig::new()
+                .with_epsilon(1e-15)
+                .init()
+                .load_record(optim_record),
+            cached,
+        }
+    }
+}
+
+/// Apply a `[translation, rotation]` correction to the view matrix of a camera.
+pub(crate) fn apply_pose_delta(camera: &Camera, delta: [f32; 6]) -> Camera {
+    let translation = Vec3::new(delta[0], delta[1], delta[2]);
+    let rotation = Quat::from_scaled_axis(Vec3::new(delta[3], delta[4], delta[5]));
+    let world_to_local =
+        Affine3A::from_rotation_translation(rotation, translation) * camera.world_to_local();
+    let (_, rotation, position) = world_to_local.inverse().to_scale_rotation_translation();
+
+    Camera {
+        position,
+        rotation,
+        ..camera.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zero_delta_is_identity() {
+        let camera = Camera::new(
+            glam::vec3(1.0, -2.0, 3.0),
+            Quat::from_rotation_y(0.5),
+            0.8,
+            0.6,
+            glam::vec2(0.5, 0.5),
+        );
+        let refined = apply_pose_delta(&camera, [0.0; 6]);
+        assert!(refined.position.abs_diff_eq(camera.position, 1e-5));
+        assert!(refined.rotation.abs_diff_eq(camera.rotation, 1e-5));
+    }
+
+    #[test]
+    fn test_translation_moves_camera() {
+        let camera = Camera::new(Vec3::ZERO, Quat::IDENTITY, 0.8, 0.6, glam::vec2(0.5, 0.5));
+        // Moving the world by +x in camera space moves the camera by -x.
+        let refined = apply_pose_delta(&camera, [1.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        assert!(
+            refined
+                .position
+                .abs_diff_eq(glam::vec3(-1.0, 0.0, 0.0), 1e-5)
+        );
+        assert!(refined.rotation.abs_diff_eq(Quat::IDENTITY, 1e-5));
+    }
+}
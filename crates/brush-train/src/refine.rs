@@ -0,0 +1,627 @@
+use crate::{
+    adam_scaled::{AdamScaled, AdamState},
+    config::TrainConfig,
+    msg::RefineStats,
+    multinomial::multinomial_sample,
+    quat_vec::quaternion_vec_multiply,
+    stats::RefineRecord,
+};
+
+use brush_render::{MainBackend, bounding_box::BoundingBox, gaussian_splats::Splats};
+use burn::{
+    backend::Autodiff,
+    module::ParamId,
+    optim::record::AdaptorRecord,
+    prelude::Backend,
+    tensor::{
+        Bool, Distribution, Int, Tensor, TensorData, activation::sigmoid, backend::AutodiffBackend,
+    },
+};
+use clap::ValueEnum;
+use hashbrown::{HashMap, HashSet};
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+
+const MIN_OPACITY: f32 = 1.0 / 255.0;
+
+type DiffBackend = Autodiff<MainBackend>;
+
+/// How splats are added and removed during training.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RefineStrategyKind {
+    /// Grow splats with large gradients, and resample dead splats from visible ones.
+    Default,
+    /// 3DGS-MCMC: relocate dead splats to live ones, and grow towards a fixed budget of `max_splats`.
+    Mcmc,
+}
+
+impl RefineStrategyKind {
+    pub const ALL: [Self; 2] = [Self::Default, Self::Mcmc];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Default => "Default",
+            Self::Mcmc => "MCMC",
+        }
+    }
+
+    pub(crate) fn regularization(
+        self,
+        config: &TrainConfig,
+        splats: &Splats<DiffBackend>,
+    ) -> Option<Tensor<DiffBackend, 1>> {
+        match self {
+            Self::Default => DefaultRefine.regularization(config, splats),
+            Self::Mcmc => McmcRefine.regularization(config, splats),
+        }
+    }
+
+    pub(crate) async fn refine(
+        self,
+        ctx: RefineContext<'_>,
+        splats: Splats<DiffBackend>,
+    ) -> (Splats<DiffBackend>, RefineStats) {
+        match self {
+            Self::Default => DefaultRefine.refine(ctx, splats).await,
+            Self::Mcmc => McmcRefine.refine(ctx, splats).await,
+        }
+    }
+}
+
+/// Everything a refine strategy has access to while refining.
+pub(crate) struct RefineContext<'a> {
+    pub config: &'a TrainConfig,
+    pub iter: u32,
+    /// Fraction of training that has been done, from 0 to 1.
+    pub train_t: f32,
+    pub bounds: &'a BoundingBox,
+    pub rng: &'a mut StdRng,
+    /// Optimizer state, which has to be kept in sync with the splats.
+    pub record: &'a mut HashMap<ParamId, AdaptorRecord<AdamScaled, DiffBackend>>,
+    pub refiner: RefineRecord<MainBackend>,
+}
+
+pub(crate) trait RefineStrategy {
+    /// Extra loss terms this strategy adds to every training step.
+    fn regularization(
+        &self,
+        _config: &TrainConfig,
+        _splats: &Splats<DiffBackend>,
+    ) -> Option<Tensor<DiffBackend, 1>> {
+        None
+    }
+
+    /// Prune, relocate and grow splats.
+    async fn refine(
+        &self,
+        ctx: RefineContext<'_>,
+        splats: Splats<DiffBackend>,
+    ) -> (Splats<DiffBackend>, RefineStats);
+}
+
+/// Grows splats whose gradients are above a threshold, and replaces dead splats by
+/// resampling visible splats weighted by their opacity.
+pub(crate) struct DefaultRefine;
+
+impl RefineStrategy for DefaultRefine {
+    async fn refine(
+        &self,
+        ctx: RefineContext<'_>,
+        splats: Splats<DiffBackend>,
+    ) -> (Splats<DiffBackend>, RefineStats) {
+        let RefineContext {
+            config,
+            iter,
+            train_t,
+            bounds,
+            rng,
+            record,
+            refiner,
+        } = ctx;
+
+        let device = splats.device();
+
+        // Prune dead splats. This ALWAYS happen even if we're not "refining" anymore.
+        let alpha_mask = splats.opacities().inner().lower_elem(MIN_OPACITY);
+        let prune_mask = alpha_mask.bool_or(invalid_mask(&splats, bounds));
+
+        let (mut splats, refiner, pruned_count) =
+            prune_points(splats, record, refiner, prune_mask).await;
+        let mut split_inds = HashSet::new();
+
+        // Replace dead gaussians.
+        if pruned_count > 0 {
+            // Sample weighted by opacity from splat visible during optimization.
+            let resampled_weights = splats.opacities().inner() * refiner.vis_mask().float();
+            let resampled_weights = resampled_weights
+                .into_data_async()
+                .await
+                .into_vec::<f32>()
+                .expect("Failed to read weights");
+            let resampled_inds = multinomial_sample(&resampled_weights, pruned_count, rng);
+            split_inds.extend(resampled_inds);
+        }
+
+        if iter < config.growth_stop_iter {
+            let above_threshold = refiner.above_threshold(config.growth_grad_threshold);
+
+            let threshold_count = above_threshold
+                .clone()
+                .int()
+                .sum()
+                .into_scalar_async()
+                .await as u32;
+
+            let grow_count =
+                (threshold_count as f32 * config.growth_select_fraction).round() as u32;
+
+            let sample_high_grad = grow_count.saturating_sub(pruned_count);
+
+            // Only grow to the max nr. of splats.
+            let cur_splats = splats.num_splats() + split_inds.len() as u32;
+            let grow_count = sample_high_grad.min(config.max_splats.saturating_sub(cur_splats));
+
+            // If still growing, sample from indices which are over the threshold.
+            if grow_count > 0 {
+                let weights = above_threshold.float() * refiner.refine_weight_norm;
+                let weights = weights
+                    .into_data_async()
+                    .await
+                    .into_vec::<f32>()
+                    .expect("Failed to read weights");
+                let growth_inds = multinomial_sample(&weights, grow_count, rng);
+                split_inds.extend(growth_inds);
+            }
+        }
+
+        let refine_count = split_inds.len();
+
+        if refine_count > 0 {
+            let refine_inds = Tensor::from_data(
+                TensorData::new(split_inds.into_iter().collect(), [refine_count]),
+                &device,
+            );
+
+            let cur_rots = splats
+                .rotations_normed()
+                .inner()
+                .select(0, refine_inds.clone());
+            let cur_log_scale = splats
+                .log_scales
+                .val()
+                .inner()
+                .select(0, refine_inds.clone());
+            let cur_raw_opac = splats
+                .raw_opacity
+                .val()
+                .inner()
+                .select(0, refine_inds.clone());
+
+            // The amount to offset the scale and opacity should maybe depend on how far away we have sampled these gaussians,
+            // but a fixed amount seems to work ok. The only note is that divide by _less_ than SQRT(2) seems to exponentially
+            // blow up, as more 'mass' is added each refine.
+            let cur_scales = cur_log_scale.exp();
+
+            let cur_opac = sigmoid(cur_raw_opac);
+            let inv_opac: Tensor<_, 1> = 1.0 - cur_opac;
+            let new_opac: Tensor<_, 1> = 1.0 - inv_opac.sqrt();
+            let new_raw_opac = inv_sigmoid(new_opac.clamp(MIN_OPACITY, 1.0 - MIN_OPACITY));
+            let new_scales = scale_down_largest_dim(cur_scales.clone(), 0.5);
+
+            // Move in direction of scaling axis.
+            let offset = quaternion_vec_multiply(
+                cur_rots,
+                Tensor::random([refine_count, 1], Distribution::Normal(0.0, 1.0), &device)
+                    * cur_scales,
+            );
+
+            splats = split_splats(
+                splats,
+                record,
+                refine_inds,
+                offset,
+                new_scales.log(),
+                new_raw_opac,
+            );
+        }
+
+        let t_shrink_strength = 1.0 - train_t;
+        let minus_opac = config.opac_decay * t_shrink_strength;
+        let scale_scaling = 1.0 - config.scale_decay * t_shrink_strength;
+
+        // Lower opacity slowly over time.
+        splats.raw_opacity = splats.raw_opacity.map(|f| {
+            let new_opac = sigmoid(f.inner()) - minus_opac;
+            Tensor::from_inner(inv_sigmoid(new_opac.clamp(1e-12, 1.0 - 1e-12))).require_grad()
+        });
+
+        splats.log_scales = splats.log_scales.map(|f| {
+            let new_scale = f.inner().exp() * scale_scaling;
+            Tensor::from_inner(new_scale.log()).require_grad()
+        });
+
+        (
+            splats,
+            RefineStats {
+                num_added: refine_count as u32,
+                num_pruned: pruned_count,
+            },
+        )
+    }
+}
+
+/// Densification from "3D Gaussian Splatting as Markov Chain Monte Carlo".
+///
+/// Dead splats are relocated onto live splats, and new splats are spawned at a fixed rate
+/// until `max_splats` is reached. Relocating and spawning both split a splat in place,
+/// correcting the opacity and scale such that the rendered result stays roughly the same.
+pub(crate) struct McmcRefine;
+
+impl RefineStrategy for McmcRefine {
+    fn regularization(
+        &self,
+        config: &TrainConfig,
+        splats: &Splats<DiffBackend>,
+    ) -> Option<Tensor<DiffBackend, 1>> {
+        let opac_reg = splats.opacities().mean() * config.mcmc_opac_reg;
+        let scale_reg = splats.scales().mean() * config.mcmc_scale_reg;
+        Some(opac_reg + scale_reg)
+    }
+
+    async fn refine(
+        &self,
+        ctx: RefineContext<'_>,
+        splats: Splats<DiffBackend>,
+    ) -> (Splats<DiffBackend>, RefineStats) {
+        let RefineContext {
+            config,
+            iter,
+            bounds,
+            rng,
+            record,
+            refiner,
+            ..
+        } = ctx;
+
+        let device = splats.device();
+
+        let dead_mask = splats
+            .opacities()
+            .inner()
+            .lower_elem(MIN_OPACITY)
+            .bool_or(invalid_mask(&splats, bounds));
+        let (splats, _, dead_count) = prune_points(splats, record, refiner, dead_mask).await;
+
+        // Grow by a fixed fraction of the current count, up to the budget.
+        let cur_splats = splats.num_splats() + dead_count;
+        let grow_count = if iter < config.growth_stop_iter {
+            let target = (cur_splats as f32 * (1.0 + config.mcmc_grow_rate)) as u32;
+            target.min(config.max_splats).saturating_sub(cur_splats)
+        } else {
+            0
+        };
+
+        // Dead splats are relocated to live splats by splitting those, the same way new splats are grown.
+        let sample_count = (dead_count + grow_count).min(splats.num_splats());
+
+        let split_inds = if sample_count > 0 {
+            let weights = splats
+                .opacities()
+                .inner()
+                .into_data_async()
+                .await
+                .into_vec::<f32>()
+                .expect("Failed to read weights");
+            multinomial_sample(&weights, sample_count, rng)
+        } else {
+            vec![]
+        };
+        let split_count = split_inds.len();
+
+        let splats = if split_count > 0 {
+            let split_inds = Tensor::from_data(TensorData::new(split_inds, [split_count]), &device);
+
+            let cur_opac = splats.opacities().inner().select(0, split_inds.clone());
+            let cur_log_scale = splats
+                .log_scales
+                .val()
+                .inner()
+                .select(0, split_inds.clone());
+
+            // Each sampled splat is split into two.
+            let (new_opac, scale_coeff) = relocation_update(cur_opac, 2);
+            let new_log_scales = cur_log_scale + scale_coeff.log().unsqueeze_dim(1);
+
+            split_splats(
+                splats,
+                record,
+                split_inds,
+                Tensor::zeros([split_count, 3], &device),
+                new_log_scales,
+                inv_sigmoid(new_opac),
+            )
+        } else {
+            splats
+        };
+
+        (
+            splats,
+            RefineStats {
+                num_added: split_count as u32,
+                num_pruned: dead_count,
+            },
+        )
+    }
+}
+
+/// Opacity and scale correction for splitting splats with opacity `opac` into `n` identical copies,
+/// such that the copies together render about the same as the original.
+///
+/// Returns the new opacity and the factor to multiply the scale by.
+fn relocation_update<B: Backend>(opac: Tensor<B, 1>, n: u32) -> (Tensor<B, 1>, Tensor<B, 1>) {
+    let opac = opac.clamp(MIN_OPACITY, 1.0 - MIN_OPACITY);
+    let new_opac: Tensor<B, 1> = 1.0 - (1.0 - opac.clone()).powf_scalar(1.0 / n as f32);
+    let new_opac = new_opac.clamp(MIN_OPACITY, 1.0 - MIN_OPACITY);
+
+    let mut denom = Tensor::zeros_like(&opac);
+    for i in 1..=n {
+        for k in 0..i {
+            let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+            let coeff = sign * binomial(i - 1, k) / ((k + 1) as f32).sqrt();
+            denom = denom + new_opac.clone().powi_scalar(k as i32 + 1) * coeff;
+        }
+    }
+
+    (new_opac, opac / denom)
+}
+
+fn binomial(n: u32, k: u32) -> f32 {
+    (0..k).fold(1.0, |acc, i| acc * (n - i) as f32 / (i + 1) as f32)
+}
+
+fn inv_sigmoid<B: Backend>(x: Tensor<B, 1>) -> Tensor<B, 1> {
+    (x.clone() / (1.0f32 - x)).log()
+}
+
+// Mask of splats with degenerate scales, or which have moved way out of bounds.
+fn invalid_mask(
+    splats: &Splats<DiffBackend>,
+    bounds: &BoundingBox,
+) -> Tensor<MainBackend, 1, Bool> {
+    let device = splats.device();
+    let max_allowed_bounds = bounds.extent.max_element() * 100.0;
+
+    let scales = splats.scales().inner();
+    let scale_small = scales.clone().lower_elem(1e-10).any_dim(1).squeeze(1);
+    let scale_big = scales
+        .greater_elem(max_allowed_bounds)
+        .any_dim(1)
+        .squeeze(1);
+
+    let center = bounds.center;
+    let bound_center =
+        Tensor::<_, 1>::from_floats([center.x, center.y, center.z], &device).reshape([1, 3]);
+    let splat_dists = (splats.means.val().inner() - bound_center).abs();
+    let bound_mask = splat_dists
+        .greater_elem(max_allowed_bounds)
+        .any_dim(1)
+        .squeeze(1);
+
+    scale_small.bool_or(scale_big).bool_or(bound_mask)
+}
+
+// Splits the splats at `inds` in two. One half stays in place, the other is appended with
+// a fresh optimizer state. The halves are moved by -offset and +offset respectively, and
+// both get the new scales and opacities.
+fn split_splats(
+    mut splats: Splats<DiffBackend>,
+    record: &mut HashMap<ParamId, AdaptorRecord<AdamScaled, DiffBackend>>,
+    inds: Tensor<MainBackend, 1, Int>,
+    offset: Tensor<MainBackend, 2>,
+    new_log_scales: Tensor<MainBackend, 2>,
+    new_raw_opac: Tensor<MainBackend, 1>,
+) -> Splats<DiffBackend> {
+    let device = splats.device();
+    let count = inds.dims()[0];
+
+    let cur_means = splats.means.val().inner().select(0, inds.clone());
+    let cur_rots = splats.rotations_normed().inner().select(0, inds.clone());
+    let cur_log_scale = splats.log_scales.val().inner().select(0, inds.clone());
+    let cur_coeff = splats.sh_coeffs.val().inner().select(0, inds.clone());
+    let cur_raw_opac = splats.raw_opacity.val().inner().select(0, inds.clone());
+
+    // Shrink & offset existing splats.
+
+    // Scatter needs [N, 3] indices for means and scales.
+    let inds_3 = inds.clone().unsqueeze_dim(1).repeat_dim(1, 3);
+
+    splats.means = splats.means.map(|m| {
+        let new_means = m.inner().scatter(0, inds_3.clone(), -offset.clone());
+        Tensor::from_inner(new_means).require_grad()
+    });
+    splats.log_scales = splats.log_scales.map(|s| {
+        let difference = new_log_scales.clone() - cur_log_scale.clone();
+        let new_scales = s.inner().scatter(0, inds_3.clone(), difference);
+        Tensor::from_inner(new_scales).require_grad()
+    });
+    splats.raw_opacity = splats.raw_opacity.map(|m| {
+        let difference = new_raw_opac.clone() - cur_raw_opac.clone();
+        let new_opacities = m.inner().scatter(0, inds.clone(), difference);
+        Tensor::from_inner(new_opacities).require_grad()
+    });
+
+    // Concatenate new splats.
+    let sh_dim = splats.sh_coeffs.dims()[1];
+    map_splats_and_opt(
+        splats,
+        record,
+        |x| Tensor::cat(vec![x, cur_means + offset], 0),
+        |x| Tensor::cat(vec![x, cur_rots], 0),
+        |x| Tensor::cat(vec![x, new_log_scales], 0),
+        |x| Tensor::cat(vec![x, cur_coeff], 0),
+        |x| Tensor::cat(vec![x, new_raw_opac], 0),
+        |x| Tensor::cat(vec![x, Tensor::zeros([count, 3], &device)], 0),
+        |x| Tensor::cat(vec![x, Tensor::zeros([count, 4], &device)], 0),
+        |x| Tensor::cat(vec![x, Tensor::zeros([count, 3], &device)], 0),
+        |x| Tensor::cat(vec![x, Tensor::zeros([count, sh_dim, 3], &device)], 0),
+        |x| Tensor::cat(vec![x, Tensor::zeros([count], &device)], 0),
+    )
+}
+
+fn map_splats_and_opt(
+    mut splats: Splats<DiffBackend>,
+    record: &mut HashMap<ParamId, AdaptorRecord<AdamScaled, DiffBackend>>,
+    map_mean: impl FnOnce(Tensor<MainBackend, 2>) -> Tensor<MainBackend, 2>,
+    map_rotation: impl FnOnce(Tensor<MainBackend, 2>) -> Tensor<MainBackend, 2>,
+    map_scale: impl FnOnce(Tensor<MainBackend, 2>) -> Tensor<MainBackend, 2>,
+    map_coeffs: impl FnOnce(Tensor<MainBackend, 3>) -> Tensor<MainBackend, 3>,
+    map_opac: impl FnOnce(Tensor<MainBackend, 1>) -> Tensor<MainBackend, 1>,
+
+    map_opt_mean: impl Fn(Tensor<MainBackend, 2>) -> Tensor<MainBackend, 2>,
+    map_opt_rotation: impl Fn(Tensor<MainBackend, 2>) -> Tensor<MainBackend, 2>,
+    map_opt_scale: impl Fn(Tensor<MainBackend, 2>) -> Tensor<MainBackend, 2>,
+    map_opt_coeffs: impl Fn(Tensor<MainBackend, 3>) -> Tensor<MainBackend, 3>,
+    map_opt_opac: impl Fn(Tensor<MainBackend, 1>) -> Tensor<MainBackend, 1>,
+) -> Splats<DiffBackend> {
+    // The 3D filter depends on the splat positions, and is recomputed after refining.
+    splats.filter_3d = None;
+
+    splats.means = splats
+        .means
+        .map(|x| Tensor::from_inner(map_mean(x.inner())).require_grad());
+    map_opt(splats.means.id, record, &map_opt_mean);
+
+    splats.rotation = splats
+        .rotation
+        .map(|x| Tensor::from_inner(map_rotation(x.inner())).require_grad());
+    map_opt(splats.rotation.id, record, &map_opt_rotation);
+
+    splats.log_scales = splats
+        .log_scales
+        .map(|x| Tensor::from_inner(map_scale(x.inner())).require_grad());
+    map_opt(splats.log_scales.id, record, &map_opt_scale);
+
+    splats.sh_coeffs = splats
+        .sh_coeffs
+        .map(|x| Tensor::from_inner(map_coeffs(x.inner())).require_grad());
+    map_opt(splats.sh_coeffs.id, record, &map_opt_coeffs);
+
+    splats.raw_opacity = splats
+        .raw_opacity
+        .map(|x| Tensor::from_inner(map_opac(x.inner())).require_grad());
+    map_opt(splats.raw_opacity.id, record, &map_opt_opac);
+
+    splats
+}
+
+fn map_opt<B: AutodiffBackend, const D: usize>(
+    param_id: ParamId,
+    record: &mut HashMap<ParamId, AdaptorRecord<AdamScaled, B>>,
+    map_opt: &impl Fn(Tensor<B::InnerBackend, D>) -> Tensor<B::InnerBackend, D>,
+) {
+    let mut state: AdamState<_, D> = record
+        .remove(&param_id)
+        .expect("failed to get optimizer record")
+        .into_state();
+
+    state.momentum = state.momentum.map(|mut moment| {
+        moment.moment_1 = map_opt(moment.moment_1);
+        moment.moment_2 = map_opt(moment.moment_2);
+        moment
+    });
+
+    record.insert(param_id, AdaptorRecord::from_state(state));
+}
+
+// Prunes points based on the given mask.
+//
+// Args:
+//   mask: bool[n]. If True, prune this Gaussian.
+async fn prune_points(
+    mut splats: Splats<DiffBackend>,
+    record: &mut HashMap<ParamId, AdaptorRecord<AdamScaled, DiffBackend>>,
+    mut refiner: RefineRecord<MainBackend>,
+    prune: Tensor<MainBackend, 1, Bool>,
+) -> (Splats<DiffBackend>, RefineRecord<MainBackend>, u32) {
+    assert_eq!(
+        prune.dims()[0] as u32,
+        splats.num_splats(),
+        "Prune mask must have same number of elements as splats"
+    );
+
+    let prune_count = prune.dims()[0];
+    if prune_count == 0 {
+        return (splats, refiner, 0);
+    }
+
+    let valid_inds = prune.bool_not().argwhere_async().await;
+
+    if valid_inds.dims()[0] == 0 {
+        log::warn!("Trying to create empty splat!");
+        return (splats, refiner, 0);
+    }
+
+    let start_splats = splats.num_splats();
+    let new_points = valid_inds.dims()[0] as u32;
+    if new_points < start_splats {
+        let valid_inds = valid_inds.squeeze(1);
+        splats = map_splats_and_opt(
+            splats,
+            record,
+            |x| x.select(0, valid_inds.clone()),
+            |x| x.select(0, valid_inds.clone()),
+            |x| x.select(0, valid_inds.clone()),
+            |x| x.select(0, valid_inds.clone()),
+            |x| x.select(0, valid_inds.clone()),
+            |x| x.select(0, valid_inds.clone()),
+            |x| x.select(0, valid_inds.clone()),
+            |x| x.select(0, valid_inds.clone()),
+            |x| x.select(0, valid_inds.clone()),
+            |x| x.select(0, valid_inds.clone()),
+        );
+        refiner = refiner.keep(valid_inds);
+    }
+    (splats, refiner, start_splats - new_points)
+}
+
+fn scale_down_largest_dim<B: Backend>(scales: Tensor<B, 2>, factor: f32) -> Tensor<B, 2> {
+    // Find the maximum values along dimension 1 (keeping dimensions for broadcasting)
+    let max_mask = scales.clone().equal(scales.clone().max_dim(1));
+    let scale = Tensor::ones_like(&scales).mask_fill(max_mask, factor);
+    scales.mul(scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use burn::backend::wgpu::WgpuDevice;
+
+    fn to_vec(t: Tensor<MainBackend, 1>) -> Vec<f32> {
+        t.into_data().into_vec().expect("Wrong type")
+    }
+
+    #[tokio::test]
+    async fn test_relocation_update() {
+        let device = WgpuDevice::DefaultDevice;
+        let opac = Tensor::<MainBackend, 1>::from_floats([0.1, 0.5, 0.9], &device);
+
+        // Splitting into a single splat doesn't change anything.
+        let (new_opac, scale_coeff) = relocation_update(opac.clone(), 1);
+        for (a, b) in to_vec(new_opac).into_iter().zip(to_vec(opac.clone())) {
+            assert!((a - b).abs() < 1e-5);
+        }
+        for c in to_vec(scale_coeff) {
+            assert!((c - 1.0).abs() < 1e-5);
+        }
+
+        // Two splats with the new opacity should be as opaque as the original.
+        let (new_opac, scale_coeff) = relocation_update(opac.clone(), 2);
+        for (a, o) in to_vec(new_opac).into_iter().zip(to_vec(opac)) {
+            assert!((1.0 - (1.0 - a) * (1.0 - a) - o).abs() < 1e-5);
+        }
+        for c in to_vec(scale_coeff) {
+            assert!(c > 0.0 && c.is_finite());
+        }
+    }
+}
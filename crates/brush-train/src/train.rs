@@ -1,15 +1,22 @@
 use crate::{
     adam_scaled::{AdamScaled, AdamScaledConfig, AdamState},
+    appearance::{Appearance, apply_color_transform},
+    checkpoint::{CHECKPOINT_VERSION, CheckpointRecord, TrainCheckpoint},
     config::TrainConfig,
+    lr_schedule::{LrSchedule, LrScheduleKind},
+    mip_filter,
     msg::{RefineStats, TrainStepStats},
-    multinomial::multinomial_sample,
-    quat_vec::quaternion_vec_multiply,
+    pose::PoseRefiner,
+    refine::RefineContext,
     ssim::Ssim,
     stats::RefineRecord,
 };
 
-use brush_dataset::scene::SceneBatch;
-use brush_render::{MainBackend, gaussian_splats::Splats};
+use brush_dataset::{
+    scene::{Scene, SceneBatch},
+    scene_loader::LoaderState,
+};
+use brush_render::{MainBackend, camera::Camera, gaussian_splats::Splats};
 use brush_render::{bounding_box::BoundingBox, sh::sh_coeffs_for_degree};
 use brush_render_bwd::burn_glue::SplatForwardDiff;
 use burn::{
@@ -17,25 +24,18 @@ use burn::{
         Autodiff,
         wgpu::{WgpuDevice, WgpuRuntime},
     },
-    lr_scheduler::{
-        LrScheduler,
-        exponential::{ExponentialLrScheduler, ExponentialLrSchedulerConfig},
-    },
     module::ParamId,
     optim::{GradientsParams, Optimizer, adaptor::OptimizerAdaptor, record::AdaptorRecord},
     prelude::Backend,
-    tensor::{
-        Bool, Distribution, Tensor, TensorData, TensorPrimitive, activation::sigmoid,
-        backend::AutodiffBackend, s,
-    },
+    tensor::{Distribution, Tensor, TensorPrimitive, s},
 };
 
 use burn_cubecl::cubecl::Runtime;
 use glam::Vec3;
-use hashbrown::{HashMap, HashSet};
+use hashbrown::HashMap;
+use rand::{SeedableRng, rngs::StdRng};
 use tracing::trace_span;
 
-const MIN_OPACITY: f32 = 1.0 / 255.0;
 const BOUND_PERCENTILE: f32 = 0.8;
 
 type DiffBackend = Autodiff<MainBackend>;
@@ -43,23 +43,25 @@ type OptimizerType = OptimizerAdaptor<AdamScaled, Splats<DiffBackend>, DiffBacke
 
 pub struct SplatTrainer {
     config: TrainConfig,
-    sched_mean: ExponentialLrScheduler,
-    sched_scale: ExponentialLrScheduler,
+    // Nr. of steps taken by this trainer, used to evaluate the learning rate schedules.
+    step_count: u32,
     refine_record: Option<RefineRecord<MainBackend>>,
     optim: Option<OptimizerType>,
 
     ssim: Option<Ssim<DiffBackend>>,
 
     bounds: BoundingBox,
+    rng: StdRng,
+
+    // Per view colour transforms, when appearance compensation is enabled.
+    appearance: Option<Appearance>,
+    // Per view pose corrections, when pose refinement is enabled.
+    poses: Option<PoseRefiner>,
 
     #[cfg(not(target_family = "wasm"))]
     lpips: Option<lpips::LpipsModel<DiffBackend>>,
 }
 
-fn inv_sigmoid<B: Backend>(x: Tensor<B, 1>) -> Tensor<B, 1> {
-    (x.clone() / (1.0f32 - x)).log()
-}
-
 fn create_default_optimizer() -> OptimizerType {
     AdamScaledConfig::new().with_epsilon(1e-15).init()
 }
@@ -70,30 +72,196 @@ impl SplatTrainer {
         device: &WgpuDevice,
         init_splats: Splats<B>,
     ) -> Self {
-        let decay = (config.lr_mean_end / config.lr_mean).powf(1.0 / config.total_steps as f64);
-        let lr_mean = ExponentialLrSchedulerConfig::new(config.lr_mean, decay);
-
-        let decay = (config.lr_scale_end / config.lr_scale).powf(1.0 / config.total_steps as f64);
-        let lr_scale = ExponentialLrSchedulerConfig::new(config.lr_scale, decay);
+        let bounds = init_splats.get_bounds(BOUND_PERCENTILE).await;
+        Self::with_bounds(config, device, bounds)
+    }
 
+    fn with_bounds(config: &TrainConfig, device: &WgpuDevice, bounds: BoundingBox) -> Self {
         const SSIM_WINDOW_SIZE: usize = 11; // Could be configurable but meh, rather keep consistent.
         let ssim = (config.ssim_weight > 0.0).then(|| Ssim::new(SSIM_WINDOW_SIZE, 3, device));
 
-        let bounds = init_splats.get_bounds(BOUND_PERCENTILE).await;
-
         Self {
             config: config.clone(),
-            sched_mean: lr_mean.init().expect("Mean lr schedule must be valid."),
-            sched_scale: lr_scale.init().expect("Scale lr schedule must be valid."),
+            step_count: 0,
             optim: None,
             refine_record: None,
             ssim,
             bounds,
+            rng: StdRng::from_rng(&mut rand::rng()),
+            appearance: config.appearance.then(Appearance::new),
+            poses: config.pose_refine.then(PoseRefiner::new),
             #[cfg(not(target_family = "wasm"))]
             lpips: (config.lpips_loss_weight > 0.0).then(|| lpips::load_vgg_lpips(device)),
         }
     }
 
+    /// Reset all randomness used while training, both on the CPU and for random tensors on the device.
+    ///
+    /// Reseeding at a fixed iteration makes training from that point on reproducible, which is what allows
+    /// a resumed run to continue the same way as an uninterrupted one.
+    pub fn reseed(&mut self, seed: u64, device: &WgpuDevice) {
+        self.rng = StdRng::seed_from_u64(seed);
+        <MainBackend as Backend>::seed(device, seed);
+    }
+
+    /// Capture the full training state after `iter` steps.
+    pub fn checkpoint(
+        &self,
+        splats: &Splats<DiffBackend>,
+        iter: u32,
+        seed: u64,
+        loader_state: LoaderState,
+    ) -> TrainCheckpoint {
+        let mut optim_record = self
+            .optim
+            .as_ref()
+            .map(|optim| optim.to_record())
+            .unwrap_or_default();
+        let (appearance, appearance_state) = self
+            .appearance
+            .as_ref()
+            .map(Appearance::to_record)
+            .unwrap_or_default();
+        let (poses, poses_state, poses_cached) = self
+            .poses
+            .as_ref()
+            .map(PoseRefiner::to_record)
+            .unwrap_or_default();
+
+        let record = CheckpointRecord {
+            version: CHECKPOINT_VERSION,
+            iter,
+            seed,
+            loader_seed: loader_state.seed,
+            loader_position: loader_state.position,
+            config: serde_json::to_string(&self.config).expect("Train config must serialize"),
+            bounds_center: self.bounds.center.to_array(),
+            bounds_extent: self.bounds.extent.to_array(),
+            step_count: self.step_count,
+            means: splats.means.val().inner(),
+            rotation: splats.rotation.val().inner(),
+            log_scales: splats.log_scales.val().inner(),
+            sh_coeffs: splats.sh_coeffs.val().inner(),
+            raw_opacity: splats.raw_opacity.val().inner(),
+            filter_3d: splats.filter_3d.clone().map(Tensor::inner),
+            means_state: take_param_state(&mut optim_record, splats.means.id),
+            rotation_state: take_param_state(&mut optim_record, splats.rotation.id),
+            log_scales_state: take_param_state(&mut optim_record, splats.log_scales.id),
+            sh_coeffs_state: take_param_state(&mut optim_record, splats.sh_coeffs.id),
+            raw_opacity_state: take_param_state(&mut optim_record, splats.raw_opacity.id),
+            refine_weight_norm: self
+                .refine_record
+                .as_ref()
+                .map(|r| r.refine_weight_norm.clone()),
+            vis_weight: self.refine_record.as_ref().map(|r| r.vis_weight.clone()),
+            appearance,
+            appearance_state,
+            poses,
+            poses_state,
+            poses_cached,
+        };
+
+        TrainCheckpoint {
+            record,
+            config: self.config.clone(),
+        }
+    }
+
+    /// Restore a trainer and its splats from a checkpoint.
+    ///
+    /// NB: Call [`Self::reseed`] afterwards to continue with the same random state.
+    pub fn from_checkpoint(
+        checkpoint: TrainCheckpoint,
+        device: &WgpuDevice,
+    ) -> (Self, Splats<DiffBackend>) {
+        let TrainCheckpoint { record, config } = checkpoint;
+
+        let bounds = BoundingBox {
+            center: Vec3::from_array(record.bounds_center),
+            extent: Vec3::from_array(record.bounds_extent),
+        };
+        let mut trainer = Self::with_bounds(&config, device, bounds);
+
+        trainer.step_count = record.step_count;
+        if config.appearance {
+            trainer.appearance = Some(Appearance::from_record(
+                record.appearance,
+                record.appearance_state,
+            ));
+        }
+        if config.pose_refine {
+            trainer.poses = Some(PoseRefiner::from_record(
+                record.poses,
+                record.poses_state,
+                record.poses_cached,
+            ));
+        }
+
+        let splats: Splats<DiffBackend> = Splats::from_tensor_data(
+            record.means,
+            record.rotation,
+            record.log_scales,
+            record.sh_coeffs,
+            record.raw_opacity,
+        )
+        .with_filter_3d(record.filter_3d)
+        .into_autodiff();
+
+        // Parameter IDs are not stable across runs, so key the optimizer state by the new IDs.
+        let optim_record: HashMap<_, _> = [
+            (
+                splats.means.id,
+                record.means_state.map(AdaptorRecord::from_state),
+            ),
+            (
+                splats.rotation.id,
+                record.rotation_state.map(AdaptorRecord::from_state),
+            ),
+            (
+                splats.log_scales.id,
+                record.log_scales_state.map(AdaptorRecord::from_state),
+            ),
+            (
+                splats.sh_coeffs.id,
+                record.sh_coeffs_state.map(AdaptorRecord::from_state),
+            ),
+            (
+                splats.raw_opacity.id,
+                record.raw_opacity_state.map(AdaptorRecord::from_state),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(id, state)| Some((id, state?)))
+        .collect();
+
+        if !optim_record.is_empty() {
+            trainer.optim = Some(create_default_optimizer().load_record(optim_record));
+        }
+
+        if let (Some(refine_weight_norm), Some(vis_weight)) =
+            (record.refine_weight_norm, record.vis_weight)
+        {
+            trainer.refine_record = Some(RefineRecord {
+                refine_weight_norm,
+                vis_weight,
+            });
+        }
+
+        (trainer, splats)
+    }
+
+    fn lr(&self, kind: LrScheduleKind, start: f64, end: f64) -> f64 {
+        LrSchedule {
+            kind,
+            start,
+            end,
+            total_steps: self.config.total_steps,
+            warmup_steps: self.config.lr_warmup_steps,
+            step_count: self.config.lr_step_count,
+        }
+        .lr(self.step_count)
+    }
+
     pub fn step(
         &mut self,
         batch: &SceneBatch<DiffBackend>,
@@ -104,40 +272,66 @@ impl SplatTrainer {
         let mut splats = splats;
 
         let [img_h, img_w, _] = batch.img_tensor.dims();
-        let camera = &batch.camera;
-
-        let (pred_image, aux, refine_weight_holder) = trace_span!("Forward").in_scope(|| {
-            // Could generate a random background color, but so far
-            // results just seem worse.
-            let background = Vec3::ZERO;
-
-            let diff_out = <DiffBackend as SplatForwardDiff<_>>::render_splats(
-                camera,
-                glam::uvec2(img_w as u32, img_h as u32),
-                splats.means.val().into_primitive().tensor(),
-                splats.log_scales.val().into_primitive().tensor(),
-                splats.rotation.val().into_primitive().tensor(),
-                splats.sh_coeffs.val().into_primitive().tensor(),
-                splats.raw_opacity.val().into_primitive().tensor(),
-                background,
-            );
-
-            let img = Tensor::from_primitive(TensorPrimitive::Float(diff_out.img));
-
-            #[cfg(any(feature = "debug-validation", test))]
-            {
-                splats.validate_values();
-                diff_out.aux.validate_values();
-            }
+        let camera = if let Some(poses) = &self.poses {
+            poses.refined_camera(batch.view_index, &batch.camera)
+        } else {
+            batch.camera.clone()
+        };
 
-            (img, diff_out.aux, diff_out.refine_weight_holder)
-        });
+        // Only render (and differentiate) depth when there's a depth map to supervise it with.
+        let gt_depth = batch
+            .depth_tensor
+            .as_ref()
+            .filter(|_| self.config.depth_loss_weight > 0.0);
+
+        let (pred_image, aux, refine_weight_holder, viewmat_holder) = trace_span!("Forward")
+            .in_scope(|| {
+                // Could generate a random background color, but so far
+                // results just seem worse.
+                let background = Vec3::ZERO;
+
+                let diff_out = <DiffBackend as SplatForwardDiff<_>>::render_splats(
+                    &camera,
+                    glam::uvec2(img_w as u32, img_h as u32),
+                    splats.means.val().into_primitive().tensor(),
+                    splats.filtered_log_scales().into_primitive().tensor(),
+                    splats.rotation.val().into_primitive().tensor(),
+                    splats.sh_coeffs.val().into_primitive().tensor(),
+                    splats.filtered_raw_opacity().into_primitive().tensor(),
+                    background,
+                    splats.filter_3d.is_some(),
+                    self.config.render_scale,
+                    self.poses.is_some(),
+                    gt_depth.is_some(),
+                );
+
+                let img = Tensor::from_primitive(TensorPrimitive::Float(diff_out.img));
+
+                #[cfg(any(feature = "debug-validation", test))]
+                {
+                    splats.validate_values();
+                    diff_out.aux.validate_values();
+                }
+
+                (
+                    img,
+                    diff_out.aux,
+                    diff_out.refine_weight_holder,
+                    diff_out.viewmat_holder,
+                )
+            });
 
         let median_scale = self.bounds.median_size();
         let num_visible = aux.num_visible().inner();
         let num_intersections = aux.num_intersections().inner();
 
         let pred_rgb = pred_image.clone().slice(s![.., .., 0..3]);
+        let pred_rgb = if let Some(appearance) = &mut self.appearance {
+            let transform = appearance.transform(batch.view_index, &splats.device());
+            apply_color_transform(pred_rgb, transform)
+        } else {
+            pred_rgb
+        };
         let gt_rgb = batch.img_tensor.clone().slice(s![.., .., 0..3]);
 
         let visible: Tensor<Autodiff<MainBackend>, 1> =
@@ -168,6 +362,29 @@ impl SplatTrainer {
 
             let loss = total_err.mean();
 
+            let loss = if let Some(gt_depth) = gt_depth {
+                let pred_alpha = pred_image.clone().slice(s![.., .., 3..4]);
+                let pred_depth = pred_image.clone().slice(s![.., .., 4..5]);
+                let depth_loss = self.config.depth_loss.loss(
+                    pred_depth.reshape([img_h, img_w]),
+                    pred_alpha.reshape([img_h, img_w]),
+                    gt_depth.clone(),
+                );
+                loss + depth_loss * self.config.depth_loss_weight
+            } else {
+                loss
+            };
+
+            let loss = if let Some(reg) = self
+                .config
+                .refine_strategy
+                .regularization(&self.config, &splats)
+            {
+                loss + reg
+            } else {
+                loss
+            };
+
             // TODO: Support masked lpips.
             #[cfg(not(target_family = "wasm"))]
             let loss = if let Some(lpips) = &self.lpips {
@@ -187,15 +404,28 @@ impl SplatTrainer {
             brush_render::validation::validate_splat_gradients(&splats, &grads);
         }
 
+        if let Some(appearance) = &mut self.appearance {
+            appearance.step(self.config.lr_appearance, batch.view_index, &mut grads);
+        }
+
+        if let Some(poses) = &mut self.poses {
+            let v_viewmat = viewmat_holder
+                .grad_remove(&mut grads)
+                .expect("View matrix gradients need to be calculated.");
+            poses.step(self.config.lr_pose, batch.view_index, &camera, v_viewmat);
+        }
+
+        let tc = &self.config;
         let (lr_mean, lr_rotation, lr_scale, lr_coeffs, lr_opac) = (
-            self.sched_mean.step() * median_scale as f64,
-            self.config.lr_rotation,
+            self.lr(tc.lr_mean_schedule, tc.lr_mean, tc.lr_mean_end) * median_scale as f64,
+            self.lr(tc.lr_rotation_schedule, tc.lr_rotation, tc.lr_rotation_end),
             // Scale is relative to the scene scale, but the exp() activation function
             // means "offsetting" all values also solves the learning rate scaling.
-            self.sched_scale.step(),
-            self.config.lr_coeffs_dc,
-            self.config.lr_opac,
+            self.lr(tc.lr_scale_schedule, tc.lr_scale, tc.lr_scale_end),
+            self.lr(tc.lr_coeffs_schedule, tc.lr_coeffs_dc, tc.lr_coeffs_dc_end),
+            self.lr(tc.lr_opac_schedule, tc.lr_opac, tc.lr_opac_end),
         );
+        self.step_count += 1;
 
         let optimizer = self.optim.get_or_insert_with(|| {
             let sh_degree = splats.sh_degree();
@@ -298,13 +528,58 @@ impl SplatTrainer {
         (splats, stats)
     }
 
+    /// The cameras of the training views with the learned pose corrections applied, if pose
+    /// refinement is enabled.
+    pub async fn refined_cameras(&mut self, scene: &Scene) -> Option<Vec<Camera>> {
+        let poses = self.poses.as_mut()?;
+        poses.sync().await;
+        Some(
+            scene
+                .views
+                .iter()
+                .enumerate()
+                .map(|(i, view)| poses.refined_camera(i, &view.camera))
+                .collect(),
+        )
+    }
+
+    /// Recompute the Mip-Splatting 3D filter of the splats from the training views, if enabled.
+    pub fn update_filter_3d(
+        &self,
+        splats: Splats<DiffBackend>,
+        scene: &Scene,
+    ) -> Splats<DiffBackend> {
+        if !self.config.mip_filter {
+            return splats;
+        }
+
+        let cameras = scene.views.iter().enumerate().map(|(i, view)| {
+            let camera = if let Some(poses) = &self.poses {
+                poses.refined_camera(i, &view.camera)
+            } else {
+                view.camera.clone()
+            };
+            (camera, view.image.dimensions())
+        });
+        let filter_3d = mip_filter::filter_3d(splats.means.val().inner(), cameras);
+        splats.with_filter_3d(Some(Tensor::from_inner(filter_3d)))
+    }
+
     pub async fn refine_if_needed(
         &mut self,
         iter: u32,
         splats: Splats<DiffBackend>,
+        scene: &Scene,
     ) -> (Splats<DiffBackend>, Option<RefineStats>) {
         let train_t = (iter as f32 / self.config.total_steps as f32).clamp(0.0, 1.0);
 
+        // Pose corrections are only read back at refine steps, to not stall every training step.
+        if let Some(poses) = &mut self.poses
+            && iter.is_multiple_of(self.config.refine_every)
+        {
+            poses.sync().await;
+        }
+
         if iter == 0 || !iter.is_multiple_of(self.config.refine_every) || train_t > 0.95 {
             return (splats, None);
         }
@@ -316,340 +591,38 @@ impl SplatTrainer {
             .refine_record
             .take()
             .expect("Can only refine if refine stats are initialized");
-
-        let max_allowed_bounds = self.bounds.extent.max_element() * 100.0;
-
-        // If not refining, update splat to step with gradients applied.
-        // Prune dead splats. This ALWAYS happen even if we're not "refining" anymore.
         let mut record = self
             .optim
             .take()
             .expect("Can only refine after optimizer is initialized")
             .to_record();
-        let alpha_mask = splats.opacities().inner().lower_elem(MIN_OPACITY);
-        let scales = splats.scales().inner();
-
-        let scale_small = scales.clone().lower_elem(1e-10).any_dim(1).squeeze(1);
-        let scale_big = scales
-            .greater_elem(max_allowed_bounds)
-            .any_dim(1)
-            .squeeze(1);
-
-        // Remove splats that are way out of bounds.
-        let center = self.bounds.center;
-        let bound_center =
-            Tensor::<_, 1>::from_floats([center.x, center.y, center.z], &device).reshape([1, 3]);
-        let splat_dists = (splats.means.val().inner() - bound_center).abs();
-        let bound_mask = splat_dists
-            .greater_elem(max_allowed_bounds)
-            .any_dim(1)
-            .squeeze(1);
-        let prune_mask = alpha_mask
-            .bool_or(scale_small)
-            .bool_or(scale_big)
-            .bool_or(bound_mask);
-
-        let (mut splats, refiner, pruned_count) =
-            prune_points(splats, &mut record, refiner, prune_mask).await;
-        let mut split_inds = HashSet::new();
-
-        // Replace dead gaussians.
-        if pruned_count > 0 {
-            // Sample weighted by opacity from splat visible during optimization.
-            let resampled_weights = splats.opacities().inner() * refiner.vis_mask().float();
-            let resampled_weights = resampled_weights
-                .into_data_async()
-                .await
-                .into_vec::<f32>()
-                .expect("Failed to read weights");
-            let resampled_inds = multinomial_sample(&resampled_weights, pruned_count);
-            split_inds.extend(resampled_inds);
-        }
 
-        if iter < self.config.growth_stop_iter {
-            let above_threshold = refiner.above_threshold(self.config.growth_grad_threshold);
-
-            let threshold_count = above_threshold
-                .clone()
-                .int()
-                .sum()
-                .into_scalar_async()
-                .await as u32;
-
-            let grow_count =
-                (threshold_count as f32 * self.config.growth_select_fraction).round() as u32;
-
-            let sample_high_grad = grow_count.saturating_sub(pruned_count);
-
-            // Only grow to the max nr. of splats.
-            let cur_splats = splats.num_splats() + split_inds.len() as u32;
-            let grow_count = sample_high_grad.min(self.config.max_splats - cur_splats);
-
-            // If still growing, sample from indices which are over the threshold.
-            if grow_count > 0 {
-                let weights = above_threshold.float() * refiner.refine_weight_norm;
-                let weights = weights
-                    .into_data_async()
-                    .await
-                    .into_vec::<f32>()
-                    .expect("Failed to read weights");
-                let growth_inds = multinomial_sample(&weights, grow_count);
-                split_inds.extend(growth_inds);
-            }
-        }
-
-        let refine_count = split_inds.len();
+        let ctx = RefineContext {
+            config: &self.config,
+            iter,
+            train_t,
+            bounds: &self.bounds,
+            rng: &mut self.rng,
+            record: &mut record,
+            refiner,
+        };
+        let (splats, stats) = self.config.refine_strategy.refine(ctx, splats).await;
+        let splats = self.update_filter_3d(splats, scene);
 
-        splats = self.refine_splats(&device, record, splats, split_inds, train_t);
+        self.optim = Some(create_default_optimizer().load_record(record));
 
         // Update current bounds based on the splats.
         self.bounds = splats.clone().get_bounds(BOUND_PERCENTILE).await;
 
         client.memory_cleanup();
 
-        (
-            splats,
-            Some(RefineStats {
-                num_added: refine_count as u32,
-                num_pruned: pruned_count,
-            }),
-        )
-    }
-
-    fn refine_splats(
-        &mut self,
-        device: &WgpuDevice,
-        mut record: HashMap<ParamId, AdaptorRecord<AdamScaled, DiffBackend>>,
-        mut splats: Splats<DiffBackend>,
-        split_inds: HashSet<i32>,
-        train_t: f32,
-    ) -> Splats<DiffBackend> {
-        let refine_count = split_inds.len();
-
-        if refine_count > 0 {
-            let refine_inds = Tensor::from_data(
-                TensorData::new(split_inds.into_iter().collect(), [refine_count]),
-                device,
-            );
-
-            let cur_means = splats.means.val().inner().select(0, refine_inds.clone());
-            let cur_rots = splats
-                .rotations_normed()
-                .inner()
-                .select(0, refine_inds.clone());
-            let cur_log_scale = splats
-                .log_scales
-                .val()
-                .inner()
-                .select(0, refine_inds.clone());
-            let cur_coeff = splats
-                .sh_coeffs
-                .val()
-                .inner()
-                .select(0, refine_inds.clone());
-            let cur_raw_opac = splats
-                .raw_opacity
-                .val()
-                .inner()
-                .select(0, refine_inds.clone());
-
-            // The amount to offset the scale and opacity should maybe depend on how far away we have sampled these gaussians,
-            // but a fixed amount seems to work ok. The only note is that divide by _less_ than SQRT(2) seems to exponentially
-            // blow up, as more 'mass' is added each refine.
-            // let scale_div = Tensor::ones_like(&cur_log_scale) * SQRT_2.ln();
-            //
-            let cur_scales = cur_log_scale.clone().exp();
-
-            let cur_opac = sigmoid(cur_raw_opac.clone());
-            let inv_opac: Tensor<_, 1> = 1.0 - cur_opac;
-            let new_opac: Tensor<_, 1> = 1.0 - inv_opac.sqrt();
-            let new_raw_opac = inv_sigmoid(new_opac.clamp(MIN_OPACITY, 1.0 - MIN_OPACITY));
-            let new_scales = scale_down_largest_dim(cur_scales.clone(), 0.5);
-            let new_log_scales = new_scales.log();
-
-            // Move in direction of scaling axis.
-            let samples = quaternion_vec_multiply(
-                cur_rots.clone(),
-                Tensor::random([refine_count, 1], Distribution::Normal(0.0, 1.0), device)
-                    * cur_scales,
-            );
-
-            // Shrink & offset existing splats.
-
-            // Scatter needs [N, 3] indices for means and scales.
-            let refine_inds_3 = refine_inds.clone().unsqueeze_dim(1).repeat_dim(1, 3);
-
-            splats.means = splats.means.map(|m| {
-                let new_means = m
-                    .inner()
-                    .scatter(0, refine_inds_3.clone(), -samples.clone());
-                Tensor::from_inner(new_means).require_grad()
-            });
-            splats.log_scales = splats.log_scales.map(|s| {
-                let difference = new_log_scales.clone() - cur_log_scale.clone();
-                let new_scales = s.inner().scatter(0, refine_inds_3.clone(), difference);
-                Tensor::from_inner(new_scales).require_grad()
-            });
-            splats.raw_opacity = splats.raw_opacity.map(|m| {
-                let difference = new_raw_opac.clone() - cur_raw_opac.clone();
-                let new_opacities = m.inner().scatter(0, refine_inds.clone(), difference);
-                Tensor::from_inner(new_opacities).require_grad()
-            });
-
-            // Concatenate new splats.
-            let sh_dim = splats.sh_coeffs.dims()[1];
-            splats = map_splats_and_opt(
-                splats,
-                &mut record,
-                |x| Tensor::cat(vec![x, cur_means + samples], 0),
-                |x| Tensor::cat(vec![x, cur_rots], 0),
-                |x| Tensor::cat(vec![x, new_log_scales], 0),
-                |x| Tensor::cat(vec![x, cur_coeff], 0),
-                |x| Tensor::cat(vec![x, new_raw_opac], 0),
-                |x| Tensor::cat(vec![x, Tensor::zeros([refine_count, 3], device)], 0),
-                |x| Tensor::cat(vec![x, Tensor::zeros([refine_count, 4], device)], 0),
-                |x| Tensor::cat(vec![x, Tensor::zeros([refine_count, 3], device)], 0),
-                |x| Tensor::cat(vec![x, Tensor::zeros([refine_count, sh_dim, 3], device)], 0),
-                |x| Tensor::cat(vec![x, Tensor::zeros([refine_count], device)], 0),
-            );
-        }
-
-        let t_shrink_strength = 1.0 - train_t;
-        let minus_opac = self.config.opac_decay * t_shrink_strength;
-        let scale_scaling = 1.0 - self.config.scale_decay * t_shrink_strength;
-
-        // Lower opacity slowly over time.
-        splats.raw_opacity = splats.raw_opacity.map(|f| {
-            let new_opac = sigmoid(f.inner()) - minus_opac;
-            Tensor::from_inner(inv_sigmoid(new_opac.clamp(1e-12, 1.0 - 1e-12))).require_grad()
-        });
-
-        splats.log_scales = splats.log_scales.map(|f| {
-            let new_scale = f.inner().exp() * scale_scaling;
-            Tensor::from_inner(new_scale.log()).require_grad()
-        });
-
-        self.optim = Some(create_default_optimizer().load_record(record));
-        splats
+        (splats, Some(stats))
     }
 }
 
-fn map_splats_and_opt(
-    mut splats: Splats<DiffBackend>,
+fn take_param_state<const D: usize>(
     record: &mut HashMap<ParamId, AdaptorRecord<AdamScaled, DiffBackend>>,
-    map_mean: impl FnOnce(Tensor<MainBackend, 2>) -> Tensor<MainBackend, 2>,
-    map_rotation: impl FnOnce(Tensor<MainBackend, 2>) -> Tensor<MainBackend, 2>,
-    map_scale: impl FnOnce(Tensor<MainBackend, 2>) -> Tensor<MainBackend, 2>,
-    map_coeffs: impl FnOnce(Tensor<MainBackend, 3>) -> Tensor<MainBackend, 3>,
-    map_opac: impl FnOnce(Tensor<MainBackend, 1>) -> Tensor<MainBackend, 1>,
-
-    map_opt_mean: impl Fn(Tensor<MainBackend, 2>) -> Tensor<MainBackend, 2>,
-    map_opt_rotation: impl Fn(Tensor<MainBackend, 2>) -> Tensor<MainBackend, 2>,
-    map_opt_scale: impl Fn(Tensor<MainBackend, 2>) -> Tensor<MainBackend, 2>,
-    map_opt_coeffs: impl Fn(Tensor<MainBackend, 3>) -> Tensor<MainBackend, 3>,
-    map_opt_opac: impl Fn(Tensor<MainBackend, 1>) -> Tensor<MainBackend, 1>,
-) -> Splats<DiffBackend> {
-    splats.means = splats
-        .means
-        .map(|x| Tensor::from_inner(map_mean(x.inner())).require_grad());
-    map_opt(splats.means.id, record, &map_opt_mean);
-
-    splats.rotation = splats
-        .rotation
-        .map(|x| Tensor::from_inner(map_rotation(x.inner())).require_grad());
-    map_opt(splats.rotation.id, record, &map_opt_rotation);
-
-    splats.log_scales = splats
-        .log_scales
-        .map(|x| Tensor::from_inner(map_scale(x.inner())).require_grad());
-    map_opt(splats.log_scales.id, record, &map_opt_scale);
-
-    splats.sh_coeffs = splats
-        .sh_coeffs
-        .map(|x| Tensor::from_inner(map_coeffs(x.inner())).require_grad());
-    map_opt(splats.sh_coeffs.id, record, &map_opt_coeffs);
-
-    splats.raw_opacity = splats
-        .raw_opacity
-        .map(|x| Tensor::from_inner(map_opac(x.inner())).require_grad());
-    map_opt(splats.raw_opacity.id, record, &map_opt_opac);
-
-    splats
-}
-
-fn map_opt<B: AutodiffBackend, const D: usize>(
     param_id: ParamId,
-    record: &mut HashMap<ParamId, AdaptorRecord<AdamScaled, B>>,
-    map_opt: &impl Fn(Tensor<B::InnerBackend, D>) -> Tensor<B::InnerBackend, D>,
-) {
-    let mut state: AdamState<_, D> = record
-        .remove(&param_id)
-        .expect("failed to get optimizer record")
-        .into_state();
-
-    state.momentum = state.momentum.map(|mut moment| {
-        moment.moment_1 = map_opt(moment.moment_1);
-        moment.moment_2 = map_opt(moment.moment_2);
-        moment
-    });
-
-    record.insert(param_id, AdaptorRecord::from_state(state));
-}
-
-// Prunes points based on the given mask.
-//
-// Args:
-//   mask: bool[n]. If True, prune this Gaussian.
-async fn prune_points(
-    mut splats: Splats<DiffBackend>,
-    record: &mut HashMap<ParamId, AdaptorRecord<AdamScaled, DiffBackend>>,
-    mut refiner: RefineRecord<MainBackend>,
-    prune: Tensor<MainBackend, 1, Bool>,
-) -> (Splats<DiffBackend>, RefineRecord<MainBackend>, u32) {
-    assert_eq!(
-        prune.dims()[0] as u32,
-        splats.num_splats(),
-        "Prune mask must have same number of elements as splats"
-    );
-
-    let prune_count = prune.dims()[0];
-    if prune_count == 0 {
-        return (splats, refiner, 0);
-    }
-
-    let valid_inds = prune.bool_not().argwhere_async().await;
-
-    if valid_inds.dims()[0] == 0 {
-        log::warn!("Trying to create empty splat!");
-        return (splats, refiner, 0);
-    }
-
-    let start_splats = splats.num_splats();
-    let new_points = valid_inds.dims()[0] as u32;
-    if new_points < start_splats {
-        let valid_inds = valid_inds.squeeze(1);
-        splats = map_splats_and_opt(
-            splats,
-            record,
-            |x| x.select(0, valid_inds.clone()),
-            |x| x.select(0, valid_inds.clone()),
-            |x| x.select(0, valid_inds.clone()),
-            |x| x.select(0, valid_inds.clone()),
-            |x| x.select(0, valid_inds.clone()),
-            |x| x.select(0, valid_inds.clone()),
-            |x| x.select(0, valid_inds.clone()),
-            |x| x.select(0, valid_inds.clone()),
-            |x| x.select(0, valid_inds.clone()),
-            |x| x.select(0, valid_inds.clone()),
-        );
-        refiner = refiner.keep(valid_inds);
-    }
-    (splats, refiner, start_splats - new_points)
-}
-
-fn scale_down_largest_dim<B: Backend>(scales: Tensor<B, 2>, factor: f32) -> Tensor<B, 2> {
-    // Find the maximum values along dimension 1 (keeping dimensions for broadcasting)
-    let max_mask = scales.clone().equal(scales.clone().max_dim(1));
-    let scale = Tensor::ones_like(&scales).mask_fill(max_mask, factor);
-    scales.mul(scale)
+) -> Option<AdamState<MainBackend, D>> {
+    record.remove(&param_id).map(AdaptorRecord::into_state)
 }
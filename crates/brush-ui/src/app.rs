@@ -5,6 +5,7 @@ use crate::{
     camera_controls::CameraClamping, datasets::DatasetPanel, scene::ScenePanel,
     settings::SettingsPanel, stats::StatsPanel,
 };
+use brush_render::RenderScale;
 use eframe::egui;
 use egui::ThemePreference;
 use egui_tiles::{SimplificationOptions, Tile, TileId, Tiles};
@@ -62,12 +63,41 @@ impl egui_tiles::Behavior<PaneType> for AppTree {
     }
 }
 
+/// What the scene view displays.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DisplayMode {
+    #[default]
+    Color,
+    /// The expected depth of the splats, with a colormap.
+    Depth,
+    /// The world space normals of the splats.
+    Normals,
+}
+
+impl DisplayMode {
+    pub const ALL: [Self; 3] = [Self::Color, Self::Depth, Self::Normals];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Color => "Color",
+            Self::Depth => "Depth",
+            Self::Normals => "Normals",
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Default)]
 pub struct CameraSettings {
     pub speed_scale: Option<f32>,
     pub splat_scale: Option<f32>,
     pub background: Option<Vec3>,
     pub grid_enabled: Option<bool>,
+    /// Resolution splats are rendered at, defaults to adaptive.
+    pub render_scale: Option<RenderScale>,
+    /// What to display, defaults to color.
+    pub display_mode: Option<DisplayMode>,
+    /// Whether to view the scene with an orthographic projection, defaults to false.
+    pub orthographic: Option<bool>,
     pub clamping: CameraClamping,
 }
 
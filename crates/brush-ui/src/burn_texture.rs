@@ -56,6 +56,14 @@ impl BurnTexture {
     }
 
     pub fn update_texture(&mut self, img: Tensor<MainBackend, 3>) -> TextureId {
+        let img_prim = img.into_primitive().tensor();
+        let fusion_client = img_prim.client.clone();
+        let img = fusion_client.resolve_tensor_float::<MainBackendBase>(img_prim);
+        self.update_texture_base(Tensor::from_primitive(TensorPrimitive::Float(img)))
+    }
+
+    /// Like [`Self::update_texture`], for an image on the inner backend.
+    pub fn update_texture_base(&mut self, img: Tensor<MainBackendBase, 3>) -> TextureId {
         let [h, w, c] = img.shape().dims();
         assert!(c == 1, "texture should be u8 packed RGBA");
         let size = glam::uvec2(w as u32, h as u32);
@@ -106,11 +114,6 @@ impl BurnTexture {
 
         let padded_shape = vec![height, width.div_ceil(64) * 64, c];
 
-        let img_prim = img.into_primitive().tensor();
-        let fusion_client = img_prim.client.clone();
-        let img = fusion_client.resolve_tensor_float::<MainBackendBase>(img_prim);
-        let img: Tensor<MainBackendBase, 3> = Tensor::from_primitive(TensorPrimitive::Float(img));
-
         // Create padded tensor if needed. The bytes_per_row needs to be divisible
         // by 256 in WebGPU, so 4 bytes per pixel means width needs to be divisible by 64.
         let img = if width % 64 != 0 {
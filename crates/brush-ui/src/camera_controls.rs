@@ -351,7 +351,13 @@ impl CameraController {
         // Fade out grid timer
         self.grid_fade_timer = (self.grid_fade_timer - delta_time * 2.0).max(0.0);
 
-        let delta = self.fly_velocity * delta_time;
+        let mut delta = self.fly_velocity * delta_time;
+        // Flying forward doesn't change an orthographic view, so zoom in instead.
+        let mut fly_zoom = 0.0;
+        if self.settings.orthographic.unwrap_or(false) {
+            fly_zoom = delta.z;
+            delta.z = 0.0;
+        }
         self.position += delta.x * right + delta.y * up + delta.z * forward;
 
         // Damp velocities towards zero.
@@ -374,6 +380,10 @@ impl CameraController {
             }
         }
 
+        // Zoom speed of flying in an orthographic view, relative to the focus distance.
+        let ortho_zoom_speed = 0.04;
+        zoom_delta += fly_zoom * ortho_zoom_speed * self.focus_distance;
+
         // Scroll speed depends on how far zoomed out we are.
         self.focus_distance -= scrolled * scroll_speed * self.focus_distance + zoom_delta;
         self.focus_distance = self.focus_distance.max(0.01);
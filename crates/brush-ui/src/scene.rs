@@ -1,26 +1,40 @@
 use brush_process::message::ProcessMessage;
+use brush_serde::{ExportOptions, UpAxis};
 use core::f32;
 use egui::{Align2, Area, Frame, Pos2, Ui, epaint::mutex::RwLock as EguiRwLock};
 use std::sync::Arc;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 
 use brush_render::{
-    MainBackend,
-    camera::{Camera, focal_to_fov, fov_to_focal},
+    MainBackend, MainBackendBase, RenderScale,
+    bounding_box::BoundingBox,
+    camera::{Camera, Projection, focal_to_fov, fov_to_focal},
+    depth::depth_colormap,
     gaussian_splats::Splats,
+    normals::normal_colormap,
 };
+use burn::tensor::{Tensor, TensorPrimitive};
+use burn_fusion::client::FusionClient;
 use eframe::egui_wgpu::Renderer;
 use egui::{Color32, Rect, Slider, collapsing_header::CollapsingState};
-use glam::{UVec2, Vec3};
+use glam::{EulerRot, Quat, UVec2, Vec3};
 use tokio_with_wasm::alias as tokio_wasm;
 use tracing::trace_span;
 use web_time::Instant;
 
 use crate::{
-    UiMode, app::CameraSettings, burn_texture::BurnTexture, draw_checkerboard, panels::AppPane,
-    ui_process::UiProcess, widget_3d::Widget3D,
+    UiMode,
+    app::{CameraSettings, DisplayMode},
+    burn_texture::BurnTexture,
+    draw_checkerboard,
+    panels::AppPane,
+    ui_process::UiProcess,
+    widget_3d::Widget3D,
 };
 
+// How far behind the camera orthographic views are rendered from.
+const ORTHO_VIEW_DISTANCE: f32 = 1000.0;
+
 #[derive(Clone, PartialEq)]
 struct RenderState {
     size: UVec2,
@@ -57,9 +71,90 @@ impl ErrorDisplay {
     }
 }
 
-async fn export(splat: Splats<MainBackend>) -> Result<(), anyhow::Error> {
-    let data = brush_serde::splat_to_ply(splat).await?;
-    rrfd::save_file("export.ply", data).await?;
+// Formats the export button can write, as a label and the file name to save to.
+const EXPORT_FORMATS: [(&str, &str); 5] = [
+    ("Ply", "export.ply"),
+    ("Compressed ply", "export.compressed.ply"),
+    (".splat", "export.splat"),
+    ("SPZ", "export.spz"),
+    ("glTF", "export.glb"),
+];
+
+struct ExportSettings {
+    format: usize,
+    sh_degree: u32,
+    min_opacity: f32,
+    up: Option<UpAxis>,
+    crop: bool,
+    box_min: Vec3,
+    box_max: Vec3,
+    scale: f32,
+    // Rotation around the x, y and z axis in degrees.
+    rotation: Vec3,
+    translation: Vec3,
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self {
+            format: 0,
+            sh_degree: 4,
+            min_opacity: 0.0,
+            up: None,
+            crop: false,
+            box_min: Vec3::splat(-1.0),
+            box_max: Vec3::splat(1.0),
+            scale: 1.0,
+            rotation: Vec3::ZERO,
+            translation: Vec3::ZERO,
+        }
+    }
+}
+
+impl ExportSettings {
+    // Splats without a known up axis use the Y-down convention of most plys.
+    fn options(&self, sh_degree: u32, up_axis: Option<Vec3>) -> ExportOptions {
+        let mut options = ExportOptions {
+            max_sh_degree: Some(sh_degree),
+            min_opacity: (self.min_opacity > 0.0).then_some(self.min_opacity),
+            bounds: self
+                .crop
+                .then(|| BoundingBox::from_min_max(self.box_min, self.box_max)),
+            scale: self.scale,
+            translation: self.translation,
+            ..Default::default()
+        };
+        if let Some(up) = self.up {
+            options = options.with_up_axis(up_axis.unwrap_or(Vec3::NEG_Y), up);
+        }
+        let rotation = self.rotation * (std::f32::consts::PI / 180.0);
+        options.rotated(Quat::from_euler(
+            EulerRot::ZYX,
+            rotation.z,
+            rotation.y,
+            rotation.x,
+        ))
+    }
+}
+
+// Edit a vector as three values next to each other.
+fn vec3_edit(ui: &mut Ui, label: &str, value: &mut Vec3, speed: f32) {
+    ui.label(egui::RichText::new(label).size(12.0));
+    ui.horizontal(|ui| {
+        for v in [&mut value.x, &mut value.y, &mut value.z] {
+            ui.add(egui::DragValue::new(v).speed(speed).max_decimals(3));
+        }
+    });
+}
+
+async fn export(
+    splat: Splats<MainBackend>,
+    file_name: &str,
+    options: ExportOptions,
+    up_axis: Option<Vec3>,
+) -> Result<(), anyhow::Error> {
+    let data = brush_serde::splat_to_file_data(splat, file_name, &options, up_axis).await?;
+    rrfd::save_file(file_name, data).await?;
     Ok(())
 }
 
@@ -108,6 +203,9 @@ pub struct ScenePanel {
         UnboundedSender<anyhow::Error>,
         UnboundedReceiver<anyhow::Error>,
     ),
+    export_settings: ExportSettings,
+    // Up direction of the current splats, if known.
+    up_axis: Option<Vec3>,
 
     // Keep track of what was last rendered.
     last_state: Option<RenderState>,
@@ -140,6 +238,8 @@ impl ScenePanel {
             frame: 0.0,
             fully_loaded: false,
             export_channel: channel,
+            export_settings: ExportSettings::default(),
+            up_axis: None,
             widget_3d,
         }
     }
@@ -184,6 +284,18 @@ impl ScenePanel {
 
         let focal_y = fov_to_focal(camera.fov_y, size.y) as f32;
         camera.fov_x = focal_to_fov(focal_y as f64, size.x);
+
+        if settings.orthographic.unwrap_or(false) {
+            // Show the area the perspective view shows at the focus point, so zooming works the same.
+            let height = 2.0 * process.get_cam_focus_distance() as f64 * (camera.fov_y / 2.0).tan();
+            camera.projection = Projection::Orthographic {
+                width: height * size.x as f64 / size.y as f64,
+                height,
+            };
+            // Splats behind the camera are culled. Moving back doesn't change an orthographic
+            // view, so render from far behind to see the whole scene.
+            camera.position -= camera.rotation * Vec3::Z * ORTHO_VIEW_DISTANCE;
+        }
         let grid_opacity = process.get_grid_opacity();
 
         let state = RenderState {
@@ -206,14 +318,47 @@ impl ScenePanel {
             // If this viewport is re-rendering.
             if size.x > 8 && size.y > 8 && dirty {
                 let _span = trace_span!("Render splats").entered();
+                let display_mode = settings.display_mode.unwrap_or_default();
+                let show_depth = display_mode == DisplayMode::Depth;
+                let show_normals = display_mode == DisplayMode::Normals;
+                let render_scale = settings.render_scale.unwrap_or(RenderScale::Adaptive);
                 // Could add an option for background color.
-                let (img, _) = splats.render(
+                let (img, aux) = splats.render(
                     &camera,
                     size,
                     settings.background.unwrap_or(Vec3::ZERO),
                     settings.splat_scale,
+                    render_scale,
+                    show_depth,
+                    show_normals,
                 );
-                self.backbuffer.update_texture(img);
+
+                match display_mode {
+                    DisplayMode::Color => {
+                        self.backbuffer.update_texture(img);
+                    }
+                    DisplayMode::Depth => {
+                        let depth = aux.expected_depth().into_primitive().tensor();
+                        let depth = depth
+                            .client
+                            .clone()
+                            .resolve_tensor_float::<MainBackendBase>(depth);
+                        let img =
+                            depth_colormap(Tensor::from_primitive(TensorPrimitive::Float(depth)));
+                        self.backbuffer.update_texture_base(img);
+                    }
+                    DisplayMode::Normals => {
+                        let normals = aux.normals;
+                        let normals = normals
+                            .client
+                            .clone()
+                            .resolve_tensor_float::<MainBackendBase>(normals);
+                        let img = normal_colormap(Tensor::from_primitive(TensorPrimitive::Float(
+                            normals,
+                        )));
+                        self.backbuffer.update_texture_base(img);
+                    }
+                }
 
                 // Render 3D widgets directly onto the splat backbuffer
                 if let Some(widget_3d) = &mut self.widget_3d
@@ -311,6 +456,7 @@ impl ScenePanel {
                         ui.label("• Middle click + drag to pan");
                         ui.label("• Scroll to zoom");
                         ui.label("• WASD to fly, Q&E up/down");
+                        ui.label("• W&S to zoom in orthographic view");
                         ui.label("• Z&C to roll, X to reset roll");
                         ui.label("• Shift to move faster");
                     });
@@ -332,6 +478,15 @@ impl ScenePanel {
                             process.set_train_paused(self.paused);
                         }
 
+                        if ui
+                            .small_button("⏹ Stop")
+                            .on_hover_text("Stop training after the current step")
+                            .clicked()
+                        {
+                            self.paused = false;
+                            process.stop_training();
+                        }
+
                         ui.scope(|ui| {
                             ui.style_mut().visuals.selection.bg_fill =
                                 Color32::from_rgb(120, 40, 40);
@@ -343,17 +498,8 @@ impl ScenePanel {
                             }
                         });
 
-                        if let Some(splats) = splats
-                            && ui.small_button("⬆ Export").clicked()
-                        {
-                            let sender = self.export_channel.0.clone();
-                            let ctx = ui.ctx().clone();
-                            tokio_wasm::task::spawn(async move {
-                                if let Err(e) = export(splats).await {
-                                    let _ = sender.send(e.context("Failed to export splat"));
-                                    ctx.request_repaint();
-                                }
-                            });
+                        if let Some(splats) = splats {
+                            ui.menu_button("⬆ Export", |ui| self.export_menu(ui, splats));
                         }
                         ui.add_space(4.0);
                         ui.separator();
@@ -400,6 +546,14 @@ impl ScenePanel {
                         process.set_cam_fov(fov_degrees.to_radians() as f64);
                     }
 
+                    // Projection toggle
+                    let mut settings = process.get_cam_settings();
+                    let mut orthographic = settings.orthographic.unwrap_or(false);
+                    if ui.checkbox(&mut orthographic, "Orthographic").changed() {
+                        settings.orthographic = Some(orthographic);
+                        process.set_cam_settings(&settings);
+                    }
+
                     // Splat scale slider
                     ui.label(egui::RichText::new("Splat Scale").size(12.0));
                     let mut settings = process.get_cam_settings();
@@ -417,6 +571,36 @@ impl ScenePanel {
                         process.set_cam_settings(&settings);
                     }
 
+                    // Render resolution
+                    let current_scale = settings.render_scale.unwrap_or(RenderScale::Adaptive);
+                    let mut render_scale = current_scale;
+                    egui::ComboBox::from_label(egui::RichText::new("Quality").size(12.0))
+                        .selected_text(render_scale.label())
+                        .show_ui(ui, |ui| {
+                            for scale in RenderScale::ALL {
+                                ui.selectable_value(&mut render_scale, scale, scale.label());
+                            }
+                        });
+                    if render_scale != current_scale {
+                        settings.render_scale = Some(render_scale);
+                        process.set_cam_settings(&settings);
+                    }
+
+                    // Display mode
+                    let current_mode = settings.display_mode.unwrap_or_default();
+                    let mut display_mode = current_mode;
+                    egui::ComboBox::from_label(egui::RichText::new("Display").size(12.0))
+                        .selected_text(display_mode.label())
+                        .show_ui(ui, |ui| {
+                            for mode in DisplayMode::ALL {
+                                ui.selectable_value(&mut display_mode, mode, mode.label());
+                            }
+                        });
+                    if display_mode != current_mode {
+                        settings.display_mode = Some(display_mode);
+                        process.set_cam_settings(&settings);
+                    }
+
                     ui.add_space(4.0);
 
                     // Grid toggle
@@ -435,6 +619,72 @@ impl ScenePanel {
         box_ui("controls_box", ui, Align2::LEFT_TOP, pos, inner);
     }
 
+    fn export_menu(&mut self, ui: &mut egui::Ui, splats: Splats<MainBackend>) {
+        let settings = &mut self.export_settings;
+
+        egui::ComboBox::from_label(egui::RichText::new("Format").size(12.0))
+            .selected_text(EXPORT_FORMATS[settings.format].0)
+            .show_ui(ui, |ui| {
+                for (i, (label, _)) in EXPORT_FORMATS.iter().enumerate() {
+                    ui.selectable_value(&mut settings.format, i, *label);
+                }
+            });
+
+        let max_degree = splats.sh_degree();
+        let mut sh_degree = settings.sh_degree.min(max_degree);
+        ui.label(egui::RichText::new("SH degree").size(12.0));
+        if ui
+            .add_enabled(max_degree > 0, Slider::new(&mut sh_degree, 0..=max_degree))
+            .changed()
+        {
+            settings.sh_degree = sh_degree;
+        }
+
+        ui.label(egui::RichText::new("Minimum opacity").size(12.0));
+        ui.add(Slider::new(&mut settings.min_opacity, 0.0..=1.0).max_decimals(2));
+
+        egui::ComboBox::from_label(egui::RichText::new("Up axis").size(12.0))
+            .selected_text(settings.up.map_or("Original", UpAxis::label))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut settings.up, None, "Original");
+                for up in UpAxis::ALL {
+                    ui.selectable_value(&mut settings.up, Some(up), up.label());
+                }
+            });
+
+        ui.checkbox(&mut settings.crop, "Crop to box");
+        if settings.crop {
+            vec3_edit(ui, "Box min", &mut settings.box_min, 0.05);
+            vec3_edit(ui, "Box max", &mut settings.box_max, 0.05);
+        }
+
+        ui.collapsing("Transform", |ui| {
+            ui.label(egui::RichText::new("Scale").size(12.0));
+            ui.add(
+                egui::DragValue::new(&mut settings.scale)
+                    .speed(0.01)
+                    .range(0.001..=1000.0),
+            );
+            vec3_edit(ui, "Rotation (°)", &mut settings.rotation, 1.0);
+            vec3_edit(ui, "Translation", &mut settings.translation, 0.05);
+        });
+
+        if ui.button("Save").clicked() {
+            let options = settings.options(sh_degree, self.up_axis);
+            let file_name = EXPORT_FORMATS[settings.format].1;
+            let up_axis = self.up_axis;
+            let sender = self.export_channel.0.clone();
+            let ctx = ui.ctx().clone();
+            tokio_wasm::task::spawn(async move {
+                if let Err(e) = export(splats, file_name, options, up_axis).await {
+                    let _ = sender.send(e.context("Failed to export splat"));
+                    ctx.request_repaint();
+                }
+            });
+            ui.close();
+        }
+    }
+
     fn draw_play_pause(&mut self, ui: &egui::Ui, rect: Rect) {
         if self.view_splats.len() > 1 && self.view_splats.len() as u32 == self.frame_count {
             let id = ui.auto_id_with("play_pause_button");
@@ -540,6 +790,7 @@ impl AppPane for ScenePanel {
             ProcessMessage::NewSource => {
                 self.live_update = true;
                 self.err = None;
+                self.up_axis = None;
             }
             ProcessMessage::StartLoading { training } => {
                 // If training reset. Otherwise, keep existing splats until new ones are fully loaded.
@@ -554,10 +805,11 @@ impl AppPane for ScenePanel {
                 total_frames,
                 progress,
             } => {
-                if !process.is_training()
-                    && let Some(up_axis) = up_axis
-                {
-                    process.set_model_up(*up_axis);
+                if let Some(up_axis) = up_axis {
+                    self.up_axis = Some(*up_axis);
+                    if !process.is_training() {
+                        process.set_model_up(*up_axis);
+                    }
                 }
 
                 self.frame_count = *total_frames;
@@ -1,14 +1,30 @@
 use crate::{UiMode, panels::AppPane, ui_process::UiProcess};
+use anyhow::Context;
 use brush_process::config::ProcessArgs;
+use brush_render::RenderScale;
+use brush_train::{
+    depth_loss::DepthLossKind, lr_schedule::LrScheduleKind, refine::RefineStrategyKind,
+};
 use brush_vfs::DataSource;
 use egui::{Align2, Slider, Ui};
-use tokio::sync::oneshot::Sender;
+use tokio::{
+    io::AsyncReadExt,
+    sync::{
+        mpsc::{UnboundedReceiver, UnboundedSender},
+        oneshot::Sender,
+    },
+};
 
 pub struct SettingsPanel {
     args: ProcessArgs,
     url: String,
     send_args: Option<Sender<ProcessArgs>>,
     show_url_dialog: bool,
+    preset_channel: (
+        UnboundedSender<anyhow::Result<ProcessArgs>>,
+        UnboundedReceiver<anyhow::Result<ProcessArgs>>,
+    ),
+    preset_error: Option<String>,
 }
 
 impl SettingsPanel {
@@ -18,6 +34,8 @@ impl SettingsPanel {
             url: "splat.com/example.ply".to_owned(),
             send_args: None,
             show_url_dialog: false,
+            preset_channel: tokio::sync::mpsc::unbounded_channel(),
+            preset_error: None,
         }
     }
 
@@ -33,6 +51,16 @@ impl SettingsPanel {
             return;
         }
 
+        while let Ok(preset) = self.preset_channel.1.try_recv() {
+            match preset {
+                Ok(args) => {
+                    self.args = args;
+                    self.preset_error = None;
+                }
+                Err(e) => self.preset_error = Some(format!("{e:#}")),
+            }
+        }
+
         egui::Window::new("Settings")
             .resizable(true)
             .collapsible(false)
@@ -41,6 +69,36 @@ impl SettingsPanel {
             .pivot(Align2::CENTER_CENTER)
             .show(ui.ctx(), |ui| {
                 egui::ScrollArea::vertical().show(ui, |ui| {
+                // Presets
+                ui.horizontal(|ui| {
+                    if ui.button("Load preset").clicked() {
+                        let sender = self.preset_channel.0.clone();
+                        let args = self.args.clone();
+                        let ctx = ui.ctx().clone();
+                        tokio_with_wasm::alias::task::spawn(async move {
+                            let _ = sender.send(load_preset(args).await.context("Failed to load preset"));
+                            ctx.request_repaint();
+                        });
+                    }
+
+                    let can_save = !cfg!(target_os = "android");
+                    if can_save && ui.button("Save preset").clicked() {
+                        let sender = self.preset_channel.0.clone();
+                        let args = self.args.clone();
+                        let ctx = ui.ctx().clone();
+                        tokio_with_wasm::alias::task::spawn(async move {
+                            if let Err(e) = save_preset(args).await {
+                                let _ = sender.send(Err(e.context("Failed to save preset")));
+                                ctx.request_repaint();
+                            }
+                        });
+                    }
+                });
+                if let Some(err) = &self.preset_error {
+                    ui.colored_label(egui::Color32::RED, err);
+                }
+                ui.add_space(10.0);
+
                 // Training
                 ui.heading("Training");
                 slider(ui, &mut self.args.train_config.total_steps, 1..=50000, " steps", false);
@@ -53,14 +111,29 @@ impl SettingsPanel {
                 ui.collapsing("Learning rates", |ui| {
                     let tc = &mut self.args.train_config;
                     slider(ui, &mut tc.lr_mean, 1e-7..=1e-4, "Mean learning rate start", true);
-                    slider(ui, &mut tc.lr_mean, 1e-7..=1e-4, "Mean learning rate end", true);
+                    slider(ui, &mut tc.lr_mean_end, 1e-8..=1e-4, "Mean learning rate end", true);
+                    schedule_combo(ui, "Mean schedule", &mut tc.lr_mean_schedule);
                     slider(ui, &mut tc.mean_noise_weight, 1e3..=1e5, "Mean noise weight", true);
+                    ui.add_space(5.0);
                     slider(ui, &mut tc.lr_coeffs_dc, 1e-4..=1e-2, "SH coefficients", true);
+                    slider(ui, &mut tc.lr_coeffs_dc_end, 1e-5..=1e-2, "SH coefficients (end)", true);
+                    schedule_combo(ui, "SH coefficients schedule", &mut tc.lr_coeffs_schedule);
                     slider(ui, &mut tc.lr_coeffs_sh_scale, 1.0..=50.0, "SH division for higher orders", false);
+                    ui.add_space(5.0);
                     slider(ui, &mut tc.lr_opac, 1e-3..=1e-1, "opacity", true);
+                    slider(ui, &mut tc.lr_opac_end, 1e-4..=1e-1, "opacity (end)", true);
+                    schedule_combo(ui, "Opacity schedule", &mut tc.lr_opac_schedule);
+                    ui.add_space(5.0);
                     slider(ui, &mut tc.lr_scale, 1e-3..=1e-1, "scale", true);
                     slider(ui, &mut tc.lr_scale_end, 1e-4..=1e-2, "scale (end)", true);
+                    schedule_combo(ui, "Scale schedule", &mut tc.lr_scale_schedule);
+                    ui.add_space(5.0);
                     slider(ui, &mut tc.lr_rotation, 1e-4..=1e-2, "rotation", true);
+                    slider(ui, &mut tc.lr_rotation_end, 1e-5..=1e-2, "rotation (end)", true);
+                    schedule_combo(ui, "Rotation schedule", &mut tc.lr_rotation_schedule);
+                    ui.add_space(5.0);
+                    slider(ui, &mut tc.lr_warmup_steps, 0..=5000, "Warmup steps", false);
+                    slider(ui, &mut tc.lr_step_count, 1..=10, "Step schedule drops", false);
                 });
 
                 ui.collapsing("Growth & refinement", |ui| {
@@ -69,6 +142,19 @@ impl SettingsPanel {
                     slider(ui, &mut tc.growth_grad_threshold, 0.0001..=0.001, "Growth threshold", true);
                     slider(ui, &mut tc.growth_select_fraction, 0.01..=0.2, "Growth selection fraction", false);
                     slider(ui, &mut tc.growth_stop_iter, 5000..=20000, "Growth stop iteration", false);
+
+                    egui::ComboBox::from_label("Refine strategy")
+                        .selected_text(tc.refine_strategy.label())
+                        .show_ui(ui, |ui| {
+                            for kind in RefineStrategyKind::ALL {
+                                ui.selectable_value(&mut tc.refine_strategy, kind, kind.label());
+                            }
+                        });
+                    if tc.refine_strategy == RefineStrategyKind::Mcmc {
+                        slider(ui, &mut tc.mcmc_grow_rate, 0.01..=0.2, "MCMC growth rate", false);
+                        slider(ui, &mut tc.mcmc_opac_reg, 0.0..=0.1, "MCMC opacity regularization", false);
+                        slider(ui, &mut tc.mcmc_scale_reg, 0.0..=0.1, "MCMC scale regularization", false);
+                    }
                 });
 
                 ui.collapsing("Losses", |ui| {
@@ -77,6 +163,48 @@ impl SettingsPanel {
                     slider(ui, &mut tc.opac_decay, 0.0..=0.01, "Splat opacity decay", true);
                     slider(ui, &mut tc.scale_decay, 0.0..=0.01, "Splat scale decay", true);
                     slider(ui, &mut tc.match_alpha_weight, 0.01..=1.0, "Alpha match weight", false);
+                    slider(ui, &mut tc.depth_loss_weight, 0.0..=1.0, "Depth weight", false);
+                    if tc.depth_loss_weight > 0.0 {
+                        egui::ComboBox::from_label("Depth loss")
+                            .selected_text(tc.depth_loss.label())
+                            .show_ui(ui, |ui| {
+                                for kind in DepthLossKind::ALL {
+                                    ui.selectable_value(&mut tc.depth_loss, kind, kind.label());
+                                }
+                            });
+                    }
+                });
+
+                ui.collapsing("Appearance", |ui| {
+                    let tc = &mut self.args.train_config;
+                    ui.checkbox(&mut tc.appearance, "Compensate exposure & white balance per view");
+                    if tc.appearance {
+                        slider(ui, &mut tc.lr_appearance, 1e-4..=1e-2, "Appearance learning rate", true);
+                    }
+                });
+
+                ui.collapsing("Camera poses", |ui| {
+                    let tc = &mut self.args.train_config;
+                    ui.checkbox(&mut tc.pose_refine, "Refine camera poses");
+                    if tc.pose_refine {
+                        slider(ui, &mut tc.lr_pose, 1e-7..=1e-3, "Pose learning rate", true);
+                    }
+                });
+
+                ui.collapsing("Anti-aliasing", |ui| {
+                    let tc = &mut self.args.train_config;
+                    ui.checkbox(&mut tc.mip_filter, "Mip-Splatting filters");
+                });
+
+                ui.collapsing("Render resolution", |ui| {
+                    let tc = &mut self.args.train_config;
+                    egui::ComboBox::from_label("Training resolution")
+                        .selected_text(tc.render_scale.label())
+                        .show_ui(ui, |ui| {
+                            for scale in RenderScale::ALL {
+                                ui.selectable_value(&mut tc.render_scale, scale, scale.label());
+                            }
+                        });
                 });
 
                 ui.add_space(15.0);
@@ -156,6 +284,8 @@ impl SettingsPanel {
                     ui.add(Slider::new(&mut pc.eval_every, 1..=5000)
                         .clamping(egui::SliderClamping::Never).prefix("every ").suffix(" steps"));
                     ui.checkbox(&mut pc.eval_save_to_disk, "Save Eval images to disk");
+                    #[cfg(not(target_family = "wasm"))]
+                    ui.checkbox(&mut pc.eval_lpips, "Compute LPIPS");
                 });
 
                 ui.add_space(15.0);
@@ -333,6 +463,20 @@ impl AppPane for SettingsPanel {
     }
 }
 
+/// Apply a config file picked by the user on top of the current settings.
+async fn load_preset(args: ProcessArgs) -> anyhow::Result<ProcessArgs> {
+    let (_, mut reader) = rrfd::pick_file().await?;
+    let mut config = String::new();
+    reader.read_to_string(&mut config).await?;
+    args.merge_config(&config, |_| false)
+}
+
+async fn save_preset(args: ProcessArgs) -> anyhow::Result<()> {
+    let config = args.to_toml()?;
+    rrfd::save_file("config.toml", config.into_bytes()).await?;
+    Ok(())
+}
+
 // Helper functions to reduce repetition
 fn slider<T>(
     ui: &mut Ui,
@@ -353,6 +497,16 @@ fn slider<T>(
     ui.add(s);
 }
 
+fn schedule_combo(ui: &mut Ui, label: &str, value: &mut LrScheduleKind) {
+    egui::ComboBox::from_label(label)
+        .selected_text(value.label())
+        .show_ui(ui, |ui| {
+            for kind in LrScheduleKind::ALL {
+                ui.selectable_value(value, kind, kind.label());
+            }
+        });
+}
+
 #[allow(unused)]
 fn text_input(ui: &mut Ui, label: &str, text: &mut String) {
     let label = ui.label(label);
@@ -103,8 +103,14 @@ impl AppPane for StatsPanel {
                 iter: _,
                 avg_psnr,
                 avg_ssim,
+                avg_lpips,
+                ..
             } => {
-                self.last_eval = Some(format!("{avg_psnr:.2} PSNR, {avg_ssim:.3} SSIM"));
+                let mut last_eval = format!("{avg_psnr:.2} PSNR, {avg_ssim:.3} SSIM");
+                if let Some(avg_lpips) = avg_lpips {
+                    last_eval += &format!(", {avg_lpips:.3} LPIPS");
+                }
+                self.last_eval = Some(last_eval);
             }
             _ => {}
         }
@@ -10,11 +10,14 @@ use glam::{Affine3A, Quat, Vec3};
 use parking_lot::RwLock;
 use tokio::sync::{self, oneshot::Receiver};
 use tokio_stream::StreamExt;
+use tokio_util::sync::CancellationToken;
 use tokio_with_wasm::alias as tokio_wasm;
 
 #[derive(Debug, Clone)]
 enum ControlMessage {
     Paused(bool),
+    /// Finish the current step, export the splat, and stop training.
+    Stop,
 }
 
 #[derive(Debug, Clone)]
@@ -95,6 +98,12 @@ impl UiProcess {
         }
     }
 
+    pub fn stop_training(&self) {
+        if let Some(process) = self.read().running_process.as_ref() {
+            let _ = process.control.send(ControlMessage::Stop);
+        }
+    }
+
     pub fn get_cam_settings(&self) -> CameraSettings {
         self.read().controls.settings.clone()
     }
@@ -124,6 +133,10 @@ impl UiProcess {
         self.read().repaint();
     }
 
+    pub fn get_cam_focus_distance(&self) -> f32 {
+        self.read().controls.focus_distance
+    }
+
     pub fn set_cam_focus_distance(&self, distance: f32) {
         self.write().controls.focus_distance = distance;
         self.read().repaint();
@@ -187,7 +200,8 @@ impl UiProcess {
                 return;
             };
 
-            let stream = process_stream(source, args, device_ctx.device);
+            let cancel = CancellationToken::new();
+            let stream = process_stream(source, args, cancel.clone(), device_ctx.device);
             let mut stream = std::pin::pin!(stream);
 
             while let Some(msg) = stream.next().await {
@@ -201,16 +215,26 @@ impl UiProcess {
                     break;
                 }
 
-                // Check if training is paused. Don't care about other messages as pausing loading
-                // doesn't make much sense.
-                if is_train_step
-                    && matches!(train_receiver.try_recv(), Ok(ControlMessage::Paused(true)))
-                {
-                    // Pause if needed.
-                    while !matches!(
-                        train_receiver.recv().await,
-                        Some(ControlMessage::Paused(false))
-                    ) {}
+                // Check if training is paused or stopped. Don't care about other messages as
+                // pausing loading doesn't make much sense.
+                if is_train_step {
+                    let mut paused = false;
+                    loop {
+                        // While paused, wait for the next control message.
+                        let control = if paused {
+                            train_receiver.recv().await
+                        } else {
+                            train_receiver.try_recv().ok()
+                        };
+                        match control {
+                            Some(ControlMessage::Paused(p)) => paused = p,
+                            Some(ControlMessage::Stop) => {
+                                cancel.cancel();
+                                break;
+                            }
+                            None => break,
+                        }
+                    }
                 }
                 // Give back control to the runtime.
                 // This only really matters in the browser:
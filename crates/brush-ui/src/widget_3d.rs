@@ -1,3 +1,4 @@
+use brush_render::camera::Projection;
 use glam::{Mat4, Vec3};
 use wgpu::util::DeviceExt;
 
@@ -238,7 +239,16 @@ impl Widget3D {
         // Use perspective_lh since camera uses +Z as forward
         // But flip Y since camera uses Y-down while perspective_lh uses Y-up
         let aspect = size.x as f32 / size.y as f32;
-        let proj_matrix = Mat4::perspective_lh(camera.fov_y as f32, aspect, 0.1, 1000.0);
+        let proj_matrix = match camera.projection {
+            Projection::Perspective => {
+                Mat4::perspective_lh(camera.fov_y as f32, aspect, 0.1, 1000.0)
+            }
+            // Orthographic views are rendered from far away, so use a larger far plane.
+            Projection::Orthographic { width, height } => {
+                let (w, h) = (width as f32 / 2.0, height as f32 / 2.0);
+                Mat4::orthographic_lh(-w, w, -h, h, 0.1, 10000.0)
+            }
+        };
 
         // Y-flip to convert from Y-up to Y-down
         let y_flip = Mat4::from_scale(Vec3::new(1.0, -1.0, 1.0));
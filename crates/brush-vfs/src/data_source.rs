@@ -47,9 +47,9 @@ impl DataSource {
     pub async fn into_vfs(self) -> Result<BrushVfs, DataSourceError> {
         match self {
             Self::PickFile => {
-                let reader = BufReader::new(rrfd::pick_file().await?);
+                let (name, reader) = rrfd::pick_file().await?;
                 log::info!("Got file reader");
-                Ok(BrushVfs::from_reader(reader).await?)
+                Ok(BrushVfs::from_named_reader(BufReader::new(reader), name.as_deref()).await?)
             }
             Self::PickDirectory => {
                 #[cfg(not(target_family = "wasm"))]
@@ -70,6 +70,12 @@ impl DataSource {
 
     async fn fetch_url(url: String) -> Result<BrushVfs, DataSourceError> {
         let mut url = url.clone();
+        // The file name at the end of the url, without any query.
+        let name = url
+            .split(['?', '#'])
+            .next()
+            .and_then(|path| path.rsplit('/').next())
+            .map(str::to_owned);
 
         if url.starts_with("https://") || url.starts_with("http://") {
             // fine, can use as is.
@@ -98,7 +104,7 @@ impl DataSource {
             let response = reqwest::get(url).await?.bytes_stream();
             let response = response.map(|b| b.map_err(|_e| std::io::ErrorKind::ConnectionAborted));
             let reader = StreamReader::new(response);
-            Ok(BrushVfs::from_reader(reader).await?)
+            Ok(BrushVfs::from_named_reader(reader, name.as_deref()).await?)
         }
 
         #[cfg(target_family = "wasm")]
@@ -145,7 +151,7 @@ impl DataSource {
             let readable_stream = ReadableStream::from_raw(body);
             let async_read = readable_stream.into_async_read().compat();
             let async_read = BufReader::new(async_read);
-            Ok(BrushVfs::from_reader(async_read).await?)
+            Ok(BrushVfs::from_named_reader(async_read, name.as_deref()).await?)
         }
     }
 }
@@ -118,6 +118,9 @@ pub struct BrushVfs {
     container: VfsContainer,
 }
 
+/// Extensions of splat files that can't be recognized by their first bytes alone.
+const NAMED_EXTENSIONS: &[&str] = &["splat", "spz"];
+
 fn lookup_from_paths(paths: &[PathBuf]) -> HashMap<PathKey, PathBuf> {
     let mut result = HashMap::new();
     for path in paths {
@@ -150,7 +153,7 @@ pub enum VfsConstructError {
     IoError(#[from] std::io::Error),
     #[error("Got a status page instead of content: \n\n {0}")]
     InvalidHtml(String),
-    #[error("Unknown data type. Only zip and ply files are supported")]
+    #[error("Unknown data type. Only zip, ply, splat, spz and glb files are supported")]
     UnknownDataType,
 }
 
@@ -164,6 +167,23 @@ impl BrushVfs {
     }
 
     pub async fn from_reader(reader: impl DynRead + 'static) -> Result<Self, VfsConstructError> {
+        Self::from_named_reader(reader, None).await
+    }
+
+    /// Like [`Self::from_reader`], but with the name of the file being read, if known.
+    ///
+    /// Some splat formats have no header to recognize them by, or are just gzipped, these are
+    /// detected by the extension of the name.
+    pub async fn from_named_reader(
+        reader: impl DynRead + 'static,
+        name: Option<&str>,
+    ) -> Result<Self, VfsConstructError> {
+        let named_ext = name
+            .and_then(|name| Path::new(name).extension())
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase())
+            .filter(|ext| NAMED_EXTENSIONS.contains(&ext.as_str()));
+
         // Small hack to peek some bytes: Read them
         // and add them at the start again.
         let mut data = BufReader::new(reader);
@@ -171,8 +191,19 @@ impl BrushVfs {
         let mut reader: Box<dyn DynRead> =
             Box::new(AsyncReadExt::chain(Cursor::new(peek.clone()), data));
 
-        if peek.starts_with(b"ply") {
-            let path = PathBuf::from("input.ply");
+        // An html page is more likely an error page than a headerless file, so don't take it as one.
+        let single_file = if peek.starts_with(b"ply") {
+            Some("ply".to_owned())
+        } else if peek.starts_with(b"glTF") {
+            Some("glb".to_owned())
+        } else if peek.starts_with(b"PK") || peek.starts_with(b"<!DOCTYPE html>") {
+            None
+        } else {
+            named_ext
+        };
+
+        if let Some(ext) = single_file {
+            let path = PathBuf::from(format!("input.{ext}"));
             let reader_ref = Arc::new(Mutex::new(Some(reader)));
             Ok(Self {
                 lookup: lookup_from_paths(std::slice::from_ref(&path)),
@@ -221,7 +252,8 @@ impl BrushVfs {
                 // it's not really just a single path.
                 let file = tokio::fs::File::open(dir).await?;
                 let reader = BufReader::new(file);
-                Self::from_reader(reader).await
+                let name = dir.file_name().and_then(|name| name.to_str());
+                Self::from_named_reader(reader, name).await
             } else {
                 // Make a VFS with all files contained in the directory.
                 async fn walk_dir(dir: impl AsRef<Path>) -> io::Result<Vec<PathBuf>> {
@@ -518,6 +550,25 @@ mod tests {
             .unwrap();
         assert_eq!(content, "ply\nformat ascii 1.0\nend_header\nvertex data");
 
+        let vfs = BrushVfs::from_reader(Cursor::new(b"glTF\x02\0\0\0"))
+            .await
+            .unwrap();
+        assert_eq!(vfs.files_with_extension("glb").count(), 1);
+
+        // Headerless files are only recognized by their name.
+        let vfs = BrushVfs::from_named_reader(Cursor::new(b"\x1f\x8bspz data"), Some("scene.spz"))
+            .await
+            .unwrap();
+        assert_eq!(vfs.files_with_extension("spz").count(), 1);
+        let vfs = BrushVfs::from_named_reader(Cursor::new(b"splat data"), Some("scene.SPLAT"))
+            .await
+            .unwrap();
+        assert_eq!(vfs.files_with_extension("splat").count(), 1);
+        assert!(matches!(
+            BrushVfs::from_named_reader(Cursor::new(b"splat data"), Some("scene.txt")).await,
+            Err(VfsConstructError::UnknownDataType)
+        ));
+
         // Test error cases
         assert!(matches!(
             BrushVfs::from_reader(Cursor::new(b"unknown")).await,
@@ -87,6 +87,9 @@ impl CameraSettings {
             },
             background: background.map(|v| v.to_glam()),
             grid_enabled,
+            render_scale: None,
+            display_mode: None,
+            orthographic: None,
         })
     }
 }
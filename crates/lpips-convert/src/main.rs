@@ -5,26 +5,49 @@ use burn::record::FullPrecisionSettings;
 use burn::record::HalfPrecisionSettings;
 use burn::record::Recorder;
 use burn::tensor::backend::Backend;
-use lpips::LpipsModel;
+use lpips::{AlexLpipsModel, LpipsModel};
+
+fn load_record<B: Backend, M: Module<B>>(path: &str, device: &B::Device) -> M::Record {
+    burn_import::pytorch::PyTorchFileRecorder::<FullPrecisionSettings>::default()
+        .load(burn_import::pytorch::LoadArgs::new(path.into()), device)
+        .expect("Should decode state successfully")
+}
 
 fn convert_lpips<B: Backend>(device: &B::Device) {
     let model = LpipsModel::<B>::new(device);
-    let record: <LpipsModel<B> as Module<B>>::Record =
-        burn_import::pytorch::PyTorchFileRecorder::<FullPrecisionSettings>::default()
-            .load(
-                burn_import::pytorch::LoadArgs::new("./lpips_vgg_remapped.pth".into()),
-                device,
-            )
-            .expect("Should decode state successfully");
-    let model = model.load_record(record);
+    let model = model.load_record(load_record::<B, LpipsModel<B>>(
+        "./lpips_vgg_remapped.pth",
+        device,
+    ));
     let recorder = burn::record::BinFileRecorder::<HalfPrecisionSettings>::new();
     model
         .save_file("./burn_mapped", &recorder)
         .expect("Failed to convert model");
 }
 
+// The AlexNet checkpoint is expected to have the AlexNet features as `convs.{i}` and the LPIPS
+// layers as `heads.{i}`.
+fn convert_alex_lpips<B: Backend>(device: &B::Device) {
+    let model = AlexLpipsModel::<B>::new(device);
+    let model = model.load_record(load_record::<B, AlexLpipsModel<B>>(
+        "./lpips_alex_remapped.pth",
+        device,
+    ));
+    let recorder = burn::record::BinFileRecorder::<HalfPrecisionSettings>::new();
+    model
+        .save_file("./burn_alex_mapped", &recorder)
+        .expect("Failed to convert model");
+}
+
 fn main() {
-    println!("Converting LPIPS PyTorch model to Burn format...");
-    convert_lpips::<burn::backend::Wgpu>(&burn::backend::wgpu::WgpuDevice::default());
+    let device = burn::backend::wgpu::WgpuDevice::default();
+
+    if std::env::args().nth(1).as_deref() == Some("alex") {
+        println!("Converting AlexNet LPIPS PyTorch model to Burn format...");
+        convert_alex_lpips::<burn::backend::Wgpu>(&device);
+    } else {
+        println!("Converting LPIPS PyTorch model to Burn format...");
+        convert_lpips::<burn::backend::Wgpu>(&device);
+    }
     println!("Conversion completed successfully!");
 }
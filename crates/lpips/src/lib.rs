@@ -75,22 +75,33 @@ fn norm_vec<B: Backend>(vec: Tensor<B, 4>) -> Tensor<B, 4> {
     vec / (norm_factor + 1e-10)
 }
 
+// Convert NHWC images in [0, 1] to the NCHW input the networks were trained on.
+fn scale_input<B: Backend>(imgs: Tensor<B, 4>) -> Tensor<B, 4> {
+    let device = imgs.device();
+    let imgs = imgs.permute([0, 3, 1, 2]) * 2.0 - 1.0;
+    let shift =
+        Tensor::<B, 1>::from_floats([-0.030, -0.088, -0.188], &device).reshape([1, 3, 1, 1]);
+    let scale = Tensor::<B, 1>::from_floats([0.458, 0.448, 0.450], &device).reshape([1, 3, 1, 1]);
+    (imgs - shift) / scale
+}
+
+// Distance between the features of one layer, weighted by its head and averaged spatially.
+fn layer_distance<B: Backend>(
+    feats_a: Tensor<B, 4>,
+    feats_b: Tensor<B, 4>,
+    head: &Conv2d<B>,
+) -> Tensor<B, 1> {
+    let diff = (norm_vec(feats_a) - norm_vec(feats_b)).powi_scalar(2);
+    head.forward(diff).mean_dim(2).mean_dim(3).reshape([1])
+}
+
 impl<B: Backend> LpipsModel<B> {
-    /// Calculate the lpips. Imgs are in NCHW order. Inputs should be 0-1 normalised.
+    /// Calculate the lpips. Imgs are in NHWC order. Inputs should be 0-1 normalised.
     pub fn lpips(&self, imgs_a: Tensor<B, 4>, imgs_b: Tensor<B, 4>) -> Tensor<B, 1> {
         let device = imgs_a.device();
 
-        // Convert NHWC to NCHW and to [-1, 1].
-        let imgs_a = imgs_a.permute([0, 3, 1, 2]) * 2.0 - 1.0;
-        let imgs_b = imgs_b.permute([0, 3, 1, 2]) * 2.0 - 1.0;
-
-        let shift =
-            Tensor::<B, 1>::from_floats([-0.030, -0.088, -0.188], &device).reshape([1, 3, 1, 1]);
-        let scale =
-            Tensor::<B, 1>::from_floats([0.458, 0.448, 0.450], &device).reshape([1, 3, 1, 1]);
-
-        let mut imgs_a = (imgs_a - shift.clone()) / scale.clone();
-        let mut imgs_b = (imgs_b - shift) / scale;
+        let mut imgs_a = scale_input(imgs_a);
+        let mut imgs_b = scale_input(imgs_b);
 
         let mut loss = Tensor::<B, 1>::zeros([1], &device);
         for (i, (block, head)) in self.blocks.iter().zip(&self.heads).enumerate() {
@@ -104,18 +115,104 @@ impl<B: Backend> LpipsModel<B> {
             imgs_a = block.forward(imgs_a);
             imgs_b = block.forward(imgs_b);
 
-            let normed_a = norm_vec(imgs_a.clone());
-            let normed_b = norm_vec(imgs_b.clone());
+            loss = loss + layer_distance(imgs_a.clone(), imgs_b.clone(), head);
+        }
+        loss
+    }
+}
+
+// Heads weighting the features of each layer, mapping them to a single channel.
+fn init_heads<B: Backend>(channels: &[usize], device: &B::Device) -> Vec<Conv2d<B>> {
+    channels
+        .iter()
+        .map(|&channels| {
+            Conv2dConfig::new([channels, 1], [1, 1])
+                .with_stride([1, 1])
+                .with_bias(false)
+                .init(device)
+        })
+        .collect()
+}
+
+/// LPIPS on top of AlexNet features.
+///
+/// This is the variant most published results quote. The weights aren't included, they can be
+/// converted with `lpips-convert` and loaded with [`load_alex_lpips`].
+#[derive(Module, Debug)]
+pub struct AlexLpipsModel<B: Backend> {
+    convs: Vec<Conv2d<B>>,
+    heads: Vec<Conv2d<B>>,
+    max_pool: MaxPool2d,
+}
+
+impl<B: Backend> AlexLpipsModel<B> {
+    pub fn new(device: &B::Device) -> Self {
+        // (in channels, out channels, kernel size, stride, padding) of the AlexNet features.
+        let convs = [
+            (3, 64, 11, 4, 2),
+            (64, 192, 5, 1, 2),
+            (192, 384, 3, 1, 1),
+            (384, 256, 3, 1, 1),
+            (256, 256, 3, 1, 1),
+        ]
+        .iter()
+        .map(|&(in_channels, out_channels, kernel, stride, padding)| {
+            Conv2dConfig::new([in_channels, out_channels], [kernel, kernel])
+                .with_stride([stride, stride])
+                .with_padding(PaddingConfig2d::Explicit(padding, padding))
+                .with_bias(true)
+                .init(device)
+        })
+        .collect();
+
+        Self {
+            convs,
+            heads: init_heads(&[64, 192, 384, 256, 256], device),
+            max_pool: MaxPool2dConfig::new([3, 3]).with_strides([2, 2]).init(),
+        }
+    }
+
+    /// Calculate the lpips. Imgs are in NHWC order. Inputs should be 0-1 normalised.
+    pub fn lpips(&self, imgs_a: Tensor<B, 4>, imgs_b: Tensor<B, 4>) -> Tensor<B, 1> {
+        let device = imgs_a.device();
+
+        let mut imgs_a = scale_input(imgs_a);
+        let mut imgs_b = scale_input(imgs_b);
 
-            let diff = (normed_a - normed_b).powi_scalar(2);
-            let class = head.forward(diff);
-            // Add spatial mean.
-            loss = loss + class.mean_dim(2).mean_dim(3).reshape([1]);
+        let mut loss = Tensor::<B, 1>::zeros([1], &device);
+        for (i, (conv, head)) in self.convs.iter().zip(&self.heads).enumerate() {
+            // AlexNet pools after the first two layers.
+            if i == 1 || i == 2 {
+                imgs_a = self.max_pool.forward(imgs_a);
+                imgs_b = self.max_pool.forward(imgs_b);
+            }
+
+            imgs_a = relu(conv.forward(imgs_a));
+            imgs_b = relu(conv.forward(imgs_b));
+
+            loss = loss + layer_distance(imgs_a.clone(), imgs_b.clone(), head);
         }
         loss
     }
 }
 
+/// An LPIPS model with either of the supported networks.
+#[derive(Debug)]
+pub enum LpipsNet<B: Backend> {
+    Vgg(LpipsModel<B>),
+    Alex(AlexLpipsModel<B>),
+}
+
+impl<B: Backend> LpipsNet<B> {
+    /// Calculate the lpips. Imgs are in NHWC order. Inputs should be 0-1 normalised.
+    pub fn lpips(&self, imgs_a: Tensor<B, 4>, imgs_b: Tensor<B, 4>) -> Tensor<B, 1> {
+        match self {
+            Self::Vgg(model) => model.lpips(imgs_a, imgs_b),
+            Self::Alex(model) => model.lpips(imgs_a, imgs_b),
+        }
+    }
+}
+
 impl<B: Backend> LpipsModel<B> {
     pub fn new(device: &B::Device) -> Self {
         // Could have different variations here but just doing VGG for now.
@@ -132,19 +229,9 @@ impl<B: Backend> LpipsModel<B> {
         })
         .collect();
 
-        let heads = [64, 128, 256, 512, 512]
-            .iter()
-            .map(|&channels| {
-                Conv2dConfig::new([channels, 1], [1, 1])
-                    .with_stride([1, 1])
-                    .with_bias(false)
-                    .init(device)
-            })
-            .collect();
-
         Self {
             blocks,
-            heads,
+            heads: init_heads(&[64, 128, 256, 512, 512], device),
             max_pool: MaxPool2dConfig::new([2, 2]).with_strides([2, 2]).init(),
         }
     }
@@ -166,6 +253,19 @@ pub fn load_vgg_lpips<B: Backend>(device: &B::Device) -> LpipsModel<B> {
     )
 }
 
+/// Load AlexNet LPIPS weights converted with `lpips-convert`.
+#[cfg(not(target_family = "wasm"))]
+pub fn load_alex_lpips<B: Backend>(
+    path: &std::path::Path,
+    device: &B::Device,
+) -> Result<AlexLpipsModel<B>, burn::record::RecorderError> {
+    use burn::record::BinFileRecorder;
+
+    let record =
+        BinFileRecorder::<HalfPrecisionSettings>::new().load(path.to_path_buf(), device)?;
+    Ok(AlexLpipsModel::<B>::new(device).load_record(record))
+}
+
 #[cfg(test)]
 mod tests {
     use super::load_vgg_lpips;
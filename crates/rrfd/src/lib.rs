@@ -19,8 +19,8 @@ pub enum PickFileError {
     IoError(#[from] std::io::Error),
 }
 
-/// Pick a file and return the name & bytes of the file.
-pub async fn pick_file() -> Result<impl AsyncRead + Unpin, PickFileError> {
+/// Pick a file and return the name & bytes of the file. The name isn't known on Android.
+pub async fn pick_file() -> Result<(Option<String>, impl AsyncRead + Unpin), PickFileError> {
     #[cfg(all(not(target_os = "android"), not(target_family = "wasm")))]
     {
         let file = rfd::AsyncFileDialog::new()
@@ -28,8 +28,9 @@ pub async fn pick_file() -> Result<impl AsyncRead + Unpin, PickFileError> {
             .await
             .ok_or(PickFileError::NoFileSelected)?;
 
+        let name = file.file_name();
         let file = tokio::fs::File::open(file.path()).await?;
-        Ok(tokio::io::BufReader::new(file))
+        Ok((Some(name), tokio::io::BufReader::new(file)))
     }
 
     #[cfg(target_family = "wasm")]
@@ -40,7 +41,7 @@ pub async fn pick_file() -> Result<impl AsyncRead + Unpin, PickFileError> {
     #[cfg(target_os = "android")]
     {
         let file = android::pick_file().await?;
-        Ok(tokio::io::BufReader::new(file))
+        Ok((None, tokio::io::BufReader::new(file)))
     }
 }
 
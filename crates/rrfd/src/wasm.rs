@@ -41,9 +41,10 @@ pub async fn save_file(default_name: &str, data: &[u8]) -> Result<(), PickFileEr
     Ok(())
 }
 
-pub async fn pick_file() -> Result<impl AsyncRead + Unpin, PickFileError> {
+pub async fn pick_file() -> Result<(Option<String>, impl AsyncRead + Unpin), PickFileError> {
     let files = pick_files(false).await?;
     let file = files.get(0).ok_or(PickFileError::NoFileSelected)?;
+    let name = file.name();
 
     let readable_stream: ReadableStream = file.stream();
     let wasm_stream = WasmReadableStream::from_raw(readable_stream);
@@ -65,7 +66,7 @@ pub async fn pick_file() -> Result<impl AsyncRead + Unpin, PickFileError> {
             })
     });
 
-    Ok(StreamReader::new(byte_stream))
+    Ok((Some(name), StreamReader::new(byte_stream)))
 }
 
 pub async fn pick_directory() -> Result<PathBuf, PickFileError> {